@@ -200,7 +200,11 @@ impl BB {
                             lhs_reg = self.resolve_operand(func, lhs, true, map_info, pool);
                             match rhs.as_ref().get_kind() {
                                 InstKind::ConstInt(imm) | InstKind::GlobalConstInt(imm) => {
-                                    self.resolve_opt_div(dst_reg, lhs_reg, imm, pool)
+                                    let non_negative = map_info
+                                        .value_range
+                                        .get_range_at(lhs, block)
+                                        .is_non_negative();
+                                    self.resolve_opt_div(dst_reg, lhs_reg, imm, non_negative, pool)
                                     // rhs_reg = self.resolve_operand(func, rhs, true, map_info, pool);
                                     // self.insts.push(pool.put_inst(LIRInst::new(
                                     //     InstrsType::Binary(BinaryOp::Div),
@@ -241,8 +245,18 @@ impl BB {
                                             )));
                                         }
                                         _ => {
+                                            let non_negative = map_info
+                                                .value_range
+                                                .get_range_at(lhs, block)
+                                                .is_non_negative();
                                             self.resolve_opt_rem(
-                                                func, map_info, dst_reg, lhs, imm, pool,
+                                                func,
+                                                map_info,
+                                                dst_reg,
+                                                lhs,
+                                                imm,
+                                                non_negative,
+                                                pool,
                                             );
                                             // lhs_reg = self
                                             //     .resolve_operand(func, lhs, true, map_info, pool);
@@ -269,6 +283,71 @@ impl BB {
                                 }
                             };
                         }
+                        BinOp::And | BinOp::Or | BinOp::Xor => {
+                            let inst_kind = InstrsType::Binary(match op {
+                                BinOp::And => BinaryOp::And,
+                                BinOp::Or => BinaryOp::Or,
+                                _ => BinaryOp::Xor,
+                            });
+                            // 可交换,立即数放在右边
+                            let (lhs, rhs) = match lhs.as_ref().get_kind() {
+                                InstKind::ConstInt(..) => (rhs, lhs),
+                                _ => (lhs, rhs),
+                            };
+                            lhs_reg = self.resolve_operand(func, lhs, true, map_info, pool);
+                            rhs_reg = self.resolve_operand(func, rhs, false, map_info, pool);
+                            self.insts.push(pool.put_inst(LIRInst::new(
+                                inst_kind,
+                                vec![dst_reg, lhs_reg, rhs_reg],
+                            )));
+                        }
+                        BinOp::Shl | BinOp::LShr | BinOp::AShr => {
+                            let inst_kind = InstrsType::Binary(match op {
+                                BinOp::Shl => BinaryOp::Shl,
+                                BinOp::LShr => BinaryOp::Shr,
+                                _ => BinaryOp::Sar,
+                            });
+                            lhs_reg = self.resolve_operand(func, lhs, true, map_info, pool);
+                            rhs_reg = match rhs.as_ref().get_kind() {
+                                // 移位量只取低5位
+                                InstKind::ConstInt(imm) => self.resolve_iimm(imm & 31, pool),
+                                _ => self.resolve_operand(func, rhs, true, map_info, pool),
+                            };
+                            self.insts.push(pool.put_inst(LIRInst::new(
+                                inst_kind,
+                                vec![dst_reg, lhs_reg, rhs_reg],
+                            )));
+                        }
+                        BinOp::UDiv | BinOp::URem => {
+                            lhs_reg = self.resolve_operand(func, lhs, true, map_info, pool);
+                            match rhs.as_ref().get_kind() {
+                                // 除以2的幂等价于逻辑右移,求余等价于取低位
+                                InstKind::ConstInt(imm) if imm > 0 && imm & (imm - 1) == 0 => {
+                                    let (inst_kind, imm) = if op == BinOp::UDiv {
+                                        (BinaryOp::Shr, imm.trailing_zeros() as i32)
+                                    } else {
+                                        (BinaryOp::And, imm - 1)
+                                    };
+                                    rhs_reg = self.resolve_iimm(imm, pool);
+                                    self.insts.push(pool.put_inst(LIRInst::new(
+                                        InstrsType::Binary(inst_kind),
+                                        vec![dst_reg, lhs_reg, rhs_reg],
+                                    )));
+                                }
+                                _ => {
+                                    rhs_reg = self.resolve_operand(func, rhs, true, map_info, pool);
+                                    let inst_kind = if op == BinOp::UDiv {
+                                        BinaryOp::UDiv
+                                    } else {
+                                        BinaryOp::URem
+                                    };
+                                    self.insts.push(pool.put_inst(LIRInst::new(
+                                        InstrsType::Binary(inst_kind),
+                                        vec![dst_reg, lhs_reg, rhs_reg],
+                                    )));
+                                }
+                            }
+                        }
                         _ => {}
                     }
                 }
@@ -342,6 +421,60 @@ impl BB {
                     }
                 }
 
+                // 无分支选择: mask = -(cond != 0), dst = f ^ ((t ^ f) & mask)
+                InstKind::Select => {
+                    let dst_reg = self.resolve_operand(func, ir_block_inst, true, map_info, pool);
+                    let cond = inst_ref.get_select_cond();
+                    let mask = Operand::Reg(Reg::init(ScalarType::Int));
+                    match is_cond_op(cond) {
+                        // 比较的结果已经是0或1
+                        Some(cond_op) => {
+                            let cond_reg = self.resolve_bool(func, cond_op, cond, map_info, pool);
+                            self.insts.push(pool.put_inst(LIRInst::new(
+                                InstrsType::OpReg(SingleOp::Neg),
+                                vec![mask.clone(), cond_reg],
+                            )));
+                        }
+                        None => {
+                            let cond_reg = self.resolve_operand(func, cond, true, map_info, pool);
+                            self.insts.push(pool.put_inst(LIRInst::new(
+                                InstrsType::OpReg(SingleOp::Snez),
+                                vec![mask.clone(), cond_reg],
+                            )));
+                            self.insts.push(pool.put_inst(LIRInst::new(
+                                InstrsType::OpReg(SingleOp::Neg),
+                                vec![mask.clone(), mask.clone()],
+                            )));
+                        }
+                    }
+                    let true_reg =
+                        self.resolve_operand(func, inst_ref.get_true_value(), true, map_info, pool);
+                    let false_value = inst_ref.get_false_value();
+                    if let InstKind::ConstInt(0) = false_value.get_kind() {
+                        // dst = t & mask
+                        self.insts.push(pool.put_inst(LIRInst::new(
+                            InstrsType::Binary(BinaryOp::And),
+                            vec![dst_reg, true_reg, mask],
+                        )));
+                    } else {
+                        let tmp = Operand::Reg(Reg::init(ScalarType::Int));
+                        let false_reg =
+                            self.resolve_operand(func, false_value, false, map_info, pool);
+                        self.insts.push(pool.put_inst(LIRInst::new(
+                            InstrsType::Binary(BinaryOp::Xor),
+                            vec![tmp.clone(), true_reg, false_reg.clone()],
+                        )));
+                        self.insts.push(pool.put_inst(LIRInst::new(
+                            InstrsType::Binary(BinaryOp::And),
+                            vec![tmp.clone(), tmp.clone(), mask],
+                        )));
+                        self.insts.push(pool.put_inst(LIRInst::new(
+                            InstrsType::Binary(BinaryOp::Xor),
+                            vec![dst_reg, tmp, false_reg],
+                        )));
+                    }
+                }
+
                 //TODO: load/store float
                 InstKind::Load => {
                     let addr = inst_ref.get_ptr();
@@ -2096,7 +2229,15 @@ impl BB {
         }
     }
 
-    fn resolve_opt_div(&mut self, dst: Operand, src: Operand, imm: i32, pool: &mut BackendPool) {
+    /// non_negative为真时被除数一定非负,可以省去向零取整的修正
+    fn resolve_opt_div(
+        &mut self,
+        dst: Operand,
+        src: Operand,
+        imm: i32,
+        non_negative: bool,
+        pool: &mut BackendPool,
+    ) {
         let abs = imm.abs();
         let is_neg = imm < 0;
         match abs {
@@ -2117,7 +2258,13 @@ impl BB {
                 }
             }
             _ => {
-                if is_opt_num(abs) {
+                if is_opt_num(abs) && non_negative && !is_neg {
+                    // 非负数右移就是向零取整
+                    self.insts.push(pool.put_inst(LIRInst::new(
+                        InstrsType::Binary(BinaryOp::Sar),
+                        vec![dst, src, Operand::IImm(IImm::new(log2(abs)))],
+                    )))
+                } else if is_opt_num(abs) {
                     let bits = log2(abs);
                     let tmp = Operand::Reg(Reg::init(ScalarType::Int));
                     self.insts.push(pool.put_inst(LIRInst::new(
@@ -2168,6 +2315,14 @@ impl BB {
                     self.insts.push(pool.put_inst(inst));
                     // q = q >> s
                     // shrsi q, q, s
+                    if non_negative {
+                        // n非负时不需要加1
+                        self.insts.push(pool.put_inst(LIRInst::new(
+                            InstrsType::Binary(BinaryOp::Sar),
+                            vec![dst, tmp3, Operand::IImm(IImm::new(shift))],
+                        )));
+                        return;
+                    }
                     let tmp4 = Operand::Reg(Reg::init(ScalarType::Int));
                     self.insts.push(pool.put_inst(LIRInst::new(
                         InstrsType::Binary(BinaryOp::Sar),
@@ -2197,12 +2352,20 @@ impl BB {
         dst: Operand,
         lhs: ObjPtr<Inst>,
         imm: i32,
+        non_negative: bool,
         pool: &mut BackendPool,
     ) {
         let lhs_reg = self.resolve_operand(func, lhs, true, map, pool);
         let abs = imm.abs();
         // let is_neg = imm < 0;
-        if is_opt_num(abs) {
+        if is_opt_num(abs) && non_negative {
+            // n非负时 r = n & (2^k - 1)
+            let abs_reg = self.resolve_iimm(abs - 1, pool);
+            self.insts.push(pool.put_inst(LIRInst::new(
+                InstrsType::Binary(BinaryOp::And),
+                vec![dst, lhs_reg, abs_reg],
+            )));
+        } else if is_opt_num(abs) {
             let k = log2(abs);
             // r = ((n + t) & (2^k - 1)) - t
             // t = (n >> k - 1) >> 32 - k
@@ -2249,7 +2412,7 @@ impl BB {
         } else {
             let tmp1 = Operand::Reg(Reg::init(ScalarType::Int));
             let tmp2 = Operand::Reg(Reg::init(ScalarType::Int));
-            self.resolve_opt_div(tmp1.clone(), lhs_reg.clone(), imm, pool);
+            self.resolve_opt_div(tmp1.clone(), lhs_reg.clone(), imm, non_negative, pool);
             self.resolve_opt_mul(tmp2.clone(), tmp1, imm, pool);
             self.insts.push(pool.put_inst(LIRInst::new(
                 InstrsType::Binary(BinaryOp::Sub),
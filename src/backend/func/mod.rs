@@ -25,6 +25,7 @@ use crate::backend::regalloc::structs::FuncAllocStat;
 use crate::backend::regalloc::structs::RegUsedStat;
 use crate::backend::{block::*, operand};
 use crate::container::bitmap::Bitmap;
+use crate::ir::analysis::value_range::calculate_value_range;
 use crate::ir::basicblock::BasicBlock;
 use crate::ir::function::Function;
 use crate::ir::instruction::Inst;
@@ -156,6 +157,7 @@ impl Func {
             }
         }
         self.handle_parameters(ir_func);
+        self.info.value_range = calculate_value_range(ObjPtr::new(ir_func));
         // 第二遍pass
         let first_block = self.info.ir_block_map.get(&ir_func.get_head()).unwrap();
         self.entry.unwrap().as_mut().out_edge.push(*first_block);
@@ -397,6 +399,13 @@ impl Func {
         self.context.as_mut().set_offset(stack_size - ADDR_SIZE);
 
         let ra = Reg::new(1, ScalarType::Int);
+        let mut saved_regs = vec![(ra, stack_size - ADDR_SIZE)];
+        if !is_main {
+            for (reg, slot) in map.iter() {
+                saved_regs.push((*reg, stack_size - ADDR_SIZE - slot.get_pos()));
+            }
+        }
+        self.context.as_mut().set_saved_regs(saved_regs);
         let map_clone = map.clone();
 
         self.context.as_mut().set_prologue_event(move || {
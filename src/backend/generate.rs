@@ -13,6 +13,8 @@ impl GenerateAsm for LIRInst {
                     BinaryOp::Mul => "mul",
                     BinaryOp::Div => "div",
                     BinaryOp::Rem => "rem",
+                    BinaryOp::UDiv => "divu",
+                    BinaryOp::URem => "remu",
                     BinaryOp::And => "and",
                     BinaryOp::Or => "or",
                     BinaryOp::Xor => "xor",
@@ -32,6 +32,9 @@ pub enum BinaryOp {
     Mul,
     Div,
     Rem,
+    /// 无符号除法和求余
+    UDiv,
+    URem,
     And,
     Or,
     Xor,
@@ -139,6 +142,8 @@ impl fmt::Display for LIRInst {
                     BinaryOp::Mul => "mul",
                     BinaryOp::Div => "div",
                     BinaryOp::Rem => "rem",
+                    BinaryOp::UDiv => "divu",
+                    BinaryOp::URem => "remu",
                     BinaryOp::And => "and",
                     BinaryOp::Or => "or",
                     BinaryOp::Xor => "xor",
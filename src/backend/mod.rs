@@ -17,6 +17,8 @@ use std::io::Write;
 use crate::backend::module::AsmModule;
 use crate::backend::opt::BackendPass;
 use crate::config;
+use crate::ir::{ExecError, ExecResult};
+use crate::pass_manager::PassManager;
 use crate::utility::ObjPool;
 
 use self::func::Func;
@@ -63,6 +65,8 @@ impl BackendPool {
     }
 }
 
+///生成汇编,如果给出了程序输入,则在释放之前模拟执行生成的汇编并返回结果
+#[allow(clippy::too_many_arguments)]
 pub fn generate_asm(
     in_path: &str,
     path: &str,
@@ -70,7 +74,9 @@ pub fn generate_asm(
     module: &mut AsmModule,
     is_opt: bool,
     is_pa: bool,
-) {
+    passes: &PassManager,
+    simulate_input: Option<&str>,
+) -> Option<Result<ExecResult, ExecError>> {
     let mut file = match File::create(path) {
         Ok(f) => f,
         Err(e) => panic!("Create    output path error: {}", e),
@@ -87,7 +93,7 @@ pub fn generate_asm(
     // module.print_asm("after_build.log");
     // 后端优化
     if is_opt {
-        BackendPass::new(ObjPtr::new(module)).run_pass(&mut pool);
+        BackendPass::new(ObjPtr::new(module)).run_pass(&mut pool, passes);
     }
 
     // 检查地址溢出，插入间接寻址
@@ -104,13 +110,19 @@ pub fn generate_asm(
         // module.re_list_scheduling();
 
         // 额外的块优化处理
-        BackendPass::new(ObjPtr::new(module)).block_pass_pre_clear(&mut pool);
-        BackendPass::new(ObjPtr::new(module)).block_pass(&mut pool);
-		BackendPass::new(ObjPtr::new(module)).rm_useless_shift();
+        if passes.is_enabled("block-pre-clear") {
+            BackendPass::new(ObjPtr::new(module)).block_pass_pre_clear(&mut pool);
+        }
+        if passes.is_enabled("block") {
+            BackendPass::new(ObjPtr::new(module)).block_pass(&mut pool);
+        }
+        if passes.is_enabled("rm-useless-shift") {
+            BackendPass::new(ObjPtr::new(module)).rm_useless_shift();
+        }
     }
     // 检查b型指令溢出，用j型指令替换
     module.handle_overflow_br(&mut pool);
-    if is_opt {
+    if is_opt && passes.is_enabled("block-last") {
         BackendPass::new(ObjPtr::new(module)).block_last_pass();
     }
 
@@ -382,9 +394,14 @@ pub fn generate_asm(
     //生成汇编
     module.generate_asm(&mut file, &mut pool);
 
+    //模拟执行
+    let result = simulate_input
+        .map(|input| simulator::simulate(module, input, crate::ir::DEFAULT_STEP_LIMIT));
+
     //释放
     pool.free_all();
 
     // writeln!(file, "    .ident	\"GCC: (Ubuntu 9.4.0-1ubuntu1~20.04) 9.4.0\"");
     writeln!(file, "    .section	.note.GNU-stack,\"\",@progbits").unwrap();
+    result
 }
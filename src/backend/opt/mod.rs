@@ -7,6 +7,7 @@ use crate::backend::operand;
 use crate::backend::operand::*;
 use crate::backend::BackendPool;
 use crate::log;
+use crate::pass_manager::{BackendPassInfo, PassManager};
 pub use crate::utility::ObjPtr;
 
 mod block_pass;
@@ -14,6 +15,34 @@ mod clear_pass;
 mod particular_opt;
 mod peephole_pass;
 
+/// 所有可以在--passes和--disable-pass中使用的后端遍
+pub const PASSES: &[BackendPassInfo] = &[
+    BackendPassInfo {
+        name: "block-pre-clear",
+        desc: "删除只有跳转的块并合并基本块",
+    },
+    BackendPassInfo {
+        name: "clear",
+        desc: "删除无用的指令",
+    },
+    BackendPassInfo {
+        name: "peephole",
+        desc: "窥孔优化",
+    },
+    BackendPassInfo {
+        name: "block",
+        desc: "合并分支与跳转,清除空块",
+    },
+    BackendPassInfo {
+        name: "rm-useless-shift",
+        desc: "删除无用的移位指令",
+    },
+    BackendPassInfo {
+        name: "block-last",
+        desc: "删除跳转到紧邻块的跳转指令",
+    },
+];
+
 pub struct BackendPass {
     pub module: ObjPtr<AsmModule>,
 }
@@ -23,9 +52,15 @@ impl BackendPass {
         Self { module }
     }
 
-    pub fn run_pass(&mut self, pool: &mut BackendPool) {
-        self.block_pass_pre_clear(pool);
-        self.clear_pass(pool);
-        self.peephole_pass(pool);
+    pub fn run_pass(&mut self, pool: &mut BackendPool, passes: &PassManager) {
+        if passes.is_enabled("block-pre-clear") {
+            self.block_pass_pre_clear(pool);
+        }
+        if passes.is_enabled("clear") {
+            self.clear_pass(pool);
+        }
+        if passes.is_enabled("peephole") {
+            self.peephole_pass(pool);
+        }
     }
 }
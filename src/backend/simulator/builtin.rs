@@ -0,0 +1,177 @@
+use crate::backend::operand::Reg;
+use crate::ir::{hex_float, trap, ExecError};
+use crate::utility::ScalarType;
+
+use super::{structs::BuiltInFunc, Flow, Simulator, ThreadStat};
+use super::{FPOISON, POISON, THREAD_STACK_BASE, THREAD_STACK_SIZE};
+
+///子线程创建时从父线程栈上复制的大小,与hitsz_thread_create相同
+const THREAD_COPY_SIZE: usize = 800;
+
+impl<'a> Simulator<'a> {
+    ///执行内置函数,参数和返回值按调用约定放在a0-a7/fa0-fa7中
+    pub(super) fn call_builtin(&mut self, func: BuiltInFunc) -> Result<Flow, ExecError> {
+        let a0 = self.stat.iregs[10];
+        let a1 = self.stat.iregs[11];
+        let a2 = self.stat.iregs[12];
+        let fa0 = f32::from_bits(self.stat.fregs[10]);
+        let mut ret = None;
+        match func {
+            BuiltInFunc::GetInt => ret = Some(self.input.read_int()? as i64),
+            BuiltInFunc::GetCh => ret = Some(self.input.read_char() as i64),
+            BuiltInFunc::GetFloat => {
+                let value = self.input.read_float()?;
+                self.clobber_caller_saved();
+                self.stat.fregs[10] = value.to_bits();
+                return Ok(Flow::Next);
+            }
+            BuiltInFunc::GetArray | BuiltInFunc::GetFArray => {
+                let n = self.input.read_int()?;
+                for i in 0..n as i64 {
+                    let value = if func == BuiltInFunc::GetArray {
+                        self.input.read_int()? as u32
+                    } else {
+                        self.input.read_float()?.to_bits()
+                    };
+                    self.memory.write_u32(a0 + i * 4, value)?;
+                }
+                ret = Some(n as i64);
+            }
+            BuiltInFunc::PutInt => self.output += (a0 as i32).to_string().as_str(),
+            BuiltInFunc::PutCh => self.output.push(a0 as u8 as char),
+            BuiltInFunc::PutFloat => self.output += hex_float(fa0).as_str(),
+            BuiltInFunc::PutArray | BuiltInFunc::PutFArray => {
+                let n = a0 as i32;
+                self.output += format!("{}:", n).as_str();
+                for i in 0..n.max(0) as i64 {
+                    let word = self.memory.read_u32(a1 + i * 4)?;
+                    if func == BuiltInFunc::PutArray {
+                        self.output += format!(" {}", word as i32).as_str();
+                    } else {
+                        self.output += format!(" {}", hex_float(f32::from_bits(word))).as_str();
+                    }
+                }
+                self.output.push('\n');
+            }
+            BuiltInFunc::StartTime | BuiltInFunc::StopTime | BuiltInFunc::ThreadInit => {}
+            BuiltInFunc::Memset => {
+                self.memory.fill(a0, a1 as u8, a2 as usize)?;
+                ret = Some(a0);
+            }
+            BuiltInFunc::Memcpy => {
+                let bytes = self.memory.read(a1, a2 as usize)?.to_vec();
+                self.memory.write(a0, &bytes)?;
+                ret = Some(a0);
+            }
+            BuiltInFunc::ThreadCreate => {
+                if self.thread != 0 {
+                    //子线程中再次create直接返回0
+                    ret = Some(0);
+                } else {
+                    self.create_thread()?;
+                    ret = Some(0);
+                }
+            }
+            BuiltInFunc::ThreadJoin => {
+                if self.thread != 0 {
+                    return Ok(Flow::Joined);
+                }
+                self.join_threads()?;
+            }
+        }
+        self.clobber_caller_saved();
+        if let Some(ret) = ret {
+            debug_assert!(func.ret_type() == ScalarType::Int);
+            self.stat.iregs[10] = ret;
+        }
+        Ok(Flow::Next)
+    }
+
+    ///外部函数调用之后caller saved寄存器(除ra外)的值是不确定的
+    fn clobber_caller_saved(&mut self) {
+        for color in 0..64 {
+            let reg = Reg::from_color(color);
+            if !reg.is_caller_save() || color == 1 {
+                continue;
+            }
+            if color < 32 {
+                self.stat.iregs[color as usize] = POISON;
+            } else {
+                self.stat.fregs[color as usize - 32] = FPOISON;
+            }
+        }
+    }
+
+    ///记录一个子线程,它从create返回处开始执行,a0为线程编号
+    ///子线程只保留ra和callee saved寄存器,使用自己的栈,栈顶复制父线程栈上的内容
+    fn create_thread(&mut self) -> Result<(), ExecError> {
+        let id = self.threads.len() as i64 + 1;
+        let mut child = ThreadStat {
+            iregs: [POISON; 32],
+            fregs: [FPOISON; 32],
+            pc: self.stat.pc,
+        };
+        for color in 0..32 {
+            let reg = Reg::from_color(color);
+            if color <= 4 || reg.is_callee_save() {
+                child.iregs[color as usize] = self.stat.iregs[color as usize];
+            }
+        }
+        child.iregs[10] = id;
+
+        let base = thread_stack_base(id);
+        let top = base + THREAD_STACK_SIZE as i64;
+        if !self.memory.contains(base) {
+            self.memory.add_segment(base, THREAD_STACK_SIZE);
+        }
+        let sp = top - THREAD_COPY_SIZE as i64;
+        let bytes = self
+            .memory
+            .read(self.stat.iregs[2], THREAD_COPY_SIZE)?
+            .to_vec();
+        self.memory.write(sp, &bytes)?;
+        child.iregs[2] = sp;
+        self.threads.push(child);
+        Ok(())
+    }
+
+    ///主线程到达join时依次运行所有子线程,直到它们也到达join
+    fn join_threads(&mut self) -> Result<(), ExecError> {
+        let children = std::mem::take(&mut self.threads);
+        let main = self.stat.clone();
+        for (i, child) in children.into_iter().enumerate() {
+            self.stat = child;
+            self.thread = i as i32 + 1;
+            if self.run()?.is_some() {
+                return Err(trap("child thread exited the program"));
+            }
+        }
+        self.stat = main;
+        self.thread = 0;
+        Ok(())
+    }
+}
+
+///子线程栈的起始地址,相邻两段之间留出同样大小的空隙,越过栈顶的访问会报错
+fn thread_stack_base(id: i64) -> i64 {
+    THREAD_STACK_BASE + (id - 1) * 2 * THREAD_STACK_SIZE as i64
+}
+
+///子线程栈顶只有create时从父线程栈上复制的内容,
+///真实的运行库中更高的地址是线程库自己的数据,父线程栈帧中未复制的部分在子线程中无法访问
+pub(super) fn check_thread_stack(thread: i32, addr: i64) -> Result<(), ExecError> {
+    if thread == 0 {
+        return Ok(());
+    }
+    let top = thread_stack_base(thread as i64) + THREAD_STACK_SIZE as i64;
+    if addr >= top && addr < top + THREAD_STACK_SIZE as i64 {
+        return Err(ExecError::Trap(format!(
+            "child thread {} accessed byte {} of the parent's frame, \
+             but hitsz_thread_create only copies the first {} bytes",
+            thread,
+            addr - (top - THREAD_COPY_SIZE as i64),
+            THREAD_COPY_SIZE
+        )));
+    }
+    Ok(())
+}
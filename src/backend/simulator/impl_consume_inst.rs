@@ -3,10 +3,12 @@ use crate::backend::{
     operand::Reg,
 };
 
+use crate::utility::ObjPtr;
+
 use super::{
     execute_stat::ExecuteStat,
+    program_stat::ProgramStat,
     structs::{Value, ValueType},
-    *,
 };
 
 ///简单值传递 , mv, li,la等
@@ -161,8 +163,11 @@ impl ProgramStat {
         let dst_reg = inst.get_dst().drop_reg();
         self.reg_val.insert(dst_reg, Value::Inst(*inst));
     }
-    //对这个没法分析
-    pub fn consume_store_param_to_stack(&mut self, inst: &ObjPtr<LIRInst>) {}
+    //对这个没法分析,保守地认为栈上的值都失效
+    pub fn consume_store_param_to_stack(&mut self, inst: &ObjPtr<LIRInst>) {
+        debug_assert!(inst.get_type() == InstrsType::StoreParamToStack);
+        self.miss_certain_mem("sp_init");
+    }
 }
 
 ///关于执行流的变化:call,branch,jump等
@@ -281,7 +286,7 @@ impl ProgramStat {
             match rhs {
                 Operand::Reg(rhs) => {
                     if let Some(r_val) = self.reg_val.get(rhs) {
-                        let new_v = do_calc(calc_kind, r_val, l_val);
+                        let new_v = do_calc(calc_kind, l_val, r_val);
                         if new_v.is_none() {
                             self.reg_val.insert(def_reg, Value::Inst(inst));
                         } else {
@@ -314,6 +319,7 @@ fn do_calc(kind: InstrsType, one: &Value, another: &Value) -> Option<Value> {
     match kind {
         InstrsType::Binary(cal) => match cal {
             BinaryOp::Add => Value::add(one, another),
+            BinaryOp::Sub => Value::minus(one, another),
             _ => None,
         },
         _ => unreachable!("do_calc kind is not binary"),
@@ -0,0 +1,86 @@
+use crate::ir::ExecError;
+
+/// 一段连续的内存
+struct Segment {
+    base: i64,
+    data: Vec<u8>,
+}
+
+///模拟的内存
+///由数据段和各线程的栈段组成,访问段以外的地址会报错
+pub struct Memory {
+    segments: Vec<Segment>,
+}
+
+impl Memory {
+    pub fn new() -> Memory {
+        Memory {
+            segments: Vec::new(),
+        }
+    }
+
+    ///加入一段从base开始的内存,内容初始为0
+    pub fn add_segment(&mut self, base: i64, size: usize) {
+        debug_assert!(self.segments.iter().all(|seg| {
+            base + size as i64 <= seg.base || seg.base + seg.data.len() as i64 <= base
+        }));
+        self.segments.push(Segment {
+            base,
+            data: vec![0; size],
+        });
+    }
+
+    ///判断是否已经有包含该地址的段
+    pub fn contains(&self, addr: i64) -> bool {
+        self.locate(addr, 1).is_ok()
+    }
+
+    fn locate(&self, addr: i64, len: usize) -> Result<(usize, usize), ExecError> {
+        for (i, seg) in self.segments.iter().enumerate() {
+            if addr >= seg.base && addr + len as i64 <= seg.base + seg.data.len() as i64 {
+                return Ok((i, (addr - seg.base) as usize));
+            }
+        }
+        Err(ExecError::Trap(format!(
+            "invalid memory access of {} bytes at {:#x}",
+            len, addr
+        )))
+    }
+
+    pub fn read(&self, addr: i64, len: usize) -> Result<&[u8], ExecError> {
+        let (seg, offset) = self.locate(addr, len)?;
+        Ok(&self.segments[seg].data[offset..offset + len])
+    }
+
+    pub fn write(&mut self, addr: i64, bytes: &[u8]) -> Result<(), ExecError> {
+        let (seg, offset) = self.locate(addr, bytes.len())?;
+        self.segments[seg].data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    pub fn read_u32(&self, addr: i64) -> Result<u32, ExecError> {
+        let bytes = self.read(addr, 4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn read_u64(&self, addr: i64) -> Result<u64, ExecError> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.read(addr, 8)?);
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    pub fn write_u32(&mut self, addr: i64, value: u32) -> Result<(), ExecError> {
+        self.write(addr, &value.to_le_bytes())
+    }
+
+    pub fn write_u64(&mut self, addr: i64, value: u64) -> Result<(), ExecError> {
+        self.write(addr, &value.to_le_bytes())
+    }
+
+    ///把一段内存填充为同一个字节
+    pub fn fill(&mut self, addr: i64, byte: u8, len: usize) -> Result<(), ExecError> {
+        let (seg, offset) = self.locate(addr, len)?;
+        self.segments[seg].data[offset..offset + len].fill(byte);
+        Ok(())
+    }
+}
@@ -1,27 +1,902 @@
-use std::collections::{HashMap, LinkedList};
+use std::cmp::Ordering::{Equal, Less};
+use std::collections::HashMap;
 
-use crate::utility::ObjPtr;
+use crate::backend::block::ADDR_SIZE;
+use crate::backend::module::AsmModule;
+use crate::backend::operand::{is_imm_12bs, Reg};
+use crate::backend::structs::GlobalVar;
+use crate::ir::{trap, ExecError, ExecResult, ProgramInput};
+use crate::utility::{ObjPtr, ScalarType};
 
-use self::{program_stat::ProgramStat, structs::BuiltInFunc};
+use self::{builtin::check_thread_stack, memory::Memory, structs::BuiltInFunc};
 
-use super::instrs::{Func, BB};
+use super::instrs::{BinaryOp, CmpOp, Func, InstrsType, LIRInst, Operand, SingleOp};
 
 // 对程序进行建模
 //以为 死代码消除 ,编译时计算等提供接口
+// Simulator则在寄存器分配之后真正地执行整个AsmModule,用于检查生成的汇编
+mod builtin;
 pub mod execute_stat;
 mod impl_consume_inst;
+mod memory;
 pub mod program_stat;
 pub mod structs;
 
+///返回地址所在的区域,返回地址由调用点的编号得到
+const CODE_BASE: i64 = 0x1_0000;
+///main函数的返回地址
+const EXIT_ADDR: i64 = CODE_BASE;
+///全局变量和常量数组所在的区域
+const DATA_BASE: i64 = 0x1000_0000;
+///主线程的栈
+const STACK_TOP: i64 = 0x7fff_0000;
+const STACK_SIZE: usize = 256 << 20;
+///子线程的栈,每个线程一段
+const THREAD_STACK_BASE: i64 = 0x4000_0000;
+const THREAD_STACK_SIZE: usize = 8 << 20;
+///调用外部函数后caller saved寄存器的值
+const POISON: i64 = 0x5a5a_5a5a_5a5a_5a5a;
+const FPOISON: u32 = 0x7fc0_5a5a;
+
+///指令的位置
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct Pc {
+    func: ObjPtr<Func>,
+    block: usize,
+    inst: usize,
+}
+
+///一个线程的寄存器和执行位置
+#[derive(Clone)]
+struct ThreadStat {
+    iregs: [i64; 32],
+    ///浮点寄存器保存单精度浮点数的位
+    fregs: [u32; 32],
+    pc: Pc,
+}
+
+///一条指令执行后的去向
+enum Flow {
+    Next,
+    ///main函数返回
+    Exit(i32),
+    ///子线程到达join,结束运行
+    Joined,
+}
+
 ///解释器
-pub struct Simulator {
+///在寄存器分配之后执行AsmModule,指令的语义与生成的汇编相同:
+///prologue/epilogue按Context中记录的栈大小和保存的寄存器模拟,
+///调用外部函数后caller saved寄存器被写入无意义的值,
+///自动并行化的子线程在主线程到达join时依次运行
+pub struct Simulator<'a> {
     ///块缓存(只有在块缓存中的内容才能够解释执行)
-    name_blocks: HashMap<String, ObjPtr<BB>>,
+    name_blocks: HashMap<ObjPtr<Func>, HashMap<String, usize>>,
     name_funcs: HashMap<String, ObjPtr<Func>>,
     ///内置函数
     build_in_funcs: HashMap<String, BuiltInFunc>,
-    ///程序资源状态
-    program_stat: ProgramStat,
-    ///调用栈 (实际解释执行的时候需要)
-    call_stack: LinkedList<(ObjPtr<BB>, usize)>, //函数调用栈
+    ///全局变量和常量数组的地址
+    labels: HashMap<String, i64>,
+    memory: Memory,
+    stat: ThreadStat,
+    ///返回地址对应的调用点
+    ret_sites: Vec<Pc>,
+    ret_ids: HashMap<Pc, usize>,
+    input: ProgramInput<'a>,
+    output: String,
+    steps: u64,
+    step_limit: u64,
+    ///当前线程编号,0为主线程
+    thread: i32,
+    ///已创建但还没有运行的子线程
+    threads: Vec<ThreadStat>,
+}
+
+///模拟执行汇编模块的main函数
+pub fn simulate(module: &AsmModule, input: &str, step_limit: u64) -> Result<ExecResult, ExecError> {
+    let mut simulator = Simulator::new(module, input, step_limit)?;
+    let ret = simulator.run()?;
+    match ret {
+        Some(ret) => Ok(ExecResult {
+            ret,
+            output: simulator.output,
+            steps: simulator.steps,
+        }),
+        None => Err(trap("main thread reached hitsz_thread_join as a child")),
+    }
+}
+
+impl<'a> Simulator<'a> {
+    pub fn new(
+        module: &AsmModule,
+        input: &'a str,
+        step_limit: u64,
+    ) -> Result<Simulator<'a>, ExecError> {
+        let mut name_funcs = HashMap::new();
+        let mut name_blocks = HashMap::new();
+        for (name, func) in module.name_func.iter() {
+            if func.is_extern {
+                continue;
+            }
+            name_funcs.insert(name.clone(), *func);
+            let labels = func
+                .blocks
+                .iter()
+                .enumerate()
+                .map(|(i, bb)| (bb.label.clone(), i))
+                .collect();
+            name_blocks.insert(*func, labels);
+        }
+        let main = match name_funcs.get("main") {
+            Some(main) => *main,
+            None => return Err(trap("no main function")),
+        };
+        let build_in_funcs = BuiltInFunc::all()
+            .into_iter()
+            .map(|(name, func)| (name.to_string(), func))
+            .collect();
+
+        let mut simulator = Simulator {
+            name_blocks,
+            name_funcs,
+            build_in_funcs,
+            labels: HashMap::new(),
+            memory: Memory::new(),
+            stat: ThreadStat {
+                iregs: [0; 32],
+                fregs: [0; 32],
+                pc: Pc {
+                    func: main,
+                    block: 0,
+                    inst: 0,
+                },
+            },
+            ret_sites: Vec::new(),
+            ret_ids: HashMap::new(),
+            input: ProgramInput::new(input),
+            output: String::new(),
+            steps: 0,
+            step_limit,
+            thread: 0,
+            threads: Vec::new(),
+        };
+        simulator.init_data(module);
+        //sp之上留出一页,对应真实进程栈顶的参数和环境变量
+        simulator
+            .memory
+            .add_segment(STACK_TOP - STACK_SIZE as i64, STACK_SIZE);
+        simulator.stat.iregs[2] = STACK_TOP - 4096;
+        simulator.stat.iregs[1] = EXIT_ADDR;
+        simulator.enter(main)?;
+        Ok(simulator)
+    }
+
+    ///为全局变量和各函数的常量数组分配数据段
+    fn init_data(&mut self, module: &AsmModule) {
+        let mut objects: Vec<(String, Vec<u32>)> = Vec::new();
+        for (_, var) in module.global_var_list.iter() {
+            let words = match var {
+                GlobalVar::IGlobalVar(var) => vec![var.get_init().get_data() as u32],
+                GlobalVar::FGlobalVar(var) => vec![var.get_init().get_data().to_bits()],
+                GlobalVar::GlobalConstIntArray(array) => {
+                    array_words(array.size, array.value.iter().map(|v| *v as u32))
+                }
+                GlobalVar::GlobalConstFloatArray(array) => {
+                    array_words(array.size, array.value.iter().map(|v| v.to_bits()))
+                }
+            };
+            objects.push((var.get_name().clone(), words));
+        }
+        let mut funcs: Vec<_> = self.name_funcs.values().copied().collect();
+        funcs.sort_by(|a, b| a.label.cmp(&b.label));
+        for func in funcs {
+            for array in func.const_array.iter() {
+                let words = array_words(array.size, array.value.iter().map(|v| *v as u32));
+                objects.push((array.name.clone(), words));
+            }
+            for array in func.float_array.iter() {
+                let words = array_words(array.size, array.value.iter().map(|v| v.to_bits()));
+                objects.push((array.name.clone(), words));
+            }
+        }
+
+        let mut addr = DATA_BASE;
+        let mut contents = Vec::new();
+        for (name, words) in objects {
+            if self.labels.contains_key(&name) {
+                continue;
+            }
+            self.labels.insert(name, addr);
+            addr += (words.len() as i64 * 4 + 7) / 8 * 8;
+            contents.push(words);
+        }
+        self.memory
+            .add_segment(DATA_BASE, (addr - DATA_BASE).max(8) as usize);
+        let mut addr = DATA_BASE;
+        for words in contents {
+            for (i, word) in words.iter().enumerate() {
+                self.memory.write_u32(addr + i as i64 * 4, *word).unwrap();
+            }
+            addr += (words.len() as i64 * 4 + 7) / 8 * 8;
+        }
+    }
+
+    ///运行当前线程,直到main函数返回或者子线程到达join
+    fn run(&mut self) -> Result<Option<i32>, ExecError> {
+        loop {
+            let pc = self.stat.pc;
+            let func = pc.func;
+            if pc.block >= func.blocks.len() {
+                return Err(ExecError::Trap(format!(
+                    "execution fell off the end of function `{}`",
+                    func.label
+                )));
+            }
+            let block = func.blocks[pc.block];
+            if pc.inst >= block.insts.len() {
+                //顺序执行下一个块
+                self.stat.pc.block += 1;
+                self.stat.pc.inst = 0;
+                continue;
+            }
+            self.steps += 1;
+            if self.steps > self.step_limit {
+                return Err(ExecError::StepLimit(self.step_limit));
+            }
+            self.stat.pc.inst += 1;
+            let inst = block.insts[pc.inst];
+            let flow = self.exec(inst).map_err(|err| match err {
+                ExecError::Trap(msg) => ExecError::Trap(format!(
+                    "{} (at `{}` in block `{}` of `{}`)",
+                    msg,
+                    inst.as_ref(),
+                    block.label,
+                    func.label
+                )),
+                err => err,
+            })?;
+            match flow {
+                Flow::Next => {}
+                Flow::Exit(ret) => return Ok(Some(ret)),
+                Flow::Joined => return Ok(None),
+            }
+        }
+    }
+
+    ///吞入一条指令,修改程序状态
+    fn exec(&mut self, inst: ObjPtr<LIRInst>) -> Result<Flow, ExecError> {
+        match inst.get_type() {
+            InstrsType::Binary(op) => self.exec_binary(&inst, op)?,
+            InstrsType::OpReg(op) => self.exec_single(&inst, op)?,
+            InstrsType::Load => {
+                let offset = imm12(inst.get_offset().get_data())?;
+                let addr = self.ireg(&reg_of(inst.get_lhs())?)? + offset;
+                self.load(&inst, addr)?;
+            }
+            InstrsType::Store => {
+                let offset = imm12(inst.get_offset().get_data())?;
+                let addr = self.ireg(&reg_of(inst.get_lhs())?)? + offset;
+                self.store(&inst, addr)?;
+            }
+            InstrsType::LoadFromStack => {
+                let offset = imm12(inst.get_stack_offset().get_data())?;
+                self.load(&inst, self.stat.iregs[2] + offset)?;
+            }
+            InstrsType::StoreToStack => {
+                let offset = imm12(inst.get_stack_offset().get_data())?;
+                self.store(&inst, self.stat.iregs[2] + offset)?;
+            }
+            InstrsType::LoadParamFromStack | InstrsType::StoreParamToStack => {
+                let context = self.stat.pc.func.context;
+                let offset = imm12(context.get_offset() - inst.get_stack_offset().get_data())?;
+                let addr = self.stat.iregs[2] + offset;
+                if inst.get_type() == InstrsType::LoadParamFromStack {
+                    self.load(&inst, addr)?;
+                } else {
+                    self.store(&inst, addr)?;
+                }
+            }
+            InstrsType::Branch(cond) => {
+                let lhs = self.ireg(&reg_of(inst.get_lhs())?)?;
+                let taken = match cond {
+                    CmpOp::Eqz => lhs == 0,
+                    CmpOp::Nez => lhs != 0,
+                    _ => {
+                        let rhs = self.ireg(&reg_of(inst.get_rhs())?)?;
+                        match cond {
+                            CmpOp::Eq => lhs == rhs,
+                            CmpOp::Ne => lhs != rhs,
+                            CmpOp::Lt => lhs < rhs,
+                            CmpOp::Le => lhs <= rhs,
+                            CmpOp::Gt => lhs > rhs,
+                            CmpOp::Ge => lhs >= rhs,
+                            _ => unreachable!(),
+                        }
+                    }
+                };
+                if taken {
+                    self.jump(&inst.get_bb_label().unwrap())?;
+                }
+            }
+            InstrsType::Jump => self.jump(&inst.get_bb_label().unwrap())?,
+            InstrsType::Call => {
+                let name = inst.get_func_name().unwrap();
+                self.stat.iregs[1] = self.ret_addr(self.stat.pc);
+                if let Some(func) = self.name_funcs.get(&name) {
+                    let func = *func;
+                    self.enter(func)?;
+                } else if let Some(func) = self.build_in_funcs.get(&name) {
+                    let func = *func;
+                    return self.call_builtin(func);
+                } else {
+                    return Err(ExecError::Trap(format!(
+                        "call to unknown function `{}`",
+                        name
+                    )));
+                }
+            }
+            InstrsType::Ret(..) => return self.ret(),
+        }
+        Ok(Flow::Next)
+    }
+
+    fn exec_binary(&mut self, inst: &LIRInst, op: BinaryOp) -> Result<(), ExecError> {
+        let dst = reg_of(inst.get_dst())?;
+        let lhs = reg_of(inst.get_lhs())?;
+        let is_float = dst.get_type() == ScalarType::Float
+            || matches!(inst.get_rhs(), Operand::Reg(reg) if reg.get_type() == ScalarType::Float);
+        if is_float {
+            let rhs = match inst.get_rhs() {
+                Operand::Reg(reg) => self.freg(reg)?,
+                _ => return Err(trap("float operation with an immediate operand")),
+            };
+            let lhs = self.freg(&lhs)?;
+            match op {
+                BinaryOp::Add => self.set_freg(&dst, lhs + rhs)?,
+                BinaryOp::Sub => self.set_freg(&dst, lhs - rhs)?,
+                BinaryOp::Mul => self.set_freg(&dst, lhs * rhs)?,
+                BinaryOp::Div => self.set_freg(&dst, lhs / rhs)?,
+                //fne/fgt/fge生成为feq/fle/flt取反,与NaN比较时结果为1
+                BinaryOp::FCmp(cmp) => {
+                    let value = match cmp {
+                        CmpOp::Eq => lhs == rhs,
+                        CmpOp::Ne => lhs != rhs,
+                        CmpOp::Lt => lhs < rhs,
+                        CmpOp::Le => lhs <= rhs,
+                        CmpOp::Gt => !matches!(lhs.partial_cmp(&rhs), Some(Less | Equal)),
+                        CmpOp::Ge => !matches!(rhs.partial_cmp(&lhs), Some(Less)),
+                        _ => return Err(trap("invalid float comparison")),
+                    };
+                    self.set_ireg(&dst, value as i64)?;
+                }
+                _ => {
+                    return Err(ExecError::Trap(format!(
+                        "unsupported float operation {:?}",
+                        op
+                    )))
+                }
+            }
+            return Ok(());
+        }
+
+        let lhs = self.ireg(&lhs)?;
+        let (rhs, is_imm) = match inst.get_rhs() {
+            Operand::Reg(reg) => (self.ireg(reg)?, false),
+            Operand::IImm(imm) => (imm.get_data() as i64, true),
+            _ => return Err(trap("invalid operand of integer operation")),
+        };
+        //and/or/xor/slt没有w版本,总是64位运算
+        let is_double = inst.is_double()
+            || matches!(
+                op,
+                BinaryOp::And | BinaryOp::Or | BinaryOp::Xor | BinaryOp::Slt
+            );
+        if is_imm {
+            match op {
+                BinaryOp::Shl | BinaryOp::Shr | BinaryOp::Sar => {
+                    let bits = if is_double { 64 } else { 32 };
+                    if rhs < 0 || rhs >= bits {
+                        return Err(ExecError::Trap(format!(
+                            "shift amount {} out of range",
+                            rhs
+                        )));
+                    }
+                }
+                BinaryOp::Add | BinaryOp::And | BinaryOp::Or | BinaryOp::Xor | BinaryOp::Slt => {
+                    imm12(rhs as i32)?;
+                }
+                _ => {
+                    return Err(ExecError::Trap(format!(
+                        "{:?} does not take an immediate operand",
+                        op
+                    )))
+                }
+            }
+        }
+        let value = if is_double {
+            int_op(op, lhs, rhs)?
+        } else {
+            int_op(op, lhs as i32 as i64, rhs as i32 as i64)
+                .map(|_| int_op_w(op, lhs as i32, rhs as i32))? as i64
+        };
+        self.set_ireg(&dst, value)
+    }
+
+    fn exec_single(&mut self, inst: &LIRInst, op: SingleOp) -> Result<(), ExecError> {
+        let dst = reg_of(inst.get_dst())?;
+        match op {
+            SingleOp::Li => {
+                let value = match inst.get_lhs() {
+                    Operand::IImm(imm) => imm.get_data() as i64,
+                    Operand::FImm(fimm) => fimm.get_data().to_bits() as i32 as i64,
+                    //除法优化中的magic number
+                    Operand::Addr(num) => num
+                        .parse::<i64>()
+                        .map_err(|_| ExecError::Trap(format!("invalid immediate `{}`", num)))?,
+                    Operand::Reg(_) => return Err(trap("li with a register operand")),
+                };
+                self.set_ireg(&dst, value)?;
+            }
+            SingleOp::LoadAddr => {
+                let label = match inst.get_lhs() {
+                    Operand::Addr(label) => label,
+                    _ => return Err(trap("la without a label")),
+                };
+                let addr = match self.labels.get(label) {
+                    Some(addr) => *addr,
+                    None => return Err(ExecError::Trap(format!("unknown label `{}`", label))),
+                };
+                self.set_ireg(&dst, addr)?;
+            }
+            _ => {
+                let src = reg_of(inst.get_lhs())?;
+                let is_float = src.get_type() == ScalarType::Float;
+                match op {
+                    SingleOp::Mv if is_float => self.set_freg_bits(&dst, self.freg_bits(&src)?)?,
+                    SingleOp::Mv => self.set_ireg(&dst, self.ireg(&src)?)?,
+                    SingleOp::Neg if is_float => {
+                        self.set_freg_bits(&dst, self.freg_bits(&src)? ^ 0x8000_0000)?
+                    }
+                    SingleOp::Neg => self.set_ireg(&dst, self.ireg(&src)?.wrapping_neg())?,
+                    SingleOp::I2F => self.set_freg(&dst, self.ireg(&src)? as i32 as f32)?,
+                    //fcvt.w.s rtz: 向零取整,溢出时饱和,NaN转换为最大值
+                    SingleOp::F2I => {
+                        let value = self.freg(&src)?;
+                        let value = if value.is_nan() {
+                            i32::MAX
+                        } else {
+                            value as i32
+                        };
+                        self.set_ireg(&dst, value as i64)?;
+                    }
+                    SingleOp::Seqz => self.set_ireg(&dst, (self.ireg(&src)? == 0) as i64)?,
+                    SingleOp::Snez => self.set_ireg(&dst, (self.ireg(&src)? != 0) as i64)?,
+                    SingleOp::LoadFImm => self.set_freg_bits(&dst, self.ireg(&src)? as u32)?,
+                    SingleOp::Li | SingleOp::LoadAddr => unreachable!(),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    ///浮点寄存器或者标记为float的指令使用flw,否则根据是否为double使用ld或lw
+    fn load(&mut self, inst: &LIRInst, addr: i64) -> Result<(), ExecError> {
+        check_thread_stack(self.thread, addr)?;
+        let dst = reg_of(inst.get_dst())?;
+        if inst.is_float() || dst.get_type() == ScalarType::Float {
+            let value = self.memory.read_u32(addr)?;
+            self.set_freg_bits(&dst, value)
+        } else if inst.is_double() {
+            let value = self.memory.read_u64(addr)?;
+            self.set_ireg(&dst, value as i64)
+        } else {
+            let value = self.memory.read_u32(addr)?;
+            self.set_ireg(&dst, value as i32 as i64)
+        }
+    }
+
+    fn store(&mut self, inst: &LIRInst, addr: i64) -> Result<(), ExecError> {
+        check_thread_stack(self.thread, addr)?;
+        let src = reg_of(inst.get_dst())?;
+        if inst.is_float() || src.get_type() == ScalarType::Float {
+            let value = self.freg_bits(&src)?;
+            self.memory.write_u32(addr, value)
+        } else if inst.is_double() {
+            let value = self.ireg(&src)?;
+            self.memory.write_u64(addr, value as u64)
+        } else {
+            let value = self.ireg(&src)?;
+            self.memory.write_u32(addr, value as u32)
+        }
+    }
+
+    fn jump(&mut self, label: &str) -> Result<(), ExecError> {
+        let func = self.stat.pc.func;
+        match self.name_blocks.get(&func).unwrap().get(label) {
+            Some(block) => {
+                self.stat.pc = Pc {
+                    func,
+                    block: *block,
+                    inst: 0,
+                };
+                Ok(())
+            }
+            None => Err(ExecError::Trap(format!(
+                "jump to unknown label `{}`",
+                label
+            ))),
+        }
+    }
+
+    ///进入函数,模拟prologue:开栈并保存ra和callee saved寄存器
+    fn enter(&mut self, func: ObjPtr<Func>) -> Result<(), ExecError> {
+        let context = func.context;
+        let stack_size = context.get_offset() + ADDR_SIZE;
+        let old_sp = self.stat.iregs[2];
+        let sp = old_sp - stack_size as i64;
+        self.stat.iregs[2] = sp;
+        for (reg, offset) in context.get_saved_regs().iter() {
+            let addr = sp + *offset as i64;
+            if reg.get_type() == ScalarType::Float {
+                self.memory.write_u32(addr, self.freg_bits(reg)?)?;
+            } else {
+                self.memory.write_u64(addr, self.ireg(reg)? as u64)?;
+            }
+        }
+        //栈过大时用s0计算地址
+        if !is_imm_12bs(stack_size) {
+            self.stat.iregs[8] = old_sp;
+        }
+        self.stat.pc = Pc {
+            func,
+            block: 0,
+            inst: 0,
+        };
+        Ok(())
+    }
+
+    ///模拟epilogue并返回到ra
+    fn ret(&mut self) -> Result<Flow, ExecError> {
+        let context = self.stat.pc.func.context;
+        let stack_size = context.get_offset() + ADDR_SIZE;
+        let sp = self.stat.iregs[2];
+        for (reg, offset) in context.get_saved_regs().iter() {
+            let addr = sp + *offset as i64;
+            if reg.get_type() == ScalarType::Float {
+                let value = self.memory.read_u32(addr)?;
+                self.set_freg_bits(reg, value)?;
+            } else {
+                let value = self.memory.read_u64(addr)?;
+                self.set_ireg(reg, value as i64)?;
+            }
+        }
+        self.stat.iregs[2] = sp + stack_size as i64;
+        if !is_imm_12bs(stack_size) {
+            self.stat.iregs[8] = stack_size as i64;
+        }
+
+        let ra = self.stat.iregs[1];
+        if ra == EXIT_ADDR {
+            if self.thread != 0 {
+                return Err(trap("child thread returned from main"));
+            }
+            return Ok(Flow::Exit(self.stat.iregs[10] as i32));
+        }
+        let site = (ra - CODE_BASE) / 4 - 1;
+        if ra % 4 != 0 || site < 0 || site >= self.ret_sites.len() as i64 {
+            return Err(ExecError::Trap(format!(
+                "return to invalid address {:#x}",
+                ra
+            )));
+        }
+        self.stat.pc = self.ret_sites[site as usize];
+        Ok(Flow::Next)
+    }
+
+    ///调用点对应的返回地址
+    fn ret_addr(&mut self, pc: Pc) -> i64 {
+        let id = match self.ret_ids.get(&pc) {
+            Some(id) => *id,
+            None => {
+                self.ret_sites.push(pc);
+                self.ret_ids.insert(pc, self.ret_sites.len() - 1);
+                self.ret_sites.len() - 1
+            }
+        };
+        CODE_BASE + 4 * (id as i64 + 1)
+    }
+}
+
+///寄存器的读写
+impl<'a> Simulator<'a> {
+    fn ireg(&self, reg: &Reg) -> Result<i64, ExecError> {
+        Ok(self.stat.iregs[reg_index(reg, ScalarType::Int)?])
+    }
+
+    fn set_ireg(&mut self, reg: &Reg, value: i64) -> Result<(), ExecError> {
+        let index = reg_index(reg, ScalarType::Int)?;
+        if index != 0 {
+            self.stat.iregs[index] = value;
+        }
+        Ok(())
+    }
+
+    fn freg_bits(&self, reg: &Reg) -> Result<u32, ExecError> {
+        Ok(self.stat.fregs[reg_index(reg, ScalarType::Float)?])
+    }
+
+    fn set_freg_bits(&mut self, reg: &Reg, value: u32) -> Result<(), ExecError> {
+        self.stat.fregs[reg_index(reg, ScalarType::Float)?] = value;
+        Ok(())
+    }
+
+    fn freg(&self, reg: &Reg) -> Result<f32, ExecError> {
+        Ok(f32::from_bits(self.freg_bits(reg)?))
+    }
+
+    fn set_freg(&mut self, reg: &Reg, value: f32) -> Result<(), ExecError> {
+        self.set_freg_bits(reg, value.to_bits())
+    }
+}
+
+///物理寄存器在寄存器组中的下标
+fn reg_index(reg: &Reg, r_type: ScalarType) -> Result<usize, ExecError> {
+    if reg.is_virtual() {
+        return Err(ExecError::Trap(format!(
+            "virtual register {} after register allocation",
+            reg
+        )));
+    }
+    if reg.get_type() != r_type {
+        return Err(ExecError::Trap(format!(
+            "expected {:?} register, found {}",
+            r_type,
+            reg.to_string(false)
+        )));
+    }
+    match r_type {
+        ScalarType::Float => Ok((reg.get_id() - 32) as usize),
+        _ => Ok(reg.get_id() as usize),
+    }
+}
+
+fn reg_of(operand: &Operand) -> Result<Reg, ExecError> {
+    match operand {
+        Operand::Reg(reg) => Ok(*reg),
+        _ => Err(ExecError::Trap(format!(
+            "expected register, found {:?}",
+            operand
+        ))),
+    }
+}
+
+///汇编中的偏移和立即数只能是12位
+fn imm12(imm: i32) -> Result<i64, ExecError> {
+    if is_imm_12bs(imm) {
+        Ok(imm as i64)
+    } else {
+        Err(ExecError::Trap(format!("immediate {} out of range", imm)))
+    }
+}
+
+fn array_words(size: i32, values: impl Iterator<Item = u32>) -> Vec<u32> {
+    let mut words: Vec<u32> = values.collect();
+    if words.len() < size as usize {
+        words.resize(size as usize, 0);
+    }
+    words
+}
+
+///64位整数运算,除法的结果与RISC-V相同:除以0得到-1,取余得到被除数
+fn int_op(op: BinaryOp, lhs: i64, rhs: i64) -> Result<i64, ExecError> {
+    let value = match op {
+        BinaryOp::Add => lhs.wrapping_add(rhs),
+        BinaryOp::Sub => lhs.wrapping_sub(rhs),
+        BinaryOp::Mul => lhs.wrapping_mul(rhs),
+        BinaryOp::Div if rhs == 0 => -1,
+        BinaryOp::Div => lhs.wrapping_div(rhs),
+        BinaryOp::Rem if rhs == 0 => lhs,
+        BinaryOp::Rem => lhs.wrapping_rem(rhs),
+        BinaryOp::UDiv if rhs == 0 => -1,
+        BinaryOp::UDiv => (lhs as u64 / rhs as u64) as i64,
+        BinaryOp::URem if rhs == 0 => lhs,
+        BinaryOp::URem => (lhs as u64 % rhs as u64) as i64,
+        BinaryOp::And => lhs & rhs,
+        BinaryOp::Or => lhs | rhs,
+        BinaryOp::Xor => lhs ^ rhs,
+        BinaryOp::Slt => (lhs < rhs) as i64,
+        BinaryOp::Shl => lhs.wrapping_shl((rhs & 63) as u32),
+        BinaryOp::Shr => ((lhs as u64) >> (rhs & 63)) as i64,
+        BinaryOp::Sar => lhs >> (rhs & 63),
+        BinaryOp::FCmp(_) => return Err(trap("float comparison of integer registers")),
+    };
+    Ok(value)
+}
+
+///w后缀的32位运算,结果符号扩展到64位
+fn int_op_w(op: BinaryOp, lhs: i32, rhs: i32) -> i32 {
+    match op {
+        BinaryOp::Add => lhs.wrapping_add(rhs),
+        BinaryOp::Sub => lhs.wrapping_sub(rhs),
+        BinaryOp::Mul => lhs.wrapping_mul(rhs),
+        BinaryOp::Div if rhs == 0 => -1,
+        BinaryOp::Div => lhs.wrapping_div(rhs),
+        BinaryOp::Rem if rhs == 0 => lhs,
+        BinaryOp::Rem => lhs.wrapping_rem(rhs),
+        BinaryOp::UDiv if rhs == 0 => -1,
+        BinaryOp::UDiv => (lhs as u32 / rhs as u32) as i32,
+        BinaryOp::URem if rhs == 0 => lhs,
+        BinaryOp::URem => (lhs as u32 % rhs as u32) as i32,
+        BinaryOp::Shl => lhs.wrapping_shl((rhs & 31) as u32),
+        BinaryOp::Shr => ((lhs as u32) >> (rhs & 31)) as i32,
+        BinaryOp::Sar => lhs >> (rhs & 31),
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod simulator_test {
+    use super::{builtin::check_thread_stack, int_op, int_op_w, memory::Memory};
+    use super::{THREAD_STACK_BASE, THREAD_STACK_SIZE};
+    use crate::backend::{generate_asm, instrs::BinaryOp, module::AsmModule};
+    use crate::ir::{interpret, test_util::parse_module, DEFAULT_STEP_LIMIT};
+    use crate::pass_manager::PassManager;
+
+    #[test]
+    fn test_riscv_division() {
+        assert_eq!(int_op_w(BinaryOp::Div, 7, 0), -1);
+        assert_eq!(int_op_w(BinaryOp::Rem, 7, 0), 7);
+        assert_eq!(int_op_w(BinaryOp::Div, i32::MIN, -1), i32::MIN);
+        assert_eq!(int_op_w(BinaryOp::Rem, i32::MIN, -1), 0);
+        assert_eq!(int_op_w(BinaryOp::Div, -7, 2), -3);
+        assert_eq!(int_op_w(BinaryOp::Rem, -7, 2), -1);
+        assert_eq!(int_op(BinaryOp::Div, i64::MIN, -1), Ok(i64::MIN));
+        assert_eq!(int_op_w(BinaryOp::UDiv, -1, 2), i32::MAX);
+        assert_eq!(int_op_w(BinaryOp::URem, -1, 16), 15);
+        assert_eq!(int_op_w(BinaryOp::UDiv, 7, 0), -1);
+        assert_eq!(int_op_w(BinaryOp::URem, 7, 0), 7);
+    }
+
+    #[test]
+    fn test_shift() {
+        assert_eq!(int_op_w(BinaryOp::Shl, 1, 33), 2);
+        assert_eq!(int_op_w(BinaryOp::Shr, -1, 28), 0xf);
+        assert_eq!(int_op_w(BinaryOp::Sar, -16, 2), -4);
+        assert_eq!(int_op(BinaryOp::Shr, -1, 60), Ok(0xf));
+        assert_eq!(int_op(BinaryOp::Slt, -1, 0), Ok(1));
+        assert!(int_op(BinaryOp::FCmp(crate::backend::instrs::CmpOp::Eq), 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_memory() {
+        let mut memory = Memory::new();
+        memory.add_segment(0x1000, 16);
+        memory.write_u64(0x1000, 0x1122_3344_5566_7788).unwrap();
+        assert_eq!(memory.read_u32(0x1000), Ok(0x5566_7788));
+        assert_eq!(memory.read_u32(0x1004), Ok(0x1122_3344));
+        memory.fill(0x1008, 0xff, 8).unwrap();
+        assert_eq!(memory.read_u64(0x1008), Ok(u64::MAX));
+        assert!(memory.read_u32(0x100e).is_err());
+        assert!(memory.write_u32(0xffc, 0).is_err());
+        assert!(memory.contains(0x100f));
+        assert!(!memory.contains(0x1010));
+    }
+
+    #[test]
+    fn test_thread_stack() {
+        let top = THREAD_STACK_BASE + THREAD_STACK_SIZE as i64;
+        assert!(check_thread_stack(1, top - 4).is_ok());
+        assert!(check_thread_stack(0, top + 4).is_ok());
+        let err = check_thread_stack(1, top + 4).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "child thread 1 accessed byte 804 of the parent's frame, \
+             but hitsz_thread_create only copies the first 800 bytes"
+        );
+    }
+
+    #[test]
+    fn test_int_binary_ir() {
+        //操作数来自输入,不会在编译时被折叠,寄存器和立即数两种形式都要经过指令选择
+        let ir = "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %a = call i32 @getint()
+  %b = call i32 @getint()
+  %r0 = and i32 %a, %b
+  %r1 = or i32 %a, %b
+  %r2 = xor i32 %a, %b
+  %r3 = shl i32 %a, %b
+  %r4 = lshr i32 %a, %b
+  %r5 = ashr i32 %a, %b
+  %r6 = udiv i32 %a, %b
+  %r7 = urem i32 %a, %b
+  %r8 = and i32 %a, 255
+  %r9 = or i32 %a, 4096
+  %r10 = xor i32 %a, -1
+  %r11 = shl i32 %a, 5
+  %r12 = lshr i32 %a, 28
+  %r13 = ashr i32 %a, 3
+  %r14 = udiv i32 %a, 16
+  %r15 = urem i32 %a, 16
+  %r16 = udiv i32 %a, 7
+  %r17 = urem i32 %a, 7
+  call void @putint(i32 noundef %r0)
+  call void @putch(i32 noundef 32)
+  call void @putint(i32 noundef %r1)
+  call void @putch(i32 noundef 32)
+  call void @putint(i32 noundef %r2)
+  call void @putch(i32 noundef 32)
+  call void @putint(i32 noundef %r3)
+  call void @putch(i32 noundef 32)
+  call void @putint(i32 noundef %r4)
+  call void @putch(i32 noundef 32)
+  call void @putint(i32 noundef %r5)
+  call void @putch(i32 noundef 32)
+  call void @putint(i32 noundef %r6)
+  call void @putch(i32 noundef 32)
+  call void @putint(i32 noundef %r7)
+  call void @putch(i32 noundef 32)
+  call void @putint(i32 noundef %r8)
+  call void @putch(i32 noundef 32)
+  call void @putint(i32 noundef %r9)
+  call void @putch(i32 noundef 32)
+  call void @putint(i32 noundef %r10)
+  call void @putch(i32 noundef 32)
+  call void @putint(i32 noundef %r11)
+  call void @putch(i32 noundef 32)
+  call void @putint(i32 noundef %r12)
+  call void @putch(i32 noundef 32)
+  call void @putint(i32 noundef %r13)
+  call void @putch(i32 noundef 32)
+  call void @putint(i32 noundef %r14)
+  call void @putch(i32 noundef 32)
+  call void @putint(i32 noundef %r15)
+  call void @putch(i32 noundef 32)
+  call void @putint(i32 noundef %r16)
+  call void @putch(i32 noundef 32)
+  call void @putint(i32 noundef %r17)
+  ret i32 0
+}
+
+declare i32 @getint()
+declare void @putint(i32)
+declare void @putch(i32)
+";
+        let expected = "4 -99 -103 -3200 134217724 -4 858993439 1 \
+                        156 -100 99 -3200 15 -13 268435449 12 613566742 2";
+        let input = "-100 5";
+        let (module, _) = parse_module(ir);
+        let result = interpret(&module, input, DEFAULT_STEP_LIMIT).unwrap();
+        assert_eq!(result.output, expected);
+
+        //调试构建的后端会在当前目录写入日志文件,在临时目录中生成汇编
+        let dir = std::env::temp_dir().join(format!("sysy_sim_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cwd = std::env::current_dir().unwrap();
+        std::env::set_current_dir(&dir).unwrap();
+        let mut outputs = vec![];
+        for is_opt in [false, true] {
+            let (module, _) = parse_module(ir);
+            let result = generate_asm(
+                "int_binary.ll",
+                "int_binary.s",
+                "row_asm.log",
+                &mut AsmModule::new(module),
+                is_opt,
+                false,
+                &PassManager::new(),
+                Some(input),
+            )
+            .unwrap();
+            outputs.push(result);
+        }
+        std::env::set_current_dir(cwd).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        for result in outputs {
+            let result = result.unwrap();
+            assert_eq!(result.output, expected);
+            assert_eq!(result.ret, 0);
+        }
+    }
 }
@@ -1,6 +1,9 @@
 use std::{collections::HashMap, hash::Hash};
 
-use crate::{backend::instrs::LIRInst, utility::ObjPtr};
+use crate::{
+    backend::instrs::LIRInst,
+    utility::{ObjPtr, ScalarType},
+};
 
 ///复杂值类型 (实际实现的时候需要)
 pub struct ComplexValue {
@@ -154,15 +157,37 @@ impl PartialOrd for Value {
     }
     ///value并不都能排序
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        todo!()
+        match (self, other) {
+            (Value::IImm(lhs), Value::IImm(rhs)) => lhs.partial_cmp(rhs),
+            (Value::Addr(lhs), Value::Addr(rhs)) if lhs.0 == rhs.0 => lhs.1.partial_cmp(&rhs.1),
+            _ if self == other => Some(std::cmp::Ordering::Equal),
+            _ => None,
+        }
     }
 }
 
 ///实现一些对value的运算
 impl Value {
     //只有同类型的值才可以进行加减运算
-    pub fn add_another(&mut self, another: &Value) {}
-    pub fn minus_another(&mut self, another: &Value) {}
+    ///运算成功时修改自身并返回true,否则保持不变并返回false
+    pub fn add_another(&mut self, another: &Value) -> bool {
+        match Value::add(self, another) {
+            Some(new_v) => {
+                *self = new_v;
+                true
+            }
+            None => false,
+        }
+    }
+    pub fn minus_another(&mut self, another: &Value) -> bool {
+        match Value::minus(self, another) {
+            Some(new_v) => {
+                *self = new_v;
+                true
+            }
+            None => false,
+        }
+    }
 
     ///如果运算成功,返回值,如果失败,返回None
     pub fn add(one: &Value, another: &Value) -> Option<Value> {
@@ -185,7 +210,80 @@ impl Value {
         }
         None
     }
+
+    ///整数相减,地址减去偏移,或者同一数组的两个地址相减
+    pub fn minus(one: &Value, another: &Value) -> Option<Value> {
+        match (one, another) {
+            (Value::IImm(v1), Value::IImm(v2)) => Some(Value::IImm(v1 - v2)),
+            (Value::Addr(addr), Value::IImm(imm)) => {
+                Some(Value::Addr((addr.0.clone(), addr.1 - imm)))
+            }
+            (Value::Addr(a1), Value::Addr(a2)) if a1.0 == a2.0 => Some(Value::IImm(a1.1 - a2.1)),
+            _ => None,
+        }
+    }
 }
 
 ///内置函数 (比如一些io函数)
-pub struct BuiltInFunc {}
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BuiltInFunc {
+    GetInt,
+    GetCh,
+    GetFloat,
+    GetArray,
+    GetFArray,
+    PutInt,
+    PutCh,
+    PutFloat,
+    PutArray,
+    PutFArray,
+    StartTime,
+    StopTime,
+    Memset,
+    Memcpy,
+    ThreadInit,
+    ThreadCreate,
+    ThreadJoin,
+}
+
+impl BuiltInFunc {
+    ///所有内置函数及其在汇编中被调用的名字
+    pub fn all() -> Vec<(&'static str, BuiltInFunc)> {
+        vec![
+            ("getint", BuiltInFunc::GetInt),
+            ("getch", BuiltInFunc::GetCh),
+            ("getfloat", BuiltInFunc::GetFloat),
+            ("getarray", BuiltInFunc::GetArray),
+            ("getfarray", BuiltInFunc::GetFArray),
+            ("putint", BuiltInFunc::PutInt),
+            ("putch", BuiltInFunc::PutCh),
+            ("putfloat", BuiltInFunc::PutFloat),
+            ("putarray", BuiltInFunc::PutArray),
+            ("putfarray", BuiltInFunc::PutFArray),
+            ("_sysy_starttime", BuiltInFunc::StartTime),
+            ("_sysy_stoptime", BuiltInFunc::StopTime),
+            ("memset", BuiltInFunc::Memset),
+            ("memset@plt", BuiltInFunc::Memset),
+            ("memcpy", BuiltInFunc::Memcpy),
+            ("memcpy@plt", BuiltInFunc::Memcpy),
+            ("hitsz_thread_init", BuiltInFunc::ThreadInit),
+            ("hitsz_thread_create", BuiltInFunc::ThreadCreate),
+            ("hitsz_thread_join", BuiltInFunc::ThreadJoin),
+        ]
+    }
+
+    ///返回值所在的寄存器类型
+    pub fn ret_type(&self) -> ScalarType {
+        match self {
+            BuiltInFunc::GetInt
+            | BuiltInFunc::GetCh
+            | BuiltInFunc::GetArray
+            | BuiltInFunc::GetFArray
+            | BuiltInFunc::Memset
+            | BuiltInFunc::Memcpy
+            | BuiltInFunc::ThreadCreate => ScalarType::Int,
+            BuiltInFunc::GetFloat => ScalarType::Float,
+            _ => ScalarType::Void,
+        }
+    }
+}
@@ -7,7 +7,8 @@ pub use std::io::Result;
 
 use crate::backend::block::BB;
 use crate::backend::instrs::{LIRInst, Operand};
-use crate::backend::operand::{FImm, IImm};
+use crate::backend::operand::{FImm, IImm, Reg};
+use crate::ir::analysis::value_range::ValueRange;
 use crate::ir::basicblock::BasicBlock;
 use crate::ir::instruction::Inst;
 use crate::utility::ObjPtr;
@@ -56,6 +57,8 @@ pub struct Context {
     reg_info: HashMap<i32, i32>,
     epilogue: Option<Box<dyn FnMut()>>,
     prologue: Option<Box<dyn FnMut()>>,
+    /// prologue中保存的寄存器及其相对于开栈后sp的偏移,供模拟执行使用
+    saved_regs: Vec<(Reg, i32)>,
     pub is_row: bool,
 }
 
@@ -73,6 +76,7 @@ impl Context {
             reg_info: HashMap::new(),
             epilogue: None,
             prologue: None,
+            saved_regs: Vec::new(),
             is_row: false,
         }
     }
@@ -101,6 +105,14 @@ impl Context {
         self.stack_offset
     }
 
+    pub fn set_saved_regs(&mut self, saved_regs: Vec<(Reg, i32)>) {
+        self.saved_regs = saved_regs;
+    }
+
+    pub fn get_saved_regs(&self) -> &Vec<(Reg, i32)> {
+        &self.saved_regs
+    }
+
     pub fn call_epilogue_event(&mut self) {
         if let Some(ref mut callback) = self.epilogue {
             callback();
@@ -217,6 +229,8 @@ pub struct Mapping {
 
     pub val_map: HashMap<ObjPtr<Inst>, Operand>,
     pub phis_to_block: HashMap<String, Vec<ObjPtr<LIRInst>>>,
+    /// ir函数的值范围,用于判断除法和取模的被除数是否非负
+    pub value_range: ValueRange,
 }
 
 impl Mapping {
@@ -226,6 +240,7 @@ impl Mapping {
             block_ir_map: HashMap::new(),
             val_map: HashMap::new(),
             phis_to_block: HashMap::new(),
+            value_range: ValueRange::default(),
         }
     }
 }
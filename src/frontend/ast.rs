@@ -1,3 +1,16 @@
+/// 源码中的一段区间,以字节偏移表示
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+}
+
 #[derive(Debug)]
 pub struct CompUnit {
     pub global_items: Vec<GlobalItems>,
@@ -21,7 +34,7 @@ pub struct ConstDecl {
     pub const_def_vec: Vec<ConstDef>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BType {
     Int,
     Float,
@@ -32,6 +45,7 @@ pub struct ConstDef {
     pub ident: Ident,
     pub const_exp_vec: Vec<ConstExp>,
     pub const_init_val: ConstInitVal,
+    pub span: Span,
 }
 
 #[derive(Debug)]
@@ -48,10 +62,10 @@ pub struct VarDecl {
 
 #[derive(Debug)]
 pub enum VarDef {
-    NonArray(Ident),
-    Array((Ident, Vec<ConstExp>)),
-    NonArrayInit((Ident, InitVal)),
-    ArrayInit((Ident, Vec<ConstExp>, InitVal)),
+    NonArray((Ident, Span)),
+    Array((Ident, Vec<ConstExp>, Span)),
+    NonArrayInit((Ident, InitVal, Span)),
+    ArrayInit((Ident, Vec<ConstExp>, InitVal, Span)),
 }
 
 #[derive(Debug)]
@@ -62,8 +76,8 @@ pub enum InitVal {
 
 #[derive(Debug)]
 pub enum FuncDef {
-    NonParameterFuncDef((FuncType, Ident, Block)),
-    ParameterFuncDef((FuncType, Ident, FuncFParams, Block)),
+    NonParameterFuncDef((FuncType, Ident, Block, Span)),
+    ParameterFuncDef((FuncType, Ident, FuncFParams, Block, Span)),
 }
 
 #[derive(Debug)]
@@ -80,8 +94,8 @@ pub struct FuncFParams {
 
 #[derive(Debug)]
 pub enum FuncFParam {
-    NonArray((BType, Ident)),
-    Array((BType, Ident, Vec<Exp>)),
+    NonArray((BType, Ident, Span)),
+    Array((BType, Ident, Vec<Exp>, Span)),
 }
 
 #[derive(Debug)]
@@ -132,30 +146,40 @@ pub struct While {
 }
 
 #[derive(Debug)]
-pub struct Break;
+pub struct Break {
+    pub span: Span,
+}
 
 #[derive(Debug)]
-pub struct Continue;
+pub struct Continue {
+    pub span: Span,
+}
 
 #[derive(Debug)]
 pub struct Return {
     pub exp: Option<Exp>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub struct Exp {
     pub add_exp: Box<AddExp>,
+    pub span: Span,
+    /// 语义分析后标注的类型
+    pub ty: Option<ExpType>,
 }
 
 #[derive(Debug)]
 pub struct Cond {
     pub l_or_exp: LOrExp,
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub struct LVal {
     pub id: Ident,
     pub exp_vec: Vec<Exp>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
@@ -179,7 +203,7 @@ pub struct OptionFuncRParams {
 #[derive(Debug)]
 pub enum UnaryExp {
     PrimaryExp(Box<PrimaryExp>),
-    FuncCall((Ident, OptionFuncRParams)),
+    FuncCall((Ident, OptionFuncRParams, Span)),
     OpUnary((UnaryOp, Box<UnaryExp>)),
 }
 
@@ -245,6 +269,43 @@ pub enum LAndExp {
 #[derive(Debug)]
 pub struct ConstExp {
     pub add_exp: AddExp,
+    pub span: Span,
+    /// 语义分析后标注的类型
+    pub ty: Option<ExpType>,
+}
+
+/// 表达式的类型
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExpType {
+    Int,
+    Float,
+    Void,
+    /// 数组或数组的一部分,记录元素类型和各维长度
+    /// 长度为None表示未知,如数组参数的第一维
+    Array(BType, Vec<Option<i32>>),
+}
+
+impl std::fmt::Display for ExpType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExpType::Int => write!(f, "int"),
+            ExpType::Float => write!(f, "float"),
+            ExpType::Void => write!(f, "void"),
+            ExpType::Array(btype, dims) => {
+                match btype {
+                    BType::Int => write!(f, "int")?,
+                    BType::Float => write!(f, "float")?,
+                }
+                for dim in dims {
+                    match dim {
+                        Some(len) => write!(f, "[{}]", len)?,
+                        None => write!(f, "[]")?,
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -1,8 +1,218 @@
-#[derive(Debug)]
+use std::fmt;
+
+use lalrpop_util::ParseError;
+
+use super::ast::Span;
+use super::preprocess::Preprocessed;
+
+#[derive(Debug, Clone)]
 pub enum Error {
     Todo,
-    VariableNotFound,
+    /// 使用了未声明的变量
+    VariableNotFound(String),
+    /// 调用了未声明的函数
+    FunctionNotFound(String),
     PushPhiInGlobalDomain,
-    MultipleDeclaration,
+    /// 同一作用域内重复声明
+    MultipleDeclaration(String),
     FindVarError,
+    /// 数组维度不合法,如维度不是正的整型常量、下标个数过多
+    ArrayDimension(String),
+    /// 函数调用的实参个数与形参个数不一致
+    ArgumentCount {
+        func: String,
+        expected: usize,
+        found: usize,
+    },
+    /// 类型不匹配
+    TypeMismatch(String),
+    /// 语法错误
+    Syntax(String),
+    /// 其他语义错误,如break不在循环内、给常量赋值
+    Semantic(String),
+    /// 预处理错误
+    Preprocess(String),
+    /// 编译器内部错误,如符号的类型不完整、函数的最后一条指令不是返回
+    Internal(String),
+    /// 附带源码位置的错误
+    Located(Box<Error>, Span),
+}
+
+impl Error {
+    /// 给错误附上源码位置
+    /// 已经带有位置的错误保留最内层(最精确)的位置
+    pub fn at(self, span: Span) -> Error {
+        match self {
+            Error::Located(..) => self,
+            _ => Error::Located(Box::new(self), span),
+        }
+    }
+
+    /// 获得错误的源码位置
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Error::Located(_, span) => Some(*span),
+            _ => None,
+        }
+    }
+
+    /// 生成带源码摘录和下划线的诊断信息
+    pub fn render(&self, source: &str, filename: &str) -> String {
+        let span = match self.span() {
+            Some(span) => span,
+            None => return format!("error: {}\n --> {}\n", self, filename),
+        };
+        let (line, col) = line_col(source, span.start);
+        let text = source.lines().nth(line - 1).unwrap_or("");
+        let len = source[span.start.min(source.len())..span.end.clamp(span.start, source.len())]
+            .chars()
+            .count();
+        self.render_at(filename, line, col, text, len)
+    }
+
+    /// 生成诊断信息,位置通过预处理的行映射指回原始源码
+    pub fn render_with(&self, pre: &Preprocessed) -> String {
+        let span = match self.span() {
+            Some(span) => span,
+            None => return format!("error: {}\n --> {}\n", self, pre.filename()),
+        };
+        let start = pre.locate(span.start);
+        let end = pre.locate(span.end);
+        let len = if end.file == start.file && end.line == start.line {
+            end.col.saturating_sub(start.col)
+        } else {
+            usize::MAX
+        };
+        self.render_at(start.file, start.line, start.col, start.text, len)
+    }
+
+    fn render_at(&self, filename: &str, line: usize, col: usize, text: &str, len: usize) -> String {
+        let mut out = format!("error: {}\n", self);
+        let width = line.to_string().len();
+        let pad = " ".repeat(width);
+        // 下划线只画到行尾,至少一个字符
+        let line_len = text.chars().count();
+        let len = len.min(line_len.saturating_sub(col - 1)).max(1);
+        out.push_str(&format!("{}--> {}:{}:{}\n", pad, filename, line, col));
+        out.push_str(&format!("{} |\n", pad));
+        out.push_str(&format!("{} | {}\n", line, text));
+        out.push_str(&format!(
+            "{} | {}{}\n",
+            pad,
+            " ".repeat(col - 1),
+            "^".repeat(len)
+        ));
+        out
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Todo => write!(f, "unsupported construct"),
+            Error::VariableNotFound(name) => write!(f, "use of undeclared identifier `{}`", name),
+            Error::FunctionNotFound(name) => write!(f, "call to undeclared function `{}`", name),
+            Error::PushPhiInGlobalDomain => write!(f, "phi pushed in global domain"),
+            Error::MultipleDeclaration(name) => write!(f, "redeclaration of `{}`", name),
+            Error::FindVarError => write!(f, "variable lookup failed"),
+            Error::ArrayDimension(msg) => write!(f, "{}", msg),
+            Error::ArgumentCount {
+                func,
+                expected,
+                found,
+            } => write!(
+                f,
+                "function `{}` expects {} argument(s), but {} were given",
+                func, expected, found
+            ),
+            Error::TypeMismatch(msg) => write!(f, "type mismatch: {}", msg),
+            Error::Syntax(msg) => write!(f, "syntax error: {}", msg),
+            Error::Semantic(msg) => write!(f, "{}", msg),
+            Error::Preprocess(msg) => write!(f, "{}", msg),
+            Error::Internal(msg) => write!(f, "internal compiler error: {}", msg),
+            Error::Located(err, _) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl<T: fmt::Display> From<&ParseError<usize, T, &str>> for Error {
+    /// 将LALRPOP产生的语法错误转换为带位置的诊断信息
+    fn from(err: &ParseError<usize, T, &str>) -> Error {
+        let (msg, span) = match err {
+            ParseError::InvalidToken { location } => (
+                "invalid token".to_string(),
+                Span::new(*location, *location + 1),
+            ),
+            ParseError::UnrecognizedEOF { location, expected } => (
+                format!("unexpected end of file{}", expected_str(expected)),
+                Span::new(*location, *location),
+            ),
+            ParseError::UnrecognizedToken {
+                token: (l, tok, r),
+                expected,
+            } => (
+                format!("unexpected token `{}`{}", tok, expected_str(expected)),
+                Span::new(*l, *r),
+            ),
+            ParseError::ExtraToken { token: (l, tok, r) } => {
+                (format!("extra token `{}`", tok), Span::new(*l, *r))
+            }
+            ParseError::User { error } => (error.to_string(), Span::default()),
+        };
+        Error::Syntax(msg).at(span)
+    }
+}
+
+/// 生成期望的终结符列表,正则表示的终结符换成可读的名字
+fn expected_str(expected: &[String]) -> String {
+    let mut names: Vec<&str> = vec![];
+    for token in expected {
+        let name = if !token.starts_with("r#") {
+            token.as_str()
+        } else if token.contains("_a-zA-Z") {
+            "identifier"
+        } else if token.contains('.') || token.contains("eE") || token.contains("pP") {
+            "float literal"
+        } else {
+            "integer literal"
+        };
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    match names.len() {
+        0 => String::new(),
+        1 => format!(", expected {}", names[0]),
+        _ => format!(", expected one of {}", names.join(", ")),
+    }
+}
+
+/// 将字节偏移转换为从1开始的行号和列号
+pub fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let offset = offset.min(source.len());
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let col = source[line_start..offset].chars().count() + 1;
+    (line, col)
+}
+
+#[cfg(test)]
+mod error_test {
+    use super::{line_col, Error};
+    use crate::frontend::ast::Span;
+
+    #[test]
+    fn test_render() {
+        let source = "int main() {\n    return b;\n}\n";
+        let start = source.find('b').unwrap();
+        assert_eq!(line_col(source, start), (2, 12));
+        let err = Error::VariableNotFound("b".to_string()).at(Span::new(start, start + 1));
+        let expected = "error: use of undeclared identifier `b`\n \
+                        --> test.sy:2:12\n  \
+                        |\n\
+                        2 |     return b;\n  \
+                        |            ^\n";
+        assert_eq!(err.render(source, "test.sy"), expected);
+    }
 }
@@ -18,7 +18,7 @@ pub fn irgen(
     pool_inst_mut: &mut ObjPool<Inst>,
     pool_bb_mut: &mut ObjPool<BasicBlock>,
     pool_func_mut: &mut ObjPool<Function>,
-) {
+) -> Result<(), Error> {
     let mut pool_scope = ObjPool::new();
     let context_mut = pool_scope.put(Context::make_context(module_mut)).as_mut();
     let mut kit_mut = Kit {
@@ -28,9 +28,10 @@ pub fn irgen(
         pool_func_mut,
     };
     kit_mut.init_external_funcs();
-    compunit.process(1, &mut kit_mut).unwrap();
-    kit_mut.phi_padding_allfunctions();
-    kit_mut.merge_allfunctions();
+    compunit.process(1, &mut kit_mut)?;
+    kit_mut.phi_padding_allfunctions()?;
+    kit_mut.merge_allfunctions()?;
+    Ok(())
 }
 
 pub trait Process {
@@ -45,7 +46,7 @@ impl Process for CompUnit {
     fn process(&mut self, _input: Self::Message, kit_mut: &mut Kit) -> Result<Self::Ret, Error> {
         for item in &mut self.global_items {
             kit_mut.context_mut.set_stop_genir(false);
-            item.process(1, kit_mut).unwrap();
+            item.process(1, kit_mut)?;
         }
         return Ok(1);
     }
@@ -60,11 +61,11 @@ impl Process for GlobalItems {
         } else {
             match self {
                 Self::Decl(decl) => {
-                    decl.process(1, kit_mut).unwrap();
+                    decl.process(1, kit_mut)?;
                     Ok(1)
                 }
                 Self::FuncDef(funcdef) => {
-                    let inst_func = funcdef.process(true, kit_mut).unwrap();
+                    let inst_func = funcdef.process(true, kit_mut)?;
                     match kit_mut.context_mut.bb_now_mut {
                         InfuncChoice::InFunc(bb_now) => {
                             if !bb_now.get_up_bb().is_empty()
@@ -208,11 +209,11 @@ impl Process for Decl {
         } else {
             match self {
                 Self::ConstDecl(constdecl) => {
-                    constdecl.process(input, kit_mut).unwrap();
+                    constdecl.process(input, kit_mut)?;
                     return Ok(1);
                 }
                 Self::VarDecl(vardef) => {
-                    vardef.process(input, kit_mut).unwrap();
+                    vardef.process(input, kit_mut)?;
                     return Ok(1);
                 }
             }
@@ -232,7 +233,7 @@ impl Process for ConstDecl {
                         let (mut inst_ptr, val, _) = def
                             .const_init_val
                             .process((Type::ConstInt, vec![], 0, 1), kit_mut)
-                            .unwrap();
+                            .map_err(|e| e.at(def.span))?;
 
                         match val {
                             //构造const指令
@@ -258,7 +259,9 @@ impl Process for ConstDecl {
                                 Some(inst_ptr),
                                 Vec::new(),
                             ) {
-                                return Err(Error::MultipleDeclaration);
+                                return Err(
+                                    Error::MultipleDeclaration(def.ident.clone()).at(def.span)
+                                );
                             }
                         } else {
                             if !kit_mut.context_mut.add_var(
@@ -270,7 +273,9 @@ impl Process for ConstDecl {
                                 None,
                                 Vec::new(),
                             ) {
-                                return Err(Error::MultipleDeclaration);
+                                return Err(
+                                    Error::MultipleDeclaration(def.ident.clone()).at(def.span)
+                                );
                             }
                         }
                         //这里
@@ -280,26 +285,10 @@ impl Process for ConstDecl {
                             .update_var_scope_now(&def.ident, inst_ptr); //update会将全局变量放入module中不会将局部变量放入bb中
                     } else {
                         //数组
-                        let mut dimension = vec![];
+                        let mut dimension_vec_in = vec![];
                         for exp in &mut def.const_exp_vec {
-                            dimension.push(exp.process(Type::Int, kit_mut).unwrap());
-                        }
-                        let dimension_vec: Vec<_> = dimension.iter().map(|(_, x)| x).collect();
-                        let dimension_vec_in: Vec<_> = dimension_vec
-                            .iter()
-                            .map(|x| match x {
-                                ExpValue::Int(i) => *i,
-                                ExpValue::Float(_) => {
-                                    unreachable!()
-                                }
-                                ExpValue::None => {
-                                    unreachable!()
-                                }
-                                _ => {
-                                    unreachable!()
-                                }
-                            })
-                            .collect(); //生成维度vec
+                            dimension_vec_in.push(array_dimension(exp, kit_mut)?);
+                        } //生成维度vec
 
                         let mut length = 1;
                         for dm in &dimension_vec_in {
@@ -309,7 +298,7 @@ impl Process for ConstDecl {
                         let (_, _, init_vec) = def
                             .const_init_val
                             .process((Type::ConstInt, dimension_vec_in.clone(), 0, 1), kit_mut)
-                            .unwrap(); //获得初始值
+                            .map_err(|e| e.at(def.span))?; //获得初始值
                         match init_vec {
                             RetInitVec::Float(_) => {
                                 unreachable!()
@@ -342,7 +331,9 @@ impl Process for ConstDecl {
                                     None,
                                     dimension_vec_in.clone(),
                                 ) {
-                                    return Err(Error::MultipleDeclaration);
+                                    return Err(
+                                        Error::MultipleDeclaration(def.ident.clone()).at(def.span)
+                                    );
                                 } //添加该变量，但没有生成实际的指令
                                 kit_mut.context_mut.update_var_scope_now(&def.ident, inst);
                                 kit_mut.context_mut.push_inst_bb(inst);
@@ -358,7 +349,7 @@ impl Process for ConstDecl {
                         let (mut inst_ptr, val, _) = def
                             .const_init_val
                             .process((Type::ConstFloat, vec![], 0, 1), kit_mut)
-                            .unwrap();
+                            .map_err(|e| e.at(def.span))?;
 
                         let bond;
                         match val {
@@ -386,7 +377,9 @@ impl Process for ConstDecl {
                                 Some(inst_ptr),
                                 Vec::new(),
                             ) {
-                                return Err(Error::MultipleDeclaration);
+                                return Err(
+                                    Error::MultipleDeclaration(def.ident.clone()).at(def.span)
+                                );
                             }
                         } else {
                             if !kit_mut.context_mut.add_var(
@@ -398,7 +391,9 @@ impl Process for ConstDecl {
                                 None,
                                 Vec::new(),
                             ) {
-                                return Err(Error::MultipleDeclaration);
+                                return Err(
+                                    Error::MultipleDeclaration(def.ident.clone()).at(def.span)
+                                );
                             }
                         }
                         //这里
@@ -407,26 +402,10 @@ impl Process for ConstDecl {
                             .update_var_scope_now(&def.ident, inst_ptr);
                     } else {
                         //数组
-                        let mut dimension = vec![];
+                        let mut dimension_vec_in = vec![];
                         for exp in &mut def.const_exp_vec {
-                            dimension.push(exp.process(Type::Int, kit_mut).unwrap());
-                        }
-                        let dimension_vec: Vec<_> = dimension.iter().map(|(_, x)| x).collect();
-                        let dimension_vec_in: Vec<_> = dimension_vec
-                            .iter()
-                            .map(|x| match x {
-                                ExpValue::Int(i) => *i,
-                                ExpValue::Float(_) => {
-                                    unreachable!()
-                                }
-                                ExpValue::None => {
-                                    unreachable!()
-                                }
-                                _ => {
-                                    unreachable!()
-                                }
-                            })
-                            .collect(); //生成维度vec
+                            dimension_vec_in.push(array_dimension(exp, kit_mut)?);
+                        } //生成维度vec
 
                         let mut length = 1;
                         for dm in &dimension_vec_in {
@@ -436,7 +415,7 @@ impl Process for ConstDecl {
                         let (_, _, init_vec) = def
                             .const_init_val
                             .process((Type::ConstFloat, dimension_vec_in.clone(), 0, 1), kit_mut)
-                            .unwrap(); //获得初始值
+                            .map_err(|e| e.at(def.span))?; //获得初始值
                         match init_vec {
                             RetInitVec::Float(fvec) => {
                                 let mut vec_init = vec![];
@@ -468,7 +447,9 @@ impl Process for ConstDecl {
                                     None,
                                     dimension_vec_in.clone(),
                                 ) {
-                                    return Err(Error::MultipleDeclaration);
+                                    return Err(
+                                        Error::MultipleDeclaration(def.ident.clone()).at(def.span)
+                                    );
                                 } //添加该变量，但没有生成实际的指令
                                 kit_mut.context_mut.update_var_scope_now(&def.ident, inst);
                                 kit_mut.context_mut.push_inst_bb(inst);
@@ -491,7 +472,7 @@ impl Process for ConstInitVal {
     fn process(&mut self, input: Self::Message, kit_mut: &mut Kit) -> Result<Self::Ret, Error> {
         match self {
             ConstInitVal::ConstExp(constexp) => {
-                let (inst, value) = constexp.process(input.0, kit_mut).unwrap();
+                let (inst, value) = constexp.process(input.0, kit_mut)?;
                 match value {
                     ExpValue::Float(f) => {
                         let mut vec_ret = Vec::new();
@@ -523,9 +504,8 @@ impl Process for ConstInitVal {
                 }
                 // let after = dimension_vec[dimension_vec.len() - 1];
                 for val in constvalvec {
-                    let (_, _, vec_temp) = val
-                        .process((tp, dimension_vec.clone(), num_pre, layer_now + 1), kit_mut)
-                        .unwrap(); //子init_vec生成vec
+                    let (_, _, vec_temp) =
+                        val.process((tp, dimension_vec.clone(), num_pre, layer_now + 1), kit_mut)?; //子init_vec生成vec
                     match vec_temp {
                         //将子vec中值放到当前vec中
                         RetInitVec::Float(vec_float) => match tp {
@@ -570,8 +550,10 @@ impl Process for ConstInitVal {
                             RetInitVec::Int(vec_ret_int),
                         ));
                     }
-                    _ => {
-                        todo!()
+                    Type::NotForce => {
+                        return Err(Error::Internal(
+                            "initializer has no element type".to_string(),
+                        ));
                     }
                 }
             }
@@ -587,14 +569,14 @@ impl Process for VarDecl {
             BType::Int => {
                 for def in &mut self.var_def_vec {
                     match def {
-                        VarDef::NonArrayInit((id, val)) => match val {
+                        VarDef::NonArrayInit((id, val, span)) => match val {
                             InitVal::Exp(exp) => {
-                                let flag = exp.type_process(1, kit_mut).unwrap();
+                                let flag = exp.type_process(1, kit_mut)?;
                                 let mut tp = Type::Int;
                                 if flag > 1 {
                                     tp = Type::Float;
                                 }
-                                let (mut inst_ptr, val) = exp.process(tp, kit_mut).unwrap();
+                                let (mut inst_ptr, val) = exp.process(tp, kit_mut)?;
                                 let mut flag_trans = false;
                                 if flag > 1 {
                                     flag_trans = true;
@@ -616,7 +598,8 @@ impl Process for VarDecl {
                                                 Some(inst_ptr),
                                                 Vec::new(),
                                             ) {
-                                                return Err(Error::MultipleDeclaration);
+                                                return Err(Error::MultipleDeclaration(id.clone())
+                                                    .at(*span));
                                             }
                                             //这里
                                         }
@@ -632,7 +615,8 @@ impl Process for VarDecl {
                                                 Some(inst_ptr),
                                                 Vec::new(),
                                             ) {
-                                                return Err(Error::MultipleDeclaration);
+                                                return Err(Error::MultipleDeclaration(id.clone())
+                                                    .at(*span));
                                             }
                                         }
                                         _ => {
@@ -664,17 +648,23 @@ impl Process for VarDecl {
                                         None,
                                         Vec::new(),
                                     ) {
-                                        return Err(Error::MultipleDeclaration);
+                                        return Err(
+                                            Error::MultipleDeclaration(id.clone()).at(*span)
+                                        );
                                     }
                                 }
 
                                 kit_mut.context_mut.update_var_scope_now(id, inst_ptr);
                             }
                             InitVal::InitValVec(_) => {
-                                todo!()
+                                return Err(Error::Semantic(format!(
+                                    "scalar `{}` cannot be initialized with a list",
+                                    id
+                                ))
+                                .at(*span));
                             }
                         },
-                        VarDef::NonArray(id) => {
+                        VarDef::NonArray((id, span)) => {
                             if kit_mut.context_mut.get_layer() == -1 {
                                 //设计相关(全局变量指令与局部变量不同)，全局变量得在这额外判断，放到module里
                                 let inst_ptr = kit_mut.pool_inst_mut.make_global_int(0);
@@ -687,7 +677,7 @@ impl Process for VarDecl {
                                     Some(inst_ptr),
                                     Vec::new(),
                                 ) {
-                                    return Err(Error::MultipleDeclaration);
+                                    return Err(Error::MultipleDeclaration(id.clone()).at(*span));
                                 }
                                 kit_mut.context_mut.update_var_scope_now(id, inst_ptr);
                             } else {
@@ -700,34 +690,18 @@ impl Process for VarDecl {
                                     None,
                                     Vec::new(),
                                 ) {
-                                    return Err(Error::MultipleDeclaration);
+                                    return Err(Error::MultipleDeclaration(id.clone()).at(*span));
                                 }
                                 let inst_ptr = kit_mut.pool_inst_mut.make_int_const(0);
                                 kit_mut.context_mut.update_var_scope_now(id, inst_ptr);
                                 kit_mut.context_mut.push_inst_bb(inst_ptr);
                             }
                         }
-                        VarDef::ArrayInit((id, exp_vec, val)) => {
-                            let mut dimension = vec![];
+                        VarDef::ArrayInit((id, exp_vec, val, span)) => {
+                            let mut dimension_vec_in = vec![];
                             for exp in exp_vec {
-                                dimension.push(exp.process(Type::Int, kit_mut).unwrap());
-                            }
-                            let dimension_vec: Vec<_> = dimension.iter().map(|(_, x)| x).collect();
-                            let dimension_vec_in: Vec<_> = dimension_vec
-                                .iter()
-                                .map(|x| match x {
-                                    ExpValue::Int(i) => *i,
-                                    ExpValue::Float(_) => {
-                                        unreachable!()
-                                    }
-                                    ExpValue::None => {
-                                        unreachable!()
-                                    }
-                                    _ => {
-                                        unreachable!()
-                                    }
-                                })
-                                .collect(); //生成维度vec
+                                dimension_vec_in.push(array_dimension(exp, kit_mut)?);
+                            } //生成维度vec
 
                             let mut length = 1;
                             for dm in &dimension_vec_in {
@@ -735,7 +709,7 @@ impl Process for VarDecl {
                             }
                             let (init_vec, inst_vec) = val
                                 .process((Type::Int, dimension_vec_in.clone(), 0, 1), kit_mut)
-                                .unwrap(); //获得初始值
+                                .map_err(|e| e.at(*span))?; //获得初始值
                             match init_vec {
                                 RetInitVec::Int(mut ivec) => {
                                     let mut inst = kit_mut.pool_inst_mut.make_int_const(-1129);
@@ -779,7 +753,9 @@ impl Process for VarDecl {
                                         None, //可能得改,数组的就没有对全局区域留inst
                                         dimension_vec_in.clone(),
                                     ) {
-                                        return Err(Error::MultipleDeclaration);
+                                        return Err(
+                                            Error::MultipleDeclaration(id.clone()).at(*span)
+                                        );
                                     } //添加该变量，但没有生成实际的指令
                                     kit_mut.context_mut.update_var_scope_now(&id, inst);
                                     kit_mut.context_mut.push_inst_bb(inst);
@@ -801,27 +777,11 @@ impl Process for VarDecl {
                                 }
                             }
                         }
-                        VarDef::Array((id, exp_vec)) => {
-                            let mut dimension = vec![];
+                        VarDef::Array((id, exp_vec, span)) => {
+                            let mut dimension_vec_in = vec![];
                             for exp in exp_vec {
-                                dimension.push(exp.process(Type::Int, kit_mut).unwrap());
-                            }
-                            let dimension_vec: Vec<_> = dimension.iter().map(|(_, x)| x).collect();
-                            let dimension_vec_in: Vec<_> = dimension_vec
-                                .iter()
-                                .map(|x| match x {
-                                    ExpValue::Int(i) => *i,
-                                    ExpValue::Float(_) => {
-                                        unreachable!()
-                                    }
-                                    ExpValue::None => {
-                                        unreachable!()
-                                    }
-                                    _ => {
-                                        unreachable!()
-                                    }
-                                })
-                                .collect(); //生成维度vec
+                                dimension_vec_in.push(array_dimension(exp, kit_mut)?);
+                            } //生成维度vec
 
                             let mut length = 1;
                             for dm in &dimension_vec_in {
@@ -831,35 +791,35 @@ impl Process for VarDecl {
                             if kit_mut.context_mut.get_layer()<0{
                                 let inst = kit_mut.pool_inst_mut.make_int_array(length, true, ivec);
                             if !kit_mut.context_mut.add_var(
-                                &id,
-                                Type::Int,
-                                true,
-                                false,
-                                Some(inst),
-                                None,
-                                dimension_vec_in.clone(),
-                            ) {
-                                return Err(Error::MultipleDeclaration);
-                            } //添加该变量，但没有生成实际的指令
-                            kit_mut.context_mut.update_var_scope_now(&id, inst);
-                            kit_mut.context_mut.push_inst_bb(inst);
-                            }else{
-                                let inst = kit_mut.pool_inst_mut.make_int_array(length, false, ivec);
-                            if !kit_mut.context_mut.add_var(
-                                &id,
-                                Type::Int,
-                                true,
-                                false,
-                                Some(inst),
-                                None,
-                                dimension_vec_in.clone(),
-                            ) {
-                                return Err(Error::MultipleDeclaration);
-                            } //添加该变量，但没有生成实际的指令
-                            kit_mut.context_mut.update_var_scope_now(&id, inst);
-                            kit_mut.context_mut.push_inst_bb(inst);
+                                    &id,
+                                    Type::Int,
+                                    true,
+                                    false,
+                                    Some(inst),
+                                    None,
+                                    dimension_vec_in.clone(),
+                                ) {
+                                    return Err(Error::MultipleDeclaration(id.clone()).at(*span));
+                                } //添加该变量，但没有生成实际的指令
+                                kit_mut.context_mut.update_var_scope_now(&id, inst);
+                                kit_mut.context_mut.push_inst_bb(inst);
+                            } else {
+                                let inst =
+                                    kit_mut.pool_inst_mut.make_int_array(length, false, ivec);
+                                if !kit_mut.context_mut.add_var(
+                                    &id,
+                                    Type::Int,
+                                    true,
+                                    false,
+                                    Some(inst),
+                                    None,
+                                    dimension_vec_in.clone(),
+                                ) {
+                                    return Err(Error::MultipleDeclaration(id.clone()).at(*span));
+                                } //添加该变量，但没有生成实际的指令
+                                kit_mut.context_mut.update_var_scope_now(&id, inst);
+                                kit_mut.context_mut.push_inst_bb(inst);
                             }
-                            
                         }
                     }
                 }
@@ -868,14 +828,14 @@ impl Process for VarDecl {
             BType::Float => {
                 for def in &mut self.var_def_vec {
                     match def {
-                        VarDef::NonArrayInit((id, val)) => match val {
+                        VarDef::NonArrayInit((id, val, span)) => match val {
                             InitVal::Exp(exp) => {
-                                let flag = exp.type_process(1, kit_mut).unwrap();
+                                let flag = exp.type_process(1, kit_mut)?;
                                 let mut tp = Type::Int;
                                 if flag > 1 {
                                     tp = Type::Float;
                                 }
-                                let (mut inst_ptr, val) = exp.process(tp, kit_mut).unwrap();
+                                let (mut inst_ptr, val) = exp.process(tp, kit_mut)?;
                                 let mut flag_trans = false;
                                 if flag <= 1 {
                                     flag_trans = true;
@@ -894,7 +854,8 @@ impl Process for VarDecl {
                                                 Some(inst_ptr),
                                                 Vec::new(),
                                             ) {
-                                                return Err(Error::MultipleDeclaration);
+                                                return Err(Error::MultipleDeclaration(id.clone())
+                                                    .at(*span));
                                             }
                                             //这里
                                         }
@@ -910,7 +871,8 @@ impl Process for VarDecl {
                                                 Some(inst_ptr),
                                                 Vec::new(),
                                             ) {
-                                                return Err(Error::MultipleDeclaration);
+                                                return Err(Error::MultipleDeclaration(id.clone())
+                                                    .at(*span));
                                             }
                                         }
                                         _ => {
@@ -942,17 +904,23 @@ impl Process for VarDecl {
                                         None,
                                         Vec::new(),
                                     ) {
-                                        return Err(Error::MultipleDeclaration);
+                                        return Err(
+                                            Error::MultipleDeclaration(id.clone()).at(*span)
+                                        );
                                     }
                                 }
 
                                 kit_mut.context_mut.update_var_scope_now(id, inst_ptr);
                             }
                             InitVal::InitValVec(_) => {
-                                todo!()
+                                return Err(Error::Semantic(format!(
+                                    "scalar `{}` cannot be initialized with a list",
+                                    id
+                                ))
+                                .at(*span));
                             }
                         },
-                        VarDef::NonArray(id) => {
+                        VarDef::NonArray((id, span)) => {
                             if kit_mut.context_mut.get_layer() == -1 {
                                 //设计相关(全局变量指令与局部变量不同)，全局变量得在这额外判断，放到module里
                                 let inst_ptr = kit_mut.pool_inst_mut.make_global_float(0.0);
@@ -965,7 +933,7 @@ impl Process for VarDecl {
                                     Some(inst_ptr),
                                     vec![],
                                 ) {
-                                    return Err(Error::MultipleDeclaration);
+                                    return Err(Error::MultipleDeclaration(id.clone()).at(*span));
                                 }
                                 kit_mut.context_mut.update_var_scope_now(id, inst_ptr);
                             } else {
@@ -978,41 +946,25 @@ impl Process for VarDecl {
                                     None,
                                     vec![],
                                 ) {
-                                    return Err(Error::MultipleDeclaration);
+                                    return Err(Error::MultipleDeclaration(id.clone()).at(*span));
                                 }
                                 let inst_ptr = kit_mut.pool_inst_mut.make_float_const(0.0);
                                 kit_mut.context_mut.update_var_scope_now(id, inst_ptr);
                                 kit_mut.context_mut.push_inst_bb(inst_ptr);
                             }
                         }
-                        VarDef::ArrayInit((id, exp_vec, val)) => {
-                            let mut dimension = vec![];
+                        VarDef::ArrayInit((id, exp_vec, val, span)) => {
+                            let mut dimension_vec_in = vec![];
                             for exp in exp_vec {
-                                dimension.push(exp.process(Type::Int, kit_mut).unwrap());
-                            }
-                            let dimension_vec: Vec<_> = dimension.iter().map(|(_, x)| x).collect();
-                            let dimension_vec_in: Vec<_> = dimension_vec
-                                .iter()
-                                .map(|x| match x {
-                                    ExpValue::Int(i) => *i,
-                                    ExpValue::Float(_) => {
-                                        unreachable!()
-                                    }
-                                    ExpValue::None => {
-                                        unreachable!()
-                                    }
-                                    _ => {
-                                        unreachable!()
-                                    }
-                                })
-                                .collect(); //生成维度vec
+                                dimension_vec_in.push(array_dimension(exp, kit_mut)?);
+                            } //生成维度vec
                             let mut length = 1;
                             for dm in &dimension_vec_in {
                                 length = length * dm;
                             }
                             let (init_vec, inst_vec) = val
                                 .process((Type::Float, dimension_vec_in.clone(), 0, 1), kit_mut)
-                                .unwrap(); //获得初始值
+                                .map_err(|e| e.at(*span))?; //获得初始值
                             match init_vec {
                                 RetInitVec::Float(mut fvec) => {
                                     let mut last_index = 0;
@@ -1056,7 +1008,9 @@ impl Process for VarDecl {
                                         None,
                                         dimension_vec_in.clone(),
                                     ) {
-                                        return Err(Error::MultipleDeclaration);
+                                        return Err(
+                                            Error::MultipleDeclaration(id.clone()).at(*span)
+                                        );
                                     } //添加该变量，但没有生成实际的指令
                                     kit_mut.context_mut.update_var_scope_now(&id, inst);
                                     kit_mut.context_mut.push_inst_bb(inst);
@@ -1079,63 +1033,49 @@ impl Process for VarDecl {
                                 }
                             }
                         }
-                        VarDef::Array((id, exp_vec)) => {
-                            let mut dimension = vec![];
+                        VarDef::Array((id, exp_vec, span)) => {
+                            let mut dimension_vec_in = vec![];
                             for exp in exp_vec {
-                                dimension.push(exp.process(Type::Int, kit_mut).unwrap());
-                            }
-                            let dimension_vec: Vec<_> = dimension.iter().map(|(_, x)| x).collect();
-                            let dimension_vec_in: Vec<_> = dimension_vec
-                                .iter()
-                                .map(|x| match x {
-                                    ExpValue::Int(i) => *i,
-                                    ExpValue::Float(_) => {
-                                        unreachable!()
-                                    }
-                                    ExpValue::None => {
-                                        unreachable!()
-                                    }
-                                    _ => {
-                                        unreachable!()
-                                    }
-                                })
-                                .collect(); //生成维度vec
+                                dimension_vec_in.push(array_dimension(exp, kit_mut)?);
+                            } //生成维度vec
 
                             let mut length = 1;
                             for dm in &dimension_vec_in {
                                 length = length * dm;
                             }
                             let fvec = vec![];
-                            if kit_mut.context_mut.get_layer()<0{
-                                let inst = kit_mut.pool_inst_mut.make_float_array(length, true, fvec);
-                            if !kit_mut.context_mut.add_var(
-                                &id,
-                                Type::Float,
-                                true,
-                                false,
-                                Some(inst),
-                                None,
-                                dimension_vec_in.clone(),
-                            ) {
-                                return Err(Error::MultipleDeclaration);
-                            } //添加该变量，但没有生成实际的指令
-                            kit_mut.context_mut.update_var_scope_now(&id, inst);
-                            kit_mut.context_mut.push_inst_bb(inst);
-                            }else{
-                                let inst = kit_mut.pool_inst_mut.make_float_array(length, false, fvec);
-                            if !kit_mut.context_mut.add_var(
-                                &id,
-                                Type::Float,
-                                true,
-                                false,
-                                Some(inst),
-                                None,
-                                dimension_vec_in.clone(),
-                            ) {
-                                return Err(Error::MultipleDeclaration);
-                            } //添加该变量，但没有生成实际的指令
-                            kit_mut.context_mut.update_var_scope_now(&id, inst);
-                            kit_mut.context_mut.push_inst_bb(inst);
+                            if kit_mut.context_mut.get_layer() < 0 {
+                                let inst =
+                                    kit_mut.pool_inst_mut.make_float_array(length, true, fvec);
+                                if !kit_mut.context_mut.add_var(
+                                    &id,
+                                    Type::Float,
+                                    true,
+                                    false,
+                                    Some(inst),
+                                    None,
+                                    dimension_vec_in.clone(),
+                                ) {
+                                    return Err(Error::MultipleDeclaration(id.clone()).at(*span));
+                                } //添加该变量，但没有生成实际的指令
+                                kit_mut.context_mut.update_var_scope_now(&id, inst);
+                                kit_mut.context_mut.push_inst_bb(inst);
+                            } else {
+                                let inst =
+                                    kit_mut.pool_inst_mut.make_float_array(length, false, fvec);
+                                if !kit_mut.context_mut.add_var(
+                                    &id,
+                                    Type::Float,
+                                    true,
+                                    false,
+                                    Some(inst),
+                                    None,
+                                    dimension_vec_in.clone(),
+                                ) {
+                                    return Err(Error::MultipleDeclaration(id.clone()).at(*span));
+                                } //添加该变量，但没有生成实际的指令
+                                kit_mut.context_mut.update_var_scope_now(&id, inst);
+                                kit_mut.context_mut.push_inst_bb(inst);
                             }
                         }
                     }
@@ -1153,7 +1093,7 @@ impl Process for InitVal {
         match self {
             InitVal::Exp(exp) => {
                 let (tp, _, num_precessor, _) = input;
-                let (inst, val) = exp.process(tp, kit_mut).unwrap();
+                let (inst, val) = exp.process(tp, kit_mut)?;
                 let mut vecf = vec![];
                 let mut veci = vec![];
                 let mut inst_vec = vec![];
@@ -1196,9 +1136,9 @@ impl Process for InitVal {
                 match tp {
                     Type::Float | Type::ConstFloat => Ok((RetInitVec::Float(vecf), inst_vec)),
                     Type::Int | Type::ConstInt => Ok((RetInitVec::Int(veci), inst_vec)),
-                    _ => {
-                        todo!()
-                    }
+                    Type::NotForce => Err(Error::Internal(
+                        "initializer has no element type".to_string(),
+                    )),
                 }
             }
 
@@ -1228,12 +1168,10 @@ impl Process for InitVal {
                     // println!("进来了");
                     match init {
                         InitVal::Exp(_) => {
-                            let (vec_val_temp, vec_inst_temp) = init
-                                .process(
-                                    (tp, dimension.clone(), num_precessor + index, layer_now),
-                                    kit_mut,
-                                )
-                                .unwrap();
+                            let (vec_val_temp, vec_inst_temp) = init.process(
+                                (tp, dimension.clone(), num_precessor + index, layer_now),
+                                kit_mut,
+                            )?;
                             match vec_val_temp {
                                 RetInitVec::Float(vec_f) => {
                                     for val in vec_f {
@@ -1260,12 +1198,10 @@ impl Process for InitVal {
                             index = index + 1; //init为exp，相对偏移加1
                         }
                         InitVal::InitValVec(_) => {
-                            let (vec_val_temp, vec_inst_temp) = init
-                                .process(
-                                    (tp, dimension.clone(), num_precessor + index, layer_now + 1),
-                                    kit_mut,
-                                )
-                                .unwrap();
+                            let (vec_val_temp, vec_inst_temp) = init.process(
+                                (tp, dimension.clone(), num_precessor + index, layer_now + 1),
+                                kit_mut,
+                            )?;
                             match vec_val_temp {
                                 RetInitVec::Float(vec_f) => {
                                     index = index + vec_f.len() as i32;
@@ -1312,9 +1248,9 @@ impl Process for InitVal {
                         init_padding_int(&mut vec_val_i, vec_dimension_now, num_precessor, ttotal);
                         Ok((RetInitVec::Int(vec_val_i), vec_inst_init))
                     }
-                    _ => {
-                        todo!()
-                    }
+                    Type::NotForce => Err(Error::Internal(
+                        "initializer has no element type".to_string(),
+                    )),
                 }
             }
         }
@@ -1325,7 +1261,10 @@ impl Process for FuncDef {
     type Message = bool;
     fn process(&mut self, _: Self::Message, kit_mut: &mut Kit) -> Result<Self::Ret, Error> {
         match self {
-            Self::NonParameterFuncDef((tp, id, blk)) => {
+            Self::NonParameterFuncDef((tp, id, blk, span)) => {
+                if kit_mut.context_mut.module_mut.find_function(id).is_some() {
+                    return Err(Error::MultipleDeclaration(id.clone()).at(*span));
+                }
                 kit_mut.context_mut.set_funcnow(id.to_string());
                 let vec_ttt = vec![];
                 kit_mut
@@ -1356,11 +1295,14 @@ impl Process for FuncDef {
                 kit_mut
                     .context_mut
                     .push_func_module(id.to_string(), func_ptr);
-                blk.process((None, None), kit_mut).unwrap();
+                blk.process((None, None), kit_mut)?;
                 kit_mut.context_mut.delete_layer();
                 return Ok(func_ptr);
             }
-            Self::ParameterFuncDef((tp, id, params, blk)) => {
+            Self::ParameterFuncDef((tp, id, params, blk, span)) => {
+                if kit_mut.context_mut.module_mut.find_function(id).is_some() {
+                    return Err(Error::MultipleDeclaration(id.clone()).at(*span));
+                }
                 kit_mut.context_mut.set_funcnow(id.to_string());
                 let vec_ttt = vec![];
                 kit_mut
@@ -1391,7 +1333,7 @@ impl Process for FuncDef {
                 kit_mut
                     .context_mut
                     .push_func_module(id.to_string(), func_ptr);
-                let params_vec = params.process(1, kit_mut).unwrap();
+                let params_vec = params.process(1, kit_mut)?;
 
                 kit_mut.context_mut.bb_now_set(bb); //和for语句顺序改过
 
@@ -1400,7 +1342,7 @@ impl Process for FuncDef {
                     func_mut.set_parameter(name, param); //这里
                 }
 
-                blk.process((None, None), kit_mut).unwrap();
+                blk.process((None, None), kit_mut)?;
                 kit_mut.context_mut.delete_layer();
                 return Ok(func_ptr);
             }
@@ -1414,7 +1356,7 @@ impl Process for FuncFParams {
     fn process(&mut self, input: Self::Message, kit_mut: &mut Kit) -> Result<Self::Ret, Error> {
         let mut vec = vec![];
         for param in &mut self.func_fparams_vec {
-            let p = param.process(input, kit_mut).unwrap();
+            let p = param.process(input, kit_mut)?;
             vec.push(p);
         }
         Ok(vec)
@@ -1426,28 +1368,18 @@ impl Process for FuncFParam {
     type Message = i32;
     fn process(&mut self, _: Self::Message, kit_mut: &mut Kit) -> Result<Self::Ret, Error> {
         match self {
-            FuncFParam::Array((tp, id, vec)) => {
+            FuncFParam::Array((tp, id, vec, span)) => {
                 //vec中存储的是从第二维开始的维度信息，第一维默认存在且为空
                 match tp {
                     BType::Int => {
                         let param = kit_mut.pool_inst_mut.make_param(IrType::IntPtr);
-                        let mut dimension_vec = vec![];
-                        let mut dimension_vec_in = vec![];
+                        let mut dimension_vec_in = vec![-1];
                         for exp in vec {
-                            dimension_vec.push(exp.process(Type::Int, kit_mut).unwrap());
+                            let (_, val) = exp.process(Type::Int, kit_mut)?;
+                            dimension_vec_in
+                                .push(dimension_value(val).map_err(|e| e.at(exp.span))?);
                         }
-                        dimension_vec_in.push(-1);
-                        for (_, dimension) in dimension_vec {
-                            match dimension {
-                                ExpValue::Int(i) => {
-                                    dimension_vec_in.push(i);
-                                }
-                                _ => {
-                                    unreachable!()
-                                }
-                            }
-                        }
-                        kit_mut.context_mut.add_var(
+                        if !kit_mut.context_mut.add_var(
                             id,
                             Type::Int,
                             true,
@@ -1455,29 +1387,21 @@ impl Process for FuncFParam {
                             Some(param),
                             None,
                             dimension_vec_in,
-                        );
+                        ) {
+                            return Err(Error::MultipleDeclaration(id.clone()).at(*span));
+                        }
                         kit_mut.context_mut.update_var_scope_now(id, param);
                         Ok((id.clone(), param))
                     }
                     BType::Float => {
                         let param = kit_mut.pool_inst_mut.make_param(IrType::FloatPtr);
-                        let mut dimension_vec = vec![];
-                        let mut dimension_vec_in = vec![];
+                        let mut dimension_vec_in = vec![-1];
                         for exp in vec {
-                            dimension_vec.push(exp.process(Type::Int, kit_mut).unwrap());
-                        }
-                        dimension_vec_in.push(-1);
-                        for (_, dimension) in dimension_vec {
-                            match dimension {
-                                ExpValue::Int(i) => {
-                                    dimension_vec_in.push(i);
-                                }
-                                _ => {
-                                    unreachable!()
-                                }
-                            }
+                            let (_, val) = exp.process(Type::Int, kit_mut)?;
+                            dimension_vec_in
+                                .push(dimension_value(val).map_err(|e| e.at(exp.span))?);
                         }
-                        kit_mut.context_mut.add_var(
+                        if !kit_mut.context_mut.add_var(
                             id,
                             Type::Float,
                             true,
@@ -1485,26 +1409,36 @@ impl Process for FuncFParam {
                             Some(param),
                             None,
                             dimension_vec_in,
-                        );
+                        ) {
+                            return Err(Error::MultipleDeclaration(id.clone()).at(*span));
+                        }
                         //这里
                         kit_mut.context_mut.update_var_scope_now(id, param);
                         Ok((id.clone(), param))
                     }
                 }
             }
-            FuncFParam::NonArray((tp, id)) => match tp {
+            FuncFParam::NonArray((tp, id, span)) => match tp {
                 BType::Int => {
                     let param = kit_mut.pool_inst_mut.make_param(IrType::Int);
-                    kit_mut
-                        .context_mut
-                        .add_var(id, Type::Int, false, true, None, None, Vec::new());
+                    if !kit_mut.context_mut.add_var(
+                        id,
+                        Type::Int,
+                        false,
+                        true,
+                        None,
+                        None,
+                        Vec::new(),
+                    ) {
+                        return Err(Error::MultipleDeclaration(id.clone()).at(*span));
+                    }
                     //这里
                     kit_mut.context_mut.update_var_scope_now(id, param);
                     Ok((id.clone(), param))
                 }
                 BType::Float => {
                     let param = kit_mut.pool_inst_mut.make_param(IrType::Float);
-                    kit_mut.context_mut.add_var(
+                    if !kit_mut.context_mut.add_var(
                         id,
                         Type::Float,
                         false,
@@ -1512,7 +1446,9 @@ impl Process for FuncFParam {
                         None,
                         None,
                         Vec::new(),
-                    );
+                    ) {
+                        return Err(Error::MultipleDeclaration(id.clone()).at(*span));
+                    }
                     kit_mut.context_mut.update_var_scope_now(id, param);
                     Ok((id.clone(), param))
                 }
@@ -1529,7 +1465,7 @@ impl Process for Block {
         } else {
             kit_mut.context_mut.add_layer();
             for item in &mut self.block_vec {
-                item.process(input, kit_mut).unwrap();
+                item.process(input, kit_mut)?;
             }
             kit_mut.context_mut.delete_layer();
             Ok(1)
@@ -1546,11 +1482,11 @@ impl Process for BlockItem {
         } else {
             match self {
                 BlockItem::Decl(decl) => {
-                    decl.process(1, kit_mut).unwrap();
+                    decl.process(1, kit_mut)?;
                     return Ok(1);
                 }
                 BlockItem::Stmt(stmt) => {
-                    stmt.process(input, kit_mut).unwrap();
+                    stmt.process(input, kit_mut)?;
                     return Ok(1);
                 }
             }
@@ -1571,17 +1507,17 @@ impl Process for Stmt {
                 Stmt::If(if_stmt) => if_stmt.process(input, kit_mut),
                 Stmt::While(while_stmt) => while_stmt.process(input, kit_mut),
                 Stmt::Break(break_stmt) => {
-                    break_stmt.process(input, kit_mut).unwrap();
+                    break_stmt.process(input, kit_mut)?;
                     kit_mut.context_mut.set_stop_genir(true);
                     Ok(1)
                 }
                 Stmt::Continue(continue_stmt) => {
-                    continue_stmt.process(input, kit_mut).unwrap();
+                    continue_stmt.process(input, kit_mut)?;
                     kit_mut.context_mut.set_stop_genir(true);
                     Ok(1)
                 }
                 Stmt::Return(ret_stmt) => {
-                    ret_stmt.process(input, kit_mut).unwrap();
+                    ret_stmt.process(input, kit_mut)?;
                     kit_mut.context_mut.set_stop_genir(true);
                     Ok(1)
                 }
@@ -1595,13 +1531,22 @@ impl Process for Assign {
     type Message = (Option<ObjPtr<BasicBlock>>, Option<ObjPtr<BasicBlock>>);
     fn process(&mut self, _: Self::Message, kit_mut: &mut Kit) -> Result<Self::Ret, Error> {
         let lval = &mut self.lval;
-        let symbol = kit_mut.get_var_symbol(&lval.id).unwrap();
-        let flag = self.exp.type_process(1, kit_mut).unwrap();
+        let symbol = kit_mut
+            .get_var_symbol(&lval.id)
+            .map_err(|e| e.at(lval.span))?;
+        check_subscript_count(&lval.id, symbol.dimension.len(), lval.exp_vec.len())
+            .map_err(|e| e.at(lval.span))?;
+        if symbol.dimension.len() > lval.exp_vec.len() {
+            return Err(
+                Error::TypeMismatch(format!("cannot assign to array `{}`", lval.id)).at(lval.span),
+            );
+        }
+        let flag = self.exp.type_process(1, kit_mut)?;
         let mut tp = Type::Int;
         if flag > 1 {
             tp = Type::Float;
         }
-        let (mut inst_r, val) = self.exp.process(tp, kit_mut).unwrap();
+        let (mut inst_r, val) = self.exp.process(tp, kit_mut)?;
         match symbol.tp {
             Type::ConstFloat | Type::Float => {
                 if flag <= 1 {
@@ -1635,40 +1580,40 @@ impl Process for Assign {
                     }
                 }
             }
-            _ => {
-                todo!()
+            Type::NotForce => {
+                return Err(
+                    Error::Internal(format!("`{}` has no value type", lval.id)).at(lval.span)
+                );
             }
         }
+        //上面已经排除了没有类型的变量
+        let is_float = matches!(symbol.tp, Type::ConstFloat | Type::Float);
         //函数参数不再单独处理
         if let Some(array_inst) = symbol.array_inst {
             //如果是数组
             if symbol.layer < 0 {
-                match symbol.tp {
-                    Type::ConstFloat | Type::Float => {
-                        let ptr = kit_mut
-                            .pool_inst_mut
-                            .make_global_float_array_load(array_inst);
-                        kit_mut.context_mut.push_inst_bb(ptr);
-                        let inst_offset = offset_calculate(&lval.id, &mut lval.exp_vec, kit_mut);
-                        let inst_ptr = kit_mut.pool_inst_mut.make_gep(ptr, inst_offset);
-                        // kit_mut.context_mut.push_inst_bb(inst_offset); //这里需要吗  不需要
-                        kit_mut.context_mut.push_inst_bb(inst_ptr);
-                        let inst_store = kit_mut.pool_inst_mut.make_float_store(inst_ptr, inst_r);
-                        kit_mut.context_mut.push_inst_bb(inst_store);
-                    }
-                    Type::ConstInt | Type::Int => {
-                        let ptr = kit_mut.pool_inst_mut.make_global_int_array_load(array_inst);
-                        kit_mut.context_mut.push_inst_bb(ptr);
-                        let inst_offset = offset_calculate(&lval.id, &mut lval.exp_vec, kit_mut);
-                        let inst_ptr = kit_mut.pool_inst_mut.make_gep(ptr, inst_offset);
-                        // kit_mut.context_mut.push_inst_bb(inst_offset); //这里需要吗  不需要
-                        kit_mut.context_mut.push_inst_bb(inst_ptr);
-                        let inst_store = kit_mut.pool_inst_mut.make_int_store(inst_ptr, inst_r);
-                        kit_mut.context_mut.push_inst_bb(inst_store);
-                    }
-                    _ => {
-                        todo!()
-                    }
+                if is_float {
+                    let ptr = kit_mut
+                        .pool_inst_mut
+                        .make_global_float_array_load(array_inst);
+                    kit_mut.context_mut.push_inst_bb(ptr);
+                    let inst_offset = offset_calculate(&lval.id, &mut lval.exp_vec, kit_mut)
+                        .map_err(|e| e.at(lval.span))?;
+                    let inst_ptr = kit_mut.pool_inst_mut.make_gep(ptr, inst_offset);
+                    // kit_mut.context_mut.push_inst_bb(inst_offset); //这里需要吗  不需要
+                    kit_mut.context_mut.push_inst_bb(inst_ptr);
+                    let inst_store = kit_mut.pool_inst_mut.make_float_store(inst_ptr, inst_r);
+                    kit_mut.context_mut.push_inst_bb(inst_store);
+                } else {
+                    let ptr = kit_mut.pool_inst_mut.make_global_int_array_load(array_inst);
+                    kit_mut.context_mut.push_inst_bb(ptr);
+                    let inst_offset = offset_calculate(&lval.id, &mut lval.exp_vec, kit_mut)
+                        .map_err(|e| e.at(lval.span))?;
+                    let inst_ptr = kit_mut.pool_inst_mut.make_gep(ptr, inst_offset);
+                    // kit_mut.context_mut.push_inst_bb(inst_offset); //这里需要吗  不需要
+                    kit_mut.context_mut.push_inst_bb(inst_ptr);
+                    let inst_store = kit_mut.pool_inst_mut.make_int_store(inst_ptr, inst_r);
+                    kit_mut.context_mut.push_inst_bb(inst_store);
                 }
 
                 // }
@@ -1677,23 +1622,17 @@ impl Process for Assign {
                     .update_var_scope_now(&self.lval.id, inst_r);
                 Ok(1)
             } else {
-                let inst_offset = offset_calculate(&lval.id, &mut lval.exp_vec, kit_mut);
+                let inst_offset = offset_calculate(&lval.id, &mut lval.exp_vec, kit_mut)
+                    .map_err(|e| e.at(lval.span))?;
                 let inst_ptr = kit_mut.pool_inst_mut.make_gep(array_inst, inst_offset);
                 // kit_mut.context_mut.push_inst_bb(inst_offset); //这里需要吗  不需要
                 kit_mut.context_mut.push_inst_bb(inst_ptr);
-                match symbol.tp {
-                    Type::ConstFloat | Type::Float => {
-                        let inst_store = kit_mut.pool_inst_mut.make_float_store(inst_ptr, inst_r);
-                        kit_mut.context_mut.push_inst_bb(inst_store);
-                    }
-                    Type::ConstInt | Type::Int => {
-                        let inst_store = kit_mut.pool_inst_mut.make_int_store(inst_ptr, inst_r);
-                        kit_mut.context_mut.push_inst_bb(inst_store);
-                    }
-                    _ => {
-                        todo!()
-                    }
-                }
+                let inst_store = if is_float {
+                    kit_mut.pool_inst_mut.make_float_store(inst_ptr, inst_r)
+                } else {
+                    kit_mut.pool_inst_mut.make_int_store(inst_ptr, inst_r)
+                };
+                kit_mut.context_mut.push_inst_bb(inst_store);
 
                 // }
                 kit_mut
@@ -1706,20 +1645,12 @@ impl Process for Assign {
             //不是数组
             if let Some(global_inst) = symbol.global_inst {
                 //全局变量
-                match symbol.tp {
-                    Type::ConstFloat | Type::Float => {
-                        let inst_store =
-                            kit_mut.pool_inst_mut.make_float_store(global_inst, inst_r);
-                        kit_mut.context_mut.push_inst_bb(inst_store);
-                    }
-                    Type::ConstInt | Type::Int => {
-                        let inst_store = kit_mut.pool_inst_mut.make_int_store(global_inst, inst_r);
-                        kit_mut.context_mut.push_inst_bb(inst_store);
-                    }
-                    _ => {
-                        todo!()
-                    }
-                }
+                let inst_store = if is_float {
+                    kit_mut.pool_inst_mut.make_float_store(global_inst, inst_r)
+                } else {
+                    kit_mut.pool_inst_mut.make_int_store(global_inst, inst_r)
+                };
+                kit_mut.context_mut.push_inst_bb(inst_store);
             }
             kit_mut
                 .context_mut
@@ -1733,7 +1664,7 @@ impl Process for ExpStmt {
     type Message = (Type, Option<ObjPtr<BasicBlock>>, Option<ObjPtr<BasicBlock>>);
     fn process(&mut self, input: Self::Message, kit_mut: &mut Kit) -> Result<Self::Ret, Error> {
         if let Some(exp) = &mut self.exp {
-            exp.process(input.0, kit_mut).unwrap();
+            exp.process(input.0, kit_mut)?;
         }
         Ok(1)
     }
@@ -1753,8 +1684,7 @@ impl Process for If {
 
             let (_, _) = self
                 .cond
-                .process((Type::Int, Some(inst_bb_if), Some(inst_bb_else)), kit_mut)
-                .unwrap();
+                .process((Type::Int, Some(inst_bb_if), Some(inst_bb_else)), kit_mut)?;
 
             //生成一块新的bb
             let bb_successor_name = kit_mut.context_mut.get_newbb_name();
@@ -1762,8 +1692,8 @@ impl Process for If {
                 .pool_bb_mut
                 .new_basic_block(bb_successor_name.clone());
             kit_mut.context_mut.bb_now_set(inst_bb_else); //设置现在所在的bb块，准备归约
-            stmt_else.process(input, kit_mut).unwrap(); //向该分支块内生成指令
-                                                        //加一条直接跳转语句
+            stmt_else.process(input, kit_mut)?; //向该分支块内生成指令
+                                                //加一条直接跳转语句
 
             match kit_mut.context_mut.bb_now_mut {
                 InfuncChoice::InFunc(bb_now) => {
@@ -1781,7 +1711,7 @@ impl Process for If {
 
             kit_mut.context_mut.set_stop_genir(false);
             kit_mut.context_mut.bb_now_set(inst_bb_if);
-            self.then.process(input, kit_mut).unwrap();
+            self.then.process(input, kit_mut)?;
             match kit_mut.context_mut.bb_now_mut {
                 InfuncChoice::InFunc(bb_now) => {
                     if !kit_mut.context_mut.stop_genir {
@@ -1809,16 +1739,13 @@ impl Process for If {
                 .pool_bb_mut
                 .new_basic_block(bb_successor_name.clone());
 
-            let (_, _) = self
-                .cond
-                .process(
-                    (Type::Int, Some(inst_bb_if), Some(inst_bb_successor)),
-                    kit_mut,
-                )
-                .unwrap();
+            let (_, _) = self.cond.process(
+                (Type::Int, Some(inst_bb_if), Some(inst_bb_successor)),
+                kit_mut,
+            )?;
 
             kit_mut.context_mut.bb_now_set(inst_bb_if);
-            self.then.process(input, kit_mut).unwrap();
+            self.then.process(input, kit_mut)?;
 
             match kit_mut.context_mut.bb_now_mut {
                 InfuncChoice::InFunc(bb_now) => {
@@ -1864,17 +1791,13 @@ impl Process for While {
         }
         kit_mut.context_mut.bb_now_set(block_cond);
 
-        let (_, _) = self
-            .cond
-            .process(
-                (Type::Int, Some(block_while_head), Some(block_false)),
-                kit_mut,
-            )
-            .unwrap();
+        let (_, _) = self.cond.process(
+            (Type::Int, Some(block_while_head), Some(block_false)),
+            kit_mut,
+        )?;
         kit_mut.context_mut.bb_now_set(block_while_head); //设置当前basicblock
         self.body
-            .process((Some(block_cond), Some(block_false)), kit_mut)
-            .unwrap(); //在块内生成指令
+            .process((Some(block_cond), Some(block_false)), kit_mut)?; //在块内生成指令
         match kit_mut.context_mut.bb_now_mut {
             InfuncChoice::InFunc(bb_now) => {
                 // if !bb_now.get_up_bb().is_empty() {
@@ -1978,13 +1901,13 @@ impl Process for Return {
             }
         }
         if let Some(exp) = &mut self.exp {
-            let flag = exp.type_process(1, kit_mut).unwrap();
+            let flag = exp.type_process(1, kit_mut)?;
             let mut tp = Type::Int;
             if flag > 1 {
                 tp = Type::Float;
             }
             let mut flag_trans = false;
-            let (mut inst, val) = exp.process(tp, kit_mut).unwrap(); //这里可能有问题
+            let (mut inst, val) = exp.process(tp, kit_mut)?; //这里可能有问题
             match kit_mut.context_mut.ret_tp {
                 Type::Int => {
                     if flag > 1 {
@@ -2090,7 +2013,9 @@ impl Process for Exp {
     type Ret = (ObjPtr<Inst>, ExpValue);
     type Message = Type;
     fn process(&mut self, input: Self::Message, kit_mut: &mut Kit) -> Result<Self::Ret, Error> {
-        self.add_exp.process(input, kit_mut)
+        self.add_exp
+            .process(input, kit_mut)
+            .map_err(|e| e.at(self.span))
     }
 }
 
@@ -2122,17 +2047,25 @@ impl Process for LVal {
                 dimension: vec![],
             },
         );
-        let sym_tmp = kit_mut.get_var_symbol(&self.id).unwrap();
+        let sym_tmp = kit_mut
+            .get_var_symbol(&self.id)
+            .map_err(|e| e.at(self.span))?;
+        check_subscript_count(&self.id, sym_tmp.dimension.len(), self.exp_vec.len())
+            .map_err(|e| e.at(self.span))?;
         if self.exp_vec.is_empty() {
             //如果为空
             if sym_tmp.dimension.len() > self.exp_vec.len() {
-                (var, symbol) = kit_mut.get_var(&self.id, None, true).unwrap();
+                (var, symbol) = kit_mut
+                    .get_var(&self.id, None, true)
+                    .map_err(|e| e.at(self.span))?;
             } else {
                 // let inst_offset_temp = kit_mut.pool_inst_mut.make_int_const(0);
-                (var, symbol) = kit_mut.get_var(&self.id, None, false).unwrap();
+                (var, symbol) = kit_mut
+                    .get_var(&self.id, None, false)
+                    .map_err(|e| e.at(self.span))?;
             }
         } else {
-            let sym = kit_mut.get_var_symbol(&self.id).unwrap(); //获得符号表
+            let sym = sym_tmp.clone(); //获得符号表
             let dimension_vec = sym.dimension.clone(); //获得维度信息
             let mut index = 1;
             let mut inst_base_vec = vec![];
@@ -2149,7 +2082,7 @@ impl Process for LVal {
             let mut imm_flag = true;
             index = 0;
             for exp in &mut self.exp_vec {
-                let (inst_exp, val) = exp.process(Type::Int, kit_mut).unwrap();
+                let (inst_exp, val) = exp.process(Type::Int, kit_mut)?;
                 match val {
                     ExpValue::Int(i) => {
                         let inst_base_now =
@@ -2215,9 +2148,13 @@ impl Process for LVal {
 
             if sym_tmp.dimension.len() > self.exp_vec.len() {
                 //获得指针
-                (var, symbol) = kit_mut.get_var(&self.id, Some(inst_offset), true).unwrap();
+                (var, symbol) = kit_mut
+                    .get_var(&self.id, Some(inst_offset), true)
+                    .map_err(|e| e.at(self.span))?;
             } else {
-                (var, symbol) = kit_mut.get_var(&self.id, Some(inst_offset), false).unwrap();
+                (var, symbol) = kit_mut
+                    .get_var(&self.id, Some(inst_offset), false)
+                    .map_err(|e| e.at(self.span))?;
             }
         }
 
@@ -2392,8 +2329,12 @@ impl Process for LVal {
                         return Ok((var, val_ret));
                     }
                 },
-                _ => {
-                    todo!()
+                Type::NotForce => {
+                    return Err(Error::Internal(format!(
+                        "cannot read `{}` without an expected type",
+                        self.id
+                    ))
+                    .at(self.span));
                 }
             },
         }
@@ -2471,7 +2412,7 @@ impl Process for OptionFuncRParams {
     type Message = Vec<Type>;
     fn process(&mut self, input: Self::Message, kit_mut: &mut Kit) -> Result<Self::Ret, Error> {
         if let Some(rparams) = &mut self.func_fparams {
-            Ok(rparams.process(input, kit_mut).unwrap())
+            Ok(rparams.process(input, kit_mut)?)
         } else {
             Ok(vec![])
         }
@@ -2486,7 +2427,7 @@ impl Process for UnaryExp {
             UnaryExp::OpUnary((unaryop, unaryexp)) => match unaryop {
                 UnaryOp::Add => unaryexp.as_mut().process(input, kit_mut),
                 UnaryOp::Minus => {
-                    let (inst_u, val) = unaryexp.as_mut().process(input, kit_mut).unwrap();
+                    let (inst_u, val) = unaryexp.as_mut().process(input, kit_mut)?;
                     let mut inst = inst_u;
 
                     let mut val_ret = val;
@@ -2529,7 +2470,7 @@ impl Process for UnaryExp {
                     Ok((inst, val_ret))
                 }
                 UnaryOp::Exclamation => {
-                    let (inst_u, val) = unaryexp.as_mut().process(input, kit_mut).unwrap();
+                    let (inst_u, val) = unaryexp.as_mut().process(input, kit_mut)?;
                     let mut inst = inst_u;
                     let mut val_ret = val;
                     match val {
@@ -2582,8 +2523,8 @@ impl Process for UnaryExp {
                     }
                 }
             },
-            UnaryExp::FuncCall((funcname, funcparams)) => {
-                let inst_func = kit_mut.context_mut.module_mut.get_function(&funcname);
+            UnaryExp::FuncCall((funcname, funcparams, span)) => {
+                let inst_func = kit_mut.find_function(funcname, funcparams, *span)?;
                 let fparams = inst_func.as_ref().get_parameter_list();
                 let mut fparams_type_vec = vec![];
                 for fp in fparams {
@@ -2606,7 +2547,7 @@ impl Process for UnaryExp {
                 match inst_func.as_ref().get_return_type() {
                     //根据返回值类型生成call指令
                     IrType::Float => {
-                        let args = funcparams.process(fparams_type_vec, kit_mut).unwrap(); //获得实参
+                        let args = funcparams.process(fparams_type_vec, kit_mut)?; //获得实参
                         let fname = funcname.clone();
 
                         // let mut fname = " ".to_string();
@@ -2628,7 +2569,7 @@ impl Process for UnaryExp {
                         Ok((inst, ExpValue::None)) //这里可以进一步对返回值进行分析
                     }
                     IrType::Int => {
-                        let args = funcparams.process(fparams_type_vec, kit_mut).unwrap();
+                        let args = funcparams.process(fparams_type_vec, kit_mut)?;
                         let fname = funcname.clone();
                         // let mut fname = " ".to_string();
                         // if let Some((funcname_in, _)) = kit_mut
@@ -2651,7 +2592,7 @@ impl Process for UnaryExp {
                         Ok((inst, ExpValue::None)) //这里可以进一步对返回值进行分析
                     }
                     IrType::Void => {
-                        let args = funcparams.process(fparams_type_vec, kit_mut).unwrap();
+                        let args = funcparams.process(fparams_type_vec, kit_mut)?;
                         let fname = funcname.clone();
                         // let mut fname = " ".to_string();
                         // if let Some((funcname_in, _)) = kit_mut
@@ -2682,12 +2623,21 @@ impl Process for FuncRParams {
         let mut vec = vec![];
         let mut index = 0;
         for i in &mut self.exp_vec {
-            let flag = i.type_process(1, kit_mut).unwrap();
+            let expect_array = matches!(input[index], Type::NotForce);
+            if is_array_value(i, kit_mut)? != expect_array {
+                let msg = if expect_array {
+                    "expected an array argument, found a scalar"
+                } else {
+                    "expected a scalar argument, found an array"
+                };
+                return Err(Error::TypeMismatch(msg.to_string()).at(i.span));
+            }
+            let flag = i.type_process(1, kit_mut)?;
             let mut tp = Type::Int;
             if flag > 1 {
                 tp = Type::Float;
             }
-            let (mut inst, _) = i.process(tp, kit_mut).unwrap();
+            let (mut inst, _) = i.process(tp, kit_mut)?;
             match input[index] {
                 Type::Float | Type::ConstFloat => {
                     if flag <= 1 {
@@ -2717,8 +2667,8 @@ impl Process for MulExp {
         match self {
             MulExp::UnaryExp(unaryexp) => unaryexp.process(input, kit_mut),
             MulExp::MulExp((mulexp, unaryexp)) => {
-                let (inst_left, lval) = mulexp.as_mut().process(input, kit_mut).unwrap();
-                let (inst_right, rval) = unaryexp.process(input, kit_mut).unwrap();
+                let (inst_left, lval) = mulexp.as_mut().process(input, kit_mut)?;
+                let (inst_right, rval) = unaryexp.process(input, kit_mut)?;
                 let mut inst = kit_mut.pool_inst_mut.make_int_const(-1129);
 
                 let mut val_ret = lval;
@@ -2752,8 +2702,8 @@ impl Process for MulExp {
                 Ok((inst, val_ret))
             }
             MulExp::DivExp((mulexp, unaryexp)) => {
-                let (inst_left, lval) = mulexp.as_mut().process(input, kit_mut).unwrap();
-                let (inst_right, rval) = unaryexp.process(input, kit_mut).unwrap();
+                let (inst_left, lval) = mulexp.as_mut().process(input, kit_mut)?;
+                let (inst_right, rval) = unaryexp.process(input, kit_mut)?;
                 let mut inst = kit_mut.pool_inst_mut.make_int_const(-1129);
 
                 let mut val_ret = lval;
@@ -2787,17 +2737,23 @@ impl Process for MulExp {
                 Ok((inst, val_ret))
             }
             MulExp::ModExp((mulexp, unaryexp)) => {
-                let (inst_left, lval) = mulexp.as_mut().process(input, kit_mut).unwrap();
-                let (inst_right, rval) = unaryexp.process(input, kit_mut).unwrap();
+                if mulexp.type_process(1, kit_mut)? > 1 || unaryexp.type_process(1, kit_mut)? > 1 {
+                    return Err(Error::TypeMismatch(
+                        "operands of `%` must be integers".to_string(),
+                    ));
+                }
+                let (inst_left, lval) = mulexp.as_mut().process(input, kit_mut)?;
+                let (inst_right, rval) = unaryexp.process(input, kit_mut)?;
                 let mut inst = kit_mut.pool_inst_mut.make_int_const(-1129);
 
                 let mut val_ret = lval;
                 match lval {
                     ExpValue::Float(f1) => match rval {
                         ExpValue::Float(f2) => {
-                            val_ret = ExpValue::Float(f1 % f2);
-                            inst = kit_mut.pool_inst_mut.make_float_const(f1 % f2);
-                            unreachable!("浮点数求余")
+                            //操作数都是整型,只是被要求转成浮点数,按整型求余
+                            let f = ((f1 as i32) % (f2 as i32)) as f32;
+                            val_ret = ExpValue::Float(f);
+                            inst = kit_mut.pool_inst_mut.make_float_const(f);
                         }
                         _ => {
                             inst = kit_mut.pool_inst_mut.make_rem(inst_left, inst_right);
@@ -2834,8 +2790,8 @@ impl Process for AddExp {
             AddExp::MulExp(mulexp) => mulexp.as_mut().process(input, kit_mut),
             AddExp::OpExp((opexp, op, mulexp)) => match op {
                 AddOp::Add => {
-                    let (inst_left, lval) = opexp.process(input, kit_mut).unwrap();
-                    let (inst_right, rval) = mulexp.process(input, kit_mut).unwrap();
+                    let (inst_left, lval) = opexp.process(input, kit_mut)?;
+                    let (inst_right, rval) = mulexp.process(input, kit_mut)?;
                     let mut inst = kit_mut.pool_inst_mut.make_int_const(-1129);
 
                     let mut val_ret = lval;
@@ -2869,8 +2825,8 @@ impl Process for AddExp {
                     Ok((inst, val_ret))
                 }
                 AddOp::Minus => {
-                    let (inst_left, lval) = opexp.process(input, kit_mut).unwrap();
-                    let (inst_right, rval) = mulexp.process(input, kit_mut).unwrap();
+                    let (inst_left, lval) = opexp.process(input, kit_mut)?;
+                    let (inst_right, rval) = mulexp.process(input, kit_mut)?;
                     // let inst_right_neg = kit_mut.pool_inst_mut.make_neg(inst_right);
                     let mut inst = kit_mut.pool_inst_mut.make_int_const(-1129);
                     // kit_mut.context_mut.push_inst_bb(inst_right);
@@ -2917,8 +2873,8 @@ impl Process for RelExp {
         match self {
             RelExp::AddExp(addexp) => addexp.process(input, kit_mut),
             RelExp::OpExp((relexp, op, addexp)) => {
-                let tp1 = relexp.type_process(1, kit_mut).unwrap(); //获得表达式中各比较元素应该给的类型
-                let tp2 = addexp.type_process(1, kit_mut).unwrap(); //获得表达式中各比较元素应该给的类型
+                let tp1 = relexp.type_process(1, kit_mut)?; //获得表达式中各比较元素应该给的类型
+                let tp2 = addexp.type_process(1, kit_mut)?; //获得表达式中各比较元素应该给的类型
                 let mut tp = 0;
                 if tp1 > tp2 {
                     tp = tp1;
@@ -2930,8 +2886,8 @@ impl Process for RelExp {
                 if tp > 1 {
                     tp_in = Type::Float;
                 }
-                let (mut inst_left, val_left) = relexp.process(tp_in, kit_mut).unwrap();
-                let (mut inst_right, val_right) = addexp.process(tp_in, kit_mut).unwrap();
+                let (mut inst_left, val_left) = relexp.process(tp_in, kit_mut)?;
+                let (mut inst_right, val_right) = addexp.process(tp_in, kit_mut)?;
                 let mut fflag = -1;
                 let mut iflag = -1;
                 let mut fvec = vec![];
@@ -3108,15 +3064,15 @@ impl Process for EqExp {
     type Ret = (ObjPtr<Inst>, ExpValue);
     type Message = Type; //第一个为true,第二个为false //if中默认给Type::Int
     fn process(&mut self, input: Self::Message, kit_mut: &mut Kit) -> Result<Self::Ret, Error> {
-        // let tp = self.type_process(1, kit_mut).unwrap(); //获得表达式中各比较元素应该给的类型
+        // let tp = self.type_process(1, kit_mut)?; //获得表达式中各比较元素应该给的类型
         match self {
             EqExp::RelExp(relexp) => {
-                let (inst, _) = relexp.process(input, kit_mut).unwrap();
+                let (inst, _) = relexp.process(input, kit_mut)?;
                 Ok((inst, ExpValue::Bool(1))) //这里可以优化
             }
             EqExp::EqualExp((eqexp, relexp)) => {
-                let tp1 = relexp.type_process(1, kit_mut).unwrap(); //获得表达式中各比较元素应该给的类型
-                let tp2 = eqexp.type_process(1, kit_mut).unwrap(); //获得表达式中各比较元素应该给的类型
+                let tp1 = relexp.type_process(1, kit_mut)?; //获得表达式中各比较元素应该给的类型
+                let tp2 = eqexp.type_process(1, kit_mut)?; //获得表达式中各比较元素应该给的类型
                 let mut tp = 0;
                 if tp1 > tp2 {
                     tp = tp1;
@@ -3132,8 +3088,8 @@ impl Process for EqExp {
                 let mut iflag = -1;
                 let mut fvec = vec![];
                 let mut ivec = vec![];
-                let (inst_left, val_left) = eqexp.process(tp_in, kit_mut).unwrap();
-                let (inst_right, val_right) = relexp.process(tp_in, kit_mut).unwrap();
+                let (inst_left, val_left) = eqexp.process(tp_in, kit_mut)?;
+                let (inst_right, val_right) = relexp.process(tp_in, kit_mut)?;
                 match val_left {
                     ExpValue::Float(f) => {
                         fflag = fflag + 1;
@@ -3204,8 +3160,8 @@ impl Process for EqExp {
                 Ok((inst_eq, val_ret))
             }
             EqExp::NotEqualExp((eqexp, relexp)) => {
-                let tp1 = relexp.type_process(1, kit_mut).unwrap(); //获得表达式中各比较元素应该给的类型
-                let tp2 = eqexp.type_process(1, kit_mut).unwrap(); //获得表达式中各比较元素应该给的类型
+                let tp1 = relexp.type_process(1, kit_mut)?; //获得表达式中各比较元素应该给的类型
+                let tp2 = eqexp.type_process(1, kit_mut)?; //获得表达式中各比较元素应该给的类型
                 let mut tp = 0;
                 if tp1 > tp2 {
                     tp = tp1;
@@ -3221,8 +3177,8 @@ impl Process for EqExp {
                 let mut iflag = -1;
                 let mut fvec = vec![];
                 let mut ivec = vec![];
-                let (inst_left, val_left) = eqexp.process(tp_in, kit_mut).unwrap();
-                let (inst_right, val_right) = relexp.process(tp_in, kit_mut).unwrap();
+                let (inst_left, val_left) = eqexp.process(tp_in, kit_mut)?;
+                let (inst_right, val_right) = relexp.process(tp_in, kit_mut)?;
                 match val_left {
                     ExpValue::Float(f) => {
                         fflag = fflag + 1;
@@ -3302,7 +3258,7 @@ impl Process for LAndExp {
     fn process(&mut self, input: Self::Message, kit_mut: &mut Kit) -> Result<Self::Ret, Error> {
         match self {
             LAndExp::EqExp(eqexp) => {
-                let (mut inst_ret, val_ret) = eqexp.process(input.0, kit_mut).unwrap();
+                let (mut inst_ret, val_ret) = eqexp.process(input.0, kit_mut)?;
                 match kit_mut.context_mut.bb_now_mut {
                     InfuncChoice::InFunc(bb_now) => {
                         if let Some(bb_false) = input.2 {
@@ -3336,11 +3292,10 @@ impl Process for LAndExp {
             LAndExp::AndExp((landexp, eqexp)) => {
                 let name_bb_cond_right = kit_mut.context_mut.get_newbb_name();
                 let inst_bb_cond_right = kit_mut.pool_bb_mut.new_basic_block(name_bb_cond_right); //创建一个新的bb
-                let (_, _) = landexp
-                    .process((input.0, Some(inst_bb_cond_right), input.2), kit_mut)
-                    .unwrap();
+                let (_, _) =
+                    landexp.process((input.0, Some(inst_bb_cond_right), input.2), kit_mut)?;
                 kit_mut.context_mut.bb_now_set(inst_bb_cond_right);
-                let (mut inst_right, _) = eqexp.process(input.0, kit_mut).unwrap();
+                let (mut inst_right, _) = eqexp.process(input.0, kit_mut)?;
                 kit_mut.context_mut.bb_now_set(inst_bb_cond_right);
                 match kit_mut.context_mut.bb_now_mut {
                     InfuncChoice::InFunc(bb_now) => {
@@ -3381,7 +3336,9 @@ impl Process for ConstExp {
     type Ret = (ObjPtr<Inst>, ExpValue);
     type Message = Type;
     fn process(&mut self, input: Self::Message, kit_mut: &mut Kit) -> Result<Self::Ret, Error> {
-        self.add_exp.process(input, kit_mut)
+        self.add_exp
+            .process(input, kit_mut)
+            .map_err(|e| e.at(self.span))
     }
 }
 
@@ -3394,18 +3351,75 @@ impl Process for LOrExp {
             LOrExp::OrExp((lorexp, landexp)) => {
                 let name_bb_cond_right = kit_mut.context_mut.get_newbb_name();
                 let inst_bb_cond_right = kit_mut.pool_bb_mut.new_basic_block(name_bb_cond_right); //新建一个bb
-                let (inst_left, _) = lorexp
-                    .process((input.0, input.1, Some(inst_bb_cond_right)), kit_mut)
-                    .unwrap();
+                let (inst_left, _) =
+                    lorexp.process((input.0, input.1, Some(inst_bb_cond_right)), kit_mut)?;
                 kit_mut.context_mut.bb_now_set(inst_bb_cond_right);
-                let (_, _) = landexp.process(input, kit_mut).unwrap();
+                let (_, _) = landexp.process(input, kit_mut)?;
                 Ok((inst_left, ExpValue::None)) //这里或许可以优化//这里随便填的
             }
         }
     }
 }
 
-pub fn offset_calculate(id: &str, exp_vec: &mut Vec<Exp>, kit_mut: &mut Kit) -> ObjPtr<Inst> {
+/// 判断表达式是否是数组(或数组的一部分),即下标个数少于维度的左值
+fn is_array_value(exp: &Exp, kit_mut: &mut Kit) -> Result<bool, Error> {
+    if let AddExp::MulExp(mulexp) = exp.add_exp.as_ref() {
+        if let MulExp::UnaryExp(unaryexp) = mulexp.as_ref() {
+            if let UnaryExp::PrimaryExp(primaryexp) = unaryexp.as_ref() {
+                if let PrimaryExp::LVal(lval) = primaryexp.as_ref() {
+                    let sym = kit_mut
+                        .get_var_symbol(&lval.id)
+                        .map_err(|e| e.at(lval.span))?;
+                    return Ok(sym.dimension.len() > lval.exp_vec.len());
+                }
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// 检查数组某一维的长度,要求是非负的整型常量
+fn dimension_value(val: ExpValue) -> Result<i32, Error> {
+    match val {
+        ExpValue::Int(i) if i >= 0 => Ok(i),
+        ExpValue::Int(i) => Err(Error::ArrayDimension(format!(
+            "array dimension must not be negative, found {}",
+            i
+        ))),
+        _ => Err(Error::ArrayDimension(
+            "array dimension is not an integer constant".to_string(),
+        )),
+    }
+}
+
+/// 计算数组声明中某一维的长度
+fn array_dimension(exp: &mut ConstExp, kit_mut: &mut Kit) -> Result<i32, Error> {
+    let (_, val) = exp.process(Type::Int, kit_mut)?;
+    dimension_value(val).map_err(|e| e.at(exp.span))
+}
+
+/// 检查下标个数是否超过变量的维度
+fn check_subscript_count(id: &str, dimension: usize, subscript: usize) -> Result<(), Error> {
+    if subscript > dimension {
+        if dimension == 0 {
+            return Err(Error::ArrayDimension(format!(
+                "subscripted value `{}` is not an array",
+                id
+            )));
+        }
+        return Err(Error::ArrayDimension(format!(
+            "too many subscripts for `{}`: it has {} dimension(s), but {} were given",
+            id, dimension, subscript
+        )));
+    }
+    Ok(())
+}
+
+pub fn offset_calculate(
+    id: &str,
+    exp_vec: &mut Vec<Exp>,
+    kit_mut: &mut Kit,
+) -> Result<ObjPtr<Inst>, Error> {
     let (_, symbol) = (
         kit_mut.pool_inst_mut.make_int_const(0),
         Symbol {
@@ -3419,7 +3433,8 @@ pub fn offset_calculate(id: &str, exp_vec: &mut Vec<Exp>, kit_mut: &mut Kit) ->
         },
     ); //初始化
 
-    let sym = kit_mut.get_var_symbol(id).unwrap(); //获得符号表
+    let sym = kit_mut.get_var_symbol(id)?; //获得符号表
+    check_subscript_count(id, sym.dimension.len(), exp_vec.len())?;
     let dimension_vec = sym.dimension.clone(); //获得维度信息
     let mut index = 1;
     let mut inst_base_vec = vec![];
@@ -3436,7 +3451,7 @@ pub fn offset_calculate(id: &str, exp_vec: &mut Vec<Exp>, kit_mut: &mut Kit) ->
     let mut imm_flag = true;
     index = 0;
     for exp in exp_vec {
-        let (inst_exp, val) = exp.process(symbol.tp, kit_mut).unwrap();
+        let (inst_exp, val) = exp.process(symbol.tp, kit_mut)?;
         match val {
             ExpValue::Int(i) => {
                 let inst_base_now = kit_mut.pool_inst_mut.make_int_const(inst_base_vec[index]); //构造base对应的inst
@@ -3498,5 +3513,50 @@ pub fn offset_calculate(id: &str, exp_vec: &mut Vec<Exp>, kit_mut: &mut Kit) ->
         }
     }
 
-    inst_offset
+    Ok(inst_offset)
+}
+
+#[cfg(test)]
+mod irgen_test {
+    use super::*;
+
+    #[test]
+    fn test_error_without_semantic_check() {
+        // 未经语义检查的语法树中,标量用初始化列表初始化时返回带位置的错误
+        let def = VarDef::NonArrayInit((
+            "a".to_string(),
+            InitVal::InitValVec(vec![]),
+            Span::new(4, 5),
+        ));
+        let mut compunit = CompUnit {
+            global_items: vec![GlobalItems::FuncDef(FuncDef::NonParameterFuncDef((
+                FuncType::Int,
+                "main".to_string(),
+                Block {
+                    block_vec: vec![BlockItem::Decl(Decl::VarDecl(VarDecl {
+                        btype: BType::Int,
+                        var_def_vec: vec![def],
+                    }))],
+                },
+                Span::default(),
+            )))],
+        };
+        let mut module = Module::new();
+        let mut pool_inst = ObjPool::new();
+        let mut pool_bb = ObjPool::new();
+        let mut pool_func = ObjPool::new();
+        let err = irgen(
+            &mut compunit,
+            &mut module,
+            &mut pool_inst,
+            &mut pool_bb,
+            &mut pool_func,
+        )
+        .unwrap_err();
+        assert_eq!(err.span(), Some(Span::new(4, 5)));
+        assert_eq!(
+            err.to_string(),
+            "scalar `a` cannot be initialized with a list"
+        );
+    }
 }
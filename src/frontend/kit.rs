@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+use super::ast::{OptionFuncRParams, Span};
 use super::context::Type;
 use super::InfuncChoice;
 use crate::frontend::context::Context;
@@ -155,16 +156,17 @@ impl Kit<'_> {
         self.context_mut.push_inst_bb(inst_ptr);
     }
 
-    pub fn phi_padding_allfunctions(&mut self) {
+    pub fn phi_padding_allfunctions(&mut self) -> Result<(), Error> {
         //填充所有函数中的phi
-        let vec_funcs = self.get_functions().unwrap().clone();
+        let vec_funcs = self.get_functions().unwrap_or_default();
         for func in vec_funcs {
             if func.is_empty_bb() {
                 continue;
             }
             let head_bb_temp = func.as_ref().get_head();
-            self.phi_padding_bb(head_bb_temp); //填充该函数中所有bb中的phi
+            self.phi_padding_bb(head_bb_temp)?; //填充该函数中所有bb中的phi
         }
+        Ok(())
     }
 
     pub fn get_functions(&self) -> Option<Vec<ObjPtr<Function>>> {
@@ -183,7 +185,7 @@ impl Kit<'_> {
         }
     }
 
-    pub fn phi_padding_bb(&mut self, bb: ObjPtr<BasicBlock>) {
+    pub fn phi_padding_bb(&mut self, bb: ObjPtr<BasicBlock>) -> Result<(), Error> {
         //填充该bb中的phi
         let bbname = bb.get_name();
         let option_phi = self.context_mut.phi_list.get(bbname);
@@ -192,7 +194,7 @@ impl Kit<'_> {
             vec_phi = vec_phi_temp.clone();
         }
         for (name_changed, inst_phi, _) in vec_phi.clone() {
-            self.phi_padding_inst(&name_changed, inst_phi, bb);
+            self.phi_padding_inst(&name_changed, inst_phi, bb)?;
         }
         self.context_mut
             .phi_list
@@ -205,8 +207,9 @@ impl Kit<'_> {
                     continue;
                 }
             }
-            self.phi_padding_bb(*bb_next);
+            self.phi_padding_bb(*bb_next)?;
         }
+        Ok(())
     }
 
     pub fn phi_padding_inst(
@@ -214,13 +217,14 @@ impl Kit<'_> {
         name_changed: &str,
         inst_phi: ObjPtr<Inst>,
         bb: ObjPtr<BasicBlock>,
-    ) {
+    ) -> Result<(), Error> {
         //填充bb中的变量为name_changed的inst_phi
         let vec_pre = bb.get_up_bb();
         for pre in vec_pre {
-            let inst_find = self.find_var(*pre, &name_changed).unwrap();
+            let inst_find = self.find_var(*pre, &name_changed)?;
             inst_phi.as_mut().add_operand(inst_find); //向上找,填充
         }
+        Ok(())
     }
 
     pub fn find_var(
@@ -251,7 +255,7 @@ impl Kit<'_> {
                             map.insert(var_name_changed.to_string(), inst_phi);
                             self.context_mut.bb_map.insert(bbname.to_string(), map);
                         }
-                        self.phi_padding_inst(var_name_changed, inst_phi, bb);
+                        self.phi_padding_inst(var_name_changed, inst_phi, bb)?;
 
                         Ok(inst_phi)
                     }
@@ -266,13 +270,14 @@ impl Kit<'_> {
                             map.insert(var_name_changed.to_string(), inst_phi);
                             self.context_mut.bb_map.insert(bbname.to_string(), map);
                         }
-                        self.phi_padding_inst(var_name_changed, inst_phi, bb);
+                        self.phi_padding_inst(var_name_changed, inst_phi, bb)?;
 
                         Ok(inst_phi)
                     }
-                    _ => {
-                        todo!()
-                    }
+                    Type::NotForce => Err(Error::Internal(format!(
+                        "variable `{}` has no value type",
+                        var_name_changed
+                    ))),
                 }
             } else {
                 Err(Error::FindVarError)
@@ -280,7 +285,7 @@ impl Kit<'_> {
         }
     }
 
-    pub fn merge_allfunctions(&mut self) {
+    pub fn merge_allfunctions(&mut self) -> Result<(), Error> {
         //填充所有函数中的phi
         let vec_temp = self.context_mut.module_mut.get_all_func();
         let vec: Vec<_> = vec_temp
@@ -289,19 +294,31 @@ impl Kit<'_> {
             .collect();
         for (func_name, func) in vec {
             if !func.is_empty_bb() {
-                self.merge_function(func_name.to_string(), func.clone());
+                self.merge_function(func_name.to_string(), func.clone())?;
             }
         }
+        Ok(())
     }
 
-    pub fn merge_function(&mut self, func_name: String, inst_func: ObjPtr<Function>) {
+    pub fn merge_function(
+        &mut self,
+        func_name: String,
+        inst_func: ObjPtr<Function>,
+    ) -> Result<(), Error> {
         let ret_type = inst_func.get_return_type();
-        let vec_endpoint = self
-            .context_mut
-            .terminated_map
-            .get(&func_name)
-            .unwrap()
-            .clone();
+        //没有记录终结点的函数不需要合并
+        let vec_endpoint = match self.context_mut.terminated_map.get(&func_name) {
+            Some(vec_endpoint) => vec_endpoint.clone(),
+            None => return Ok(()),
+        };
+        // 终结点的最后一条指令必须是ret
+        let not_return = |inst_ret: ObjPtr<Inst>| {
+            Error::Internal(format!(
+                "the last instruction of function `{}` is {:?} instead of return",
+                func_name,
+                inst_ret.get_kind()
+            ))
+        };
         if vec_endpoint.len() > 1 {
             match ret_type {
                 IrType::Void => {
@@ -318,9 +335,7 @@ impl Kit<'_> {
                                     .as_mut()
                                     .push_back(self.pool_inst_mut.make_jmp()); //添加jump
                             }
-                            _ => {
-                                unreachable!()
-                            }
+                            _ => return Err(not_return(inst_ret)),
                         }
                     }
                     bb_merge
@@ -344,9 +359,7 @@ impl Kit<'_> {
                                     .push_back(self.pool_inst_mut.make_jmp()); //添加jump
                                 inst_phi.as_mut().add_operand(ret_val); //向phi指令添加参数
                             }
-                            _ => {
-                                unreachable!()
-                            }
+                            _ => return Err(not_return(inst_ret)),
                         }
                     }
                     let inst_ret = self.pool_inst_mut.make_return(inst_phi);
@@ -369,23 +382,21 @@ impl Kit<'_> {
                                     .push_back(self.pool_inst_mut.make_jmp()); //添加jump
                                 inst_phi.as_mut().add_operand(ret_val); //向phi指令添加参数
                             }
-                            _ => {
-                                unreachable!(
-                                    "func:{:?}最后一条指令是{:?}类型",
-                                    func_name,
-                                    inst_ret.get_kind()
-                                )
-                            }
+                            _ => return Err(not_return(inst_ret)),
                         }
                     }
                     let inst_ret = self.pool_inst_mut.make_return(inst_phi);
                     bb_merge.as_mut().push_back(inst_ret);
                 }
                 _ => {
-                    unreachable!()
+                    return Err(Error::Internal(format!(
+                        "function `{}` returns {:?}",
+                        func_name, ret_type
+                    )))
                 }
             }
         }
+        Ok(())
     }
 
     pub fn add_var(
@@ -485,10 +496,39 @@ impl Kit<'_> {
                 }
                 Ok(inst_phi)
             }
-            _ => {
-                todo!()
+            Type::NotForce => Err(Error::Internal(format!(
+                "variable `{}` has no value type",
+                name
+            ))),
+        }
+    }
+
+    /// 查找被调用的函数,并检查实参个数是否与形参个数一致
+    pub fn find_function(
+        &self,
+        name: &str,
+        rparams: &OptionFuncRParams,
+        span: Span,
+    ) -> Result<ObjPtr<Function>, Error> {
+        let inst_func = self
+            .context_mut
+            .module_mut
+            .find_function(name)
+            .ok_or_else(|| Error::FunctionNotFound(name.to_string()).at(span))?;
+        let expected = inst_func.get_parameter_list().len();
+        let found = rparams
+            .func_fparams
+            .as_ref()
+            .map_or(0, |params| params.exp_vec.len());
+        if expected != found {
+            return Err(Error::ArgumentCount {
+                func: name.to_string(),
+                expected,
+                found,
             }
+            .at(span));
         }
+        Ok(inst_func)
     }
 
     pub fn get_var_symbol(&mut self, s: &str) -> Result<Symbol, Error> {
@@ -502,7 +542,7 @@ impl Kit<'_> {
         if let Some(sym) = sym_opt {
             return Ok(sym);
         }
-        Err(Error::VariableNotFound)
+        Err(Error::VariableNotFound(s.to_string()))
     }
 
     pub fn get_var(
@@ -513,9 +553,7 @@ impl Kit<'_> {
     ) -> Result<(ObjPtr<Inst>, Symbol), Error> {
         match self.context_mut.bb_now_mut {
             InfuncChoice::InFunc(bb) => {
-                if let Some((inst, symbol)) = self.get_var_bb(s, bb, offset, bool_get_ptr) {
-                    return Ok((inst, symbol));
-                }
+                return self.get_var_bb(s, bb, offset, bool_get_ptr);
             }
             InfuncChoice::NInFunc() => {
                 let inst = self.context_mut.module_mut.get_var(s);
@@ -545,7 +583,7 @@ impl Kit<'_> {
                 }
             }
         }
-        return Err(Error::VariableNotFound);
+        return Err(Error::VariableNotFound(s.to_string()));
     }
 
     pub fn get_var_bb(
@@ -554,7 +592,7 @@ impl Kit<'_> {
         bb: ObjPtr<BasicBlock>,
         offset: Option<ObjPtr<Inst>>,
         bool_get_ptr: bool,
-    ) -> Option<(ObjPtr<Inst>, Symbol)> {
+    ) -> Result<(ObjPtr<Inst>, Symbol), Error> {
         let mut name_changed = " ".to_string();
         let mut layer_var = 0;
 
@@ -622,7 +660,10 @@ impl Kit<'_> {
                                     self.context_mut.push_inst_bb(inst_offset_temp);
                                     self.context_mut.push_inst_bb(inst_ret);
                                 } else {
-                                    unreachable!("没给偏移")
+                                    return Err(Error::TypeMismatch(format!(
+                                        "array `{}` cannot be used as a value",
+                                        s
+                                    )));
                                 }
                             }
                         } else {
@@ -647,7 +688,10 @@ impl Kit<'_> {
                                     self.context_mut.push_inst_bb(inst_offset_temp); //哪些不插入到块中?
                                     self.context_mut.push_inst_bb(inst_ret);
                                 } else {
-                                    unreachable!("没给偏移")
+                                    return Err(Error::TypeMismatch(format!(
+                                        "array `{}` cannot be used as a value",
+                                        s
+                                    )));
                                 }
                                 //没偏移
                             }
@@ -686,7 +730,10 @@ impl Kit<'_> {
                                     self.context_mut.push_inst_bb(inst_offset_temp);
                                     self.context_mut.push_inst_bb(inst_ret);
                                 } else {
-                                    unreachable!("没给偏移")
+                                    return Err(Error::TypeMismatch(format!(
+                                        "array `{}` cannot be used as a value",
+                                        s
+                                    )));
                                 }
                             }
                         } else {
@@ -711,17 +758,23 @@ impl Kit<'_> {
                                     self.context_mut.push_inst_bb(inst_offset_temp); //哪些不插入到块中?
                                     self.context_mut.push_inst_bb(inst_ret);
                                 } else {
-                                    unreachable!("没给偏移")
+                                    return Err(Error::TypeMismatch(format!(
+                                        "array `{}` cannot be used as a value",
+                                        s
+                                    )));
                                 }
                                 //没偏移
                             }
                         }
                     }
-                    _ => {
-                        todo!()
+                    Type::NotForce => {
+                        return Err(Error::Internal(format!(
+                            "variable `{}` has no value type",
+                            s
+                        )))
                     }
                 }
-                return Some((inst_ret, sym));
+                return Ok((inst_ret, sym));
             } else {
                 //不是数组的情况
                 if layer_var < 0 {
@@ -736,18 +789,21 @@ impl Kit<'_> {
                                 let inst_ret =
                                     self.pool_inst_mut.make_global_float_load(inst_global);
                                 self.context_mut.push_inst_bb(inst_ret); //这里
-                                return Some((inst_ret, sym));
+                                return Ok((inst_ret, sym));
                             }
                         }
                         Type::ConstInt | Type::Int => {
                             if let Some(inst_global) = sym.global_inst {
                                 let inst_ret = self.pool_inst_mut.make_global_int_load(inst_global);
                                 self.context_mut.push_inst_bb(inst_ret); //这里
-                                return Some((inst_ret, sym));
+                                return Ok((inst_ret, sym));
                             }
                         }
-                        _ => {
-                            todo!()
+                        Type::NotForce => {
+                            return Err(Error::Internal(format!(
+                                "variable `{}` has no value type",
+                                s
+                            )))
                         }
                     }
                 } else {
@@ -767,35 +823,40 @@ impl Kit<'_> {
                                     inst_ret = self.pool_inst_mut.make_global_int_load(inst_ret);
                                     self.context_mut.push_inst_bb(inst_ret);
                                 }
-                                _ => {
-                                    todo!()
+                                Type::NotForce => {
+                                    return Err(Error::Internal(format!(
+                                        "variable `{}` has no value type",
+                                        s
+                                    )))
                                 }
                             }
                         }
 
-                        return Some((inst_ret, sym));
+                        return Ok((inst_ret, sym));
                     } else {
                         //没找到
                         match sym.tp {
                             Type::ConstFloat | Type::Float => {
-                                let phi_inst = self
-                                    .push_phi(name_changed.clone(), Type::Float, bb)
-                                    .unwrap();
-                                return Some((phi_inst, sym));
+                                let phi_inst =
+                                    self.push_phi(name_changed.clone(), Type::Float, bb)?;
+                                return Ok((phi_inst, sym));
                             }
                             Type::ConstInt | Type::Int => {
                                 let phi_inst =
-                                    self.push_phi(name_changed.clone(), Type::Int, bb).unwrap();
-                                return Some((phi_inst, sym));
+                                    self.push_phi(name_changed.clone(), Type::Int, bb)?;
+                                return Ok((phi_inst, sym));
                             }
-                            _ => {
-                                todo!()
+                            Type::NotForce => {
+                                return Err(Error::Internal(format!(
+                                    "variable `{}` has no value type",
+                                    s
+                                )))
                             }
                         }
                     }
                 }
             }
         }
-        Option::None
+        Err(Error::VariableNotFound(s.to_string()))
     }
 }
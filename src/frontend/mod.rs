@@ -4,6 +4,7 @@ pub mod error;
 pub mod irgen;
 pub mod kit;
 pub mod preprocess;
+pub mod semantic;
 pub mod typesearch;
 use crate::{ir::basicblock::BasicBlock, utility::ObjPtr};
 
@@ -1,52 +1,958 @@
-pub fn preprocess(input: &str) -> String {
-    let mut line_number = 1;
-    let mut result = String::new();
-
-    for line in input.lines() {
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        for part in parts {
-            let pparts: Vec<&str> = part.split(";").collect();
-            for i in 0..pparts.len() - 1 {
-                if pparts[i].contains("starttime()") {
-                    result.push_str(&format!("_sysy_starttime({});", line_number));
-                } else if pparts[i].contains("stoptime()") {
-                    result.push_str(&format!("_sysy_stoptime({});", line_number));
-                } 
-                // else if pparts[i].contains("stoptime(") {//here
-                //     result.push_str(&format!("_sysy_stoptime({};", line_number));
-                // } else if pparts[i].contains("starttime("){//here
-                //     result.push_str(&format!("_sysy_starttime({};", line_number));
-                // } 
-                else {
-                    result.push_str((" ".to_string() + pparts[i] + ";").as_str());
-                }
-            }
-            if !pparts[pparts.len() - 1].is_empty() {
-                if pparts[pparts.len() - 1].contains("starttime()") {
-                    result.push_str(&format!("_sysy_starttime({})", line_number));
-                } else if pparts[pparts.len() - 1].contains("stoptime()") {
-                    result.push_str(&format!("_sysy_stoptime({})", line_number));
-                } 
-                // else if pparts[pparts.len() - 1].contains("starttime(") {//here
-                //     result.push_str(&format!("_sysy_starttime({}", line_number));
-                // } else if pparts[pparts.len() - 1].contains("stoptime(") {//here
-                //     result.push_str(&format!("_sysy_stoptime({}", line_number));
-                // } 
-                else {
-                    result.push_str((" ".to_string() + pparts[pparts.len() - 1]).as_str());
-                }
-            }
-        }
-        result.push('\n');
-        line_number += 1;
-    }
-
-    result
+//! 预处理器
+//!
+//! 以token为单位处理源文件,支持对象宏与函数宏(`#define`/`#undef`)、
+//! `#include "..."`/`#include <...>`、`#ifdef`/`#ifndef`/`#else`/`#endif`以及`__LINE__`。
+//! 运行时库中的`starttime()`/`stoptime()`作为内建宏展开为`_sysy_starttime(__LINE__)`。
+//!
+//! 输出尽量保持源码的行列位置,并记录每个输出行来自哪个文件的哪一行,
+//! 以便诊断信息指回原始源码。
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use super::ast::Span;
+use super::error::Error;
+
+/// include的最大嵌套深度
+const MAX_INCLUDE_DEPTH: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenKind {
+    Ident,
+    Number,
+    Str,
+    Punct,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    text: String,
+    /// 源码位置,宏展开得到的token取宏调用处的位置
+    file: usize,
+    line: usize,
+    col: usize,
+    /// 是否由宏展开得到
+    expanded: bool,
+    /// 展开过程中不能再展开的宏,防止递归展开
+    hide: Vec<String>,
+}
+
+struct Macro {
+    /// 对象宏为None
+    params: Option<Vec<String>>,
+    body: Vec<Token>,
+}
+
+struct Cond {
+    /// 外层条件是否成立
+    parent_active: bool,
+    /// 当前分支是否生效
+    active: bool,
+    has_else: bool,
+    /// `#ifdef`所在位置,用于报告未闭合的条件
+    file: usize,
+    line: usize,
+    col: usize,
+}
+
+struct SourceFile {
+    name: String,
+    text: String,
+}
+
+/// 输出列到源码列的对应关系,从out列开始对应src列
+#[derive(Debug, Clone, Copy)]
+struct Anchor {
+    out: usize,
+    src: usize,
+    /// 宏展开的结果整体对应到宏调用处
+    expanded: bool,
+}
+
+/// 输出中一行的来源
+struct LineOrigin {
+    file: usize,
+    line: usize,
+    anchors: Vec<Anchor>,
+}
+
+impl LineOrigin {
+    fn src_col(&self, out_col: usize) -> usize {
+        match self.anchors.iter().rev().find(|a| a.out <= out_col) {
+            None => out_col,
+            Some(a) if a.expanded => a.src,
+            Some(a) => a.src + (out_col - a.out),
+        }
+    }
+
+    fn out_col(&self, src_col: usize) -> usize {
+        let mut best = src_col;
+        for a in &self.anchors {
+            if a.expanded {
+                if a.src == src_col {
+                    return a.out;
+                }
+            } else if a.src <= src_col {
+                best = a.out + (src_col - a.src);
+            }
+        }
+        best
+    }
+}
+
+/// 源码中的位置,行列从1开始
+pub struct Location<'a> {
+    pub file: &'a str,
+    pub line: usize,
+    pub col: usize,
+    /// 该行的源码
+    pub text: &'a str,
+}
+
+/// 预处理的结果
+pub struct Preprocessed {
+    pub text: String,
+    files: Vec<SourceFile>,
+    lines: Vec<LineOrigin>,
+    line_starts: Vec<usize>,
+}
+
+impl Preprocessed {
+    /// 主文件的文件名
+    pub fn filename(&self) -> &str {
+        &self.files[0].name
+    }
+
+    /// 将输出中的字节偏移映射回源码位置
+    pub fn locate(&self, offset: usize) -> Location<'_> {
+        let offset = offset.min(self.text.len());
+        let index = match self.line_starts.binary_search(&offset) {
+            Ok(index) => index,
+            Err(index) => index - 1,
+        };
+        let start = self.line_starts[index];
+        let out_col = self.text[start..offset].chars().count() + 1;
+        let origin = &self.lines[index];
+        let file = &self.files[origin.file];
+        Location {
+            file: &file.name,
+            line: origin.line,
+            col: origin.src_col(out_col),
+            text: file.text.lines().nth(origin.line - 1).unwrap_or(""),
+        }
+    }
+
+    /// 源码位置在输出中对应的字节偏移
+    fn offset_of(&self, file: usize, line: usize, col: usize) -> usize {
+        let index = self
+            .lines
+            .iter()
+            .position(|origin| origin.file == file && origin.line == line);
+        let index = match index {
+            Some(index) => index,
+            None => return self.text.len(),
+        };
+        let start = self.line_starts[index];
+        let end = self.text[start..]
+            .find('\n')
+            .map_or(self.text.len(), |i| start + i);
+        let out_col = self.lines[index].out_col(col);
+        self.text[start..end]
+            .char_indices()
+            .nth(out_col - 1)
+            .map_or(end, |(i, _)| start + i)
+    }
+}
+
+/// 逐行构造输出并记录行的来源
+#[derive(Default)]
+struct Emitter {
+    text: String,
+    lines: Vec<LineOrigin>,
+    /// 当前输出行已有的字符数
+    col: usize,
+}
+
+impl Emitter {
+    fn begin_line(&mut self, file: usize, line: usize) {
+        if !self.lines.is_empty() {
+            self.text.push('\n');
+        }
+        self.lines.push(LineOrigin {
+            file,
+            line,
+            anchors: vec![],
+        });
+        self.col = 0;
+    }
+
+    /// 输出推进到file的第line行,中间的空行也要输出以保持行号
+    fn advance(&mut self, file: usize, line: usize) {
+        match self.lines.last() {
+            Some(origin) if origin.file == file && origin.line >= line => {}
+            Some(origin) if origin.file == file => {
+                for l in origin.line + 1..=line {
+                    self.begin_line(file, l);
+                }
+            }
+            _ => self.begin_line(file, line),
+        }
+    }
+
+    fn emit(&mut self, tok: &Token) {
+        self.advance(tok.file, tok.line);
+        if self.col < tok.col - 1 {
+            // 补齐空白,使输出与源码的列对齐
+            let pad = tok.col - 1 - self.col;
+            self.text.extend(std::iter::repeat(' ').take(pad));
+            self.col += pad;
+        } else if (tok.expanded || self.col > tok.col - 1)
+            && !self.text.ends_with(char::is_whitespace)
+        {
+            // 宏展开的结果与相邻的token之间加上空白,避免粘连成一个token
+            self.text.push(' ');
+            self.col += 1;
+        }
+        self.lines.last_mut().unwrap().anchors.push(Anchor {
+            out: self.col + 1,
+            src: tok.col,
+            expanded: tok.expanded,
+        });
+        self.text.push_str(&tok.text);
+        self.col += tok.text.chars().count();
+    }
+
+    /// 指令所在的行输出为等宽的空白,使指令中的错误位置仍然准确
+    fn blank(&mut self, width: usize) {
+        self.text.extend(std::iter::repeat(' ').take(width));
+        self.col += width;
+    }
+}
+
+pub struct Preprocessor {
+    include_dirs: Vec<PathBuf>,
+    macros: HashMap<String, Rc<Macro>>,
+    files: Vec<SourceFile>,
+    out: Emitter,
+    /// 错误及其源码位置(文件,行,列,宽度)
+    errors: Vec<(Error, usize, usize, usize, usize)>,
+}
+
+impl Preprocessor {
+    pub fn new() -> Preprocessor {
+        let mut preprocessor = Preprocessor {
+            include_dirs: vec![],
+            macros: HashMap::new(),
+            files: vec![],
+            out: Emitter::default(),
+            errors: vec![],
+        };
+        preprocessor.init_builtin_macros();
+        preprocessor
+    }
+
+    /// 运行时库中的计时函数,展开后带上调用处的行号
+    fn init_builtin_macros(&mut self) {
+        for (name, func) in [
+            ("starttime", "_sysy_starttime"),
+            ("stoptime", "_sysy_stoptime"),
+        ] {
+            let body = tokenize(&format!("{}(__LINE__)", func), 0, 0);
+            let mac = Macro {
+                params: Some(vec![]),
+                body,
+            };
+            self.macros.insert(name.to_string(), Rc::new(mac));
+        }
+    }
+
+    /// 添加`#include`的搜索路径
+    pub fn include_dir(&mut self, dir: impl Into<PathBuf>) {
+        self.include_dirs.push(dir.into());
+    }
+
+    /// 预处理源文件,返回预处理结果与所有错误
+    pub fn process(mut self, filename: &str, source: &str) -> (Preprocessed, Vec<Error>) {
+        self.files.push(SourceFile {
+            name: filename.to_string(),
+            text: source.to_string(),
+        });
+        self.process_file(0, 0);
+        if self.out.lines.is_empty() {
+            self.out.begin_line(0, 1);
+        }
+
+        let mut line_starts = vec![0];
+        for (i, c) in self.out.text.char_indices() {
+            if c == '\n' {
+                line_starts.push(i + 1);
+            }
+        }
+        let preprocessed = Preprocessed {
+            text: self.out.text,
+            files: self.files,
+            lines: self.out.lines,
+            line_starts,
+        };
+        let errors = self
+            .errors
+            .into_iter()
+            .map(|(err, file, line, col, width)| {
+                let start = preprocessed.offset_of(file, line, col);
+                err.at(Span::new(start, start + width))
+            })
+            .collect();
+        (preprocessed, errors)
+    }
+
+    fn error(&mut self, msg: String, tok: &Token) {
+        let width = tok.text.chars().count().max(1);
+        self.errors
+            .push((Error::Preprocess(msg), tok.file, tok.line, tok.col, width));
+    }
+
+    fn process_file(&mut self, file: usize, depth: usize) {
+        let source = strip_comments(&self.files[file].text);
+        let source = match source {
+            Ok(source) => source,
+            Err((source, line, col)) => {
+                let tok = Token {
+                    kind: TokenKind::Punct,
+                    text: "/*".to_string(),
+                    file,
+                    line,
+                    col,
+                    expanded: false,
+                    hide: vec![],
+                };
+                self.error("unterminated comment".to_string(), &tok);
+                source
+            }
+        };
+        let lines: Vec<&str> = source.lines().collect();
+
+        let mut conds: Vec<Cond> = vec![];
+        let mut pending: Vec<Token> = vec![];
+        let mut pending_lines = None;
+        let mut i = 0;
+        while i < lines.len() {
+            let active = conds.iter().all(|c| c.active);
+            if lines[i].trim_start().starts_with('#') {
+                self.flush(&mut pending, file, pending_lines.take());
+                // 以反斜杠结尾的指令延续到下一行
+                let first = i;
+                let mut toks = tokenize(lines[i], file, i + 1);
+                while i + 1 < lines.len() && lines[i].trim_end().ends_with('\\') {
+                    toks.pop();
+                    i += 1;
+                    toks.extend(tokenize(lines[i], file, i + 1));
+                }
+                for (l, text) in lines.iter().enumerate().take(i + 1).skip(first) {
+                    self.out.advance(file, l + 1);
+                    self.out.blank(text.chars().count());
+                }
+                self.directive(file, toks, &mut conds, depth);
+            } else if active {
+                pending.extend(tokenize(lines[i], file, i + 1));
+                let start = pending_lines.map_or(i + 1, |(start, _)| start);
+                pending_lines = Some((start, i + 1));
+            } else {
+                self.out.advance(file, i + 1);
+            }
+            i += 1;
+        }
+        self.flush(&mut pending, file, pending_lines);
+
+        for cond in conds {
+            let tok = Token {
+                kind: TokenKind::Punct,
+                text: "#".to_string(),
+                file: cond.file,
+                line: cond.line,
+                col: cond.col,
+                expanded: false,
+                hide: vec![],
+            };
+            self.error("unterminated conditional directive".to_string(), &tok);
+        }
+    }
+
+    /// 展开并输出一段连续的普通行
+    fn flush(&mut self, pending: &mut Vec<Token>, file: usize, lines: Option<(usize, usize)>) {
+        let (start, end) = match lines {
+            Some(lines) => lines,
+            None => return,
+        };
+        self.out.advance(file, start);
+        let toks = self.expand(std::mem::take(pending));
+        for tok in &toks {
+            self.out.emit(tok);
+        }
+        self.out.advance(file, end);
+    }
+
+    fn directive(&mut self, file: usize, toks: Vec<Token>, conds: &mut Vec<Cond>, depth: usize) {
+        let name = match toks.get(1) {
+            Some(name) => name.clone(),
+            // 空指令
+            None => return,
+        };
+        let active = conds.iter().all(|c| c.active);
+        match name.text.as_str() {
+            "ifdef" | "ifndef" => {
+                let mut taken = false;
+                if active {
+                    match toks.get(2) {
+                        Some(tok) if tok.kind == TokenKind::Ident => {
+                            let defined = self.macros.contains_key(&tok.text);
+                            taken = defined == (name.text == "ifdef");
+                        }
+                        _ => self.error("macro name missing".to_string(), &name),
+                    }
+                }
+                conds.push(Cond {
+                    parent_active: active,
+                    active: taken,
+                    has_else: false,
+                    file,
+                    line: toks[0].line,
+                    col: toks[0].col,
+                });
+            }
+            "if" => {
+                // 只需要维护嵌套关系,使对应的`#endif`能够匹配
+                if active {
+                    self.error("`#if` is not supported, use `#ifdef`".to_string(), &name);
+                }
+                conds.push(Cond {
+                    parent_active: active,
+                    active: false,
+                    has_else: false,
+                    file,
+                    line: toks[0].line,
+                    col: toks[0].col,
+                });
+            }
+            "elif" => {
+                if conds.iter().all(|c| c.parent_active) && !conds.is_empty() {
+                    self.error("`#elif` is not supported".to_string(), &name);
+                }
+            }
+            "else" => match conds.last_mut() {
+                Some(cond) if cond.has_else => {
+                    self.error("`#else` after `#else`".to_string(), &name)
+                }
+                Some(cond) => {
+                    cond.has_else = true;
+                    cond.active = cond.parent_active && !cond.active;
+                }
+                None => self.error("`#else` without `#ifdef`".to_string(), &name),
+            },
+            "endif" => {
+                if conds.pop().is_none() {
+                    self.error("`#endif` without `#ifdef`".to_string(), &name);
+                }
+            }
+            _ if !active => {}
+            "define" => self.define(&name, &toks[2..]),
+            "undef" => match toks.get(2) {
+                Some(tok) if tok.kind == TokenKind::Ident => {
+                    self.macros.remove(&tok.text);
+                }
+                _ => self.error("macro name missing".to_string(), &name),
+            },
+            "include" => self.include(file, &name, &toks[2..], depth),
+            "pragma" => {}
+            _ => self.error(
+                format!("unknown preprocessing directive `#{}`", name.text),
+                &name,
+            ),
+        }
+    }
+
+    fn define(&mut self, directive: &Token, toks: &[Token]) {
+        let name = match toks.first() {
+            Some(tok) if tok.kind == TokenKind::Ident => tok,
+            _ => return self.error("macro name missing".to_string(), directive),
+        };
+        let mut body = &toks[1..];
+        let mut params = None;
+        // 宏名后紧跟"("的是函数宏
+        let is_func = matches!(body.first(),
+            Some(tok) if tok.text == "(" && tok.col == name.col + name.text.chars().count());
+        if is_func {
+            let mut names = vec![];
+            let mut i = 1;
+            loop {
+                match (body.get(i), body.get(i + 1)) {
+                    (Some(tok), _) if tok.text == ")" && names.is_empty() => {
+                        i += 1;
+                        break;
+                    }
+                    (Some(param), Some(sep)) if param.kind == TokenKind::Ident => {
+                        if names.contains(&param.text) {
+                            return self.error(
+                                format!("duplicate macro parameter `{}`", param.text),
+                                param,
+                            );
+                        }
+                        names.push(param.text.clone());
+                        i += 2;
+                        match sep.text.as_str() {
+                            "," => {}
+                            ")" => break,
+                            _ => {
+                                return self.error(
+                                    "expected `,` or `)` in macro parameters".to_string(),
+                                    sep,
+                                )
+                            }
+                        }
+                    }
+                    (Some(tok), _) => {
+                        return self.error(format!("invalid macro parameter `{}`", tok.text), tok)
+                    }
+                    (None, _) => {
+                        return self.error("missing `)` in macro parameter list".to_string(), name)
+                    }
+                }
+            }
+            body = &body[i..];
+            params = Some(names);
+        }
+        if let Some(tok) = body.first().filter(|t| t.text == "##") {
+            return self.error(
+                "`##` cannot appear at either end of a macro".to_string(),
+                tok,
+            );
+        }
+        if let Some(tok) = body.last().filter(|t| t.text == "##") {
+            return self.error(
+                "`##` cannot appear at either end of a macro".to_string(),
+                tok,
+            );
+        }
+        let mac = Macro {
+            params,
+            body: body.to_vec(),
+        };
+        self.macros.insert(name.text.clone(), Rc::new(mac));
+    }
+
+    fn include(&mut self, file: usize, directive: &Token, toks: &[Token], depth: usize) {
+        let (path, quoted) = match toks.first() {
+            Some(tok) if tok.kind == TokenKind::Str && tok.text.len() >= 2 => {
+                (tok.text[1..tok.text.len() - 1].to_string(), true)
+            }
+            Some(tok) if tok.text == "<" => match toks.iter().position(|t| t.text == ">") {
+                Some(end) => (
+                    toks[1..end].iter().map(|t| t.text.as_str()).collect(),
+                    false,
+                ),
+                None => {
+                    return self.error("missing `>` in `#include`".to_string(), tok);
+                }
+            },
+            _ => {
+                return self.error(
+                    "`#include` expects \"FILENAME\" or <FILENAME>".to_string(),
+                    directive,
+                )
+            }
+        };
+        if depth >= MAX_INCLUDE_DEPTH {
+            return self.error("`#include` nested too deeply".to_string(), directive);
+        }
+
+        // 引号形式先在当前文件所在目录中查找
+        let mut dirs = vec![];
+        if quoted {
+            let current = Path::new(&self.files[file].name);
+            dirs.push(current.parent().unwrap_or(Path::new("")).to_path_buf());
+        }
+        dirs.extend(self.include_dirs.iter().cloned());
+        let found = dirs
+            .iter()
+            .map(|dir| dir.join(&path))
+            .find_map(|p| std::fs::read_to_string(&p).ok().map(|text| (p, text)));
+        match found {
+            Some((p, text)) => {
+                self.files.push(SourceFile {
+                    name: p.to_string_lossy().into_owned(),
+                    text,
+                });
+                self.process_file(self.files.len() - 1, depth + 1);
+            }
+            None => self.error(format!("cannot find include file `{}`", path), &toks[0]),
+        }
+    }
+
+    /// 展开一串token中的宏,展开结果会和后面的token一起重新扫描
+    fn expand(&mut self, toks: Vec<Token>) -> Vec<Token> {
+        let mut input: VecDeque<Token> = toks.into();
+        let mut out = vec![];
+        while let Some(tok) = input.pop_front() {
+            if tok.kind != TokenKind::Ident || tok.hide.contains(&tok.text) {
+                out.push(tok);
+                continue;
+            }
+            let mac = match self.macros.get(&tok.text) {
+                Some(mac) => mac.clone(),
+                None if tok.text == "__LINE__" => {
+                    out.push(Token {
+                        kind: TokenKind::Number,
+                        text: tok.line.to_string(),
+                        expanded: true,
+                        ..tok
+                    });
+                    continue;
+                }
+                None => {
+                    out.push(tok);
+                    continue;
+                }
+            };
+            let args = match &mac.params {
+                None => vec![],
+                Some(params) => {
+                    // 函数宏后面没有"("时不展开
+                    if !matches!(input.front(), Some(t) if t.text == "(") {
+                        out.push(tok);
+                        continue;
+                    }
+                    let mut args = match collect_args(&mut input) {
+                        Some(args) => args,
+                        None => {
+                            self.error(
+                                format!("unterminated argument list invoking macro `{}`", tok.text),
+                                &tok,
+                            );
+                            continue;
+                        }
+                    };
+                    if params.is_empty() && args.len() == 1 && args[0].is_empty() {
+                        args.clear();
+                    }
+                    if args.len() != params.len() {
+                        self.error(
+                            format!(
+                                "macro `{}` requires {} argument(s), but {} were given",
+                                tok.text,
+                                params.len(),
+                                args.len()
+                            ),
+                            &tok,
+                        );
+                        continue;
+                    }
+                    args
+                }
+            };
+            let result = self.substitute(&mac, args, &tok);
+            for t in result.into_iter().rev() {
+                input.push_front(t);
+            }
+        }
+        out
+    }
+
+    /// 用实参替换宏体中的形参,并处理`#`和`##`
+    fn substitute(&mut self, mac: &Macro, args: Vec<Vec<Token>>, inv: &Token) -> Vec<Token> {
+        let params: &[String] = mac.params.as_deref().unwrap_or(&[]);
+        let param_of = |tok: &Token| {
+            if tok.kind == TokenKind::Ident {
+                params.iter().position(|p| *p == tok.text)
+            } else {
+                None
+            }
+        };
+        let mut expanded_args: Vec<Option<Vec<Token>>> = vec![None; args.len()];
+        let mut out: Vec<Token> = vec![];
+        let mut paste = false;
+        let mut i = 0;
+        while i < mac.body.len() {
+            let tok = &mac.body[i];
+            if tok.text == "##" {
+                paste = true;
+                i += 1;
+                continue;
+            }
+            let next_paste = mac.body.get(i + 1).map_or(false, |t| t.text == "##");
+            let piece = match mac.body.get(i + 1).and_then(param_of) {
+                Some(p) if tok.text == "#" && mac.params.is_some() => {
+                    i += 1;
+                    vec![stringify(&args[p], tok)]
+                }
+                _ => match param_of(tok) {
+                    // 与`##`相邻的实参不展开
+                    Some(p) if paste || next_paste => args[p].clone(),
+                    Some(p) => {
+                        if expanded_args[p].is_none() {
+                            expanded_args[p] = Some(self.expand(args[p].clone()));
+                        }
+                        expanded_args[p].clone().unwrap()
+                    }
+                    None => vec![tok.clone()],
+                },
+            };
+            if paste {
+                self.paste(&mut out, piece, inv);
+            } else {
+                out.extend(piece);
+            }
+            paste = false;
+            i += 1;
+        }
+        for tok in &mut out {
+            tok.file = inv.file;
+            tok.line = inv.line;
+            tok.col = inv.col;
+            tok.expanded = true;
+            tok.hide.extend(inv.hide.iter().cloned());
+            tok.hide.push(inv.text.clone());
+        }
+        out
+    }
+
+    /// 将piece的第一个token与out的最后一个token拼接
+    fn paste(&mut self, out: &mut Vec<Token>, piece: Vec<Token>, inv: &Token) {
+        let mut piece = piece.into_iter();
+        let (left, right) = match (out.pop(), piece.next()) {
+            (Some(left), Some(right)) => (left, right),
+            (left, right) => {
+                out.extend(left);
+                out.extend(right);
+                out.extend(piece);
+                return;
+            }
+        };
+        let text = left.text.clone() + &right.text;
+        let toks = tokenize(&text, left.file, left.line);
+        if toks.len() == 1 {
+            out.push(Token {
+                kind: toks[0].kind,
+                text,
+                ..left
+            });
+        } else {
+            self.error(
+                format!(
+                    "pasting `{}` and `{}` does not give a valid token",
+                    left.text, right.text
+                ),
+                inv,
+            );
+            out.push(left);
+            out.push(right);
+        }
+        out.extend(piece);
+    }
+}
+
+impl Default for Preprocessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 收集函数宏的实参,input以"("开头;缺少")"时返回None
+fn collect_args(input: &mut VecDeque<Token>) -> Option<Vec<Vec<Token>>> {
+    input.pop_front();
+    let mut args = vec![vec![]];
+    let mut depth = 1;
+    loop {
+        let tok = input.pop_front()?;
+        match tok.text.as_str() {
+            "(" => depth += 1,
+            ")" => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(args);
+                }
+            }
+            "," if depth == 1 => {
+                args.push(vec![]);
+                continue;
+            }
+            _ => {}
+        }
+        args.last_mut().unwrap().push(tok);
+    }
+}
+
+fn stringify(toks: &[Token], hash: &Token) -> Token {
+    let text: Vec<&str> = toks.iter().map(|t| t.text.as_str()).collect();
+    let text = text.join(" ").replace('\\', "\\\\").replace('"', "\\\"");
+    Token {
+        kind: TokenKind::Str,
+        text: format!("\"{}\"", text),
+        ..hash.clone()
+    }
+}
+
+/// 将注释替换为等宽的空白,保留换行,使行列位置不变
+/// 块注释未闭合时返回已处理的文本和注释开始的位置
+fn strip_comments(source: &str) -> Result<String, (String, usize, usize)> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut out = String::with_capacity(source.len());
+    let (mut line, mut col) = (1, 1);
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if c == '/' && next == Some('/') {
+            while i < chars.len() && chars[i] != '\n' {
+                out.push(if chars[i] == '\r' { '\r' } else { ' ' });
+                i += 1;
+            }
+            continue;
+        }
+        if c == '/' && next == Some('*') {
+            let (start_line, start_col) = (line, col);
+            out.push_str("  ");
+            i += 2;
+            col += 2;
+            loop {
+                match (chars.get(i), chars.get(i + 1)) {
+                    (Some('*'), Some('/')) => {
+                        out.push_str("  ");
+                        i += 2;
+                        col += 2;
+                        break;
+                    }
+                    (Some(&c), _) => {
+                        if c == '\n' || c == '\r' {
+                            out.push(c);
+                        } else {
+                            out.push(' ');
+                        }
+                        if c == '\n' {
+                            line += 1;
+                            col = 1;
+                        } else {
+                            col += 1;
+                        }
+                        i += 1;
+                    }
+                    (None, _) => return Err((out, start_line, start_col)),
+                }
+            }
+            continue;
+        }
+        if c == '"' || c == '\'' {
+            // 字面量中的注释符号不是注释
+            out.push(c);
+            i += 1;
+            col += 1;
+            while i < chars.len() && chars[i] != c && chars[i] != '\n' {
+                if chars[i] == '\\' && i + 1 < chars.len() && chars[i + 1] != '\n' {
+                    out.push(chars[i]);
+                    i += 1;
+                    col += 1;
+                }
+                out.push(chars[i]);
+                i += 1;
+                col += 1;
+            }
+            continue;
+        }
+        out.push(c);
+        if c == '\n' {
+            line += 1;
+            col = 1;
+        } else {
+            col += 1;
+        }
+        i += 1;
+    }
+    Ok(out)
+}
+
+const PUNCTS: [&str; 24] = [
+    "...", "<<=", ">>=", "==", "!=", "<=", ">=", "&&", "||", "##", "++", "--", "<<", ">>", "->",
+    "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", "::",
+];
+
+/// 将一行切分为预处理token,列号从1开始
+fn tokenize(line: &str, file: usize, lineno: usize) -> Vec<Token> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut toks = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        let kind = if c.is_ascii_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            TokenKind::Ident
+        } else if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).map_or(false, |c| c.is_ascii_digit()))
+        {
+            // pp-number,包括十六进制浮点数和指数部分的符号
+            i += 1;
+            while i < chars.len() {
+                let d = chars[i];
+                if "eEpP".contains(d) && matches!(chars.get(i + 1), Some('+' | '-')) {
+                    i += 2;
+                } else if d.is_ascii_alphanumeric() || d == '_' || d == '.' {
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+            TokenKind::Number
+        } else if c == '"' || c == '\'' {
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                if chars[i] == '\\' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            i = (i + 1).min(chars.len());
+            TokenKind::Str
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 3)].iter().collect();
+            let len = PUNCTS
+                .iter()
+                .find(|p| rest.starts_with(*p))
+                .map_or(1, |p| p.len());
+            i += len;
+            TokenKind::Punct
+        };
+        toks.push(Token {
+            kind,
+            text: chars[start..i].iter().collect(),
+            file,
+            line: lineno,
+            col: start + 1,
+            expanded: false,
+            hide: vec![],
+        });
+    }
+    toks
 }
 
 #[cfg(test)]
 mod pre_test {
-    use crate::frontend::preprocess::preprocess;
+    use super::Preprocessor;
+    use std::fs;
+
+    fn run(input: &str) -> String {
+        let (pre, errors) = Preprocessor::new().process("test.sy", input);
+        assert!(errors.is_empty(), "{:?}", errors);
+        pre.text
+    }
+
+    fn squash(text: &str) -> String {
+        text.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
 
     #[test]
     fn test() {
@@ -58,7 +964,95 @@ mod pre_test {
             Some more code.
         "#;
 
-        let processed = preprocess(input);
-        println!("{}", processed);
+        let processed = run(input);
+        let lines: Vec<&str> = processed.lines().collect();
+        assert_eq!(processed.split('\n').count(), input.lines().count());
+        assert_eq!(
+            squash(lines[2]),
+            "_sysy_starttime ( 3 ) ; _sysy_stoptime ( 3 ) ; _sysy_stoptime ( 3 ) ; _sysy_stoptime ( 3 ) ;"
+        );
+        assert_eq!(squash(lines[4]), "_sysy_starttime ( 5 ) ;");
+        // 没有宏的行保持原样
+        assert_eq!(lines[3], "            More code here.");
+    }
+
+    #[test]
+    fn test_macros() {
+        let input = "#define N 10\n\
+                     #define MAX(a, b) ((a) > (b) ? (a) : (b))\n\
+                     #define CAT(a, b) a ## b\n\
+                     #define SELF SELF + 1\n\
+                     int x[N] = MAX(N, 2);\n\
+                     int CAT(va, r1) = SELF; int l = __LINE__;\n\
+                     #undef N\n\
+                     int N;\n";
+        let out = run(input);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            squash(lines[4]),
+            "int x[ 10 ] = ( ( 10 ) > ( 2 ) ? ( 10 ) : ( 2 ) ) ;"
+        );
+        assert_eq!(squash(lines[5]), "int var1 = SELF + 1 ; int l = 6 ;");
+        assert_eq!(squash(lines[7]), "int N;");
+    }
+
+    #[test]
+    fn test_conditionals() {
+        let input = "#define A\n\
+                     #ifdef A\n\
+                     int a;\n\
+                     #ifndef A\n\
+                     int b;\n\
+                     #else\n\
+                     int c;\n\
+                     #endif\n\
+                     #else\n\
+                     int d;\n\
+                     #endif\n";
+        assert_eq!(squash(&run(input)), "int a; int c;");
+
+        let (_, errors) =
+            Preprocessor::new().process("test.sy", "#ifdef A\nint a;\n#endif\n#endif\n#foo\n");
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "`#endif` without `#ifdef`",
+                "unknown preprocessing directive `#foo`"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_include_and_locate() {
+        let dir = std::env::temp_dir().join(format!("sysy_pre_test_{}", std::process::id()));
+        fs::create_dir_all(dir.join("inc")).unwrap();
+        fs::write(dir.join("a.h"), "#define TWO 2\nint from_a;\n").unwrap();
+        fs::write(dir.join("inc/b.h"), "int from_b = TWO;\n").unwrap();
+        let main = dir.join("main.sy");
+        let source = "#include \"a.h\"\n#include <b.h>\nint main() { /* c */ return y; }\n";
+
+        let mut preprocessor = Preprocessor::new();
+        preprocessor.include_dir(dir.join("inc"));
+        let (pre, errors) = preprocessor.process(main.to_str().unwrap(), source);
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(
+            squash(&pre.text),
+            "int from_a; int from_b = 2 ; int main() { return y; }"
+        );
+
+        let loc = pre.locate(pre.text.find("from_b").unwrap());
+        assert!(loc.file.ends_with("b.h"));
+        assert_eq!((loc.line, loc.col), (1, 5));
+        let loc = pre.locate(pre.text.find('y').unwrap());
+        assert_eq!(loc.file, pre.filename());
+        assert_eq!((loc.line, loc.col), (3, 29));
+        assert_eq!(loc.text, "int main() { /* c */ return y; }");
+
+        let (pre, errors) =
+            Preprocessor::new().process("test.sy", "int a;\n  #include \"missing.h\"\n");
+        let loc = pre.locate(errors[0].span().unwrap().start);
+        assert_eq!((loc.line, loc.col), (2, 12));
     }
 }
@@ -0,0 +1,1070 @@
+//! 语义分析
+//!
+//! 在生成IR之前遍历AST,检查标识符是否声明、重复声明、常量性、左值、
+//! break/continue的位置、返回值类型以及数组维度,并把类型标注到表达式上。
+//! 只有通过检查的程序才会进入IR生成。
+use std::collections::HashMap;
+
+use super::ast::*;
+use super::error::Error;
+
+/// 常量表达式的值
+#[derive(Debug, Clone, Copy)]
+enum ConstVal {
+    Int(i32),
+    Float(f32),
+    /// 是常量但没有求出具体值,如常量数组的元素
+    Unknown,
+}
+
+impl ConstVal {
+    fn to_btype(self, btype: BType) -> ConstVal {
+        match (self, btype) {
+            (ConstVal::Int(i), BType::Float) => ConstVal::Float(i as f32),
+            (ConstVal::Float(f), BType::Int) => ConstVal::Int(f as i32),
+            _ => self,
+        }
+    }
+}
+
+/// 表达式检查的结果:类型和常量值(非常量为None)
+struct Value {
+    ty: ExpType,
+    konst: Option<ConstVal>,
+}
+
+impl Value {
+    fn new(ty: ExpType, konst: Option<ConstVal>) -> Value {
+        Value { ty, konst }
+    }
+}
+
+struct VarInfo {
+    btype: BType,
+    is_const: bool,
+    dims: Vec<Option<i32>>,
+    value: Option<ConstVal>,
+}
+
+struct FuncInfo {
+    ret: ExpType,
+    params: Vec<ExpType>,
+}
+
+pub struct Analyzer {
+    scopes: Vec<HashMap<String, VarInfo>>,
+    funcs: HashMap<String, FuncInfo>,
+    ret_tp: ExpType,
+    loop_depth: usize,
+    errors: Vec<Error>,
+}
+
+/// 对整个编译单元做语义分析,返回所有诊断信息
+pub fn check(compunit: &mut CompUnit) -> Vec<Error> {
+    let mut analyzer = Analyzer::new();
+    for item in &mut compunit.global_items {
+        match item {
+            GlobalItems::Decl(decl) => analyzer.check_decl(decl),
+            GlobalItems::FuncDef(funcdef) => analyzer.check_funcdef(funcdef),
+        }
+    }
+    analyzer.errors
+}
+
+impl Analyzer {
+    pub fn new() -> Analyzer {
+        let mut analyzer = Analyzer {
+            scopes: vec![HashMap::new()],
+            funcs: HashMap::new(),
+            ret_tp: ExpType::Void,
+            loop_depth: 0,
+            errors: vec![],
+        };
+        analyzer.init_external_funcs();
+        analyzer
+    }
+
+    /// 与Kit::init_external_funcs中的运行时库函数保持一致
+    fn init_external_funcs(&mut self) {
+        let int_array = ExpType::Array(BType::Int, vec![None]);
+        let float_array = ExpType::Array(BType::Float, vec![None]);
+        let funcs = vec![
+            ("getint", ExpType::Int, vec![]),
+            ("getch", ExpType::Int, vec![]),
+            ("getfloat", ExpType::Float, vec![]),
+            ("getarray", ExpType::Int, vec![int_array.clone()]),
+            ("getfarray", ExpType::Int, vec![float_array.clone()]),
+            ("putint", ExpType::Void, vec![ExpType::Int]),
+            ("putch", ExpType::Void, vec![ExpType::Int]),
+            ("putfloat", ExpType::Void, vec![ExpType::Float]),
+            ("putarray", ExpType::Void, vec![ExpType::Int, int_array]),
+            ("putfarray", ExpType::Void, vec![ExpType::Int, float_array]),
+            ("starttime", ExpType::Void, vec![]),
+            ("stoptime", ExpType::Void, vec![]),
+            ("_sysy_starttime", ExpType::Void, vec![ExpType::Int]),
+            ("_sysy_stoptime", ExpType::Void, vec![ExpType::Int]),
+        ];
+        for (name, ret, params) in funcs {
+            self.funcs
+                .insert(name.to_string(), FuncInfo { ret, params });
+        }
+    }
+
+    fn error(&mut self, err: Error) {
+        self.errors.push(err);
+    }
+
+    fn is_global(&self) -> bool {
+        self.scopes.len() == 1
+    }
+
+    fn find_var(&self, name: &str) -> Option<&VarInfo> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    /// 在当前作用域声明变量,重复声明时记录错误
+    fn declare(&mut self, name: &str, info: VarInfo, span: Span) {
+        let scope = self.scopes.last_mut().unwrap();
+        if scope.contains_key(name) {
+            self.errors
+                .push(Error::MultipleDeclaration(name.to_string()).at(span));
+        } else {
+            scope.insert(name.to_string(), info);
+        }
+    }
+
+    /* -------------------------------------------------------------------------- */
+    /*                                 declaration                                */
+    /* -------------------------------------------------------------------------- */
+
+    fn check_decl(&mut self, decl: &mut Decl) {
+        match decl {
+            Decl::ConstDecl(constdecl) => {
+                let btype = constdecl.btype;
+                for def in &mut constdecl.const_def_vec {
+                    self.check_const_def(btype, def);
+                }
+            }
+            Decl::VarDecl(vardecl) => {
+                let btype = vardecl.btype;
+                for def in &mut vardecl.var_def_vec {
+                    self.check_var_def(btype, def);
+                }
+            }
+        }
+    }
+
+    fn check_const_def(&mut self, btype: BType, def: &mut ConstDef) {
+        let dims = self.check_dims(&mut def.const_exp_vec);
+        let mut value = None;
+        match &mut def.const_init_val {
+            ConstInitVal::ConstExp(exp) if dims.is_empty() => {
+                value = self.check_const_init_exp(btype, exp);
+            }
+            ConstInitVal::ConstInitValVec(_) if dims.is_empty() => {
+                self.error(
+                    Error::Semantic(format!(
+                        "scalar `{}` cannot be initialized with a list",
+                        def.ident
+                    ))
+                    .at(def.span),
+                );
+            }
+            ConstInitVal::ConstExp(exp) => {
+                self.check_const_init_exp(btype, exp);
+                self.error(
+                    Error::Semantic(format!(
+                        "array `{}` must be initialized with a list",
+                        def.ident
+                    ))
+                    .at(def.span),
+                );
+            }
+            ConstInitVal::ConstInitValVec(vec) => {
+                let mut count = 0;
+                if let Err(e) = self.check_const_init_list(btype, vec, &dims, &mut count) {
+                    self.error(e.at(def.span));
+                }
+            }
+        }
+        let info = VarInfo {
+            btype,
+            is_const: true,
+            dims,
+            value,
+        };
+        self.declare(&def.ident, info, def.span);
+    }
+
+    /// 检查常量初始值,要求是可以转换为btype的常量
+    fn check_const_init_exp(&mut self, btype: BType, exp: &mut ConstExp) -> Option<ConstVal> {
+        let span = exp.span;
+        match self.check_const_exp(exp) {
+            Ok(v) => match (v.ty, v.konst) {
+                (ExpType::Int | ExpType::Float, Some(konst)) => Some(konst.to_btype(btype)),
+                (ExpType::Int | ExpType::Float, None) => {
+                    self.error(
+                        Error::TypeMismatch(
+                            "initializer of a constant must be a constant expression".to_string(),
+                        )
+                        .at(span),
+                    );
+                    None
+                }
+                (ty, _) => {
+                    self.error(
+                        Error::TypeMismatch(format!("cannot initialize a scalar with `{}`", ty))
+                            .at(span),
+                    );
+                    None
+                }
+            },
+            Err(e) => {
+                self.error(e);
+                None
+            }
+        }
+    }
+
+    /// 按照C的规则检查嵌套初始化列表,count为已经初始化的元素个数
+    fn check_const_init_list(
+        &mut self,
+        btype: BType,
+        vec: &mut Vec<ConstInitVal>,
+        dims: &[Option<i32>],
+        count: &mut i64,
+    ) -> Result<(), Error> {
+        let start = *count;
+        let total = total_len(dims);
+        for val in vec {
+            match val {
+                ConstInitVal::ConstExp(exp) => {
+                    self.check_const_init_exp(btype, exp);
+                    *count += 1;
+                }
+                ConstInitVal::ConstInitValVec(sub) => {
+                    let sub_dims = sub_aggregate(dims, *count - start);
+                    let mut sub_count = 0;
+                    self.check_const_init_list(btype, sub, sub_dims, &mut sub_count)?;
+                    *count += total_len(sub_dims).unwrap_or(1).max(1);
+                }
+            }
+        }
+        check_excess(total, *count - start)
+    }
+
+    fn check_var_def(&mut self, btype: BType, def: &mut VarDef) {
+        let (ident, dims, span) = match def {
+            VarDef::NonArray((ident, span)) => (ident, vec![], *span),
+            VarDef::NonArrayInit((ident, init, span)) => {
+                match init {
+                    InitVal::Exp(exp) => self.check_var_init_exp(exp),
+                    InitVal::InitValVec(_) => self.error(
+                        Error::Semantic(format!(
+                            "scalar `{}` cannot be initialized with a list",
+                            ident
+                        ))
+                        .at(*span),
+                    ),
+                }
+                (ident, vec![], *span)
+            }
+            VarDef::Array((ident, exp_vec, span)) => {
+                let dims = self.check_dims(exp_vec);
+                (ident, dims, *span)
+            }
+            VarDef::ArrayInit((ident, exp_vec, init, span)) => {
+                let dims = self.check_dims(exp_vec);
+                match init {
+                    InitVal::Exp(exp) => {
+                        self.check_var_init_exp(exp);
+                        self.error(
+                            Error::Semantic(format!(
+                                "array `{}` must be initialized with a list",
+                                ident
+                            ))
+                            .at(*span),
+                        );
+                    }
+                    InitVal::InitValVec(vec) => {
+                        let mut count = 0;
+                        if let Err(e) = self.check_var_init_list(vec, &dims, &mut count) {
+                            self.error(e.at(*span));
+                        }
+                    }
+                }
+                (ident, dims, *span)
+            }
+        };
+        let info = VarInfo {
+            btype,
+            is_const: false,
+            dims,
+            value: None,
+        };
+        self.declare(ident, info, span);
+    }
+
+    /// 检查变量初始值,全局变量的初始值必须是常量
+    fn check_var_init_exp(&mut self, exp: &mut Exp) {
+        let span = exp.span;
+        match self.check_exp(exp) {
+            Ok(v) => match v.ty {
+                ExpType::Int | ExpType::Float => {
+                    if self.is_global() && v.konst.is_none() {
+                        self.error(
+                            Error::TypeMismatch(
+                                "initializer of a global variable must be a constant expression"
+                                    .to_string(),
+                            )
+                            .at(span),
+                        );
+                    }
+                }
+                ty => self.error(
+                    Error::TypeMismatch(format!("cannot initialize a scalar with `{}`", ty))
+                        .at(span),
+                ),
+            },
+            Err(e) => self.error(e),
+        }
+    }
+
+    fn check_var_init_list(
+        &mut self,
+        vec: &mut Vec<InitVal>,
+        dims: &[Option<i32>],
+        count: &mut i64,
+    ) -> Result<(), Error> {
+        let start = *count;
+        let total = total_len(dims);
+        for val in vec {
+            match val {
+                InitVal::Exp(exp) => {
+                    self.check_var_init_exp(exp);
+                    *count += 1;
+                }
+                InitVal::InitValVec(sub) => {
+                    let sub_dims = sub_aggregate(dims, *count - start);
+                    let mut sub_count = 0;
+                    self.check_var_init_list(sub, sub_dims, &mut sub_count)?;
+                    *count += total_len(sub_dims).unwrap_or(1).max(1);
+                }
+            }
+        }
+        check_excess(total, *count - start)
+    }
+
+    /// 检查数组声明的各维长度,要求是非负的整型常量
+    fn check_dims(&mut self, exp_vec: &mut Vec<ConstExp>) -> Vec<Option<i32>> {
+        let mut dims = vec![];
+        for exp in exp_vec {
+            dims.push(self.check_dim(exp));
+        }
+        dims
+    }
+
+    fn check_dim(&mut self, exp: &mut ConstExp) -> Option<i32> {
+        let span = exp.span;
+        let v = match self.check_const_exp(exp) {
+            Ok(v) => v,
+            Err(e) => {
+                self.error(e);
+                return None;
+            }
+        };
+        let msg = match (v.ty, v.konst) {
+            (ExpType::Int, Some(ConstVal::Int(i))) if i >= 0 => return Some(i),
+            (ExpType::Int, Some(ConstVal::Int(i))) => {
+                format!("array dimension must not be negative, found {}", i)
+            }
+            (ExpType::Int, Some(_)) => return None,
+            _ => "array dimension is not an integer constant".to_string(),
+        };
+        self.error(Error::ArrayDimension(msg).at(span));
+        None
+    }
+
+    /* -------------------------------------------------------------------------- */
+    /*                                  function                                  */
+    /* -------------------------------------------------------------------------- */
+
+    fn check_funcdef(&mut self, funcdef: &mut FuncDef) {
+        let (functype, id, params, block, span) = match funcdef {
+            FuncDef::NonParameterFuncDef((functype, id, block, span)) => {
+                (functype, id, None, block, *span)
+            }
+            FuncDef::ParameterFuncDef((functype, id, params, block, span)) => {
+                (functype, id, Some(params), block, *span)
+            }
+        };
+        let ret = match functype {
+            FuncType::Void => ExpType::Void,
+            FuncType::Int => ExpType::Int,
+            FuncType::Float => ExpType::Float,
+        };
+
+        // 形参单独一层作用域,函数体是内层的block
+        self.scopes.push(HashMap::new());
+        let mut param_types = vec![];
+        if let Some(params) = params {
+            for param in &mut params.func_fparams_vec {
+                param_types.push(self.check_param(param));
+            }
+        }
+
+        if self.funcs.contains_key(id.as_str()) {
+            self.error(Error::MultipleDeclaration(id.clone()).at(span));
+        } else {
+            // 在函数体之前登记,以支持递归调用
+            let info = FuncInfo {
+                ret: ret.clone(),
+                params: param_types,
+            };
+            self.funcs.insert(id.clone(), info);
+        }
+
+        self.ret_tp = ret;
+        self.loop_depth = 0;
+        self.check_block(block);
+        self.scopes.pop();
+    }
+
+    fn check_param(&mut self, param: &mut FuncFParam) -> ExpType {
+        match param {
+            FuncFParam::NonArray((btype, id, span)) => {
+                let info = VarInfo {
+                    btype: *btype,
+                    is_const: false,
+                    dims: vec![],
+                    value: None,
+                };
+                self.declare(id, info, *span);
+                match btype {
+                    BType::Int => ExpType::Int,
+                    BType::Float => ExpType::Float,
+                }
+            }
+            FuncFParam::Array((btype, id, exp_vec, span)) => {
+                let mut dims = vec![None];
+                for exp in exp_vec {
+                    let span = exp.span;
+                    let dim = match self.check_exp(exp) {
+                        Ok(Value {
+                            ty: ExpType::Int,
+                            konst: Some(ConstVal::Int(i)),
+                        }) if i >= 0 => Some(i),
+                        Ok(Value {
+                            ty: ExpType::Int,
+                            konst: Some(ConstVal::Unknown),
+                        }) => None,
+                        Ok(_) => {
+                            self.error(
+                                Error::ArrayDimension(
+                                    "array dimension must be a non-negative integer constant"
+                                        .to_string(),
+                                )
+                                .at(span),
+                            );
+                            None
+                        }
+                        Err(e) => {
+                            self.error(e);
+                            None
+                        }
+                    };
+                    dims.push(dim);
+                }
+                let info = VarInfo {
+                    btype: *btype,
+                    is_const: false,
+                    dims: dims.clone(),
+                    value: None,
+                };
+                self.declare(id, info, *span);
+                ExpType::Array(*btype, dims)
+            }
+        }
+    }
+
+    /* -------------------------------------------------------------------------- */
+    /*                                  statement                                 */
+    /* -------------------------------------------------------------------------- */
+
+    fn check_block(&mut self, block: &mut Block) {
+        self.scopes.push(HashMap::new());
+        for item in &mut block.block_vec {
+            match item {
+                BlockItem::Decl(decl) => self.check_decl(decl),
+                BlockItem::Stmt(stmt) => self.check_stmt(stmt),
+            }
+        }
+        self.scopes.pop();
+    }
+
+    fn check_stmt(&mut self, stmt: &mut Stmt) {
+        match stmt {
+            Stmt::Assign(assign) => {
+                if let Err(e) = self.check_assign(assign) {
+                    self.error(e);
+                }
+            }
+            Stmt::ExpStmt(exp_stmt) => {
+                if let Some(exp) = &mut exp_stmt.exp {
+                    if let Err(e) = self.check_exp(exp) {
+                        self.error(e);
+                    }
+                }
+            }
+            Stmt::Block(block) => self.check_block(block),
+            Stmt::If(if_stmt) => {
+                self.check_cond(&mut if_stmt.cond);
+                self.check_stmt(&mut if_stmt.then);
+                if let Some(else_then) = &mut if_stmt.else_then {
+                    self.check_stmt(else_then);
+                }
+            }
+            Stmt::While(while_stmt) => {
+                self.check_cond(&mut while_stmt.cond);
+                self.loop_depth += 1;
+                self.check_stmt(&mut while_stmt.body);
+                self.loop_depth -= 1;
+            }
+            Stmt::Break(break_stmt) => {
+                if self.loop_depth == 0 {
+                    self.error(
+                        Error::Semantic("`break` statement not within a loop".to_string())
+                            .at(break_stmt.span),
+                    );
+                }
+            }
+            Stmt::Continue(continue_stmt) => {
+                if self.loop_depth == 0 {
+                    self.error(
+                        Error::Semantic("`continue` statement not within a loop".to_string())
+                            .at(continue_stmt.span),
+                    );
+                }
+            }
+            Stmt::Return(ret) => {
+                if let Err(e) = self.check_return(ret) {
+                    self.error(e);
+                }
+            }
+        }
+    }
+
+    fn check_assign(&mut self, assign: &mut Assign) -> Result<(), Error> {
+        let (is_const, v) = self.check_lval(&mut assign.lval)?;
+        if is_const {
+            return Err(
+                Error::Semantic(format!("cannot assign to constant `{}`", assign.lval.id))
+                    .at(assign.lval.span),
+            );
+        }
+        if let ExpType::Array(..) = v.ty {
+            return Err(
+                Error::Semantic(format!("cannot assign to array `{}`", assign.lval.id))
+                    .at(assign.lval.span),
+            );
+        }
+        let span = assign.exp.span;
+        let v = self.check_exp(&mut assign.exp)?;
+        expect_scalar(&v, "assignment").map_err(|e| e.at(span))
+    }
+
+    fn check_return(&mut self, ret: &mut Return) -> Result<(), Error> {
+        match (&mut ret.exp, &self.ret_tp) {
+            (Some(_), ExpType::Void) => Err(Error::Semantic(
+                "void function should not return a value".to_string(),
+            )
+            .at(ret.span)),
+            (None, ExpType::Void) => Ok(()),
+            (None, ret_tp) => Err(Error::Semantic(format!(
+                "non-void function should return a value of type `{}`",
+                ret_tp
+            ))
+            .at(ret.span)),
+            (Some(exp), _) => {
+                let span = exp.span;
+                let v = self.check_exp(exp)?;
+                expect_scalar(&v, "return value").map_err(|e| e.at(span))
+            }
+        }
+    }
+
+    fn check_cond(&mut self, cond: &mut Cond) {
+        let span = cond.span;
+        if let Err(e) = self.check_lor(&mut cond.l_or_exp) {
+            self.error(e.at(span));
+        }
+    }
+
+    /* -------------------------------------------------------------------------- */
+    /*                                 expression                                 */
+    /* -------------------------------------------------------------------------- */
+
+    fn check_exp(&mut self, exp: &mut Exp) -> Result<Value, Error> {
+        let v = self
+            .check_add(&mut exp.add_exp)
+            .map_err(|e| e.at(exp.span))?;
+        exp.ty = Some(v.ty.clone());
+        Ok(v)
+    }
+
+    fn check_const_exp(&mut self, exp: &mut ConstExp) -> Result<Value, Error> {
+        let v = self
+            .check_add(&mut exp.add_exp)
+            .map_err(|e| e.at(exp.span))?;
+        exp.ty = Some(v.ty.clone());
+        Ok(v)
+    }
+
+    fn check_add(&mut self, exp: &mut AddExp) -> Result<Value, Error> {
+        match exp {
+            AddExp::MulExp(mulexp) => self.check_mul(mulexp),
+            AddExp::OpExp((addexp, op, mulexp)) => {
+                let lhs = self.check_add(addexp)?;
+                let rhs = self.check_mul(mulexp)?;
+                match op {
+                    AddOp::Add => arith(&lhs, &rhs, "+"),
+                    AddOp::Minus => arith(&lhs, &rhs, "-"),
+                }
+            }
+        }
+    }
+
+    fn check_mul(&mut self, exp: &mut MulExp) -> Result<Value, Error> {
+        match exp {
+            MulExp::UnaryExp(unaryexp) => self.check_unary(unaryexp),
+            MulExp::MulExp((mulexp, unaryexp)) => {
+                let lhs = self.check_mul(mulexp)?;
+                let rhs = self.check_unary(unaryexp)?;
+                arith(&lhs, &rhs, "*")
+            }
+            MulExp::DivExp((mulexp, unaryexp)) => {
+                let lhs = self.check_mul(mulexp)?;
+                let rhs = self.check_unary(unaryexp)?;
+                arith(&lhs, &rhs, "/")
+            }
+            MulExp::ModExp((mulexp, unaryexp)) => {
+                let lhs = self.check_mul(mulexp)?;
+                let rhs = self.check_unary(unaryexp)?;
+                if lhs.ty == ExpType::Float || rhs.ty == ExpType::Float {
+                    return Err(Error::TypeMismatch(
+                        "operands of `%` must be integers".to_string(),
+                    ));
+                }
+                arith(&lhs, &rhs, "%")
+            }
+        }
+    }
+
+    fn check_unary(&mut self, exp: &mut UnaryExp) -> Result<Value, Error> {
+        match exp {
+            UnaryExp::PrimaryExp(primaryexp) => match primaryexp.as_mut() {
+                PrimaryExp::Exp(exp) => self.check_exp(exp),
+                PrimaryExp::LVal(lval) => self.check_lval(lval).map(|(_, v)| v),
+                PrimaryExp::Number(Number::IntConst(i)) => {
+                    Ok(Value::new(ExpType::Int, Some(ConstVal::Int(*i))))
+                }
+                PrimaryExp::Number(Number::FloatConst(f)) => {
+                    Ok(Value::new(ExpType::Float, Some(ConstVal::Float(*f))))
+                }
+            },
+            UnaryExp::FuncCall((id, rparams, span)) => self.check_call(id, rparams, *span),
+            UnaryExp::OpUnary((op, unaryexp)) => {
+                let v = self.check_unary(unaryexp)?;
+                match op {
+                    UnaryOp::Add => {
+                        expect_scalar(&v, "unary `+`")?;
+                        Ok(v)
+                    }
+                    UnaryOp::Minus => {
+                        expect_scalar(&v, "unary `-`")?;
+                        let konst = v.konst.map(|c| match c {
+                            ConstVal::Int(i) => ConstVal::Int(i.wrapping_neg()),
+                            ConstVal::Float(f) => ConstVal::Float(-f),
+                            ConstVal::Unknown => ConstVal::Unknown,
+                        });
+                        Ok(Value::new(v.ty, konst))
+                    }
+                    UnaryOp::Exclamation => {
+                        expect_scalar(&v, "`!`")?;
+                        let konst = v.konst.map(|c| match c {
+                            ConstVal::Int(i) => ConstVal::Int((i == 0) as i32),
+                            ConstVal::Float(f) => ConstVal::Int((f == 0.0) as i32),
+                            ConstVal::Unknown => ConstVal::Unknown,
+                        });
+                        Ok(Value::new(ExpType::Int, konst))
+                    }
+                }
+            }
+        }
+    }
+
+    /// 检查左值,返回是否为常量以及左值的类型
+    fn check_lval(&mut self, lval: &mut LVal) -> Result<(bool, Value), Error> {
+        let (btype, is_const, dims, value) = match self.find_var(&lval.id) {
+            Some(info) => (info.btype, info.is_const, info.dims.clone(), info.value),
+            None => return Err(Error::VariableNotFound(lval.id.clone()).at(lval.span)),
+        };
+        if lval.exp_vec.len() > dims.len() {
+            let msg = if dims.is_empty() {
+                format!("subscripted value `{}` is not an array", lval.id)
+            } else {
+                format!(
+                    "too many subscripts for `{}`: it has {} dimension(s), but {} were given",
+                    lval.id,
+                    dims.len(),
+                    lval.exp_vec.len()
+                )
+            };
+            return Err(Error::ArrayDimension(msg).at(lval.span));
+        }
+        let mut const_index = true;
+        for exp in &mut lval.exp_vec {
+            let span = exp.span;
+            let v = self.check_exp(exp)?;
+            if v.ty != ExpType::Int {
+                return Err(Error::TypeMismatch(format!(
+                    "array subscript must be an integer, found `{}`",
+                    v.ty
+                ))
+                .at(span));
+            }
+            const_index &= v.konst.is_some();
+        }
+        let rest = dims[lval.exp_vec.len()..].to_vec();
+        if !rest.is_empty() {
+            return Ok((is_const, Value::new(ExpType::Array(btype, rest), None)));
+        }
+        let ty = match btype {
+            BType::Int => ExpType::Int,
+            BType::Float => ExpType::Float,
+        };
+        let konst = match (is_const, dims.is_empty()) {
+            (true, true) => value,
+            (true, false) if const_index => Some(ConstVal::Unknown),
+            _ => None,
+        };
+        Ok((is_const, Value::new(ty, konst)))
+    }
+
+    fn check_call(
+        &mut self,
+        id: &str,
+        rparams: &mut OptionFuncRParams,
+        span: Span,
+    ) -> Result<Value, Error> {
+        let (ret, params) = match self.funcs.get(id) {
+            Some(info) => (info.ret.clone(), info.params.clone()),
+            None => return Err(Error::FunctionNotFound(id.to_string()).at(span)),
+        };
+        let mut args = vec![];
+        if let Some(rparams) = &mut rparams.func_fparams {
+            for exp in &mut rparams.exp_vec {
+                args.push(exp);
+            }
+        }
+        if args.len() != params.len() {
+            return Err(Error::ArgumentCount {
+                func: id.to_string(),
+                expected: params.len(),
+                found: args.len(),
+            }
+            .at(span));
+        }
+        for (index, (exp, param)) in args.into_iter().zip(params.iter()).enumerate() {
+            let span = exp.span;
+            let v = self.check_exp(exp)?;
+            if !arg_compatible(param, &v.ty) {
+                return Err(Error::TypeMismatch(format!(
+                    "argument {} of `{}` expects `{}`, found `{}`",
+                    index + 1,
+                    id,
+                    param,
+                    v.ty
+                ))
+                .at(span));
+            }
+        }
+        Ok(Value::new(ret, None))
+    }
+
+    fn check_lor(&mut self, exp: &mut LOrExp) -> Result<Value, Error> {
+        match exp {
+            LOrExp::LAndExp(landexp) => self.check_land(landexp),
+            LOrExp::OrExp((lorexp, landexp)) => {
+                let lhs = self.check_lor(lorexp)?;
+                let rhs = self.check_land(landexp)?;
+                logic(&lhs, &rhs, "||")
+            }
+        }
+    }
+
+    fn check_land(&mut self, exp: &mut LAndExp) -> Result<Value, Error> {
+        match exp {
+            LAndExp::EqExp(eqexp) => {
+                let v = self.check_eq(eqexp)?;
+                expect_scalar(&v, "condition")?;
+                Ok(v)
+            }
+            LAndExp::AndExp((landexp, eqexp)) => {
+                let lhs = self.check_land(landexp)?;
+                let rhs = self.check_eq(eqexp)?;
+                logic(&lhs, &rhs, "&&")
+            }
+        }
+    }
+
+    fn check_eq(&mut self, exp: &mut EqExp) -> Result<Value, Error> {
+        match exp {
+            EqExp::RelExp(relexp) => self.check_rel(relexp),
+            EqExp::EqualExp((eqexp, relexp)) => {
+                let lhs = self.check_eq(eqexp)?;
+                let rhs = self.check_rel(relexp)?;
+                logic(&lhs, &rhs, "==")
+            }
+            EqExp::NotEqualExp((eqexp, relexp)) => {
+                let lhs = self.check_eq(eqexp)?;
+                let rhs = self.check_rel(relexp)?;
+                logic(&lhs, &rhs, "!=")
+            }
+        }
+    }
+
+    fn check_rel(&mut self, exp: &mut RelExp) -> Result<Value, Error> {
+        match exp {
+            RelExp::AddExp(addexp) => self.check_add(addexp),
+            RelExp::OpExp((relexp, op, addexp)) => {
+                let lhs = self.check_rel(relexp)?;
+                let rhs = self.check_add(addexp)?;
+                let op = match op {
+                    RelOp::Less => "<",
+                    RelOp::LessOrEqual => "<=",
+                    RelOp::Greater => ">",
+                    RelOp::GreaterOrEqual => ">=",
+                };
+                logic(&lhs, &rhs, op)
+            }
+        }
+    }
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn expect_scalar(v: &Value, what: &str) -> Result<(), Error> {
+    match v.ty {
+        ExpType::Int | ExpType::Float => Ok(()),
+        ExpType::Void => Err(Error::TypeMismatch(format!("void value used in {}", what))),
+        ExpType::Array(..) => Err(Error::TypeMismatch(format!(
+            "array `{}` used in {}",
+            v.ty, what
+        ))),
+    }
+}
+
+/// 算术运算,结果为两个操作数中较"大"的类型
+fn arith(lhs: &Value, rhs: &Value, op: &str) -> Result<Value, Error> {
+    let what = format!("operand of `{}`", op);
+    expect_scalar(lhs, &what)?;
+    expect_scalar(rhs, &what)?;
+    let ty = if lhs.ty == ExpType::Float || rhs.ty == ExpType::Float {
+        ExpType::Float
+    } else {
+        ExpType::Int
+    };
+    let konst = match (lhs.konst, rhs.konst) {
+        (Some(l), Some(r)) => Some(fold(l, r, op, &ty)),
+        _ => None,
+    };
+    Ok(Value::new(ty, konst))
+}
+
+/// 比较和逻辑运算,结果为int
+fn logic(lhs: &Value, rhs: &Value, op: &str) -> Result<Value, Error> {
+    let what = format!("operand of `{}`", op);
+    expect_scalar(lhs, &what)?;
+    expect_scalar(rhs, &what)?;
+    let konst = match (lhs.konst, rhs.konst) {
+        (Some(_), Some(_)) => Some(ConstVal::Unknown),
+        _ => None,
+    };
+    Ok(Value::new(ExpType::Int, konst))
+}
+
+/// 常量折叠,除零等未定义行为不求值
+fn fold(lhs: ConstVal, rhs: ConstVal, op: &str, ty: &ExpType) -> ConstVal {
+    match (lhs, rhs, ty) {
+        (ConstVal::Int(l), ConstVal::Int(r), ExpType::Int) => {
+            let v = match op {
+                "+" => Some(l.wrapping_add(r)),
+                "-" => Some(l.wrapping_sub(r)),
+                "*" => Some(l.wrapping_mul(r)),
+                "/" => l.checked_div(r),
+                "%" => l.checked_rem(r),
+                _ => None,
+            };
+            v.map_or(ConstVal::Unknown, ConstVal::Int)
+        }
+        (ConstVal::Unknown, _, _) | (_, ConstVal::Unknown, _) => ConstVal::Unknown,
+        (l, r, _) => {
+            let (l, r) = match (l.to_btype(BType::Float), r.to_btype(BType::Float)) {
+                (ConstVal::Float(l), ConstVal::Float(r)) => (l, r),
+                _ => return ConstVal::Unknown,
+            };
+            match op {
+                "+" => ConstVal::Float(l + r),
+                "-" => ConstVal::Float(l - r),
+                "*" => ConstVal::Float(l * r),
+                "/" => ConstVal::Float(l / r),
+                _ => ConstVal::Unknown,
+            }
+        }
+    }
+}
+
+/// 实参类型能否传给形参
+/// 标量之间可以隐式转换,数组要求元素类型和第一维以外已知的长度一致
+fn arg_compatible(param: &ExpType, arg: &ExpType) -> bool {
+    match (param, arg) {
+        (ExpType::Int | ExpType::Float, ExpType::Int | ExpType::Float) => true,
+        (ExpType::Array(pb, pdims), ExpType::Array(ab, adims)) => {
+            pb == ab
+                && pdims.len() == adims.len()
+                && pdims
+                    .iter()
+                    .zip(adims.iter())
+                    .skip(1)
+                    .all(|(p, a)| p.is_none() || a.is_none() || p == a)
+        }
+        _ => false,
+    }
+}
+
+/// 数组元素总个数,有未知维度时返回None
+fn total_len(dims: &[Option<i32>]) -> Option<i64> {
+    dims.iter()
+        .try_fold(1i64, |acc, dim| dim.map(|len| acc * len as i64))
+}
+
+/// 初始化列表中遇到嵌套的"{"时,它对应的子数组的维度
+/// 即在当前位置对齐的最大子数组,count为当前已经初始化的元素个数
+/// 没有对齐时按最后一维处理,与irgen的补齐方式一致
+fn sub_aggregate(dims: &[Option<i32>], count: i64) -> &[Option<i32>] {
+    for k in 1..dims.len() {
+        match total_len(&dims[k..]) {
+            Some(len) if len > 0 && count % len == 0 => return &dims[k..],
+            Some(_) => {}
+            None => return &dims[k..],
+        }
+    }
+    match dims.len() {
+        0 | 1 => &dims[dims.len()..],
+        len => &dims[len - 1..],
+    }
+}
+
+fn check_excess(total: Option<i64>, count: i64) -> Result<(), Error> {
+    match total {
+        Some(total) if count > total.max(1) => Err(Error::ArrayDimension(format!(
+            "excess elements in initializer: {} elements for {} slot(s)",
+            count, total
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod semantic_test {
+    use super::*;
+
+    fn var(name: &str, span: Span) -> VarDef {
+        VarDef::NonArray((name.to_string(), span))
+    }
+
+    fn lval_exp(name: &str, span: Span) -> Exp {
+        let lval = LVal {
+            id: name.to_string(),
+            exp_vec: vec![],
+            span,
+        };
+        let unary = UnaryExp::PrimaryExp(Box::new(PrimaryExp::LVal(lval)));
+        Exp {
+            add_exp: Box::new(AddExp::MulExp(Box::new(MulExp::UnaryExp(Box::new(unary))))),
+            span,
+            ty: None,
+        }
+    }
+
+    fn func(ret: FuncType, name: &str, stmts: Vec<BlockItem>) -> GlobalItems {
+        GlobalItems::FuncDef(FuncDef::NonParameterFuncDef((
+            ret,
+            name.to_string(),
+            Block { block_vec: stmts },
+            Span::default(),
+        )))
+    }
+
+    #[test]
+    fn test_collects_all_errors() {
+        let decl = |name: &str, at: usize| {
+            BlockItem::Decl(Decl::VarDecl(VarDecl {
+                btype: BType::Int,
+                var_def_vec: vec![var(name, Span::new(at, at + 1))],
+            }))
+        };
+        let mut compunit = CompUnit {
+            global_items: vec![func(
+                FuncType::Void,
+                "main",
+                vec![
+                    decl("a", 1),
+                    decl("a", 2),
+                    BlockItem::Stmt(Stmt::Break(Break {
+                        span: Span::new(3, 4),
+                    })),
+                    BlockItem::Stmt(Stmt::Return(Return {
+                        exp: Some(lval_exp("b", Span::new(5, 6))),
+                        span: Span::new(4, 7),
+                    })),
+                ],
+            )],
+        };
+        let errors = check(&mut compunit);
+        let spans: Vec<_> = errors.iter().map(|e| e.span().unwrap().start).collect();
+        assert_eq!(spans, vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_annotates_types() {
+        let mut exp = lval_exp("x", Span::new(0, 1));
+        let mut analyzer = Analyzer::new();
+        let info = VarInfo {
+            btype: BType::Float,
+            is_const: false,
+            dims: vec![Some(2), Some(3)],
+            value: None,
+        };
+        analyzer.declare("x", info, Span::default());
+        analyzer.check_exp(&mut exp).ok().unwrap();
+        assert_eq!(
+            exp.ty,
+            Some(ExpType::Array(BType::Float, vec![Some(2), Some(3)]))
+        );
+    }
+
+    #[test]
+    fn test_sub_aggregate() {
+        let dims = [Some(2), Some(3), Some(4)];
+        assert_eq!(sub_aggregate(&dims, 0), &dims[1..]);
+        assert_eq!(sub_aggregate(&dims, 4), &dims[2..]);
+        assert_eq!(sub_aggregate(&dims, 5), &dims[2..]);
+        assert!(check_excess(total_len(&dims), 25).is_err());
+    }
+}
@@ -39,8 +39,8 @@ impl TypeProcess for AddExp {
         match self {
             AddExp::MulExp(mulexp) => return mulexp.type_process(input, kit_mut),
             AddExp::OpExp((addexp, _op, mulexp)) => {
-                let tp_left = addexp.type_process(input, kit_mut).unwrap();
-                let tp_right = mulexp.type_process(input, kit_mut).unwrap();
+                let tp_left = addexp.type_process(input, kit_mut)?;
+                let tp_right = mulexp.type_process(input, kit_mut)?;
                 if tp_left > tp_right {
                     return Ok(tp_left);
                 } else {
@@ -66,8 +66,8 @@ impl TypeProcess for MulExp {
             MulExp::MulExp((mulexp, unaryexp))
             | MulExp::DivExp((mulexp, unaryexp))
             | MulExp::ModExp((mulexp, unaryexp)) => {
-                let tp_left = mulexp.type_process(input, kit_mut).unwrap();
-                let tp_right = unaryexp.type_process(input, kit_mut).unwrap();
+                let tp_left = mulexp.type_process(input, kit_mut)?;
+                let tp_right = unaryexp.type_process(input, kit_mut)?;
                 if tp_left > tp_right {
                     return Ok(tp_left);
                 } else {
@@ -88,14 +88,16 @@ impl TypeProcess for UnaryExp {
     ) -> Result<Self::Ret, Error> {
         match self {
             UnaryExp::PrimaryExp(primaryexp) => primaryexp.type_process(input, kit_mut),
-            UnaryExp::FuncCall((id, _)) => {
-                let inst_func = kit_mut.context_mut.module_mut.get_function(&id);
+            UnaryExp::FuncCall((id, funcparams, span)) => {
+                let inst_func = kit_mut.find_function(id, funcparams, *span)?;
                 match inst_func.as_ref().get_return_type() {
                     IrType::Float => Ok(3),
                     IrType::Int => Ok(1),
-                    _ => {
-                        unreachable!()
-                    }
+                    _ => Err(Error::TypeMismatch(format!(
+                        "void function `{}` used as a value",
+                        id
+                    ))
+                    .at(*span)),
                 }
             }
             UnaryExp::OpUnary((_, unaryexp)) => unaryexp.type_process(input, kit_mut),
@@ -114,13 +116,17 @@ impl TypeProcess for PrimaryExp {
         match self {
             PrimaryExp::Exp(exp) => exp.type_process(input, kit_mut),
             PrimaryExp::LVal(lval) => {
-                let sym = kit_mut.get_var_symbol(&lval.id).unwrap();
+                let sym = kit_mut
+                    .get_var_symbol(&lval.id)
+                    .map_err(|e| e.at(lval.span))?;
                 match sym.tp {
                     Type::ConstFloat | Type::Float => Ok(3),
                     Type::ConstInt | Type::Int => Ok(1),
-                    _ => {
-                        todo!()
-                    }
+                    Type::NotForce => Err(Error::Internal(format!(
+                        "variable `{}` has no value type",
+                        lval.id
+                    ))
+                    .at(lval.span)),
                 }
             }
             PrimaryExp::Number(imm) => match imm {
@@ -139,7 +145,15 @@ impl TypeProcess for Exp {
         input: Self::Message,
         kit_mut: &mut Kit,
     ) -> Result<Self::Ret, Error> {
-        self.add_exp.type_process(input, kit_mut)
+        // 语义分析已经标注过类型的表达式直接使用标注
+        match &self.ty {
+            Some(ExpType::Int | ExpType::Array(BType::Int, _)) => return Ok(1),
+            Some(ExpType::Float | ExpType::Array(BType::Float, _)) => return Ok(3),
+            _ => {}
+        }
+        self.add_exp
+            .type_process(input, kit_mut)
+            .map_err(|e| e.at(self.span))
     }
 }
 
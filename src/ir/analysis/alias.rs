@@ -0,0 +1,522 @@
+//! 别名分析和函数的mod/ref摘要
+//!
+//! 内存位置由基址和相对基址的偏移组成,基址是全局变量(标量或数组)、局部数组或数组形参。
+//! 不同的全局变量和局部数组互不别名;数组形参只可能指向全局数组或调用者的数组,
+//! 不会指向本函数的局部数组和标量全局变量。基址相同时,偏移确定不同的位置互不别名。
+//!
+//! 函数的mod/ref摘要记录函数及其调用的函数读写的全局变量,以及通过哪些指针形参读写了内存,
+//! 在调用图上迭代到不动点。外部函数不访问全局变量,但可能读写所有指针实参。
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    ir::{
+        instruction::{Inst, InstKind},
+        module::Module,
+        tools::{bfs_inst_process, func_process},
+    },
+    utility::ObjPtr,
+};
+
+/// 内存位置的基址
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum MemBase {
+    /// 标量全局变量,或者全局数组的alloca
+    Global(ObjPtr<Inst>),
+    /// 局部数组的alloca
+    Local(ObjPtr<Inst>),
+    /// 数组形参
+    Param(ObjPtr<Inst>),
+    /// 无法确定基址的指针,比如phi
+    Unknown,
+}
+
+/// 相对基址的偏移,以元素为单位
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum MemOffset {
+    Const(i64),
+    /// 一个变量加上常量
+    Var(ObjPtr<Inst>, i64),
+    Unknown,
+}
+
+/// 内存位置
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct MemLoc {
+    pub base: MemBase,
+    pub offset: MemOffset,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AliasResult {
+    NoAlias,
+    MayAlias,
+    MustAlias,
+}
+
+/// 指令对一个内存位置的访问
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ModRef {
+    NoModRef,
+    Ref,
+    Mod,
+    ModRef,
+}
+
+impl ModRef {
+    fn new(is_mod: bool, is_ref: bool) -> ModRef {
+        match (is_mod, is_ref) {
+            (false, false) => ModRef::NoModRef,
+            (false, true) => ModRef::Ref,
+            (true, false) => ModRef::Mod,
+            (true, true) => ModRef::ModRef,
+        }
+    }
+
+    /// 是否可能写
+    pub fn is_mod(&self) -> bool {
+        matches!(self, ModRef::Mod | ModRef::ModRef)
+    }
+
+    /// 是否可能读
+    pub fn is_ref(&self) -> bool {
+        matches!(self, ModRef::Ref | ModRef::ModRef)
+    }
+}
+
+/// 函数的mod/ref摘要,包括其调用的函数
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct ModRefSummary {
+    /// 写的全局变量
+    pub mod_globals: HashSet<ObjPtr<Inst>>,
+    /// 读的全局变量
+    pub ref_globals: HashSet<ObjPtr<Inst>>,
+    /// 通过这些位置的指针形参写内存
+    pub mod_params: HashSet<usize>,
+    /// 通过这些位置的指针形参读内存
+    pub ref_params: HashSet<usize>,
+    /// 可能写任意内存
+    pub mod_all: bool,
+    /// 可能读任意内存
+    pub ref_all: bool,
+}
+
+impl ModRefSummary {
+    /// 外部函数只读写指针实参,线程相关的函数让其他线程运行函数的剩余部分,可能读写任意内存
+    fn new_extern(name: &str, call: ObjPtr<Inst>) -> ModRefSummary {
+        if name.starts_with("hitsz_thread") {
+            return ModRefSummary {
+                mod_all: true,
+                ref_all: true,
+                ..Default::default()
+            };
+        }
+        let params: HashSet<usize> = call
+            .get_args()
+            .iter()
+            .enumerate()
+            .filter(|(_, arg)| arg.get_ir_type().is_pointer())
+            .map(|(i, _)| i)
+            .collect();
+        ModRefSummary {
+            mod_params: params.clone(),
+            ref_params: params,
+            ..Default::default()
+        }
+    }
+
+    /// 记录对一个位置的读写
+    fn add_access(&mut self, loc: MemLoc, is_mod: bool, params: &[ObjPtr<Inst>]) {
+        match loc.base {
+            MemBase::Global(global) => {
+                if is_mod {
+                    self.mod_globals.insert(global);
+                } else {
+                    self.ref_globals.insert(global);
+                }
+            }
+            MemBase::Param(param) => {
+                let index = params.iter().position(|p| *p == param).unwrap();
+                if is_mod {
+                    self.mod_params.insert(index);
+                } else {
+                    self.ref_params.insert(index);
+                }
+            }
+            MemBase::Local(_) => {}
+            MemBase::Unknown => {
+                if is_mod {
+                    self.mod_all = true;
+                } else {
+                    self.ref_all = true;
+                }
+            }
+        }
+    }
+
+    /// 合并被调用函数的摘要,被调用函数通过形参的读写转换为对实参的读写
+    fn add_call(&mut self, callee: &ModRefSummary, call: ObjPtr<Inst>, params: &[ObjPtr<Inst>]) {
+        self.mod_globals.extend(callee.mod_globals.iter().copied());
+        self.ref_globals.extend(callee.ref_globals.iter().copied());
+        self.mod_all |= callee.mod_all;
+        self.ref_all |= callee.ref_all;
+        let args = call.get_args();
+        for &index in callee.mod_params.iter() {
+            self.add_access(get_location(args[index]), true, params);
+        }
+        for &index in callee.ref_params.iter() {
+            self.add_access(get_location(args[index]), false, params);
+        }
+    }
+}
+
+/// 别名分析,提供内存访问之间的查询
+pub struct AliasAnalysis {
+    summaries: HashMap<String, ModRefSummary>,
+}
+
+impl AliasAnalysis {
+    /// 分析module中所有函数的mod/ref摘要
+    pub fn new(module: &mut Module) -> AliasAnalysis {
+        let mut funcs = vec![];
+        func_process(module, |name, func| {
+            let mut insts = vec![];
+            bfs_inst_process(func.get_head(), |inst| match inst.get_kind() {
+                InstKind::Load if inst.is_global_array_load() => {}
+                InstKind::Load | InstKind::Store | InstKind::Call(_) => insts.push(inst),
+                _ => {}
+            });
+            funcs.push((name, func.get_parameter_list().clone(), insts));
+        });
+
+        let mut analysis = AliasAnalysis {
+            summaries: funcs
+                .iter()
+                .map(|(name, _, _)| (name.clone(), ModRefSummary::default()))
+                .collect(),
+        };
+        loop {
+            let mut changed = false;
+            for (name, params, insts) in funcs.iter() {
+                let mut summary = analysis.summaries[name].clone();
+                for &inst in insts.iter() {
+                    match inst.get_kind() {
+                        InstKind::Load => {
+                            summary.add_access(get_location(inst.get_ptr()), false, params)
+                        }
+                        InstKind::Store => {
+                            summary.add_access(get_location(inst.get_dest()), true, params)
+                        }
+                        InstKind::Call(callee) => {
+                            let callee = analysis.get_call_summary(&callee, inst);
+                            summary.add_call(&callee, inst, params);
+                        }
+                        _ => unreachable!(),
+                    }
+                }
+                if summary != analysis.summaries[name] {
+                    analysis.summaries.insert(name.clone(), summary);
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        analysis
+    }
+
+    /// 获得函数的mod/ref摘要,外部函数返回None
+    pub fn get_summary(&self, func_name: &str) -> Option<&ModRefSummary> {
+        self.summaries.get(func_name)
+    }
+
+    fn get_call_summary(&self, callee: &str, call: ObjPtr<Inst>) -> ModRefSummary {
+        match self.summaries.get(callee) {
+            Some(summary) => summary.clone(),
+            None => ModRefSummary::new_extern(callee, call),
+        }
+    }
+
+    /// 判断两个指针指向的位置是否别名
+    pub fn alias(&self, lhs: ObjPtr<Inst>, rhs: ObjPtr<Inst>) -> AliasResult {
+        alias_location(get_location(lhs), get_location(rhs))
+    }
+
+    /// 判断指令是否读写ptr指向的位置
+    pub fn get_mod_ref(&self, inst: ObjPtr<Inst>, ptr: ObjPtr<Inst>) -> ModRef {
+        self.get_mod_ref_with(inst, ptr, &get_location)
+    }
+
+    /// 判断指令是否读写ptr指向的位置,指针指向的位置由locate求出
+    pub fn get_mod_ref_with(
+        &self,
+        inst: ObjPtr<Inst>,
+        ptr: ObjPtr<Inst>,
+        locate: &dyn Fn(ObjPtr<Inst>) -> MemLoc,
+    ) -> ModRef {
+        let loc = locate(ptr);
+        let may_alias =
+            |other: ObjPtr<Inst>| alias_location(locate(other), loc) != AliasResult::NoAlias;
+        match inst.get_kind() {
+            // 取全局数组的地址不访问内存
+            InstKind::Load if inst.is_global_array_load() => ModRef::NoModRef,
+            InstKind::Load => ModRef::new(false, may_alias(inst.get_ptr())),
+            InstKind::Store => ModRef::new(may_alias(inst.get_dest()), false),
+            InstKind::Call(callee) => {
+                let summary = self.get_call_summary(&callee, inst);
+                let args = inst.get_args();
+                // 被调用函数可能读写全局变量或实参指向的数组中的任意位置
+                let may_access = |globals: &HashSet<ObjPtr<Inst>>, params: &HashSet<usize>| {
+                    globals.iter().any(|&global| {
+                        alias_location(locate(global).whole(), loc) != AliasResult::NoAlias
+                    }) || params.iter().any(|&index| {
+                        alias_location(locate(args[index]).whole(), loc) != AliasResult::NoAlias
+                    })
+                };
+                ModRef::new(
+                    summary.mod_all || may_access(&summary.mod_globals, &summary.mod_params),
+                    summary.ref_all || may_access(&summary.ref_globals, &summary.ref_params),
+                )
+            }
+            // 返回后调用者可以读局部数组以外的内存
+            InstKind::Return => ModRef::new(false, !matches!(loc.base, MemBase::Local(_))),
+            _ => ModRef::NoModRef,
+        }
+    }
+
+    /// 判断调用是否可能读写内存
+    pub fn get_call_mod_ref(&self, call: ObjPtr<Inst>) -> ModRef {
+        let summary = match call.get_kind() {
+            InstKind::Call(callee) => self.get_call_summary(&callee, call),
+            _ => unreachable!("AliasAnalysis::get_call_mod_ref"),
+        };
+        ModRef::new(
+            summary.mod_all || !summary.mod_globals.is_empty() || !summary.mod_params.is_empty(),
+            summary.ref_all || !summary.ref_globals.is_empty() || !summary.ref_params.is_empty(),
+        )
+    }
+}
+
+impl MemLoc {
+    /// 基址相同的整个数组
+    pub fn whole(self) -> MemLoc {
+        MemLoc {
+            base: self.base,
+            offset: MemOffset::Unknown,
+        }
+    }
+}
+
+/// 求指针指向的内存位置
+pub fn get_location(ptr: ObjPtr<Inst>) -> MemLoc {
+    let loc = |base| MemLoc {
+        base,
+        offset: MemOffset::Const(0),
+    };
+    match ptr.get_kind() {
+        InstKind::GlobalInt(_)
+        | InstKind::GlobalFloat(_)
+        | InstKind::GlobalConstInt(_)
+        | InstKind::GlobalConstFloat(_) => loc(MemBase::Global(ptr)),
+        InstKind::Alloca(_) if ptr.is_global_var() => loc(MemBase::Global(ptr)),
+        InstKind::Alloca(_) => loc(MemBase::Local(ptr)),
+        InstKind::Parameter => loc(MemBase::Param(ptr)),
+        // 全局数组通过load其地址来使用
+        InstKind::Load if ptr.is_global_array_load() => get_location(ptr.get_ptr()),
+        InstKind::Gep => {
+            let mut loc = get_location(ptr.get_gep_ptr());
+            let offset = ptr.get_gep_offset();
+            loc.offset = match (loc.offset, offset.get_kind()) {
+                (MemOffset::Const(c), InstKind::ConstInt(value)) => {
+                    MemOffset::Const(c + value as i64)
+                }
+                (MemOffset::Const(c), _) => MemOffset::Var(offset, c),
+                (MemOffset::Var(var, c), InstKind::ConstInt(value)) => {
+                    MemOffset::Var(var, c + value as i64)
+                }
+                _ => MemOffset::Unknown,
+            };
+            loc
+        }
+        _ => MemLoc {
+            base: MemBase::Unknown,
+            offset: MemOffset::Unknown,
+        },
+    }
+}
+
+/// 判断两个内存位置是否别名
+pub fn alias_location(lhs: MemLoc, rhs: MemLoc) -> AliasResult {
+    let is_scalar = |global: ObjPtr<Inst>| !matches!(global.get_kind(), InstKind::Alloca(_));
+    match (lhs.base, rhs.base) {
+        (MemBase::Unknown, _) | (_, MemBase::Unknown) => AliasResult::MayAlias,
+        (lhs_base, rhs_base) if lhs_base == rhs_base => match (lhs.offset, rhs.offset) {
+            (MemOffset::Const(lhs), MemOffset::Const(rhs)) if lhs == rhs => AliasResult::MustAlias,
+            (MemOffset::Const(_), MemOffset::Const(_)) => AliasResult::NoAlias,
+            (MemOffset::Var(lhs_var, lhs), MemOffset::Var(rhs_var, rhs)) if lhs_var == rhs_var => {
+                if lhs == rhs {
+                    AliasResult::MustAlias
+                } else {
+                    AliasResult::NoAlias
+                }
+            }
+            _ => AliasResult::MayAlias,
+        },
+        // 数组形参可能指向全局数组或者调用者的数组,也可能两个形参指向同一个数组
+        (MemBase::Param(_), MemBase::Param(_)) => AliasResult::MayAlias,
+        (MemBase::Param(_), MemBase::Global(global))
+        | (MemBase::Global(global), MemBase::Param(_))
+            if !is_scalar(global) =>
+        {
+            AliasResult::MayAlias
+        }
+        _ => AliasResult::NoAlias,
+    }
+}
+
+#[cfg(test)]
+mod alias_test {
+    use super::{AliasAnalysis, AliasResult, ModRef};
+    use crate::ir::{
+        instruction::{Inst, InstKind},
+        module::Module,
+        test_util::parse_module,
+        tools::bfs_inst_process,
+    };
+    use crate::utility::ObjPtr;
+
+    const TEXT: &str = "
+@g = dso_local global [10 x i32] [ i32 0], align 4
+@s = dso_local global i32 0, align 4
+
+define dso_local void @f(i32* noundef %a, i32* noundef %b) #0 {
+bb_entry:
+  %p = getelementptr inbounds i32, ptr %b, i32 2
+  %x = load i32, i32* %p, align 4
+  %q = getelementptr inbounds i32, ptr %a, i32 1
+  store i32 %x, i32* %q, align 4
+  ret void
+}
+
+define dso_local signext i32 @h(i32* noundef %a) #0 {
+bb_entry:
+  %p = getelementptr inbounds i32, ptr %a, i32 0
+  %x = load i32, i32* %p, align 4
+  %y = load i32, i32* @s, align 4
+  %z = add i32 %x, %y
+  ret i32 %z
+}
+
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %l = alloca [4 x i32], align 4
+  %l0 = getelementptr inbounds [4 x i32], [4 x i32]* %l, i32 0, i32 0
+  store i32 1, i32* %l0, align 4
+  store i32 3, i32* @s, align 4
+  %garr = load ptr, ptr @g, align 8
+  %g0 = getelementptr inbounds [10 x i32], [10 x i32]* %garr, i32 0, i32 0
+  call void @f(i32* noundef %l0, i32* noundef %g0)
+  %r = call i32 @h(i32* noundef %g0)
+  %g1 = getelementptr inbounds [10 x i32], [10 x i32]* %garr, i32 0, i32 1
+  store i32 %r, i32* %g1, align 4
+  %x = load i32, i32* %l0, align 4
+  %g1b = getelementptr inbounds [10 x i32], [10 x i32]* %garr, i32 0, i32 1
+  %y = load i32, i32* %g1b, align 4
+  %z = add i32 %x, %y
+  %lz = getelementptr inbounds [4 x i32], [4 x i32]* %l, i32 0, i32 %z
+  %w = load i32, i32* %lz, align 4
+  call void @putint(i32 noundef %w)
+  ret i32 %z
+}
+
+declare void @putint(i32)
+";
+
+    /// 解析IR并分析,返回main中按顺序的load,store和call
+    fn analyse() -> (Module, AliasAnalysis, Vec<ObjPtr<Inst>>) {
+        let (mut module, _) = parse_module(TEXT);
+        let alias = AliasAnalysis::new(&mut module);
+        let mut insts = vec![];
+        bfs_inst_process(module.get_function("main").get_head(), |inst| {
+            match inst.get_kind() {
+                InstKind::Load if inst.is_global_array_load() => {}
+                InstKind::Load | InstKind::Store | InstKind::Call(_) => insts.push(inst),
+                _ => {}
+            }
+        });
+        (module, alias, insts)
+    }
+
+    #[test]
+    fn test_summary() {
+        let (_module, alias, _) = analyse();
+        let f = alias.get_summary("f").unwrap();
+        assert_eq!(f.mod_params, [0].into_iter().collect());
+        assert_eq!(f.ref_params, [1].into_iter().collect());
+        assert!(f.mod_globals.is_empty() && f.ref_globals.is_empty());
+        let h = alias.get_summary("h").unwrap();
+        assert_eq!(h.ref_params, [0].into_iter().collect());
+        assert_eq!(h.ref_globals.len(), 1);
+        assert!(h.mod_params.is_empty() && h.mod_globals.is_empty());
+        // main中的局部数组对调用者不可见,写全局数组g是通过f的形参
+        let main = alias.get_summary("main").unwrap();
+        assert_eq!(main.mod_globals.len(), 2);
+        assert!(alias.get_summary("putint").is_none());
+    }
+
+    #[test]
+    fn test_alias() {
+        let (_module, alias, insts) = analyse();
+        let (store_l0, store_s, store_g1) = (insts[0], insts[1], insts[4]);
+        let (load_l0, load_g1, load_var) = (insts[5], insts[6], insts[7]);
+        let ptr = |inst: ObjPtr<Inst>| match inst.get_kind() {
+            InstKind::Load => inst.get_ptr(),
+            _ => inst.get_dest(),
+        };
+        assert_eq!(
+            alias.alias(ptr(store_l0), ptr(load_l0)),
+            AliasResult::MustAlias
+        );
+        assert_eq!(
+            alias.alias(ptr(store_g1), ptr(load_g1)),
+            AliasResult::MustAlias
+        );
+        assert_eq!(
+            alias.alias(ptr(store_l0), ptr(load_g1)),
+            AliasResult::NoAlias
+        );
+        assert_eq!(
+            alias.alias(ptr(store_s), ptr(load_g1)),
+            AliasResult::NoAlias
+        );
+        assert_eq!(
+            alias.alias(ptr(load_var), ptr(load_l0)),
+            AliasResult::MayAlias
+        );
+        assert_eq!(
+            alias.alias(ptr(load_var), ptr(load_g1)),
+            AliasResult::NoAlias
+        );
+    }
+
+    #[test]
+    fn test_mod_ref() {
+        let (_module, alias, insts) = analyse();
+        let (call_f, call_h, call_putint) = (insts[2], insts[3], insts[8]);
+        let (l0, s, g1) = (
+            insts[0].get_dest(),
+            insts[1].get_dest(),
+            insts[4].get_dest(),
+        );
+        // f写实参l,读实参g
+        assert_eq!(alias.get_mod_ref(call_f, l0), ModRef::Mod);
+        assert_eq!(alias.get_mod_ref(call_f, g1), ModRef::Ref);
+        assert_eq!(alias.get_mod_ref(call_f, s), ModRef::NoModRef);
+        // h读实参g和全局变量s
+        assert_eq!(alias.get_mod_ref(call_h, g1), ModRef::Ref);
+        assert_eq!(alias.get_mod_ref(call_h, s), ModRef::Ref);
+        assert_eq!(alias.get_mod_ref(call_h, l0), ModRef::NoModRef);
+        // 外部函数没有指针实参
+        assert_eq!(alias.get_mod_ref(call_putint, g1), ModRef::NoModRef);
+    }
+}
@@ -1,11 +1,11 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use crate::{
-    ir::instruction::{Inst, InstKind},
+    ir::instruction::{BinOp, Inst, InstKind},
     utility::ObjPtr,
 };
 
-use super::scev::scevexp::SCEVExp;
+use super::{loop_tree::LoopInfo, scev::scevexp::SCEVExp};
 
 /// 分析循环内部关于数组操作的依赖关系
 /// 根据vector的长度，可以大致分为三种情况：
@@ -531,3 +531,179 @@ fn parse_recursion(operand: ObjPtr<Inst>, vector: &Vec<ObjPtr<SCEVExp>>) -> Vec<
         _ => Vec::new(),
     }
 }
+
+/// 数组下标关于一组归纳变量的仿射表达式:
+/// coefs[k] * iv[k]之和,加上循环外的值(符号)乘系数之和,再加上常数项
+#[derive(Clone, Debug, PartialEq)]
+pub struct AffineExpr {
+    pub coefs: Vec<i32>,
+    pub symbols: HashMap<ObjPtr<Inst>, i32>,
+    pub constant: i32,
+}
+
+/// 依赖的方向,表示第二次访问所在迭代相对于第一次访问所在迭代的位置
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    /// 第一次访问在前
+    Lt,
+    Eq,
+    /// 第一次访问在后
+    Gt,
+    Any,
+}
+
+impl AffineExpr {
+    /// 值为0的表达式
+    pub fn new(len: usize) -> Self {
+        Self {
+            coefs: vec![0; len],
+            symbols: HashMap::new(),
+            constant: 0,
+        }
+    }
+
+    fn is_constant(&self) -> bool {
+        self.coefs.iter().all(|x| *x == 0) && self.symbols.is_empty()
+    }
+
+    fn add(&self, other: &AffineExpr, sign: i32) -> Option<AffineExpr> {
+        let mut result = self.clone();
+        for (x, y) in result.coefs.iter_mut().zip(other.coefs.iter()) {
+            *x = x.checked_add(y.checked_mul(sign)?)?;
+        }
+        for (symbol, y) in other.symbols.iter() {
+            let x = result.symbols.entry(*symbol).or_insert(0);
+            *x = x.checked_add(y.checked_mul(sign)?)?;
+        }
+        result.symbols.retain(|_, x| *x != 0);
+        result.constant = result
+            .constant
+            .checked_add(other.constant.checked_mul(sign)?)?;
+        Some(result)
+    }
+
+    fn scale(&self, factor: i32) -> Option<AffineExpr> {
+        let mut result = AffineExpr::new(self.coefs.len());
+        for (x, y) in result.coefs.iter_mut().zip(self.coefs.iter()) {
+            *x = y.checked_mul(factor)?;
+        }
+        for (symbol, y) in self.symbols.iter() {
+            result.symbols.insert(*symbol, y.checked_mul(factor)?);
+        }
+        result.symbols.retain(|_, x| *x != 0);
+        result.constant = self.constant.checked_mul(factor)?;
+        Some(result)
+    }
+}
+
+/// 将gep的偏移解析为关于归纳变量的仿射表达式,无法解析时返回None
+/// # Arguments
+/// * 'offset' - GEP指令的偏移量
+/// * 'ivs' - 归纳变量,由外层循环到内层循环
+/// * 'nest' - 归纳变量所在的最外层循环,在它之外定义的值视为符号
+pub fn parse_affine(
+    offset: ObjPtr<Inst>,
+    ivs: &[ObjPtr<Inst>],
+    nest: ObjPtr<LoopInfo>,
+) -> Option<AffineExpr> {
+    let mut result = AffineExpr::new(ivs.len());
+    if offset.is_int_const() {
+        result.constant = offset.get_int_bond();
+        return Some(result);
+    }
+    if let Some(index) = ivs.iter().position(|iv| *iv == offset) {
+        result.coefs[index] = 1;
+        return Some(result);
+    }
+    if offset.is_global_var_or_param() || !nest.is_in_loop(&offset.get_parent_bb()) {
+        result.symbols.insert(offset, 1);
+        return Some(result);
+    }
+
+    let (lhs, rhs) = match offset.get_kind() {
+        InstKind::Binary(BinOp::Add | BinOp::Sub | BinOp::Mul) => (
+            parse_affine(offset.get_lhs(), ivs, nest)?,
+            parse_affine(offset.get_rhs(), ivs, nest)?,
+        ),
+        _ => return None,
+    };
+    let result = match offset.get_kind() {
+        InstKind::Binary(BinOp::Add) => lhs.add(&rhs, 1),
+        InstKind::Binary(BinOp::Sub) => lhs.add(&rhs, -1),
+        _ if rhs.is_constant() => lhs.scale(rhs.constant),
+        _ if lhs.is_constant() => rhs.scale(lhs.constant),
+        // 两个不含归纳变量的值相乘,在循环中不变,整体视为一个符号
+        _ if lhs.coefs.iter().chain(rhs.coefs.iter()).all(|x| *x == 0) => {
+            result.symbols.insert(offset, 1);
+            Some(result)
+        }
+        _ => None,
+    };
+    result
+}
+
+/// 判断两次访问之间是否可能存在指定方向的依赖,即是否存在两次迭代x和y,
+/// 使得first(x) == second(y),且每层的y[k] - x[k]符合directions[k]
+/// # Arguments
+/// * 'distances' - 每层归纳变量取值的最大差,None表示未知
+/// # Returns
+/// true表示可能存在依赖关系,false表示不存在依赖关系
+pub fn direction_check(
+    first: &AffineExpr,
+    second: &AffineExpr,
+    directions: &[Direction],
+    distances: &[Option<i32>],
+) -> bool {
+    debug_assert_eq!(first.coefs.len(), directions.len());
+    debug_assert_eq!(second.coefs.len(), directions.len());
+    if first.symbols != second.symbols {
+        return true;
+    }
+
+    let diff = second.constant as i64 - first.constant as i64;
+    if first.coefs != second.coefs {
+        // 系数不同时只做gcd测试: first.coefs·x - second.coefs·y = diff
+        let gcd = first
+            .coefs
+            .iter()
+            .chain(second.coefs.iter())
+            .fold(0, |x, y| gcd(x, y.abs()));
+        return gcd == 0 && diff == 0 || gcd != 0 && diff % gcd as i64 == 0;
+    }
+
+    // 系数相同时依赖距离d = y - x满足 coefs·d = -diff,用每层d的范围求出左边的取值区间
+    let (mut low, mut high) = (Some(0i64), Some(0i64));
+    let mut step = 0;
+    for ((coef, direction), distance) in first.coefs.iter().zip(directions).zip(distances) {
+        let distance = distance.map(|x| x as i64);
+        let (min, max) = match direction {
+            Direction::Lt => (Some(1), distance),
+            Direction::Gt => (distance.map(|x| -x), Some(-1)),
+            Direction::Eq => (Some(0), Some(0)),
+            Direction::Any => (distance.map(|x| -x), distance),
+        };
+        if matches!((min, max), (Some(min), Some(max)) if min > max) {
+            return false;
+        }
+        if *coef == 0 || *direction == Direction::Eq {
+            continue;
+        }
+        step = gcd(step, coef.abs());
+        let coef = *coef as i64;
+        let (min, max) = if coef > 0 {
+            (min.map(|x| x * coef), max.map(|x| x * coef))
+        } else {
+            (max.map(|x| x * coef), min.map(|x| x * coef))
+        };
+        low = low.zip(min).map(|(x, y)| x + y);
+        high = high.zip(max).map(|(x, y)| x + y);
+    }
+
+    let target = -diff;
+    if step == 0 {
+        return target == 0;
+    }
+    target % step as i64 == 0
+        && low.map_or(true, |low| low <= target)
+        && high.map_or(true, |high| target <= high)
+}
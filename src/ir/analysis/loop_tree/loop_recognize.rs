@@ -28,7 +28,7 @@ pub fn loop_recognize(module: &mut Module) -> HashMap<String, LoopList> {
     loops
 }
 
-fn loop_recognize_in_function(func: ObjPtr<Function>) -> LoopList {
+pub fn loop_recognize_in_function(func: ObjPtr<Function>) -> LoopList {
     let mut loop_list = LoopList::new();
     let mut visited = HashSet::new();
 
@@ -47,9 +47,10 @@ impl LoopList {
         });
     }
 
-    pub fn add_loop(&mut self, new_loop: LoopInfo) {
+    pub fn add_loop(&mut self, new_loop: LoopInfo) -> ObjPtr<LoopInfo> {
         let new_loop = self.pool.put(new_loop);
         self.loops.insert(0, new_loop);
+        new_loop
     }
 }
 
@@ -189,6 +190,11 @@ impl LoopInfo {
         self.blocks.extend(bb);
     }
 
+    /// 删除当前循环的块
+    pub fn remove_bbs(&mut self, bbs: &[ObjPtr<BasicBlock>]) {
+        self.blocks.retain(|bb| !bbs.contains(bb));
+    }
+
     /// 获得当前循环的子循环
     pub fn get_sub_loops(&self) -> &Vec<ObjPtr<LoopInfo>> {
         &self.sub_loops
@@ -199,11 +205,27 @@ impl LoopInfo {
         self.sub_loops.retain(|loop_tree| *loop_tree != sub_loop);
     }
 
+    /// 增加一个子循环
+    pub fn add_sub_loop(&mut self, sub_loop: ObjPtr<LoopInfo>) {
+        self.sub_loops.push(sub_loop);
+    }
+
     /// 获得当前循环的父循环
     pub fn get_parent_loop(&self) -> Option<ObjPtr<LoopInfo>> {
         self.parent
     }
 
+    /// 设置当前循环的父循环
+    pub fn set_parent_loop(&mut self, parent: Option<ObjPtr<LoopInfo>>) {
+        self.parent = parent;
+    }
+
+    /// 循环的块改变后清除缓存的出口块和latch块
+    pub fn clear_cache(&mut self) {
+        self.exit_blocks = None;
+        self.latchs = None;
+    }
+
     /// 获得当前循环的出口块
     /// 动态计算，当exit_blocks为空时，计算一次
     pub fn get_exit_blocks(&mut self) -> Vec<ObjPtr<BasicBlock>> {
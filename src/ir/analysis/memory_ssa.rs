@@ -0,0 +1,352 @@
+//! MemorySSA
+//!
+//! 把整个内存看作一个变量,为其构造SSA形式:可能写内存的指令(store,call)是一次定义,
+//! 只读内存的指令(load,只读的call,return)是一次使用,每次使用和定义都指向此前最近的一次定义,
+//! 有多个前继的块开头放置一个memory phi。函数入口的内存状态是LiveOnEntry。
+//!
+//! 查询一个位置的clobber时沿定义链向上走,跳过不写这个位置的定义;
+//! 遇到memory phi时分别查询每个前继,所有前继得到同一个clobber时继续向上,否则clobber就是这个phi。
+//!
+//! 沿回边查询时,环中定义的偏移变量在不同的迭代中值不同,所以这样的偏移被当作未知的偏移。
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    ir::{
+        basicblock::BasicBlock,
+        instruction::{Inst, InstKind},
+        tools::{bfs_bb_proceess, inst_process_in_bb},
+    },
+    utility::ObjPtr,
+};
+
+use super::alias::{
+    alias_location, get_location, AliasAnalysis, AliasResult, MemLoc, MemOffset, ModRef,
+};
+
+/// 内存访问的编号
+pub type AccessId = usize;
+
+#[derive(Clone, Debug)]
+pub enum AccessKind {
+    /// 函数入口的内存状态
+    LiveOnEntry,
+    /// 可能写内存的指令
+    Def(ObjPtr<Inst>),
+    /// 只读内存的指令
+    Use(ObjPtr<Inst>),
+    /// 合并前继的内存状态,每一项为(前继块,前继出口处的定义)
+    Phi(Vec<(ObjPtr<BasicBlock>, AccessId)>),
+}
+
+#[derive(Clone, Debug)]
+pub struct MemoryAccess {
+    pub kind: AccessKind,
+    /// 所在的块,LiveOnEntry没有所在的块
+    pub block: Option<ObjPtr<BasicBlock>>,
+    /// 此前最近的一次定义,LiveOnEntry和phi没有
+    pub defining: Option<AccessId>,
+}
+
+pub struct MemorySSA<'a> {
+    alias: &'a AliasAnalysis,
+    accesses: Vec<MemoryAccess>,
+    inst_map: HashMap<ObjPtr<Inst>, AccessId>,
+    /// 以某个访问为defining或者phi参数的访问
+    users: Vec<Vec<AccessId>>,
+    /// 在环中的块
+    cyclic_bbs: HashSet<ObjPtr<BasicBlock>>,
+}
+
+/// LiveOnEntry的编号
+pub const LIVE_ON_ENTRY: AccessId = 0;
+
+impl<'a> MemorySSA<'a> {
+    /// 为以head为入口的函数构造MemorySSA
+    pub fn new(head: ObjPtr<BasicBlock>, alias: &'a AliasAnalysis) -> MemorySSA<'a> {
+        let mut mssa = MemorySSA {
+            alias,
+            accesses: vec![MemoryAccess {
+                kind: AccessKind::LiveOnEntry,
+                block: None,
+                defining: None,
+            }],
+            inst_map: HashMap::new(),
+            users: vec![],
+            cyclic_bbs: get_cyclic_bbs(head),
+        };
+
+        // 广度优先遍历时只有一个前继的块一定在前继之后访问
+        let mut phis = HashMap::new();
+        let mut exit_defs = HashMap::new();
+        bfs_bb_proceess(head, |bb| {
+            let mut current = if bb == head {
+                LIVE_ON_ENTRY
+            } else if bb.get_up_bb().len() == 1 {
+                exit_defs[&bb.get_up_bb()[0]]
+            } else {
+                let phi = mssa.push(AccessKind::Phi(vec![]), Some(bb), None);
+                phis.insert(bb, phi);
+                phi
+            };
+            inst_process_in_bb(bb.get_head_inst(), |inst| {
+                let is_def = match inst.get_kind() {
+                    InstKind::Load if inst.is_global_array_load() => return,
+                    InstKind::Load | InstKind::Return => false,
+                    InstKind::Store => true,
+                    InstKind::Call(_) => {
+                        let mod_ref = alias.get_call_mod_ref(inst);
+                        if !mod_ref.is_mod() && !mod_ref.is_ref() {
+                            return;
+                        }
+                        mod_ref.is_mod()
+                    }
+                    _ => return,
+                };
+                let kind = if is_def {
+                    AccessKind::Def(inst)
+                } else {
+                    AccessKind::Use(inst)
+                };
+                let access = mssa.push(kind, Some(bb), Some(current));
+                mssa.inst_map.insert(inst, access);
+                if is_def {
+                    current = access;
+                }
+            });
+            exit_defs.insert(bb, current);
+        });
+
+        for (bb, phi) in phis {
+            let incoming = bb
+                .get_up_bb()
+                .iter()
+                .filter_map(|pred| exit_defs.get(pred).map(|def| (*pred, *def)))
+                .collect();
+            mssa.accesses[phi].kind = AccessKind::Phi(incoming);
+        }
+
+        mssa.users = vec![vec![]; mssa.accesses.len()];
+        for (id, access) in mssa.accesses.iter().enumerate() {
+            if let Some(defining) = access.defining {
+                mssa.users[defining].push(id);
+            }
+            if let AccessKind::Phi(incoming) = &access.kind {
+                for (_, def) in incoming {
+                    mssa.users[*def].push(id);
+                }
+            }
+        }
+        mssa
+    }
+
+    fn push(
+        &mut self,
+        kind: AccessKind,
+        block: Option<ObjPtr<BasicBlock>>,
+        defining: Option<AccessId>,
+    ) -> AccessId {
+        self.accesses.push(MemoryAccess {
+            kind,
+            block,
+            defining,
+        });
+        self.accesses.len() - 1
+    }
+
+    /// 获得指令对应的内存访问
+    pub fn get_access(&self, inst: ObjPtr<Inst>) -> Option<AccessId> {
+        self.inst_map.get(&inst).copied()
+    }
+
+    pub fn get_memory_access(&self, access: AccessId) -> &MemoryAccess {
+        &self.accesses[access]
+    }
+
+    /// 获得直接使用某个访问的访问
+    pub fn get_users(&self, access: AccessId) -> &Vec<AccessId> {
+        &self.users[access]
+    }
+
+    /// 获得所有的定义和使用对应的指令
+    pub fn get_insts(&self) -> Vec<ObjPtr<Inst>> {
+        self.accesses
+            .iter()
+            .filter_map(|access| match access.kind {
+                AccessKind::Def(inst) | AccessKind::Use(inst) => Some(inst),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// 求指针指向的位置,环中定义的偏移变量视为未知
+    pub fn get_location(&self, ptr: ObjPtr<Inst>) -> MemLoc {
+        let mut loc = get_location(ptr);
+        if let MemOffset::Var(var, _) = loc.offset {
+            if !var.is_global_var_or_param() && self.cyclic_bbs.contains(&var.get_parent_bb()) {
+                loc.offset = MemOffset::Unknown;
+            }
+        }
+        loc
+    }
+
+    /// 判断两个指针指向的位置是否别名
+    pub fn alias(&self, lhs: ObjPtr<Inst>, rhs: ObjPtr<Inst>) -> AliasResult {
+        alias_location(self.get_location(lhs), self.get_location(rhs))
+    }
+
+    /// 判断指令是否读写ptr指向的位置
+    pub fn get_mod_ref(&self, inst: ObjPtr<Inst>, ptr: ObjPtr<Inst>) -> ModRef {
+        self.alias
+            .get_mod_ref_with(inst, ptr, &|ptr| self.get_location(ptr))
+    }
+
+    /// 求inst处读写ptr时,此前最近的可能写ptr的访问
+    pub fn get_clobber(&self, inst: ObjPtr<Inst>, ptr: ObjPtr<Inst>) -> AccessId {
+        let defining = self.accesses[self.inst_map[&inst]].defining.unwrap();
+        let mut cache = HashMap::new();
+        self.walk(defining, ptr, &mut cache, &mut HashSet::new())
+            .0
+            .unwrap_or(defining)
+    }
+
+    /// 从access向上找ptr的clobber,正在查询的phi视为没有clobber
+    /// 返回的bool表示结果是否依赖正在查询的phi,依赖时不能缓存
+    fn walk(
+        &self,
+        mut access: AccessId,
+        ptr: ObjPtr<Inst>,
+        cache: &mut HashMap<AccessId, Option<AccessId>>,
+        visiting: &mut HashSet<AccessId>,
+    ) -> (Option<AccessId>, bool) {
+        loop {
+            match &self.accesses[access].kind {
+                AccessKind::LiveOnEntry => return (Some(access), false),
+                AccessKind::Def(inst) => {
+                    if self.get_mod_ref(*inst, ptr).is_mod() {
+                        return (Some(access), false);
+                    }
+                    access = self.accesses[access].defining.unwrap();
+                }
+                AccessKind::Use(_) => access = self.accesses[access].defining.unwrap(),
+                AccessKind::Phi(incoming) => {
+                    if let Some(result) = cache.get(&access) {
+                        return (*result, false);
+                    }
+                    if !visiting.insert(access) {
+                        return (None, true);
+                    }
+                    let mut results = HashSet::new();
+                    let mut incomplete = false;
+                    for (_, def) in incoming.iter() {
+                        let (result, dependent) = self.walk(*def, ptr, cache, visiting);
+                        results.extend(result);
+                        incomplete |= dependent;
+                    }
+                    visiting.remove(&access);
+                    let result = match results.len() {
+                        0 => None,
+                        1 => results.into_iter().next(),
+                        _ => Some(access),
+                    };
+                    // 只依赖自己的结果已经完整
+                    if !incomplete || visiting.is_empty() {
+                        cache.insert(access, result);
+                    }
+                    return (result, incomplete && !visiting.is_empty());
+                }
+            }
+        }
+    }
+}
+
+/// 求在环中的块,即能从自己的后继到达自己的块
+fn get_cyclic_bbs(head: ObjPtr<BasicBlock>) -> HashSet<ObjPtr<BasicBlock>> {
+    let mut cyclic_bbs = HashSet::new();
+    bfs_bb_proceess(head, |bb| {
+        let mut visited = HashSet::new();
+        let mut stack = bb.get_next_bb().clone();
+        while let Some(next) = stack.pop() {
+            if next == bb {
+                cyclic_bbs.insert(bb);
+                break;
+            }
+            if visited.insert(next) {
+                stack.extend(next.get_next_bb().iter().copied());
+            }
+        }
+    });
+    cyclic_bbs
+}
+
+#[cfg(test)]
+mod memory_ssa_test {
+    use super::{AccessKind, MemorySSA};
+    use crate::ir::{
+        analysis::alias::AliasAnalysis,
+        instruction::{Inst, InstKind},
+        test_util::parse_module,
+        tools::bfs_inst_process,
+    };
+    use crate::utility::ObjPtr;
+
+    #[test]
+    fn test_clobber() {
+        let (mut module, _) = parse_module(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %a = alloca [4 x i32], align 4
+  %p0 = getelementptr inbounds [4 x i32], [4 x i32]* %a, i32 0, i32 0
+  %p1 = getelementptr inbounds [4 x i32], [4 x i32]* %a, i32 0, i32 1
+  store i32 1, i32* %p0, align 4
+  store i32 2, i32* %p1, align 4
+  %n = call i32 @getint()
+  br label %bb_loop
+
+bb_loop:
+  %i = phi i32 [ 0, %bb_entry ], [ %i2, %bb_body ]
+  %c = icmp slt i32 %i, %n
+  br i1 %c, label %bb_body, label %bb_exit
+
+bb_body:
+  %x = load i32, i32* %p0, align 4
+  store i32 %x, i32* %p1, align 4
+  %i2 = add i32 %i, 1
+  br label %bb_loop
+
+bb_exit:
+  %y = load i32, i32* %p1, align 4
+  ret i32 %y
+}
+
+declare i32 @getint()
+",
+        );
+        let alias = AliasAnalysis::new(&mut module);
+        let head = module.get_function("main").get_head();
+        let mssa = MemorySSA::new(head, &alias);
+        let mut memory_insts: Vec<ObjPtr<Inst>> = vec![];
+        bfs_inst_process(head, |inst| match inst.get_kind() {
+            InstKind::Load | InstKind::Store => memory_insts.push(inst),
+            _ => {}
+        });
+        let find = |kind: InstKind, bb: &str| {
+            *memory_insts
+                .iter()
+                .find(|inst| inst.get_kind() == kind && inst.get_parent_bb().get_name() == bb)
+                .unwrap()
+        };
+        let store_p0 = find(InstKind::Store, "entry");
+        let (load_x, load_y) = (find(InstKind::Load, "body"), find(InstKind::Load, "exit"));
+
+        // 循环中没有写%p0,clobber是循环前的store
+        let clobber = mssa.get_clobber(load_x, load_x.get_ptr());
+        assert_eq!(clobber, mssa.get_access(store_p0).unwrap());
+        // 循环后的%p1可能来自循环前或循环中的store,clobber是循环头的phi
+        let clobber = mssa.get_memory_access(mssa.get_clobber(load_y, load_y.get_ptr()));
+        assert!(matches!(clobber.kind, AccessKind::Phi(_)));
+        assert_eq!(clobber.block.unwrap().get_name(), "loop");
+        // 只有定义会被使用
+        assert!(mssa.get_users(mssa.get_access(load_x).unwrap()).is_empty());
+    }
+}
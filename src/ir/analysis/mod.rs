@@ -1,3 +1,4 @@
+pub mod alias;
 pub mod call_map;
 pub mod call_optimize;
 pub mod dependent_analyse;
@@ -5,5 +6,6 @@ pub mod dominator_tree;
 pub mod downstream_tree;
 pub mod loop_tree;
 pub mod match_info;
+pub mod memory_ssa;
 pub mod scev;
-pub mod store_map;
+pub mod value_range;
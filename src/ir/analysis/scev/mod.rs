@@ -175,7 +175,7 @@ impl SCEVAnalyzer {
             (false, true) => {
                 let rhs_op = rhs.get_operands();
                 if Self::chech_constant_or_no_in_loop(lhs, cur_loop) {
-                    let result = self.parse_sub(&rhs_op, &[lhs], in_loop);
+                    let result = self.parse_sub(&[lhs], &rhs_op, in_loop);
                     self.scevexp_pool
                         .make_scev_sub_rec_expr(result, Some(*inst), in_loop)
                 } else {
@@ -200,14 +200,8 @@ impl SCEVAnalyzer {
         let rhs = self.analyze(&inst.get_rhs());
 
         match Self::check_rec_available(lhs, rhs, cur_loop) {
-            (true, true) => {
-                let lhs_op = lhs.get_operands();
-                let rhs_op = rhs.get_operands();
-
-                let result = self.parse_mul(&lhs_op, &rhs_op, in_loop);
-                self.scevexp_pool
-                    .make_scev_mul_rec_expr(result, Some(*inst), in_loop)
-            }
+            // 两个归纳变量的积的各阶差分不能逐项相乘得到
+            (true, true) => self.scevexp_pool.make_scev_unknown(Some(*inst), in_loop),
             (true, false) => {
                 let lhs_op = lhs.get_operands();
 
@@ -318,14 +312,23 @@ impl SCEVAnalyzer {
                 }
 
                 InstKind::Binary(BinOp::Sub) => {
+                    // 只有phi - step才是归纳变量,步长取反
                     let step = op.get_operand(1);
-                    if index == 0 && check_inst_avaliable(step) {
+                    if op.get_operand(0) == *inst && check_inst_avaliable(step) {
                         let start = inst
                             .get_operands()
                             .iter()
                             .find(|x| check_inst_avaliable(**x))
                             .unwrap();
-                        let result = vec![self.parse(*start, cur_loop), self.parse(step, cur_loop)];
+                        let step = self.parse(step, cur_loop);
+                        let step = if step.is_scev_constant() {
+                            self.scevexp_pool
+                                .make_scev_constant(step.get_scev_const().wrapping_neg())
+                        } else {
+                            let zero = self.scevexp_pool.make_scev_constant(0);
+                            self.scevexp_pool.make_scev_sub_expr(zero, step, in_loop)
+                        };
+                        let result = vec![self.parse(*start, cur_loop), step];
                         return self
                             .scevexp_pool
                             .make_scev_rec_expr(result, Some(*inst), in_loop);
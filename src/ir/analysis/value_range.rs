@@ -0,0 +1,712 @@
+//! 整数值范围分析
+//!
+//! 在ssa上给每个int值求一个区间,常量就是上下界相同的区间。
+//! 分支条件是两个int值的比较时,条件为真或假的后继中比较的两边互相限制对方的范围,
+//! 这些条件对这个后继支配的所有块都成立。phi的操作数还受到从前继到phi所在块的边上的条件限制。
+//! 循环头中由SCEV识别出的多个归纳变量迭代的次数相同,
+//! 因此一个归纳变量的范围可以由同一个循环中另一个有界的归纳变量推出。
+//!
+//! 先迭代到不动点,同一个值变化太多次时把变化的边界放宽到比较中出现的常量附近或者i32的边界,
+//! 之后再按不放宽的规则重新求值几轮,收紧被放宽的边界。
+//! 归纳变量之间的限制只在收紧阶段使用,因为上升阶段中的范围还不完整。
+use std::collections::HashMap;
+
+use crate::{
+    ir::{
+        analysis::{
+            dominator_tree::calculate_dominator,
+            loop_tree::loop_recognize::loop_recognize_in_function, scev::SCEVAnalyzer,
+        },
+        basicblock::BasicBlock,
+        function::Function,
+        instruction::{BinOp, Inst, InstKind, UnOp},
+        ir_type::IrType,
+        tools::{inst_process_in_bb, reverse_post_order},
+    },
+    utility::ObjPtr,
+};
+
+/// 同一个值的范围扩大超过这个次数之后进行放宽
+const WIDEN_LIMIT: usize = 3;
+/// 放宽之后重新求值的轮数
+const NARROW_ROUNDS: usize = 2;
+
+/// 整数的区间,用i64保存上下界
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Range {
+    pub lo: i64,
+    pub hi: i64,
+}
+
+impl Range {
+    /// 任意的i32
+    pub fn full() -> Range {
+        Range {
+            lo: i32::MIN as i64,
+            hi: i32::MAX as i64,
+        }
+    }
+
+    /// 构造区间,超出i32时运算会回绕,只能是任意值
+    pub fn new(lo: i64, hi: i64) -> Range {
+        if lo < i32::MIN as i64 || hi > i32::MAX as i64 {
+            Range::full()
+        } else {
+            Range { lo, hi }
+        }
+    }
+
+    pub fn constant(value: i32) -> Range {
+        Range::new(value as i64, value as i64)
+    }
+
+    fn bool(lo: bool, hi: bool) -> Range {
+        Range::new(lo as i64, hi as i64)
+    }
+
+    /// 区间只包含一个值时返回这个值
+    pub fn get_const(self) -> Option<i32> {
+        if self.lo == self.hi {
+            Some(self.lo as i32)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_non_negative(self) -> bool {
+        self.lo >= 0
+    }
+
+    pub fn contains(self, value: i64) -> bool {
+        self.lo <= value && value <= self.hi
+    }
+
+    pub fn join(self, other: Range) -> Range {
+        Range {
+            lo: self.lo.min(other.lo),
+            hi: self.hi.max(other.hi),
+        }
+    }
+
+    /// 两个区间的交集,交集为空时返回None
+    pub fn intersect(self, other: Range) -> Option<Range> {
+        let (lo, hi) = (self.lo.max(other.lo), self.hi.min(other.hi));
+        if lo <= hi {
+            Some(Range { lo, hi })
+        } else {
+            None
+        }
+    }
+
+    /// 把变化的边界放宽到thresholds中最近的值,thresholds按升序排列
+    pub fn widen(self, new: Range, thresholds: &[i64]) -> Range {
+        Range {
+            lo: if new.lo < self.lo {
+                thresholds
+                    .iter()
+                    .rev()
+                    .find(|&&threshold| threshold <= new.lo)
+                    .map_or(i32::MIN as i64, |&threshold| threshold)
+            } else {
+                self.lo
+            },
+            hi: if new.hi > self.hi {
+                thresholds
+                    .iter()
+                    .find(|&&threshold| threshold >= new.hi)
+                    .map_or(i32::MAX as i64, |&threshold| threshold)
+            } else {
+                self.hi
+            },
+        }
+    }
+
+    /// 已知 self op bound 成立时self的范围,矛盾时不做限制
+    pub fn refine(self, op: BinOp, bound: Range) -> Range {
+        let (min, max) = (i32::MIN as i64, i32::MAX as i64);
+        let limit = match op {
+            BinOp::Lt => Range::new(min, bound.hi - 1),
+            BinOp::Le => Range::new(min, bound.hi),
+            BinOp::Gt => Range::new(bound.lo + 1, max),
+            BinOp::Ge => Range::new(bound.lo, max),
+            BinOp::Eq => bound,
+            // 只能去掉区间端点上的常量
+            BinOp::Ne if bound.lo == bound.hi && self.lo == bound.lo => {
+                Range::new(self.lo + 1, self.hi)
+            }
+            BinOp::Ne if bound.lo == bound.hi && self.hi == bound.hi => {
+                Range::new(self.lo, self.hi - 1)
+            }
+            _ => return self,
+        };
+        self.intersect(limit).unwrap_or(self)
+    }
+
+    /// 整数二元运算结果的范围
+    pub fn eval_binary(op: BinOp, lhs: Range, rhs: Range) -> Range {
+        let (lo1, hi1, lo2, hi2) = (lhs.lo, lhs.hi, rhs.lo, rhs.hi);
+        match op {
+            BinOp::Add => Range::new(lo1 + lo2, hi1 + hi2),
+            BinOp::Sub => Range::new(lo1 - hi2, hi1 - lo2),
+            BinOp::Mul => {
+                let corners = [lo1 * lo2, lo1 * hi2, hi1 * lo2, hi1 * hi2];
+                Range::new(
+                    *corners.iter().min().unwrap(),
+                    *corners.iter().max().unwrap(),
+                )
+            }
+            // 除数可能为零时不做推断
+            BinOp::Div | BinOp::Rem if lo2 <= 0 && hi2 >= 0 => Range::full(),
+            BinOp::Div => {
+                // 除数的符号确定时,商在各个角上取到最值
+                let corners = [lo1 / lo2, lo1 / hi2, hi1 / lo2, hi1 / hi2];
+                Range::new(
+                    *corners.iter().min().unwrap(),
+                    *corners.iter().max().unwrap(),
+                )
+            }
+            BinOp::Rem if lo1 == hi1 && lo2 == hi2 => {
+                Range::constant((lo1 as i32).wrapping_rem(lo2 as i32))
+            }
+            BinOp::Rem => {
+                // 余数的符号与被除数相同,绝对值小于除数
+                let max = lo2.abs().max(hi2.abs()) - 1;
+                Range::new(
+                    if lo1 >= 0 { 0 } else { lo1.max(-max) },
+                    if hi1 <= 0 { 0 } else { hi1.min(max) },
+                )
+            }
+            BinOp::UDiv | BinOp::URem if lo2 <= 0 && hi2 >= 0 => Range::full(),
+            BinOp::UDiv
+            | BinOp::URem
+            | BinOp::And
+            | BinOp::Or
+            | BinOp::Xor
+            | BinOp::Shl
+            | BinOp::LShr
+            | BinOp::AShr
+                if lo1 == hi1 && lo2 == hi2 =>
+            {
+                let (lhs, rhs) = (lo1 as i32, lo2 as i32);
+                Range::constant(match op {
+                    BinOp::UDiv => (lhs as u32 / rhs as u32) as i32,
+                    BinOp::URem => (lhs as u32 % rhs as u32) as i32,
+                    BinOp::And => lhs & rhs,
+                    BinOp::Or => lhs | rhs,
+                    BinOp::Xor => lhs ^ rhs,
+                    BinOp::Shl => lhs.wrapping_shl(rhs as u32),
+                    BinOp::LShr => (lhs as u32).wrapping_shr(rhs as u32) as i32,
+                    _ => lhs.wrapping_shr(rhs as u32),
+                })
+            }
+            // 和非负数按位与的结果不超过这个非负数
+            BinOp::And if lo1 >= 0 || lo2 >= 0 => {
+                let hi = if lo1 >= 0 && lo2 >= 0 {
+                    hi1.min(hi2)
+                } else if lo1 >= 0 {
+                    hi1
+                } else {
+                    hi2
+                };
+                Range::new(0, hi)
+            }
+            // 两边都非负时按无符号运算和有符号运算相同
+            BinOp::URem if lo1 >= 0 && lo2 > 0 => Range::new(0, hi1.min(hi2 - 1)),
+            BinOp::UDiv if lo1 >= 0 && lo2 > 0 => Range::new(lo1 / hi2, hi1 / lo2),
+            BinOp::AShr if lo2 == hi2 && (0..32).contains(&lo2) => {
+                Range::new(lo1 >> lo2, hi1 >> lo2)
+            }
+            BinOp::LShr if lo2 == hi2 && lo1 >= 0 && (0..32).contains(&lo2) => {
+                Range::new(lo1 >> lo2, hi1 >> lo2)
+            }
+            BinOp::UDiv
+            | BinOp::URem
+            | BinOp::And
+            | BinOp::Or
+            | BinOp::Xor
+            | BinOp::Shl
+            | BinOp::LShr
+            | BinOp::AShr => Range::full(),
+            BinOp::Lt => Range::bool(hi1 < lo2, lo1 < hi2),
+            BinOp::Le => Range::bool(hi1 <= lo2, lo1 <= hi2),
+            BinOp::Gt => Range::bool(lo1 > hi2, hi1 > lo2),
+            BinOp::Ge => Range::bool(lo1 >= hi2, hi1 >= lo2),
+            BinOp::Eq => {
+                let equal = lo1 == hi1 && lo2 == hi2 && lo1 == lo2;
+                let disjoint = hi1 < lo2 || hi2 < lo1;
+                Range::bool(equal, !disjoint)
+            }
+            BinOp::Ne => {
+                let equal = lo1 == hi1 && lo2 == hi2 && lo1 == lo2;
+                let disjoint = hi1 < lo2 || hi2 < lo1;
+                Range::bool(disjoint, !equal)
+            }
+        }
+    }
+}
+
+/// 在某个位置成立的条件: value op bound
+#[derive(Clone, Copy)]
+struct Fact {
+    value: ObjPtr<Inst>,
+    op: BinOp,
+    bound: ObjPtr<Inst>,
+}
+
+/// 循环头中由SCEV识别出的归纳变量 {start, +, step}
+#[derive(Clone, Copy)]
+struct InductionVar {
+    phi: ObjPtr<Inst>,
+    start: ObjPtr<Inst>,
+    step: i64,
+}
+
+#[derive(Clone, Default)]
+pub struct ValueRange {
+    ranges: HashMap<ObjPtr<Inst>, Range>,
+    /// 在块中成立的分支条件
+    facts: HashMap<ObjPtr<BasicBlock>, Vec<Fact>>,
+    /// 循环头中的归纳变量
+    ivs: HashMap<ObjPtr<BasicBlock>, Vec<InductionVar>>,
+}
+
+/// 计算函数中所有int值的范围
+pub fn calculate_value_range(func: ObjPtr<Function>) -> ValueRange {
+    let mut value_range = ValueRange::default();
+    let head = func.get_head();
+    let blocks = reverse_post_order(head);
+
+    // 只有一个前继的块继承这条边上的条件,被它支配的块也都满足这些条件
+    let dominator_tree = calculate_dominator(head);
+    let entry_facts: Vec<(ObjPtr<BasicBlock>, Vec<Fact>)> = blocks
+        .iter()
+        .filter(|bb| bb.get_up_bb().len() == 1)
+        .map(|bb| (*bb, edge_facts(bb.get_up_bb()[0], *bb)))
+        .filter(|(_, facts)| !facts.is_empty())
+        .collect();
+    for &bb in blocks.iter() {
+        let facts = entry_facts
+            .iter()
+            .filter(|(dominator, _)| dominator_tree.is_dominate(dominator, &bb))
+            .flat_map(|(_, facts)| facts.iter().copied())
+            .collect::<Vec<_>>();
+        if !facts.is_empty() {
+            value_range.facts.insert(bb, facts);
+        }
+    }
+
+    // 放宽的目标: 比较中出现的常量附近,以及i32边界内侧一格,使得i+1和i-1不会溢出
+    let mut insts = vec![];
+    let mut thresholds = vec![i32::MIN as i64 + 1, i32::MAX as i64 - 1];
+    for &bb in blocks.iter() {
+        inst_process_in_bb(bb.get_head_inst(), |inst| {
+            if let InstKind::Binary(
+                BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge | BinOp::Eq | BinOp::Ne,
+            ) = inst.get_kind()
+            {
+                for operand in [inst.get_lhs(), inst.get_rhs()] {
+                    if let InstKind::ConstInt(value) = operand.get_kind() {
+                        let value = value as i64;
+                        thresholds.extend([value - 1, value, value + 1]);
+                    }
+                }
+            }
+            if inst.get_ir_type() == IrType::Int {
+                insts.push(inst);
+            }
+        });
+    }
+    thresholds.sort();
+    thresholds.dedup();
+
+    // 上升阶段,每次求值都和旧值合并,变化太多次时放宽
+    let mut counts = HashMap::new();
+    loop {
+        let mut changed = false;
+        for &inst in insts.iter() {
+            let new = match value_range.eval(inst) {
+                Some(new) => new,
+                None => continue,
+            };
+            let range = match value_range.ranges.get(&inst) {
+                Some(&old) if old.join(new) == old => continue,
+                Some(&old) => {
+                    let count = counts.entry(inst).or_insert(0);
+                    *count += 1;
+                    if *count > WIDEN_LIMIT {
+                        old.widen(old.join(new), &thresholds)
+                    } else {
+                        old.join(new)
+                    }
+                }
+                None => new,
+            };
+            value_range.ranges.insert(inst, range);
+            changed = true;
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    // 收紧阶段,在已经稳定的范围上重新求值
+    value_range.find_induction_vars(func);
+    for _ in 0..NARROW_ROUNDS {
+        for &inst in insts.iter() {
+            if let Some(new) = value_range.eval(inst) {
+                value_range.ranges.insert(inst, new);
+            }
+        }
+    }
+    value_range
+}
+
+impl ValueRange {
+    /// inst在整个函数中的范围
+    pub fn get_range(&self, inst: ObjPtr<Inst>) -> Range {
+        match inst.get_kind() {
+            InstKind::ConstInt(value) | InstKind::GlobalConstInt(value) => Range::constant(value),
+            _ => *self.ranges.get(&inst).unwrap_or(&Range::full()),
+        }
+    }
+
+    /// inst在块bb中的范围,考虑支配bb的分支条件
+    pub fn get_range_at(&self, inst: ObjPtr<Inst>, bb: ObjPtr<BasicBlock>) -> Range {
+        let range = self.get_range(inst);
+        match self.facts.get(&bb) {
+            Some(facts) => self.apply_facts(range, inst, facts),
+            None => range,
+        }
+    }
+
+    fn apply_facts(&self, mut range: Range, inst: ObjPtr<Inst>, facts: &[Fact]) -> Range {
+        for fact in facts.iter().filter(|fact| fact.value == inst) {
+            range = range.refine(fact.op, self.get_range(fact.bound));
+        }
+        range
+    }
+
+    /// 按操作数当前的范围求值,还没有任何信息时返回None
+    fn eval(&self, inst: ObjPtr<Inst>) -> Option<Range> {
+        let bb = inst.get_parent_bb();
+        let range = match inst.get_kind() {
+            InstKind::ConstInt(value) => Range::constant(value),
+            InstKind::Phi => {
+                // 忽略还没有求值的操作数,回边上的值在下一轮加入
+                let mut range: Option<Range> = None;
+                for (index, pred) in bb.get_up_bb().iter().enumerate() {
+                    let operand = inst.get_operand(index);
+                    if !operand.is_const()
+                        && !operand.is_global_var_or_param()
+                        && !self.ranges.contains_key(&operand)
+                    {
+                        continue;
+                    }
+                    let value = self.apply_facts(
+                        self.get_range_at(operand, *pred),
+                        operand,
+                        &edge_facts(*pred, bb),
+                    );
+                    range = Some(range.map_or(value, |range| range.join(value)));
+                }
+                let range = range?;
+                self.induction_bound(inst)
+                    .and_then(|bound| range.intersect(bound))
+                    .unwrap_or(range)
+            }
+            InstKind::Binary(op) if inst.get_lhs().get_ir_type() == IrType::Int => {
+                Range::eval_binary(
+                    op,
+                    self.get_range_at(inst.get_lhs(), bb),
+                    self.get_range_at(inst.get_rhs(), bb),
+                )
+            }
+            // 浮点数的比较只能是0或1
+            InstKind::Binary(_) => Range::new(0, 1),
+            InstKind::Unary(op) => {
+                let value = self.get_range_at(inst.get_unary_operand(), bb);
+                match op {
+                    UnOp::Pos => value,
+                    UnOp::Neg => Range::new(-value.hi, -value.lo),
+                    UnOp::Not => Range::bool(!value.contains(0), value.contains(0)),
+                }
+            }
+            InstKind::Select => {
+                let true_value = self.get_range_at(inst.get_true_value(), bb);
+                let false_value = self.get_range_at(inst.get_false_value(), bb);
+                let cond = self.get_range_at(inst.get_select_cond(), bb);
+                if !cond.contains(0) {
+                    true_value
+                } else if cond == Range::constant(0) {
+                    false_value
+                } else {
+                    true_value.join(false_value)
+                }
+            }
+            _ => Range::full(),
+        };
+        Some(range)
+    }
+
+    /// 识别循环头中的归纳变量,只保留步长为常数的
+    fn find_induction_vars(&mut self, func: ObjPtr<Function>) {
+        let loop_list = loop_recognize_in_function(func);
+        let mut analyzer = SCEVAnalyzer::new();
+        analyzer.set_loop_list(loop_list.get_loop_list().clone());
+        for loop_info in loop_list.get_loop_list().iter() {
+            let header = loop_info.get_header();
+            let up_bb = header.get_up_bb();
+            // 只有一个前继在循环外、一条回边时每轮迭代每个归纳变量恰好更新一次
+            let outside = up_bb.iter().filter(|bb| !loop_info.is_in_loop(bb)).count();
+            if up_bb.len() != 2 || outside != 1 || header.is_empty() {
+                continue;
+            }
+            let index = up_bb
+                .iter()
+                .position(|bb| !loop_info.is_in_loop(bb))
+                .unwrap();
+            let mut ivs = vec![];
+            inst_process_in_bb(header.get_head_inst(), |inst| {
+                if !inst.is_phi() || inst.get_ir_type() != IrType::Int {
+                    return;
+                }
+                let next = inst.get_operand(1 - index);
+                if next.get_kind() != InstKind::Binary(BinOp::Add)
+                    || !next.get_operands().contains(&inst)
+                {
+                    return;
+                }
+                let scev = analyzer.analyze(&inst);
+                if scev.is_scev_rec_expr()
+                    && scev.get_operands().len() == 2
+                    && scev.get_operands()[1].is_scev_constant()
+                    && scev.get_operands()[1].get_scev_const() != 0
+                {
+                    ivs.push(InductionVar {
+                        phi: inst,
+                        start: inst.get_operand(index),
+                        step: scev.get_operands()[1].get_scev_const() as i64,
+                    });
+                }
+            });
+            if ivs.len() > 1 {
+                self.ivs.insert(header, ivs);
+            }
+        }
+    }
+
+    /// 由同一个循环中另一个归纳变量的范围推出的phi的范围
+    fn induction_bound(&self, phi: ObjPtr<Inst>) -> Option<Range> {
+        let ivs = self.ivs.get(&phi.get_parent_bb())?;
+        let iv = ivs.iter().find(|iv| iv.phi == phi)?;
+        // 迭代次数的上界: other = start + k * step 在other的范围内
+        let count = ivs
+            .iter()
+            .filter(|other| other.phi != phi && self.ranges.contains_key(&other.phi))
+            .map(|other| {
+                let range = self.get_range(other.phi);
+                let start = self.get_range(other.start);
+                if other.step > 0 {
+                    (range.hi - start.lo) / other.step
+                } else {
+                    (range.lo - start.hi) / other.step
+                }
+            })
+            .min()?;
+        if count < 0 {
+            return None;
+        }
+        let start = self.get_range(iv.start);
+        let distance = count * iv.step;
+        let bound = Range {
+            lo: start.lo + distance.min(0),
+            hi: start.hi + distance.max(0),
+        };
+        // 超出i32时归纳变量可能回绕
+        if bound.lo < i32::MIN as i64 || bound.hi > i32::MAX as i64 {
+            return None;
+        }
+        Some(bound)
+    }
+}
+
+/// 从pred到bb的边上成立的条件
+fn edge_facts(pred: ObjPtr<BasicBlock>, bb: ObjPtr<BasicBlock>) -> Vec<Fact> {
+    let next = pred.get_next_bb();
+    if next.len() != 2 || next[0] == next[1] {
+        return vec![];
+    }
+    let cond = pred.get_tail_inst().get_br_cond();
+    let op = match cond.get_kind() {
+        InstKind::Binary(
+            op @ (BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge | BinOp::Eq | BinOp::Ne),
+        ) if cond.get_lhs().get_ir_type() == IrType::Int => op,
+        _ => return vec![],
+    };
+    // next_bb[0]是条件为假时的后继
+    let op = if bb == next[1] { op } else { negate(op) };
+    let (lhs, rhs) = (cond.get_lhs(), cond.get_rhs());
+    vec![
+        Fact {
+            value: lhs,
+            op,
+            bound: rhs,
+        },
+        Fact {
+            value: rhs,
+            op: swap(op),
+            bound: lhs,
+        },
+    ]
+    .into_iter()
+    .filter(|fact| !fact.value.is_const())
+    .collect()
+}
+
+/// 条件取反后的比较
+fn negate(op: BinOp) -> BinOp {
+    match op {
+        BinOp::Lt => BinOp::Ge,
+        BinOp::Le => BinOp::Gt,
+        BinOp::Gt => BinOp::Le,
+        BinOp::Ge => BinOp::Lt,
+        BinOp::Eq => BinOp::Ne,
+        BinOp::Ne => BinOp::Eq,
+        _ => unreachable!("negate"),
+    }
+}
+
+/// 交换两边后的比较
+fn swap(op: BinOp) -> BinOp {
+    match op {
+        BinOp::Lt => BinOp::Gt,
+        BinOp::Le => BinOp::Ge,
+        BinOp::Gt => BinOp::Lt,
+        BinOp::Ge => BinOp::Le,
+        _ => op,
+    }
+}
+
+#[cfg(test)]
+mod value_range_test {
+    use super::{calculate_value_range, Range};
+    use crate::ir::{
+        instruction::{Inst, InstKind},
+        module::Module,
+        test_util::parse_module,
+        tools::{bfs_bb_proceess, inst_process_in_bb},
+    };
+    use crate::utility::ObjPtr;
+
+    /// 按bfs顺序找到main中的所有phi
+    fn find_phis(module: &Module) -> Vec<ObjPtr<Inst>> {
+        let mut phis = vec![];
+        bfs_bb_proceess(module.get_function("main").get_head(), |bb| {
+            inst_process_in_bb(bb.get_head_inst(), |inst| {
+                if inst.get_kind() == InstKind::Phi {
+                    phis.push(inst);
+                }
+            })
+        });
+        phis
+    }
+
+    #[test]
+    fn test_loop_counter() {
+        // 回边上的条件%i2 < 100限制了%i的上界,%k2 < %n只能说明%k2不会溢出
+        let (module, _) = parse_module(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  br label %bb_loop
+
+bb_loop:
+  %i = phi i32 [ 0, %bb_entry ], [ %i2, %bb_loop ]
+  %i2 = add i32 %i, 1
+  %c = icmp slt i32 %i2, 100
+  br i1 %c, label %bb_loop, label %bb_exit
+
+bb_exit:
+  %n = call i32 @getint()
+  br label %bb_loop2
+
+bb_loop2:
+  %k = phi i32 [ 0, %bb_exit ], [ %k2, %bb_loop2 ]
+  %k2 = add i32 %k, 1
+  %c2 = icmp slt i32 %k2, %n
+  br i1 %c2, label %bb_loop2, label %bb_ret
+
+bb_ret:
+  ret i32 %k2
+}
+",
+        );
+        let value_range = calculate_value_range(module.get_function("main"));
+        let phis = find_phis(&module);
+        assert_eq!(value_range.get_range(phis[0]), Range::new(0, 99));
+        // 上界未知时%k放宽到i32::MAX - 1,%k + 1不会溢出,所以仍然非负
+        assert!(value_range.get_range(phis[1]).is_non_negative());
+    }
+
+    #[test]
+    fn test_nested_branch() {
+        // 外层分支已经保证%x < 10,内层的%x < 20一定成立
+        let (module, _) = parse_module(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %x = call i32 @getint()
+  %c = icmp slt i32 %x, 10
+  br i1 %c, label %bb_then, label %bb_exit
+
+bb_then:
+  %c2 = icmp slt i32 %x, 20
+  br i1 %c2, label %bb_inner, label %bb_exit
+
+bb_inner:
+  ret i32 1
+
+bb_exit:
+  ret i32 0
+}
+",
+        );
+        let func = module.get_function("main");
+        let value_range = calculate_value_range(func);
+        let entry = func.get_head();
+        let then = entry.get_next_bb()[1];
+        let cond = then.get_tail_inst().get_br_cond();
+        assert_eq!(value_range.get_range_at(cond, then), Range::constant(1));
+        // 在入口块中%c的结果还不确定
+        let cond = entry.get_tail_inst().get_br_cond();
+        assert_eq!(value_range.get_range_at(cond, entry), Range::new(0, 1));
+    }
+
+    #[test]
+    fn test_sibling_induction_variable() {
+        // %j没有直接的边界,由%i的迭代次数推出
+        let (module, _) = parse_module(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  br label %bb_loop
+
+bb_loop:
+  %i = phi i32 [ 0, %bb_entry ], [ %i2, %bb_loop ]
+  %j = phi i32 [ 3, %bb_entry ], [ %j2, %bb_loop ]
+  %i2 = add i32 %i, 1
+  %j2 = add i32 %j, 2
+  %c = icmp slt i32 %i2, 10
+  br i1 %c, label %bb_loop, label %bb_exit
+
+bb_exit:
+  ret i32 %j2
+}
+",
+        );
+        let value_range = calculate_value_range(module.get_function("main"));
+        let phis = find_phis(&module);
+        assert_eq!(value_range.get_range(phis[0]), Range::new(0, 9));
+        assert_eq!(value_range.get_range(phis[1]), Range::new(3, 21));
+    }
+}
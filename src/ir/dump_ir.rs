@@ -17,33 +17,44 @@ use super::{
 pub fn dump_now(module: &Module, path: &str) {
     #[cfg(debug_assertions)]
     {
-        let mut global_map = HashMap::new();
-        let mut text = String::new();
-
-        // dump global variables
-        for (name, var) in module.get_all_var() {
-            global_map.insert(var, format!("@{}", name));
-            text = format!("{}{}", text, dump_global_var(name, var));
+        match dump_module(module) {
+            Ok(text) => {
+                // write to file
+                let mut file = File::create(path).unwrap();
+                file.write_all(text.as_bytes()).unwrap();
+            }
+            Err(e) => eprintln!("dump_now: {}", e),
         }
+    }
+}
 
-        text += "\n";
+/// 生成module的文本形式,可以由parse_ir读回
+/// # Return
+/// 指令引用了不在module中的值时返回错误信息
+pub fn dump_module(module: &Module) -> Result<String, String> {
+    let mut global_map = HashMap::new();
+    let mut text = String::new();
 
-        // dump functions
-        for (name, func) in module.get_all_func() {
-            if func.is_empty_bb() {
-                continue;
-            }
+    // dump global variables
+    for (name, var) in module.get_all_var() {
+        global_map.insert(var, format!("@{}", name));
+        text = format!("{}{}", text, dump_global_var(name, var));
+    }
 
-            text = format!("{}{}\n\n\n", text, dump_func(name, func, &mut global_map));
-        }
+    text += "\n";
 
-        // dump extern functions
-        text += format!("{}\n", dump_external_func()).as_str();
+    // dump functions
+    for (name, func) in module.get_all_func() {
+        if func.is_empty_bb() {
+            continue;
+        }
 
-        // write to file
-        let mut file = File::create(path).unwrap();
-        file.write_all(text.as_bytes()).unwrap();
+        text = format!("{}{}\n\n\n", text, dump_func(name, func, &mut global_map)?);
     }
+
+    // dump extern functions
+    text += format!("{}\n", dump_external_func()).as_str();
+    Ok(text)
 }
 
 fn dump_global_var(var_name: &str, var: ObjPtr<Inst>) -> String {
@@ -90,7 +101,7 @@ fn dump_global_var(var_name: &str, var: ObjPtr<Inst>) -> String {
                     for v in init.1.iter() {
                         value_init += format!(" i32 {},", v.1).as_str();
                     }
-                    value_init.truncate(value_init.len() - 1);
+                    value_init.pop();
                     text = format!("{} [{} x i32] [{}]", text, value, value_init);
                 }
 
@@ -112,12 +123,13 @@ fn dump_global_var(var_name: &str, var: ObjPtr<Inst>) -> String {
                     value_init +=
                         format!(" [{} x float] zeroinitializer", value - init.1.len() as i32)
                             .as_str();
-                    text = format!("{} <{{{}}}> <{{{}}}", text, value_type, value_init);
+                    text = format!("{} <{{{}}}> <{{{}}}>", text, value_type, value_init);
                 } else {
                     let mut value_init = String::new();
                     for v in init.1.iter() {
                         value_init += format!(" float {},", v.1).as_str();
                     }
+                    value_init.pop();
                     text = format!("{} [{} x float] [{}]", text, value, value_init);
                 }
 
@@ -175,7 +187,7 @@ fn dump_func(
     func_name: &str,
     func: ObjPtr<Function>,
     global_map: &mut HashMap<ObjPtr<Inst>, String>,
-) -> String {
+) -> Result<String, String> {
     let mut local_map = HashMap::new();
     let mut name_index = 0;
 
@@ -193,7 +205,7 @@ fn dump_func(
 
     let bb = func.get_head();
     let mut temp;
-    (name_index, temp) = dump_block(bb, global_map, &mut local_map, name_index);
+    (name_index, temp) = dump_block(bb, global_map, &mut local_map, name_index)?;
     text = format!("{}{}:\n{}\n", text, format!("bb_{}", bb.get_name()), temp);
     text += "\n";
 
@@ -206,7 +218,7 @@ fn dump_func(
         visited.insert(bb);
         while let Some(bb) = queue.pop() {
             if !visited.contains(&bb) {
-                (name_index, temp) = dump_block(bb, global_map, &mut local_map, name_index);
+                (name_index, temp) = dump_block(bb, global_map, &mut local_map, name_index)?;
                 text = format!("{}{}:\n{}", text, format!("bb_{}", bb.get_name()), temp);
                 text += "\n";
                 visited.insert(bb);
@@ -218,12 +230,12 @@ fn dump_func(
             }
         }
     } else {
-        text.truncate(text.len() - 3);
+        text.truncate(text.len() - 2);
     }
 
     text += "}\n";
 
-    text
+    Ok(text)
 }
 
 fn dump_ir_type(ir_type: IrType) -> String {
@@ -260,7 +272,8 @@ fn dump_para_type(ir_type: IrType) -> String {
     match ir_type {
         IrType::Int => "i32 noundef signext".to_string(),
         IrType::Float => "float noundef".to_string(),
-        IrType::IntPtr | IrType::FloatPtr => "ptr noundef".to_string(),
+        IrType::IntPtr => "i32* noundef".to_string(),
+        IrType::FloatPtr => "float* noundef".to_string(),
         _ => unreachable!(),
     }
 }
@@ -270,16 +283,16 @@ fn dump_block(
     global_map: &mut HashMap<ObjPtr<Inst>, String>,
     local_map: &mut HashMap<ObjPtr<Inst>, String>,
     mut name_index: i32,
-) -> (i32, String) {
+) -> Result<(i32, String), String> {
     let mut text = String::new();
     let mut inst = block.get_head_inst();
     while !inst.is_tail() {
         let temp;
-        (name_index, temp) = dump_inst(inst, global_map, local_map, name_index);
+        (name_index, temp) = dump_inst(inst, global_map, local_map, name_index)?;
         text = format!("{}{}", text, temp);
         inst = inst.get_next();
     }
-    (name_index, text)
+    Ok((name_index, text))
 }
 
 fn dump_inst(
@@ -287,44 +300,46 @@ fn dump_inst(
     global_map: &mut HashMap<ObjPtr<Inst>, String>,
     local_map: &mut HashMap<ObjPtr<Inst>, String>,
     mut name_index: i32,
-) -> (i32, String) {
+) -> Result<(i32, String), String> {
     let mut text = String::new();
     match inst.get_kind() {
         InstKind::Alloca(len) => {
-            if let IrType::IntPtr = inst.get_ir_type() {
-                name_index = put_name(local_map, inst.clone(), name_index);
-                text = format!(
-                    "  {} = alloca [{} x i32], align 4\n",
-                    local_map.get(&inst).unwrap(),
-                    len
-                );
-
-                // 数组初始化
-                text += format!("  ; init array begin!!!!\n").as_str();
-                let init = inst.get_int_init();
-                for (i, v) in init.1.iter().enumerate() {
-                    text += format!("  %val_{} = getelementptr inbounds [{} x i32], [{} x i32]* {}, i32 0, i32 {}\n", name_index, len, len, local_map.get(&inst).unwrap(), i).as_str();
-                    text += format!("  store i32 {}, i32* %val_{}, align 4\n", v.1, name_index)
-                        .as_str();
-                    name_index += 1;
-                }
-                text += format!("  ; init array end!!!!\n").as_str();
+            let elem_type = if let IrType::IntPtr = inst.get_ir_type() {
+                "i32"
             } else {
-                name_index = put_name(local_map, inst.clone(), name_index);
-                text = format!(
-                    "  {} = alloca [{} x float], align 4\n",
-                    local_map.get(&inst).unwrap(),
-                    len
-                );
-
-                // 数组初始化
-                text += format!("  ; init array begin!!!!\n").as_str();
-                let init = inst.get_float_init();
-                for (i, v) in init.1.iter().enumerate() {
-                    text += format!("  %{} = getelementptr inbounds [{} x float], [{} x float]* {}, i32 0, i32 {}\n", name_index, len, len, local_map.get(&inst).unwrap(), i).as_str();
-                    text += format!("  store float {}, float* %{}, align 4\n", v.1, name_index)
-                        .as_str();
+                "float"
+            };
+            name_index = put_name(local_map, inst, name_index);
+            text = format!(
+                "  {} = alloca [{} x {}], align 4\n",
+                local_map.get(&inst).unwrap(),
+                len,
+                elem_type
+            );
+
+            // 数组初始化,未初始化的数组没有初始化段
+            let (is_init, init): (bool, Vec<(bool, String)>) =
+                if let IrType::IntPtr = inst.get_ir_type() {
+                    let init = inst.get_int_init();
+                    (init.0, init.1.iter().map(|v| (v.0, v.1.to_string())).collect())
+                } else {
+                    let init = inst.get_float_init();
+                    (init.0, init.1.iter().map(|v| (v.0, v.1.to_string())).collect())
+                };
+            if is_init {
+                text += "  ; init array begin!!!!\n";
+                for (i, v) in init.iter().enumerate() {
+                    text += format!("  %val_{} = getelementptr inbounds [{} x {}], [{} x {}]* {}, i32 0, i32 {}\n", name_index, len, elem_type, len, elem_type, local_map.get(&inst).unwrap(), i).as_str();
+                    // 由变量初始化的位置
+                    let by_var = if v.0 { " ; variable" } else { "" };
+                    text += format!(
+                        "  store {} {}, {}* %val_{}, align 4{}\n",
+                        elem_type, v.1, elem_type, name_index, by_var
+                    )
+                    .as_str();
+                    name_index += 1;
                 }
+                text += "  ; init array end!!!!\n";
             }
         }
         InstKind::Gep => {
@@ -333,7 +348,7 @@ fn dump_inst(
             name_index = put_name(local_map, inst, name_index);
             if let InstKind::Load = inst.get_gep_ptr().get_kind() {
                 ptr = inst.get_gep_ptr().get_ptr();
-                name = global_map.get(&ptr).unwrap().clone();
+                name = get_inst_value(inst.get_gep_ptr(), local_map, global_map);
             } else {
                 ptr = inst.get_gep_ptr();
                 name = get_inst_value(ptr, local_map, global_map);
@@ -402,20 +417,18 @@ fn dump_inst(
         }
         InstKind::Load => match inst.get_ir_type() {
             IrType::IntPtr | IrType::FloatPtr => {
+                name_index = put_name(local_map, inst, name_index);
+                let ptr_name = get_ptr_name(inst.get_ptr(), local_map, global_map)?;
                 text += format!(
-                    "  ; Load array label {}\n",
-                    global_map.get(&inst.get_ptr()).unwrap()
+                    "  {} = load ptr, ptr {}, align 8\n",
+                    local_map.get(&inst).unwrap(),
+                    ptr_name
                 )
                 .as_str();
             }
             IrType::Int => {
                 name_index = put_name(local_map, inst, name_index);
-                let ptr = inst.get_ptr();
-                let ptr_name = if let Some(name) = local_map.get(&ptr) {
-                    name
-                } else {
-                    global_map.get(&ptr).unwrap()
-                };
+                let ptr_name = get_ptr_name(inst.get_ptr(), local_map, global_map)?;
                 text += format!(
                     "  {} = load i32, i32* {}, align 4\n",
                     local_map.get(&inst).unwrap(),
@@ -425,12 +438,7 @@ fn dump_inst(
             }
             IrType::Float => {
                 name_index = put_name(local_map, inst, name_index);
-                let ptr = inst.get_ptr();
-                let ptr_name = if let Some(name) = local_map.get(&ptr) {
-                    name
-                } else {
-                    global_map.get(&ptr).unwrap()
-                };
+                let ptr_name = get_ptr_name(inst.get_ptr(), local_map, global_map)?;
                 text += format!(
                     "  {} = load float, float* {}, align 4\n",
                     local_map.get(&inst).unwrap(),
@@ -555,11 +563,47 @@ fn dump_inst(
                     )
                     .as_str();
                 } else {
-                    unreachable!("No float rem in ir");
+                    // 前端在浮点数的表达式中可能生成浮点数的取余
+                    name_index = put_name(local_map, inst, name_index);
+                    text += format!(
+                        "  {} = frem float {}, {}\n",
+                        local_map.get(&inst).unwrap().clone(),
+                        get_inst_value(inst.get_lhs(), local_map, global_map),
+                        get_inst_value(inst.get_rhs(), local_map, global_map)
+                    )
+                    .as_str();
                 }
             }
+            BinOp::UDiv
+            | BinOp::URem
+            | BinOp::And
+            | BinOp::Or
+            | BinOp::Xor
+            | BinOp::Shl
+            | BinOp::LShr
+            | BinOp::AShr => {
+                let name = match op {
+                    BinOp::UDiv => "udiv",
+                    BinOp::URem => "urem",
+                    BinOp::And => "and",
+                    BinOp::Or => "or",
+                    BinOp::Xor => "xor",
+                    BinOp::Shl => "shl",
+                    BinOp::LShr => "lshr",
+                    _ => "ashr",
+                };
+                name_index = put_name(local_map, inst, name_index);
+                text += format!(
+                    "  {} = {} i32 {}, {}\n",
+                    local_map.get(&inst).unwrap().clone(),
+                    name,
+                    get_inst_value(inst.get_lhs(), local_map, global_map),
+                    get_inst_value(inst.get_rhs(), local_map, global_map)
+                )
+                .as_str();
+            }
             BinOp::Gt => {
-                if let IrType::Int = inst.get_ir_type() {
+                if let IrType::Int = inst.get_lhs().get_ir_type() {
                     name_index = put_name(local_map, inst, name_index);
                     text += format!(
                         "  {} = icmp sgt i32 {}, {}\n",
@@ -580,7 +624,7 @@ fn dump_inst(
                 }
             }
             BinOp::Lt => {
-                if let IrType::Int = inst.get_ir_type() {
+                if let IrType::Int = inst.get_lhs().get_ir_type() {
                     name_index = put_name(local_map, inst, name_index);
                     text += format!(
                         "  {} = icmp slt i32 {}, {}\n",
@@ -601,7 +645,7 @@ fn dump_inst(
                 }
             }
             BinOp::Ge => {
-                if let IrType::Int = inst.get_ir_type() {
+                if let IrType::Int = inst.get_lhs().get_ir_type() {
                     name_index = put_name(local_map, inst, name_index);
                     text += format!(
                         "  {} = icmp sge i32 {}, {}\n",
@@ -622,7 +666,7 @@ fn dump_inst(
                 }
             }
             BinOp::Le => {
-                if let IrType::Int = inst.get_ir_type() {
+                if let IrType::Int = inst.get_lhs().get_ir_type() {
                     name_index = put_name(local_map, inst, name_index);
                     text += format!(
                         "  {} = icmp sle i32 {}, {}\n",
@@ -643,7 +687,7 @@ fn dump_inst(
                 }
             }
             BinOp::Eq => {
-                if let IrType::Int = inst.get_ir_type() {
+                if let IrType::Int = inst.get_lhs().get_ir_type() {
                     name_index = put_name(local_map, inst, name_index);
                     text += format!(
                         "  {} = icmp eq i32 {}, {}\n",
@@ -664,7 +708,7 @@ fn dump_inst(
                 }
             }
             BinOp::Ne => {
-                if let IrType::Int = inst.get_ir_type() {
+                if let IrType::Int = inst.get_lhs().get_ir_type() {
                     name_index = put_name(local_map, inst, name_index);
                     text += format!(
                         "  {} = icmp ne i32 {}, {}\n",
@@ -692,14 +736,14 @@ fn dump_inst(
                     name_index = put_name(local_map, inst, name_index);
                     if let IrType::Int = inst.get_ir_type() {
                         text += format!(
-                            "  {} = add i32 0, {}\n",
+                            "  {} = add i32 0, {} ;pos\n",
                             local_map.get(&inst).unwrap().clone(),
                             get_inst_value(inst.get_unary_operand(), local_map, global_map),
                         )
                         .as_str();
                     } else {
                         text += format!(
-                            "  {} = fadd float 0.0, {}\n",
+                            "  {} = fadd float 0.0, {} ;pos\n",
                             local_map.get(&inst).unwrap().clone(),
                             get_inst_value(inst.get_unary_operand(), local_map, global_map),
                         )
@@ -710,14 +754,14 @@ fn dump_inst(
                     name_index = put_name(local_map, inst, name_index);
                     if let IrType::Int = inst.get_ir_type() {
                         text += format!(
-                            "  {} = sub i32 0, {}\n",
+                            "  {} = sub i32 0, {} ;neg\n",
                             local_map.get(&inst).unwrap().clone(),
                             get_inst_value(inst.get_unary_operand(), local_map, global_map),
                         )
                         .as_str();
                     } else {
                         text += format!(
-                            "  {} = fsub float 0.0, {}\n",
+                            "  {} = fsub float 0.0, {} ;neg\n",
                             local_map.get(&inst).unwrap().clone(),
                             get_inst_value(inst.get_unary_operand(), local_map, global_map),
                         )
@@ -744,6 +788,47 @@ fn dump_inst(
                 }
             }
         }
+        InstKind::Select => {
+            // 条件不是比较结果时同分支一样先与0比较
+            let cond = match inst.get_select_cond().get_kind() {
+                InstKind::Binary(BinOp::Ne)
+                | InstKind::Binary(BinOp::Eq)
+                | InstKind::Binary(BinOp::Le)
+                | InstKind::Binary(BinOp::Lt)
+                | InstKind::Binary(BinOp::Gt)
+                | InstKind::Binary(BinOp::Ge)
+                | InstKind::Unary(UnOp::Not) => {
+                    get_inst_value(inst.get_select_cond(), local_map, global_map)
+                }
+                _ => {
+                    let cond = format!("%val_{}_add", name_index);
+                    text += format!(
+                        "  {} = icmp ne i32 {}, 0\n",
+                        cond,
+                        get_inst_value(inst.get_select_cond(), local_map, global_map)
+                    )
+                    .as_str();
+                    name_index += 1;
+                    cond
+                }
+            };
+            let ty = if let IrType::Int = inst.get_ir_type() {
+                "i32"
+            } else {
+                "float"
+            };
+            name_index = put_name(local_map, inst, name_index);
+            text += format!(
+                "  {} = select i1 {}, {} {}, {} {}\n",
+                local_map.get(&inst).unwrap().clone(),
+                cond,
+                ty,
+                get_inst_value(inst.get_true_value(), local_map, global_map),
+                ty,
+                get_inst_value(inst.get_false_value(), local_map, global_map)
+            )
+            .as_str();
+        }
         InstKind::Branch => {
             if inst.is_br_jmp() {
                 text += format!(
@@ -808,7 +893,8 @@ fn dump_inst(
                 let arg_type = match arg.get_ir_type() {
                     IrType::Int => "i32",
                     IrType::Float => "float",
-                    IrType::IntPtr | IrType::FloatPtr => "ptr",
+                    IrType::IntPtr => "i32*",
+                    IrType::FloatPtr => "float*",
                     _ => unreachable!("No Call in dump_inst"),
                 };
                 param += format!(
@@ -907,7 +993,7 @@ fn dump_inst(
         }
         InstKind::Head => unreachable!("No Head in dump_inst"),
     }
-    (name_index, text)
+    Ok((name_index, text))
 }
 
 fn put_name(
@@ -923,12 +1009,29 @@ fn put_name(
     }
 }
 
+/// 获得load的指针的名字,先查找局部的值,再查找全局变量
+fn get_ptr_name(
+    ptr: ObjPtr<Inst>,
+    local_map: &HashMap<ObjPtr<Inst>, String>,
+    global_map: &HashMap<ObjPtr<Inst>, String>,
+) -> Result<String, String> {
+    local_map
+        .get(&ptr)
+        .or_else(|| global_map.get(&ptr))
+        .cloned()
+        .ok_or_else(|| {
+            format!(
+                "load from {:?} which is neither a local value nor a global variable",
+                ptr.get_kind()
+            )
+        })
+}
+
 fn get_inst_value(
     inst: ObjPtr<Inst>,
     local_map: &mut HashMap<ObjPtr<Inst>, String>,
     global_map: &HashMap<ObjPtr<Inst>, String>,
 ) -> String {
-    static mut NOTFOUND: i32 = 0;
     match inst.get_kind() {
         InstKind::ConstInt(value) => value.to_string(),
         InstKind::ConstFloat(value) => value.to_string(),
@@ -940,13 +1043,9 @@ fn get_inst_value(
             } else if global_map.contains_key(&inst) {
                 global_map.get(&inst).unwrap().clone()
             } else {
-                local_map.insert(
-                    inst,
-                    format!("%notfound{}", unsafe {
-                        NOTFOUND += 1;
-                        NOTFOUND
-                    }),
-                );
+                // 在定义之前被使用的值(如phi的操作数),名字在函数内唯一
+                let name = format!("%notfound{}", local_map.len());
+                local_map.insert(inst, name);
                 local_map.get(&inst).unwrap().clone()
             }
         }
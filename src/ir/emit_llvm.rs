@@ -0,0 +1,770 @@
+//! 生成可以被llvm-as/clang接受的LLVM IR
+//!
+//! 与dump_ir不同,这里的输出只交给LLVM,不需要被parse_ir读回:
+//! - 指针使用带类型的写法,数组统一用指向首元素的指针表示
+//! - 比较的结果是i1,再零扩展为i32,与IR中比较的类型一致
+//! - 局部数组的初始化展开为整体清零和逐个store
+//! - 浮点常量使用十六进制,保证与f32的值完全一致
+//! - hitsz_memset/hitsz_memcopy生成对libc的包装,其余外部函数只声明
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::utility::ObjPtr;
+
+use super::{
+    basicblock::BasicBlock,
+    function::Function,
+    instruction::{BinOp, Inst, InstKind, UnOp},
+    ir_type::IrType,
+    module::Module,
+};
+
+/// 生成整个module的LLVM IR
+pub fn emit_llvm(module: &Module) -> String {
+    let mut text = String::new();
+
+    // 全局变量,记录其指针在函数中的写法
+    let mut globals = HashMap::new();
+    for (name, var) in module.get_all_var() {
+        let (def, ptr) = emit_global_var(name, var);
+        text += def.as_str();
+        globals.insert(var, ptr);
+    }
+    text += "\n";
+
+    // 有定义的函数
+    for (name, func) in module.get_all_func() {
+        if func.is_empty_bb() {
+            continue;
+        }
+        let mut emitter = FuncEmitter::new(module, &globals);
+        text += emitter.emit(name, func).as_str();
+        text += "\n";
+    }
+
+    // 外部函数
+    for (name, func) in module.get_all_func() {
+        if !func.is_empty_bb() {
+            continue;
+        }
+        text += emit_declare(name, func).as_str();
+    }
+    text
+}
+
+/// 生成全局变量的定义,并返回其在函数中作为指针的写法
+fn emit_global_var(name: &str, var: ObjPtr<Inst>) -> (String, String) {
+    let ptr = format!("@{}", name);
+    match var.get_kind() {
+        InstKind::GlobalConstInt(value) => (
+            format!("@{} = dso_local constant i32 {}, align 4\n", name, value),
+            ptr,
+        ),
+        InstKind::GlobalInt(value) => (
+            format!("@{} = dso_local global i32 {}, align 4\n", name, value),
+            ptr,
+        ),
+        InstKind::GlobalConstFloat(value) => (
+            format!(
+                "@{} = dso_local constant float {}, align 4\n",
+                name,
+                float_hex(value)
+            ),
+            ptr,
+        ),
+        InstKind::GlobalFloat(value) => (
+            format!(
+                "@{} = dso_local global float {}, align 4\n",
+                name,
+                float_hex(value)
+            ),
+            ptr,
+        ),
+        InstKind::Alloca(len) => {
+            let (_, elem, init) = array_init(var);
+            // 末尾的0用zeroinitializer表示,避免大数组展开
+            let used = init.iter().rposition(|v| !v.is_zero).map_or(0, |i| i + 1);
+            let (ty, value) = if used == 0 {
+                (
+                    format!("[{} x {}]", len, elem),
+                    "zeroinitializer".to_string(),
+                )
+            } else {
+                let values = init[..used]
+                    .iter()
+                    .map(|v| format!("{} {}", elem, v.text))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                if used as i32 >= len {
+                    (format!("[{} x {}]", len, elem), format!("[{}]", values))
+                } else {
+                    let rest = len - used as i32;
+                    (
+                        format!("<{{ [{} x {}], [{} x {}] }}>", used, elem, rest, elem),
+                        format!(
+                            "<{{ [{} x {}] [{}], [{} x {}] zeroinitializer }}>",
+                            used, elem, values, rest, elem
+                        ),
+                    )
+                }
+            };
+            (
+                format!("@{} = dso_local global {} {}, align 4\n", name, ty, value),
+                format!("bitcast ({}* @{} to {}*)", ty, name, elem),
+            )
+        }
+        _ => unreachable!("invalid global variable {:?}", var.get_kind()),
+    }
+}
+
+/// 生成外部函数的声明,hitsz_memset/hitsz_memcopy生成对libc的包装
+fn emit_declare(name: &str, func: ObjPtr<Function>) -> String {
+    match name {
+        "hitsz_memset" => "define internal void @hitsz_memset(i32* %array, i32 %value, i32 %n) {\n\
+                           \x20 %dst = bitcast i32* %array to i8*\n\
+                           \x20 %len = sext i32 %n to i64\n\
+                           \x20 %ret = call i8* @memset(i8* %dst, i32 %value, i64 %len)\n\
+                           \x20 ret void\n\
+                           }\n\
+                           declare i8* @memset(i8*, i32, i64)\n"
+            .to_string(),
+        "hitsz_memcopy" => "define internal void @hitsz_memcopy(i32* %dst, i32* %src, i32 %n) {\n\
+                            \x20 %to = bitcast i32* %dst to i8*\n\
+                            \x20 %from = bitcast i32* %src to i8*\n\
+                            \x20 %len = sext i32 %n to i64\n\
+                            \x20 %ret = call i8* @memcpy(i8* %to, i8* %from, i64 %len)\n\
+                            \x20 ret void\n\
+                            }\n\
+                            declare i8* @memcpy(i8*, i8*, i64)\n"
+            .to_string(),
+        _ => {
+            let params = func
+                .get_parameter_list()
+                .iter()
+                .map(|param| llvm_type(param.get_ir_type()))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!(
+                "declare {} @{}({})\n",
+                llvm_type(func.get_return_type()),
+                name,
+                params
+            )
+        }
+    }
+}
+
+/// 生成一个函数时的状态
+struct FuncEmitter<'a> {
+    module: &'a Module,
+    /// 全局变量作为指针的写法
+    globals: &'a HashMap<ObjPtr<Inst>, String>,
+    /// 局部值的名字
+    names: HashMap<ObjPtr<Inst>, String>,
+    /// 比较和取反得到的i1值的名字
+    conds: HashMap<ObjPtr<Inst>, String>,
+    labels: HashMap<ObjPtr<BasicBlock>, String>,
+    /// 临时值的个数
+    temps: usize,
+    text: String,
+}
+
+impl<'a> FuncEmitter<'a> {
+    fn new(module: &'a Module, globals: &'a HashMap<ObjPtr<Inst>, String>) -> FuncEmitter<'a> {
+        FuncEmitter {
+            module,
+            globals,
+            names: HashMap::new(),
+            conds: HashMap::new(),
+            labels: HashMap::new(),
+            temps: 0,
+            text: String::new(),
+        }
+    }
+
+    fn emit(&mut self, name: &str, func: ObjPtr<Function>) -> String {
+        let blocks = reachable_blocks(func.get_head());
+
+        // 先给所有的值和基本块命名,phi可以引用之后才定义的值
+        let mut index = 0;
+        for param in func.get_parameter_list().iter() {
+            self.names.insert(*param, format!("%v{}", index));
+            index += 1;
+        }
+        for (i, bb) in blocks.iter().enumerate() {
+            self.labels
+                .insert(*bb, format!("bb{}_{}", i, sanitize(bb.get_name())));
+            let mut inst = bb.get_head_inst();
+            while !inst.is_tail() {
+                if !inst.is_const() && inst.get_ir_type() != IrType::Void {
+                    self.names.insert(inst, format!("%v{}", index));
+                    index += 1;
+                }
+                inst = inst.get_next();
+            }
+        }
+
+        let params = func
+            .get_parameter_list()
+            .iter()
+            .map(|param| format!("{} {}", llvm_type(param.get_ir_type()), self.names[param]))
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.text = format!(
+            "define dso_local {} @{}({}) {{\n",
+            llvm_type(func.get_return_type()),
+            name,
+            params
+        );
+
+        // LLVM的入口块不能有前驱
+        let head = func.get_head();
+        if !head.get_up_bb().is_empty() {
+            self.text += format!("entry:\n  br label %{}\n", self.labels[&head]).as_str();
+        }
+
+        for (i, bb) in blocks.iter().enumerate() {
+            if i > 0 {
+                self.text += "\n";
+            }
+            self.text += format!("{}:\n", self.labels[bb]).as_str();
+            let mut inst = bb.get_head_inst();
+            let mut terminated = false;
+            while !inst.is_tail() {
+                self.emit_inst(inst);
+                terminated = matches!(inst.get_kind(), InstKind::Branch | InstKind::Return);
+                inst = inst.get_next();
+            }
+            if !terminated {
+                self.text += "  unreachable\n";
+            }
+        }
+        self.text += "}\n";
+        std::mem::take(&mut self.text)
+    }
+
+    fn emit_inst(&mut self, inst: ObjPtr<Inst>) {
+        match inst.get_kind() {
+            InstKind::Alloca(len) => {
+                let (is_init, elem, init) = array_init(inst);
+                let name = self.name(inst);
+                let array_ty = format!("[{} x {}]", len, elem);
+                self.text += format!(
+                    "  {}.arr = alloca {}, align 4\n  {} = getelementptr inbounds {}, {}* {}.arr, i32 0, i32 0\n",
+                    name, array_ty, name, array_ty, array_ty, name
+                )
+                .as_str();
+                if is_init {
+                    // 先整体清零,再写入非零的常量,由变量初始化的位置之后会有store
+                    self.text += format!(
+                        "  store {} zeroinitializer, {}* {}.arr, align 4\n",
+                        array_ty, array_ty, name
+                    )
+                    .as_str();
+                    for (i, v) in init.iter().enumerate() {
+                        if v.by_var || v.is_zero {
+                            continue;
+                        }
+                        self.text += format!(
+                            "  {}.{} = getelementptr inbounds {}, {}* {}, i32 {}\n  store {} {}, {}* {}.{}, align 4\n",
+                            name, i, elem, elem, name, i, elem, v.text, elem, name, i
+                        )
+                        .as_str();
+                    }
+                }
+            }
+            InstKind::Gep => {
+                let elem = elem_type(inst.get_ir_type());
+                self.text += format!(
+                    "  {} = getelementptr {}, {}* {}, i32 {}\n",
+                    self.name(inst),
+                    elem,
+                    elem,
+                    self.value(inst.get_gep_ptr()),
+                    self.value(inst.get_gep_offset())
+                )
+                .as_str();
+            }
+            InstKind::Load => {
+                // 全局数组的load就是数组本身,不需要生成指令
+                if is_array_ptr_load(inst) {
+                    return;
+                }
+                let ty = llvm_type(inst.get_ir_type());
+                self.text += format!(
+                    "  {} = load {}, {}* {}, align 4\n",
+                    self.name(inst),
+                    ty,
+                    ty,
+                    self.ptr(inst.get_ptr())
+                )
+                .as_str();
+            }
+            InstKind::Store => {
+                let ty = llvm_type(inst.get_value().get_ir_type());
+                self.text += format!(
+                    "  store {} {}, {}* {}, align 4\n",
+                    ty,
+                    self.value(inst.get_value()),
+                    ty,
+                    self.ptr(inst.get_dest())
+                )
+                .as_str();
+            }
+            InstKind::Binary(op) => {
+                let is_int = inst.get_lhs().get_ir_type() == IrType::Int;
+                let lhs = self.value(inst.get_lhs());
+                let rhs = self.value(inst.get_rhs());
+                let ty = if is_int { "i32" } else { "float" };
+                let name = self.name(inst);
+                let arith = match op {
+                    BinOp::Add => Some(if is_int { "add" } else { "fadd" }),
+                    BinOp::Sub => Some(if is_int { "sub" } else { "fsub" }),
+                    BinOp::Mul => Some(if is_int { "mul" } else { "fmul" }),
+                    BinOp::Div => Some(if is_int { "sdiv" } else { "fdiv" }),
+                    BinOp::Rem => Some(if is_int { "srem" } else { "frem" }),
+                    BinOp::UDiv => Some("udiv"),
+                    BinOp::URem => Some("urem"),
+                    BinOp::And => Some("and"),
+                    BinOp::Or => Some("or"),
+                    BinOp::Xor => Some("xor"),
+                    BinOp::Shl => Some("shl"),
+                    BinOp::LShr => Some("lshr"),
+                    BinOp::AShr => Some("ashr"),
+                    _ => None,
+                };
+                if let Some(arith) = arith {
+                    self.text +=
+                        format!("  {} = {} {} {}, {}\n", name, arith, ty, lhs, rhs).as_str();
+                    return;
+                }
+                let cmp = match (op, is_int) {
+                    (BinOp::Gt, true) => "icmp sgt",
+                    (BinOp::Lt, true) => "icmp slt",
+                    (BinOp::Ge, true) => "icmp sge",
+                    (BinOp::Le, true) => "icmp sle",
+                    (BinOp::Eq, true) => "icmp eq",
+                    (BinOp::Ne, true) => "icmp ne",
+                    (BinOp::Gt, false) => "fcmp ogt",
+                    (BinOp::Lt, false) => "fcmp olt",
+                    (BinOp::Ge, false) => "fcmp oge",
+                    (BinOp::Le, false) => "fcmp ole",
+                    (BinOp::Eq, false) => "fcmp oeq",
+                    (BinOp::Ne, false) => "fcmp une",
+                    _ => unreachable!(),
+                };
+                self.text += format!(
+                    "  {}.c = {} {} {}, {}\n  {} = zext i1 {}.c to i32\n",
+                    name, cmp, ty, lhs, rhs, name, name
+                )
+                .as_str();
+                self.conds.insert(inst, format!("{}.c", name));
+            }
+            InstKind::Unary(op) => {
+                let name = self.name(inst);
+                let operand = self.value(inst.get_unary_operand());
+                let is_int = inst.get_ir_type() == IrType::Int;
+                match op {
+                    // 取正不生成指令,使用者直接引用操作数
+                    UnOp::Pos => {}
+                    UnOp::Neg => {
+                        if is_int {
+                            self.text += format!("  {} = sub i32 0, {}\n", name, operand).as_str();
+                        } else {
+                            self.text += format!("  {} = fneg float {}\n", name, operand).as_str();
+                        }
+                    }
+                    UnOp::Not => {
+                        if is_int {
+                            self.text += format!(
+                                "  {}.c = icmp eq i32 {}, 0\n  {} = zext i1 {}.c to i32\n",
+                                name, operand, name, name
+                            )
+                            .as_str();
+                        } else {
+                            self.text += format!(
+                                "  {}.c = fcmp oeq float {}, 0.0\n  {} = uitofp i1 {}.c to float\n",
+                                name, operand, name, name
+                            )
+                            .as_str();
+                        }
+                        self.conds.insert(inst, format!("{}.c", name));
+                    }
+                }
+            }
+            InstKind::Select => {
+                let cond = self.cond(inst.get_select_cond());
+                let ty = llvm_type(inst.get_ir_type());
+                self.text += format!(
+                    "  {} = select i1 {}, {} {}, {} {}\n",
+                    self.name(inst),
+                    cond,
+                    ty,
+                    self.value(inst.get_true_value()),
+                    ty,
+                    self.value(inst.get_false_value())
+                )
+                .as_str();
+            }
+            InstKind::Branch => {
+                let next = inst.get_parent_bb().get_next_bb().clone();
+                if inst.is_br_jmp() || next[0] == next[next.len() - 1] {
+                    self.text += format!("  br label %{}\n", self.labels[&next[0]]).as_str();
+                    return;
+                }
+                let cond = self.cond(inst.get_br_cond());
+                self.text += format!(
+                    "  br i1 {}, label %{}, label %{}\n",
+                    cond,
+                    self.labels[&inst.get_true_bb()],
+                    self.labels[&inst.get_false_bb()]
+                )
+                .as_str();
+            }
+            InstKind::Call(callee) => {
+                let params = self
+                    .module
+                    .find_function(callee.as_str())
+                    .map(|func| func.get_parameter_list().clone())
+                    .unwrap_or_default();
+                let mut args = vec![];
+                for (i, arg) in inst.get_operands().iter().enumerate() {
+                    let ty = llvm_type(arg.get_ir_type());
+                    let value = self.value(*arg);
+                    // 指针实参的类型与形参不同时(如float数组传给hitsz_memset)需要转换
+                    match params.get(i).map(|param| llvm_type(param.get_ir_type())) {
+                        Some(param_ty) if param_ty != ty && ty.ends_with('*') => {
+                            let cast = self.temp();
+                            self.text +=
+                                format!("  {} = bitcast {} {} to {}\n", cast, ty, value, param_ty)
+                                    .as_str();
+                            args.push(format!("{} {}", param_ty, cast));
+                        }
+                        _ => args.push(format!("{} {}", ty, value)),
+                    }
+                }
+                let ret = llvm_type(inst.get_ir_type());
+                if inst.get_ir_type() == IrType::Void {
+                    self.text +=
+                        format!("  call {} @{}({})\n", ret, callee, args.join(", ")).as_str();
+                } else {
+                    self.text += format!(
+                        "  {} = call {} @{}({})\n",
+                        self.name(inst),
+                        ret,
+                        callee,
+                        args.join(", ")
+                    )
+                    .as_str();
+                }
+            }
+            InstKind::Return => {
+                if inst.get_ir_type() == IrType::Void {
+                    self.text += "  ret void\n";
+                } else {
+                    self.text += format!(
+                        "  ret {} {}\n",
+                        llvm_type(inst.get_ir_type()),
+                        self.value(inst.get_return_value())
+                    )
+                    .as_str();
+                }
+            }
+            InstKind::FtoI => {
+                self.text += format!(
+                    "  {} = fptosi float {} to i32\n",
+                    self.name(inst),
+                    self.value(inst.get_float_to_int_value())
+                )
+                .as_str();
+            }
+            InstKind::ItoF => {
+                self.text += format!(
+                    "  {} = sitofp i32 {} to float\n",
+                    self.name(inst),
+                    self.value(inst.get_int_to_float_value())
+                )
+                .as_str();
+            }
+            InstKind::Phi => {
+                // 不可达的前驱没有生成,对应的操作数一并去掉
+                let mut incoming = vec![];
+                for (i, op) in inst.get_operands().iter().enumerate() {
+                    if let Some(label) = self.labels.get(&inst.get_phi_predecessor(i)) {
+                        incoming.push(format!("[ {}, %{} ]", self.value(*op), label));
+                    }
+                }
+                self.text += format!(
+                    "  {} = phi {} {}\n",
+                    self.name(inst),
+                    llvm_type(inst.get_ir_type()),
+                    incoming.join(", ")
+                )
+                .as_str();
+            }
+            InstKind::ConstInt(_) | InstKind::ConstFloat(_) => {
+                // 常量在使用处直接写出
+            }
+            InstKind::GlobalConstInt(_)
+            | InstKind::GlobalConstFloat(_)
+            | InstKind::GlobalInt(_)
+            | InstKind::GlobalFloat(_) => unreachable!("No Global in bb"),
+            InstKind::Parameter => unreachable!("No parameter in bb"),
+            InstKind::Head => unreachable!("No Head in emit_inst"),
+        }
+    }
+
+    /// 获得作为i1条件时的写法,比较结果直接使用,其他值与0比较
+    fn cond(&mut self, cond: ObjPtr<Inst>) -> String {
+        if let Some(cond) = self.conds.get(&cond) {
+            return cond.clone();
+        }
+        let name = self.temp();
+        let value = self.value(cond);
+        if cond.get_ir_type() == IrType::Int {
+            self.text += format!("  {} = icmp ne i32 {}, 0\n", name, value).as_str();
+        } else {
+            self.text += format!("  {} = fcmp une float {}, 0.0\n", name, value).as_str();
+        }
+        name
+    }
+
+    /// 获得指令定义的值的名字
+    fn name(&self, inst: ObjPtr<Inst>) -> String {
+        self.names[&inst].clone()
+    }
+
+    /// 生成一个新的临时值名字
+    fn temp(&mut self) -> String {
+        self.temps += 1;
+        format!("%t{}", self.temps)
+    }
+
+    /// 获得作为操作数时的写法
+    fn value(&self, inst: ObjPtr<Inst>) -> String {
+        match inst.get_kind() {
+            InstKind::ConstInt(value) | InstKind::GlobalConstInt(value) => value.to_string(),
+            InstKind::ConstFloat(value) | InstKind::GlobalConstFloat(value) => float_hex(value),
+            InstKind::Unary(UnOp::Pos) => self.value(inst.get_unary_operand()),
+            InstKind::Load if is_array_ptr_load(inst) => self.ptr(inst.get_ptr()),
+            _ => self.ptr(inst),
+        }
+    }
+
+    /// 获得作为load/store地址时的写法,全局变量使用其指针
+    fn ptr(&self, inst: ObjPtr<Inst>) -> String {
+        if let Some(ptr) = self.globals.get(&inst) {
+            ptr.clone()
+        } else if let Some(name) = self.names.get(&inst) {
+            name.clone()
+        } else {
+            // 引用了不可达块中定义的值,只可能出现在不可达的路径上
+            "undef".to_string()
+        }
+    }
+}
+
+/// 从函数入口开始广度优先遍历可达的基本块
+fn reachable_blocks(head: ObjPtr<BasicBlock>) -> Vec<ObjPtr<BasicBlock>> {
+    let mut blocks = vec![];
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::new();
+    visited.insert(head);
+    queue.push_back(head);
+    while let Some(bb) = queue.pop_front() {
+        blocks.push(bb);
+        for succ in bb.get_next_bb().iter() {
+            if visited.insert(*succ) {
+                queue.push_back(*succ);
+            }
+        }
+    }
+    blocks
+}
+
+/// 数组初始化中的一个位置
+struct InitValue {
+    /// 由变量初始化,之后会有对应的store
+    by_var: bool,
+    is_zero: bool,
+    text: String,
+}
+
+/// 数组是否初始化、元素类型和各初始值
+fn array_init(array: ObjPtr<Inst>) -> (bool, &'static str, Vec<InitValue>) {
+    match array.get_ir_type() {
+        IrType::IntPtr => {
+            let init = array.get_int_init();
+            let values = init
+                .1
+                .iter()
+                .map(|v| InitValue {
+                    by_var: v.0,
+                    is_zero: v.1 == 0,
+                    text: v.1.to_string(),
+                })
+                .collect();
+            (init.0, "i32", values)
+        }
+        _ => {
+            let init = array.get_float_init();
+            let values = init
+                .1
+                .iter()
+                .map(|v| InitValue {
+                    by_var: v.0,
+                    is_zero: v.1.to_bits() == 0,
+                    text: float_hex(v.1),
+                })
+                .collect();
+            (init.0, "float", values)
+        }
+    }
+}
+
+/// 全局数组和全局数组的load的值是数组的地址,不对应LLVM中的指令
+fn is_array_ptr_load(inst: ObjPtr<Inst>) -> bool {
+    inst.get_kind() == InstKind::Load
+        && matches!(inst.get_ir_type(), IrType::IntPtr | IrType::FloatPtr)
+}
+
+fn elem_type(ir_type: IrType) -> &'static str {
+    match ir_type {
+        IrType::IntPtr => "i32",
+        IrType::FloatPtr => "float",
+        _ => unreachable!("not a pointer type {:?}", ir_type),
+    }
+}
+
+fn llvm_type(ir_type: IrType) -> &'static str {
+    match ir_type {
+        IrType::Void => "void",
+        IrType::Int => "i32",
+        IrType::Float => "float",
+        IrType::IntPtr => "i32*",
+        IrType::FloatPtr => "float*",
+        _ => unreachable!("no llvm type for {:?}", ir_type),
+    }
+}
+
+/// LLVM要求浮点常量的十六进制写法是等值的double
+fn float_hex(value: f32) -> String {
+    format!("0x{:016X}", (value as f64).to_bits())
+}
+
+/// 基本块名中只保留LLVM标识符允许的字符
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod emit_llvm_test {
+    use super::emit_llvm;
+    use crate::ir::{
+        add_interface, basicblock::BasicBlock, function::Function, instruction::Inst,
+        module::Module, parse_ir, test_util::parse_module,
+    };
+    use crate::utility::ObjPool;
+
+    const IR: &str = "
+@arr = dso_local global <{ i32, [3 x i32] }> <{ i32 5, [3 x i32] zeroinitializer}>, align 4
+
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %a = alloca [3 x float], align 4
+  ; init array begin!!!!
+  %val_0 = getelementptr inbounds [3 x float], [3 x float]* %a, i32 0, i32 0
+  store float 0.1, float* %val_0, align 4
+  ; init array end!!!!
+  %garr = load ptr, ptr @arr, align 8
+  %e = getelementptr inbounds [4 x i32], [4 x i32]* %garr, i32 0, i32 1
+  %x = load i32, i32* %e, align 4
+  %c = icmp slt i32 %x, 3
+  call void @putint(i32 noundef %c)
+  br i1 %c, label %bb_then, label %bb_end
+
+bb_then:
+  call void @hitsz_memset(float* noundef %a, i32 noundef 0, i32 noundef 12)
+  br label %bb_end
+
+bb_end:
+  ret i32 0
+}
+
+declare void @putint(i32)
+declare void @hitsz_memset(ptr, i32, i32)
+";
+
+    #[test]
+    fn test_emit() {
+        let mut module = Module::new();
+        let mut pool_inst: ObjPool<Inst> = ObjPool::new();
+        let mut pool_bb: ObjPool<BasicBlock> = ObjPool::new();
+        let mut pool_func: ObjPool<Function> = ObjPool::new();
+        parse_ir(
+            IR,
+            &mut module,
+            &mut pool_inst,
+            &mut pool_bb,
+            &mut pool_func,
+        )
+        .unwrap();
+        let text = emit_llvm(&module);
+
+        // 部分初始化的全局数组,使用时转换为元素指针
+        assert!(text.contains(
+            "@arr = dso_local global <{ [1 x i32], [3 x i32] }> \
+             <{ [1 x i32] [i32 5], [3 x i32] zeroinitializer }>, align 4"
+        ));
+        assert!(text.contains("i32* bitcast (<{ [1 x i32], [3 x i32] }>* @arr to i32*), i32 1"));
+        // 局部数组先清零再写入初始值,浮点常量使用十六进制
+        assert!(text.contains("store [3 x float] zeroinitializer, [3 x float]* %v0.arr"));
+        assert!(text.contains("store float 0x3FB99999A0000000, float* %v0.0"));
+        // 比较的结果作为i32使用,作为分支条件时直接使用i1
+        assert!(text.contains("%v4 = zext i1 %v4.c to i32"));
+        assert!(text.contains("call void @putint(i32 %v4)"));
+        assert!(text.contains("br i1 %v4.c, label %bb2_then, label %bb1_end"));
+        // 实参的指针类型与形参不同时需要转换
+        assert!(text.contains("%t1 = bitcast float* %v0 to i32*"));
+        assert!(text.contains("define internal void @hitsz_memset(i32* %array"));
+        assert!(!text.contains("init array"));
+    }
+
+    #[test]
+    fn test_emit_parsed_interface() {
+        // 读入的IR中已经声明并调用了接口函数,添加接口后每个函数只生成一次
+        let (mut module, pools) = parse_module(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  call void @hitsz_thread_init()
+  %a = alloca [4 x i32], align 4
+  call void @hitsz_memset(i32* noundef %a, i32 noundef 0, i32 noundef 16)
+  ret i32 0
+}
+
+declare void @hitsz_thread_init()
+declare void @hitsz_memset(ptr, i32, i32)
+",
+        );
+        add_interface(&mut module, pools.func, pools.inst, true, true);
+        let text = emit_llvm(&module);
+        for decl in [
+            "declare void @hitsz_thread_init()",
+            "declare i32 @hitsz_thread_create()",
+            "define internal void @hitsz_memset(",
+            "define internal void @hitsz_memcopy(",
+        ] {
+            assert_eq!(text.matches(decl).count(), 1, "{}", decl);
+        }
+        assert_eq!(text.matches("call void @hitsz_thread_init()").count(), 1);
+    }
+}
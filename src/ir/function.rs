@@ -81,6 +81,15 @@ impl Function {
         &self.index
     }
 
+    /// 获得参数的名字
+    /// 默认参数存在
+    pub fn get_parameter_name(&self, parameter: ObjPtr<Inst>) -> &str {
+        match self.parameters.iter().find(|(_, p)| **p == parameter) {
+            Some((name, _)) => name.as_str(),
+            None => panic!("尝试获得不存在的参数"),
+        }
+    }
+
     /// 获得参数
     /// 默认参数存在
     pub fn get_parameter(&self, name: &String) -> ObjPtr<Inst> {
@@ -103,6 +103,89 @@ impl ObjPool<Inst> {
         inst
     }
 
+    /// 创建无符号除法指令
+    /// # Arguments
+    /// * `lhs` - 左操作数
+    /// * `rhs` - 右操作数
+    pub fn make_udiv(&mut self, lhs: ObjPtr<Inst>, rhs: ObjPtr<Inst>) -> ObjPtr<Inst> {
+        self.make_int_binary(BinOp::UDiv, lhs, rhs)
+    }
+
+    /// 创建无符号求余指令
+    /// # Arguments
+    /// * `lhs` - 左操作数
+    /// * `rhs` - 右操作数
+    pub fn make_urem(&mut self, lhs: ObjPtr<Inst>, rhs: ObjPtr<Inst>) -> ObjPtr<Inst> {
+        self.make_int_binary(BinOp::URem, lhs, rhs)
+    }
+
+    /// 创建按位与指令
+    /// # Arguments
+    /// * `lhs` - 左操作数
+    /// * `rhs` - 右操作数
+    pub fn make_and(&mut self, lhs: ObjPtr<Inst>, rhs: ObjPtr<Inst>) -> ObjPtr<Inst> {
+        self.make_int_binary(BinOp::And, lhs, rhs)
+    }
+
+    /// 创建按位或指令
+    /// # Arguments
+    /// * `lhs` - 左操作数
+    /// * `rhs` - 右操作数
+    pub fn make_or(&mut self, lhs: ObjPtr<Inst>, rhs: ObjPtr<Inst>) -> ObjPtr<Inst> {
+        self.make_int_binary(BinOp::Or, lhs, rhs)
+    }
+
+    /// 创建按位异或指令
+    /// # Arguments
+    /// * `lhs` - 左操作数
+    /// * `rhs` - 右操作数
+    pub fn make_xor(&mut self, lhs: ObjPtr<Inst>, rhs: ObjPtr<Inst>) -> ObjPtr<Inst> {
+        self.make_int_binary(BinOp::Xor, lhs, rhs)
+    }
+
+    /// 创建左移指令
+    /// # Arguments
+    /// * `lhs` - 被移位的值
+    /// * `rhs` - 移位量,只取低5位
+    pub fn make_shl(&mut self, lhs: ObjPtr<Inst>, rhs: ObjPtr<Inst>) -> ObjPtr<Inst> {
+        self.make_int_binary(BinOp::Shl, lhs, rhs)
+    }
+
+    /// 创建逻辑右移指令
+    /// # Arguments
+    /// * `lhs` - 被移位的值
+    /// * `rhs` - 移位量,只取低5位
+    pub fn make_lshr(&mut self, lhs: ObjPtr<Inst>, rhs: ObjPtr<Inst>) -> ObjPtr<Inst> {
+        self.make_int_binary(BinOp::LShr, lhs, rhs)
+    }
+
+    /// 创建算术右移指令
+    /// # Arguments
+    /// * `lhs` - 被移位的值
+    /// * `rhs` - 移位量,只取低5位
+    pub fn make_ashr(&mut self, lhs: ObjPtr<Inst>, rhs: ObjPtr<Inst>) -> ObjPtr<Inst> {
+        self.make_int_binary(BinOp::AShr, lhs, rhs)
+    }
+
+    /// 创建只用于整数的二元运算指令
+    fn make_int_binary(
+        &mut self,
+        op: BinOp,
+        mut lhs: ObjPtr<Inst>,
+        mut rhs: ObjPtr<Inst>,
+    ) -> ObjPtr<Inst> {
+        // 正确性检查
+        debug_assert_eq!(lhs.get_ir_type(), IrType::Int);
+        debug_assert_eq!(rhs.get_ir_type(), IrType::Int);
+
+        let inst = self.put(Inst::new(IrType::Int, InstKind::Binary(op), vec![lhs, rhs]));
+
+        // 设置use list
+        lhs.add_user(inst.as_ref());
+        rhs.add_user(inst.as_ref());
+        inst
+    }
+
     /// 创建小于等于指令
     /// # Arguments
     /// * `lhs` - 左操作数
@@ -72,6 +72,14 @@ impl Inst {
         }
     }
 
+    /// 修改函数调用指令的被调用函数
+    /// # Arguments
+    /// * `callee` - 新的被调用函数,参数和返回值类型需要与原函数相同
+    pub fn set_callee(&mut self, callee: String) {
+        debug_assert!(matches!(self.kind, InstKind::Call(_)), "Inst::set_callee");
+        self.kind = InstKind::Call(callee);
+    }
+
     /// 获得函数调用指令的参数列表
     pub fn get_args(&self) -> &Vec<ObjPtr<Inst>> {
         // 正确性检查
@@ -18,6 +18,7 @@ mod ireturn;
 mod load;
 mod parameter;
 mod phi;
+mod select;
 mod store;
 mod unary;
 
@@ -44,6 +45,9 @@ pub enum InstKind {
     Binary(BinOp),
     Unary(UnOp),
 
+    // 条件选择
+    Select,
+
     // 跳转
     Branch,
 
@@ -89,6 +93,7 @@ impl Debug for InstKind {
             InstKind::Store => s = format!("Store"),
             InstKind::Binary(bop) => s = format!("Binary({:?})", bop),
             InstKind::Unary(uop) => s = format!("Unary({:?})", uop),
+            InstKind::Select => s = format!("Select"),
             InstKind::Branch => s = format!("Branch"),
             InstKind::Call(name) => s = format!("Call({})", name),
             InstKind::Parameter => s = format!("Parameter"),
@@ -125,6 +130,7 @@ impl PartialEq for InstKind {
             (Self::Store, Self::Store) => true,
             (Self::Binary(bop1), Self::Binary(bop2)) => bop1 == bop2,
             (Self::Unary(uop1), Self::Unary(uop2)) => uop1 == uop2,
+            (Self::Select, Self::Select) => true,
             (Self::Branch, Self::Branch) => true,
             (Self::Call(_), Self::Call(_)) => true,
             (Self::Parameter, Self::Parameter) => true,
@@ -144,13 +150,25 @@ impl PartialEq for InstKind {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum BinOp {
     Add,
     Sub,
     Mul,
     Div,
     Rem,
+    /// 无符号除法和求余
+    UDiv,
+    URem,
+    And,
+    Or,
+    Xor,
+    /// 左移
+    Shl,
+    /// 逻辑右移
+    LShr,
+    /// 算术右移
+    AShr,
     Eq,
     Ne,
     Le,
@@ -159,7 +177,7 @@ pub enum BinOp {
     Gt,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum UnOp {
     Pos,
     Neg,
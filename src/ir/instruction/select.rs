@@ -0,0 +1,84 @@
+use super::*;
+
+///! 此文件为select指令的实现
+
+impl ObjPool<Inst> {
+    /// 创建select指令, cond不为0时取true_value, 否则取false_value
+    /// 目前只支持int类型的select
+    /// # Arguments
+    /// * `cond` - 条件, 必须为int类型
+    /// * `true_value` - 条件成立时的值
+    /// * `false_value` - 条件不成立时的值
+    pub fn make_select(
+        &mut self,
+        mut cond: ObjPtr<Inst>,
+        mut true_value: ObjPtr<Inst>,
+        mut false_value: ObjPtr<Inst>,
+    ) -> ObjPtr<Inst> {
+        // 正确性检查
+        if cond.get_ir_type() != IrType::Int
+            || true_value.get_ir_type() != IrType::Int
+            || false_value.get_ir_type() != IrType::Int
+        {
+            unreachable!("Inst::make_select")
+        }
+
+        let operands = vec![cond, true_value, false_value];
+        let inst = self.put(Inst::new(IrType::Int, InstKind::Select, operands));
+
+        // 设置use list
+        cond.add_user(inst.as_ref());
+        true_value.add_user(inst.as_ref());
+        false_value.add_user(inst.as_ref());
+
+        inst
+    }
+}
+
+impl Inst {
+    /// 获得select指令的条件
+    pub fn get_select_cond(&self) -> ObjPtr<Inst> {
+        // 正确性检查
+        self.self_check_select();
+
+        self.user.get_operand(0)
+    }
+
+    /// 获得条件成立时的值
+    pub fn get_true_value(&self) -> ObjPtr<Inst> {
+        // 正确性检查
+        self.self_check_select();
+
+        self.user.get_operand(1)
+    }
+
+    /// 获得条件不成立时的值
+    pub fn get_false_value(&self) -> ObjPtr<Inst> {
+        // 正确性检查
+        self.self_check_select();
+
+        self.user.get_operand(2)
+    }
+
+    /// 设置select指令的条件
+    /// # Arguments
+    /// * `cond` - 新的条件
+    pub fn set_select_cond(&mut self, cond: ObjPtr<Inst>) {
+        // 正确性检查
+        self.self_check_select();
+
+        // 设置use list
+        self.user.get_operand(0).as_mut().remove_user(self);
+        cond.as_mut().add_user(self);
+
+        self.user.set_operand(0, cond);
+    }
+
+    fn self_check_select(&self) {
+        if let InstKind::Select = self.kind {
+            debug_assert_eq!(self.user.get_operands().len(), 3);
+        } else {
+            unreachable!("Inst::self_check_select")
+        }
+    }
+}
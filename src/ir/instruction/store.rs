@@ -95,9 +95,9 @@ impl Inst {
         // 正确性检查
         self.self_check_store();
         match dest.get_ir_type() {
-            IrType::IntPtr => {}
-            IrType::Int => match dest.get_kind() {
-                InstKind::GlobalInt(_) => {}
+            IrType::IntPtr | IrType::FloatPtr => {}
+            IrType::Int | IrType::Float => match dest.get_kind() {
+                InstKind::GlobalInt(_) | InstKind::GlobalFloat(_) => {}
                 _ => unreachable!("Inst::set_dest: dest must be a pointer"),
             },
             _ => unreachable!("Inst::set_dest: dest must be a pointer"),
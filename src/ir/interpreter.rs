@@ -0,0 +1,839 @@
+//! IR解释器
+//!
+//! 直接解释执行Module中的main函数,SysY运行时库的函数用给定的输入和收集的输出模拟,
+//! 用于在没有RISC-V环境时比较优化前后程序的行为。
+//! 调用栈是显式的,深度递归不会耗尽解释器自身的栈。
+//! 自动并行化插入的hitsz_thread_create/hitsz_thread_join按顺序模拟:
+//! 创建线程时记录子线程的现场,主线程到达join时依次运行各子线程直到其到达join。
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::utility::ObjPtr;
+
+use super::{
+    basicblock::BasicBlock,
+    function::Function,
+    instruction::{BinOp, Inst, InstKind, UnOp},
+    ir_type::IrType,
+    module::Module,
+};
+
+/// 默认最多执行的指令条数
+pub const DEFAULT_STEP_LIMIT: u64 = 1_000_000_000;
+
+/// 调用栈的最大深度
+const MAX_CALL_DEPTH: usize = 1 << 20;
+
+/// 程序运行的结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecResult {
+    /// main函数的返回值
+    pub ret: i32,
+    /// 程序的标准输出
+    pub output: String,
+    /// 执行的指令条数
+    pub steps: u64,
+}
+
+impl ExecResult {
+    /// 进程的退出码
+    pub fn exit_code(&self) -> i32 {
+        self.ret & 0xff
+    }
+
+    /// 评测时的输出格式: 程序输出之后另起一行写入退出码
+    pub fn judge_output(&self) -> String {
+        let mut out = self.output.clone();
+        if !out.is_empty() && !out.ends_with('\n') {
+            out.push('\n');
+        }
+        out += format!("{}\n", self.exit_code()).as_str();
+        out
+    }
+
+    /// 与期望的.out文件比较,忽略行尾空白和末尾的空行
+    pub fn matches(&self, expected: &str) -> bool {
+        let normalize = |text: &str| -> Vec<String> {
+            let mut lines: Vec<String> = text.lines().map(|l| l.trim_end().to_string()).collect();
+            while lines.last().map_or(false, |l| l.is_empty()) {
+                lines.pop();
+            }
+            lines
+        };
+        normalize(&self.judge_output()) == normalize(expected)
+    }
+}
+
+/// 程序运行中的错误
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExecError {
+    /// 执行的指令数超过了限制
+    StepLimit(u64),
+    /// 运行时错误,如除以零、越界访问
+    Trap(String),
+}
+
+impl fmt::Display for ExecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecError::StepLimit(limit) => write!(f, "step limit of {} exceeded", limit),
+            ExecError::Trap(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+/// 运行module的main函数
+pub fn interpret(module: &Module, input: &str, step_limit: u64) -> Result<ExecResult, ExecError> {
+    let mut interpreter = Interpreter::new(module, input, step_limit);
+    let main = match module.find_function("main") {
+        Some(main) if !main.is_empty_bb() => main,
+        _ => return Err(trap("no main function")),
+    };
+    let frame = interpreter.new_frame(main, vec![])?;
+    let ret = interpreter.run(vec![frame], 0)?;
+    Ok(ExecResult {
+        ret: match ret {
+            Some(ret) => ret.int()?,
+            None => 0,
+        },
+        output: interpreter.output,
+        steps: interpreter.steps,
+    })
+}
+
+pub(crate) fn trap(msg: &str) -> ExecError {
+    ExecError::Trap(msg.to_string())
+}
+
+/// 运行时的值,指针由内存段和段内偏移(以4字节为单位)表示
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Val {
+    Int(i32),
+    Float(f32),
+    Ptr(usize, i32),
+    /// 引用了不在任何基本块中的值,如遍删除了全局数组但还没有删除其死代码
+    /// 只有在被真正使用(访存、分支、输出)时才报错
+    Undef,
+}
+
+impl Val {
+    fn int(self) -> Result<i32, ExecError> {
+        match self {
+            Val::Int(value) => Ok(value),
+            Val::Undef => Err(trap("use of undefined value")),
+            _ => Err(trap("expected an int value")),
+        }
+    }
+
+    fn float(self) -> Result<f32, ExecError> {
+        match self {
+            Val::Float(value) => Ok(value),
+            Val::Undef => Err(trap("use of undefined value")),
+            _ => Err(trap("expected a float value")),
+        }
+    }
+
+    fn ptr(self) -> Result<(usize, i32), ExecError> {
+        match self {
+            Val::Ptr(seg, offset) => Ok((seg, offset)),
+            Val::Undef => Err(trap("use of undefined value")),
+            _ => Err(trap("expected a pointer")),
+        }
+    }
+
+    fn bits(self) -> Result<u32, ExecError> {
+        match self {
+            Val::Int(value) => Ok(value as u32),
+            Val::Float(value) => Ok(value.to_bits()),
+            Val::Ptr(..) => Err(trap("cannot store a pointer")),
+            Val::Undef => Err(trap("use of undefined value")),
+        }
+    }
+
+    fn is_true(self) -> Result<bool, ExecError> {
+        match self {
+            Val::Int(value) => Ok(value != 0),
+            Val::Float(value) => Ok(value != 0.0),
+            Val::Ptr(..) => Ok(true),
+            Val::Undef => Err(trap("use of undefined value")),
+        }
+    }
+}
+
+/// 一次函数调用的现场
+#[derive(Clone)]
+struct Frame {
+    values: HashMap<ObjPtr<Inst>, Val>,
+    bb: ObjPtr<BasicBlock>,
+    inst: ObjPtr<Inst>,
+    /// 调用开始时的内存段数,返回时释放之后申请的段
+    mark: usize,
+}
+
+struct Interpreter<'a> {
+    module: &'a Module,
+    memory: Vec<Vec<u32>>,
+    globals: HashMap<ObjPtr<Inst>, Val>,
+    input: ProgramInput<'a>,
+    output: String,
+    steps: u64,
+    step_limit: u64,
+    /// 已创建但还没有运行的子线程
+    threads: Vec<Frame>,
+    thread_num: i32,
+}
+
+impl<'a> Interpreter<'a> {
+    fn new(module: &'a Module, input: &'a str, step_limit: u64) -> Interpreter<'a> {
+        let mut interpreter = Interpreter {
+            module,
+            memory: vec![],
+            globals: HashMap::new(),
+            input: ProgramInput::new(input),
+            output: String::new(),
+            steps: 0,
+            step_limit,
+            threads: vec![],
+            thread_num: 1,
+        };
+        for (_, var) in module.get_all_var() {
+            let segment = match var.get_kind() {
+                InstKind::GlobalInt(value) | InstKind::GlobalConstInt(value) => vec![value as u32],
+                InstKind::GlobalFloat(value) | InstKind::GlobalConstFloat(value) => {
+                    vec![value.to_bits()]
+                }
+                InstKind::Alloca(len) => array_segment(var, len),
+                _ => unreachable!("invalid global variable {:?}", var.get_kind()),
+            };
+            let ptr = interpreter.alloc(segment);
+            interpreter.globals.insert(var, ptr);
+        }
+        interpreter
+    }
+
+    fn alloc(&mut self, segment: Vec<u32>) -> Val {
+        self.memory.push(segment);
+        Val::Ptr(self.memory.len() - 1, 0)
+    }
+
+    fn new_frame(&mut self, func: ObjPtr<Function>, args: Vec<Val>) -> Result<Frame, ExecError> {
+        let params = func.get_parameter_list();
+        if params.len() != args.len() {
+            return Err(trap("wrong number of arguments"));
+        }
+        let head = func.get_head();
+        Ok(Frame {
+            values: params.iter().cloned().zip(args).collect(),
+            bb: head,
+            inst: head.get_head_inst(),
+            mark: self.memory.len(),
+        })
+    }
+
+    /// 运行一个线程的调用栈
+    /// 返回栈底函数的返回值,子线程到达join时返回None
+    fn run(&mut self, mut stack: Vec<Frame>, thread: i32) -> Result<Option<Val>, ExecError> {
+        loop {
+            let frame = stack.last_mut().unwrap();
+            let inst = frame.inst;
+            if inst.is_tail() {
+                return Err(trap("block without terminator"));
+            }
+            self.steps += 1;
+            if self.steps > self.step_limit {
+                return Err(ExecError::StepLimit(self.step_limit));
+            }
+
+            match inst.get_kind() {
+                InstKind::Branch => {
+                    let next = frame.bb.get_next_bb();
+                    let target = if inst.is_br_jmp()
+                        || !self.operand(frame, inst.get_br_cond())?.is_true()?
+                    {
+                        next[0]
+                    } else {
+                        next[1]
+                    };
+                    self.enter(frame, target)?;
+                    continue;
+                }
+                InstKind::Return => {
+                    let ret = match inst.get_ir_type() {
+                        IrType::Void => None,
+                        _ => Some(self.operand(frame, inst.get_return_value())?),
+                    };
+                    let frame = stack.pop().unwrap();
+                    self.memory.truncate(frame.mark);
+                    match stack.last_mut() {
+                        Some(caller) => {
+                            if let Some(ret) = ret {
+                                caller.values.insert(caller.inst, ret);
+                            }
+                            caller.inst = caller.inst.get_next();
+                        }
+                        None => return Ok(ret),
+                    }
+                    continue;
+                }
+                InstKind::Call(callee) => {
+                    let args = inst
+                        .get_operands()
+                        .iter()
+                        .map(|arg| self.operand(frame, *arg))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    match self.module.find_function(callee.as_str()) {
+                        Some(func) if !func.is_empty_bb() => {
+                            if stack.len() >= MAX_CALL_DEPTH {
+                                return Err(trap("call stack overflow"));
+                            }
+                            let callee = self.new_frame(func, args)?;
+                            stack.push(callee);
+                            continue;
+                        }
+                        _ => {}
+                    }
+                    match callee.as_str() {
+                        "hitsz_thread_create" => {
+                            // 子线程从调用之后继续执行,得到自己的编号
+                            if thread == 0 {
+                                let mut child = frame.clone();
+                                child.values.insert(inst, Val::Int(self.thread_num));
+                                child.inst = inst.get_next();
+                                self.threads.push(child);
+                                self.thread_num += 1;
+                            }
+                            frame.values.insert(inst, Val::Int(0));
+                        }
+                        "hitsz_thread_join" => {
+                            if thread != 0 {
+                                return Ok(None);
+                            }
+                            let threads = std::mem::take(&mut self.threads);
+                            for (i, child) in threads.into_iter().enumerate() {
+                                self.run(vec![child], i as i32 + 1)?;
+                            }
+                            self.thread_num = 1;
+                        }
+                        "hitsz_get_thread_num" => {
+                            frame.values.insert(inst, Val::Int(thread));
+                        }
+                        _ => {
+                            if let Some(ret) = self.builtin(callee.as_str(), &args)? {
+                                frame.values.insert(inst, ret);
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    let value = self.exec(frame, inst)?;
+                    if let Some(value) = value {
+                        frame.values.insert(inst, value);
+                    }
+                }
+            }
+            let frame = stack.last_mut().unwrap();
+            frame.inst = frame.inst.get_next();
+        }
+    }
+
+    /// 跳转到target,同时计算target中的phi
+    fn enter(&self, frame: &mut Frame, target: ObjPtr<BasicBlock>) -> Result<(), ExecError> {
+        let index = target
+            .get_up_bb()
+            .iter()
+            .position(|bb| *bb == frame.bb)
+            .ok_or_else(|| trap("branch to a block that does not list it as predecessor"))?;
+        // phi的值需要同时计算
+        let mut phis = vec![];
+        let mut inst = target.get_head_inst();
+        while !inst.is_tail() && inst.is_phi() {
+            phis.push((inst, self.operand(frame, inst.get_operand(index))?));
+            inst = inst.get_next();
+        }
+        frame.values.extend(phis);
+        frame.bb = target;
+        frame.inst = inst;
+        Ok(())
+    }
+
+    /// 执行除控制流和调用外的指令,返回其定义的值
+    fn exec(&mut self, frame: &Frame, inst: ObjPtr<Inst>) -> Result<Option<Val>, ExecError> {
+        let value = match inst.get_kind() {
+            InstKind::Alloca(len) => self.alloc(array_segment(inst, len)),
+            InstKind::Gep => match (
+                self.operand(frame, inst.get_gep_ptr())?,
+                self.operand(frame, inst.get_gep_offset())?,
+            ) {
+                (Val::Undef, _) | (_, Val::Undef) => Val::Undef,
+                (base, index) => {
+                    let (seg, offset) = base.ptr()?;
+                    Val::Ptr(seg, offset.wrapping_add(index.int()?))
+                }
+            },
+            InstKind::Load => {
+                let ptr = self.address(frame, inst.get_ptr())?;
+                match inst.get_ir_type() {
+                    // 全局数组的load得到数组本身
+                    IrType::IntPtr | IrType::FloatPtr => ptr,
+                    IrType::Float => Val::Float(f32::from_bits(self.read(ptr)?)),
+                    _ => Val::Int(self.read(ptr)? as i32),
+                }
+            }
+            InstKind::Store => {
+                let value = self.operand(frame, inst.get_value())?.bits()?;
+                let ptr = self.address(frame, inst.get_dest())?;
+                self.write(ptr, value)?;
+                return Ok(None);
+            }
+            InstKind::Binary(op) => {
+                let lhs = self.operand(frame, inst.get_lhs())?;
+                let rhs = self.operand(frame, inst.get_rhs())?;
+                binary(op, lhs, rhs)?
+            }
+            InstKind::Unary(op) => match (op, self.operand(frame, inst.get_unary_operand())?) {
+                (UnOp::Pos, value) => value,
+                (UnOp::Neg, Val::Int(value)) => Val::Int(value.wrapping_neg()),
+                (UnOp::Neg, Val::Float(value)) => Val::Float(-value),
+                (UnOp::Not, Val::Int(value)) => Val::Int((value == 0) as i32),
+                (UnOp::Not, Val::Float(value)) => Val::Float((value == 0.0) as i32 as f32),
+                (_, Val::Undef) => Val::Undef,
+                _ => return Err(trap("invalid operand of unary operator")),
+            },
+            InstKind::Select => match self.operand(frame, inst.get_select_cond())? {
+                Val::Undef => Val::Undef,
+                cond if cond.is_true()? => self.operand(frame, inst.get_true_value())?,
+                _ => self.operand(frame, inst.get_false_value())?,
+            },
+            InstKind::FtoI => match self.operand(frame, inst.get_float_to_int_value())? {
+                Val::Undef => Val::Undef,
+                value => Val::Int(value.float()? as i32),
+            },
+            InstKind::ItoF => match self.operand(frame, inst.get_int_to_float_value())? {
+                Val::Undef => Val::Undef,
+                value => Val::Float(value.int()? as f32),
+            },
+            InstKind::ConstInt(_) | InstKind::ConstFloat(_) => return Ok(None),
+            kind => unreachable!("unexpected {:?} in interpreter", kind),
+        };
+        Ok(Some(value))
+    }
+
+    /// 获得作为操作数的值
+    fn operand(&self, frame: &Frame, inst: ObjPtr<Inst>) -> Result<Val, ExecError> {
+        match inst.get_kind() {
+            InstKind::ConstInt(value) | InstKind::GlobalConstInt(value) => Ok(Val::Int(value)),
+            InstKind::ConstFloat(value) | InstKind::GlobalConstFloat(value) => {
+                Ok(Val::Float(value))
+            }
+            _ => self.address(frame, inst),
+        }
+    }
+
+    /// 获得作为load/store地址的值,全局变量使用其地址
+    fn address(&self, frame: &Frame, inst: ObjPtr<Inst>) -> Result<Val, ExecError> {
+        if let Some(value) = self.globals.get(&inst) {
+            Ok(*value)
+        } else if let Some(value) = frame.values.get(&inst) {
+            Ok(*value)
+        } else {
+            Ok(Val::Undef)
+        }
+    }
+
+    fn read(&self, ptr: Val) -> Result<u32, ExecError> {
+        let (seg, offset) = ptr.ptr()?;
+        self.memory
+            .get(seg)
+            .and_then(|segment| segment.get(offset as usize))
+            .copied()
+            .ok_or_else(|| trap("out-of-bounds memory access"))
+    }
+
+    fn write(&mut self, ptr: Val, value: u32) -> Result<(), ExecError> {
+        let (seg, offset) = ptr.ptr()?;
+        let word = self
+            .memory
+            .get_mut(seg)
+            .and_then(|segment| segment.get_mut(offset as usize))
+            .ok_or_else(|| trap("out-of-bounds memory access"))?;
+        *word = value;
+        Ok(())
+    }
+
+    /// 从ptr开始的n个字
+    fn words(&self, ptr: Val, n: i32) -> Result<Vec<u32>, ExecError> {
+        let (seg, offset) = ptr.ptr()?;
+        (0..n)
+            .map(|i| self.read(Val::Ptr(seg, offset + i)))
+            .collect()
+    }
+
+    /// 运行时库函数
+    fn builtin(&mut self, name: &str, args: &[Val]) -> Result<Option<Val>, ExecError> {
+        let arg = |i: usize| {
+            args.get(i)
+                .copied()
+                .ok_or_else(|| trap("missing argument of runtime function"))
+        };
+        let ret = match name {
+            "getint" => Some(Val::Int(self.input.read_int()?)),
+            "getch" => Some(Val::Int(self.input.read_char())),
+            "getfloat" => Some(Val::Float(self.input.read_float()?)),
+            "getarray" | "getfarray" => {
+                let n = self.input.read_int()?;
+                let (seg, offset) = arg(0)?.ptr()?;
+                for i in 0..n {
+                    let value = if name == "getarray" {
+                        self.input.read_int()? as u32
+                    } else {
+                        self.input.read_float()?.to_bits()
+                    };
+                    self.write(Val::Ptr(seg, offset + i), value)?;
+                }
+                Some(Val::Int(n))
+            }
+            "putint" => {
+                self.output += arg(0)?.int()?.to_string().as_str();
+                None
+            }
+            "putch" => {
+                self.output.push(arg(0)?.int()? as u8 as char);
+                None
+            }
+            "putfloat" => {
+                self.output += hex_float(arg(0)?.float()?).as_str();
+                None
+            }
+            "putarray" | "putfarray" => {
+                let n = arg(0)?.int()?;
+                self.output += format!("{}:", n).as_str();
+                for word in self.words(arg(1)?, n)? {
+                    if name == "putarray" {
+                        self.output += format!(" {}", word as i32).as_str();
+                    } else {
+                        self.output += format!(" {}", hex_float(f32::from_bits(word))).as_str();
+                    }
+                }
+                self.output.push('\n');
+                None
+            }
+            "starttime" | "stoptime" | "_sysy_starttime" | "_sysy_stoptime"
+            | "hitsz_thread_init" => None,
+            "hitsz_memset" => {
+                // 与libc的memset相同,按字节填充
+                let byte = arg(1)?.int()? as u32 & 0xff;
+                let word = byte * 0x0101_0101;
+                let (seg, offset) = arg(0)?.ptr()?;
+                for i in 0..arg(2)?.int()? / 4 {
+                    self.write(Val::Ptr(seg, offset + i), word)?;
+                }
+                None
+            }
+            "hitsz_memcopy" => {
+                let n = arg(2)?.int()? / 4;
+                let words = self.words(arg(1)?, n)?;
+                let (seg, offset) = arg(0)?.ptr()?;
+                for (i, word) in words.into_iter().enumerate() {
+                    self.write(Val::Ptr(seg, offset + i as i32), word)?;
+                }
+                None
+            }
+            _ => {
+                return Err(ExecError::Trap(format!(
+                    "call to unknown function `{}`",
+                    name
+                )))
+            }
+        };
+        Ok(ret)
+    }
+}
+
+/// 程序的标准输入,与SysY运行时库相同地按空白分隔读取
+pub(crate) struct ProgramInput<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ProgramInput<'a> {
+    pub(crate) fn new(input: &'a str) -> ProgramInput<'a> {
+        ProgramInput {
+            input: input.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    /// 读取一个字符,输入结束时返回-1
+    pub(crate) fn read_char(&mut self) -> i32 {
+        let ch = self.input.get(self.pos).map_or(-1, |ch| *ch as i32);
+        self.pos += 1;
+        ch
+    }
+
+    /// 读取下一个以空白分隔的词
+    fn read_token(&mut self) -> Result<String, ExecError> {
+        while self.pos < self.input.len() && self.input[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        let start = self.pos;
+        while self.pos < self.input.len() && !self.input[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(trap("unexpected end of input"));
+        }
+        Ok(String::from_utf8_lossy(&self.input[start..self.pos]).to_string())
+    }
+
+    pub(crate) fn read_int(&mut self) -> Result<i32, ExecError> {
+        let token = self.read_token()?;
+        token
+            .parse::<i64>()
+            .map(|value| value as i32)
+            .map_err(|_| ExecError::Trap(format!("invalid int input `{}`", token)))
+    }
+
+    pub(crate) fn read_float(&mut self) -> Result<f32, ExecError> {
+        let token = self.read_token()?;
+        let (negative, hex) = match token.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, token.strip_prefix('+').unwrap_or(token.as_str())),
+        };
+        let value = if hex.starts_with("0x") || hex.starts_with("0X") {
+            hexf_parse::parse_hexf32(hex, false).ok()
+        } else {
+            hex.parse::<f32>().ok()
+        };
+        match value {
+            Some(value) if negative => Ok(-value),
+            Some(value) => Ok(value),
+            None => Err(ExecError::Trap(format!("invalid float input `{}`", token))),
+        }
+    }
+}
+
+/// 数组的初始内容,未初始化的位置为0,由变量初始化的位置之后会有store
+fn array_segment(array: ObjPtr<Inst>, len: i32) -> Vec<u32> {
+    let mut segment = vec![0; len.max(0) as usize];
+    match array.get_ir_type() {
+        IrType::IntPtr => {
+            for (i, (_, value)) in array
+                .get_int_init()
+                .1
+                .iter()
+                .enumerate()
+                .take(segment.len())
+            {
+                segment[i] = *value as u32;
+            }
+        }
+        _ => {
+            for (i, (_, value)) in array
+                .get_float_init()
+                .1
+                .iter()
+                .enumerate()
+                .take(segment.len())
+            {
+                segment[i] = value.to_bits();
+            }
+        }
+    }
+    segment
+}
+
+fn binary(op: BinOp, lhs: Val, rhs: Val) -> Result<Val, ExecError> {
+    let value = match (lhs, rhs) {
+        (Val::Int(lhs), Val::Int(rhs)) => match op {
+            BinOp::Add => Val::Int(lhs.wrapping_add(rhs)),
+            BinOp::Sub => Val::Int(lhs.wrapping_sub(rhs)),
+            BinOp::Mul => Val::Int(lhs.wrapping_mul(rhs)),
+            BinOp::Div | BinOp::Rem if rhs == 0 => return Err(trap("division by zero")),
+            BinOp::Div => Val::Int(lhs.wrapping_div(rhs)),
+            BinOp::Rem => Val::Int(lhs.wrapping_rem(rhs)),
+            BinOp::UDiv | BinOp::URem if rhs == 0 => return Err(trap("division by zero")),
+            BinOp::UDiv => Val::Int((lhs as u32 / rhs as u32) as i32),
+            BinOp::URem => Val::Int((lhs as u32 % rhs as u32) as i32),
+            BinOp::And => Val::Int(lhs & rhs),
+            BinOp::Or => Val::Int(lhs | rhs),
+            BinOp::Xor => Val::Int(lhs ^ rhs),
+            // 移位量和RISC-V一样只取低5位
+            BinOp::Shl => Val::Int(lhs.wrapping_shl(rhs as u32)),
+            BinOp::LShr => Val::Int((lhs as u32).wrapping_shr(rhs as u32) as i32),
+            BinOp::AShr => Val::Int(lhs.wrapping_shr(rhs as u32)),
+            BinOp::Gt => Val::Int((lhs > rhs) as i32),
+            BinOp::Lt => Val::Int((lhs < rhs) as i32),
+            BinOp::Ge => Val::Int((lhs >= rhs) as i32),
+            BinOp::Le => Val::Int((lhs <= rhs) as i32),
+            BinOp::Eq => Val::Int((lhs == rhs) as i32),
+            BinOp::Ne => Val::Int((lhs != rhs) as i32),
+        },
+        (Val::Undef, _) | (_, Val::Undef) => Val::Undef,
+        (Val::Float(lhs), Val::Float(rhs)) => match op {
+            BinOp::Add => Val::Float(lhs + rhs),
+            BinOp::Sub => Val::Float(lhs - rhs),
+            BinOp::Mul => Val::Float(lhs * rhs),
+            BinOp::Div => Val::Float(lhs / rhs),
+            BinOp::Rem => Val::Float(lhs % rhs),
+            BinOp::UDiv
+            | BinOp::URem
+            | BinOp::And
+            | BinOp::Or
+            | BinOp::Xor
+            | BinOp::Shl
+            | BinOp::LShr
+            | BinOp::AShr => return Err(trap("integer operator on float operands")),
+            BinOp::Gt => Val::Int((lhs > rhs) as i32),
+            BinOp::Lt => Val::Int((lhs < rhs) as i32),
+            BinOp::Ge => Val::Int((lhs >= rhs) as i32),
+            BinOp::Le => Val::Int((lhs <= rhs) as i32),
+            BinOp::Eq => Val::Int((lhs == rhs) as i32),
+            BinOp::Ne => Val::Int((lhs != rhs) as i32),
+        },
+        _ => return Err(trap("operands of binary operator have different types")),
+    };
+    Ok(value)
+}
+
+/// 与C的printf("%a")相同的十六进制浮点数格式
+pub(crate) fn hex_float(value: f32) -> String {
+    let sign = if value.is_sign_negative() { "-" } else { "" };
+    if value.is_nan() {
+        return format!("{}nan", sign);
+    }
+    if value.is_infinite() {
+        return format!("{}inf", sign);
+    }
+    if value == 0.0 {
+        return format!("{}0x0p+0", sign);
+    }
+    // float转换为double后总是规格化数
+    let bits = (value as f64).to_bits();
+    let exp = ((bits >> 52) & 0x7ff) as i32 - 1023;
+    let mantissa = format!("{:013x}", bits & ((1 << 52) - 1));
+    let mantissa = mantissa.trim_end_matches('0');
+    if mantissa.is_empty() {
+        format!("{}0x1p{:+}", sign, exp)
+    } else {
+        format!("{}0x1.{}p{:+}", sign, mantissa, exp)
+    }
+}
+
+#[cfg(test)]
+mod interpreter_test {
+    use super::{hex_float, interpret, ExecError, DEFAULT_STEP_LIMIT};
+    use crate::ir::test_util::parse_module;
+
+    const IR: &str = "
+@arr = dso_local global [3 x i32] [ i32 1, i32 2, i32 3], align 4
+
+define dso_local signext i32 @fact(i32 noundef signext %n) #0 {
+bb_entry:
+  %c = icmp sle i32 %n, 1
+  br i1 %c, label %bb_base, label %bb_rec
+
+bb_base:
+  ret i32 1
+
+bb_rec:
+  %m = sub i32 %n, 1
+  %r = call i32 @fact(i32 noundef %m)
+  %p = mul i32 %n, %r
+  ret i32 %p
+}
+
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %n = call i32 @getint()
+  %a = alloca [4 x i32], align 4
+  %garr = load ptr, ptr @arr, align 8
+  %e = getelementptr inbounds [3 x i32], [3 x i32]* %garr, i32 0, i32 2
+  %x = load i32, i32* %e, align 4
+  %q = getelementptr inbounds [4 x i32], [4 x i32]* %a, i32 0, i32 1
+  store i32 %x, i32* %q, align 4
+  call void @putarray(i32 noundef 4, i32* noundef %a)
+  br label %bb_loop
+
+bb_loop:
+  %i = phi i32 [ 0, %bb_entry ], [ %i2, %bb_loop ]
+  %i2 = add i32 %i, 1
+  %d = icmp slt i32 %i2, %n
+  br i1 %d, label %bb_loop, label %bb_exit
+
+bb_exit:
+  %f = call i32 @fact(i32 noundef %i2)
+  call void @putint(i32 noundef %f)
+  %h = sitofp i32 %f to float
+  %g = fmul float %h, 0.5
+  call void @putfloat(float noundef %g)
+  %z = sdiv i32 %f, %x
+  ret i32 %z
+}
+
+declare i32 @getint()
+declare void @putint(i32)
+declare void @putfloat(float)
+declare void @putarray(i32, ptr)
+";
+
+    #[test]
+    fn test_interpret() {
+        let (module, _) = parse_module(IR);
+        let result = interpret(&module, " 5\n", DEFAULT_STEP_LIMIT).unwrap();
+        assert_eq!(result.output, "4: 0 3 0 0\n1200x1.ep+5");
+        assert_eq!(result.ret, 40);
+
+        // 超过指令数限制
+        assert_eq!(
+            interpret(&module, "100000", 1000),
+            Err(ExecError::StepLimit(1000))
+        );
+        // 缺少输入
+        assert!(interpret(&module, "", DEFAULT_STEP_LIMIT).is_err());
+
+        // 评测格式: 输出之后另起一行写入退出码
+        assert_eq!(result.judge_output(), "4: 0 3 0 0\n1200x1.ep+5\n40\n");
+        assert!(result.matches("4: 0 3 0 0  \n1200x1.ep+5\n40\n\n"));
+        assert!(!result.matches("4: 0 3 0 0\n1200x1.ep+5\n0\n"));
+    }
+
+    #[test]
+    fn test_bitwise() {
+        let (module, _) = parse_module(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %a = and i32 -1, 12
+  %o = or i32 %a, 3
+  %x = xor i32 %o, 5
+  %s = shl i32 %x, 33
+  %l = lshr i32 -16, 28
+  %r = ashr i32 -16, 2
+  %u = udiv i32 -1, %l
+  %m = urem i32 %u, 7
+  call void @putint(i32 noundef %s)
+  call void @putint(i32 noundef %l)
+  call void @putint(i32 noundef %r)
+  ret i32 %m
+}
+
+declare void @putint(i32)
+",
+        );
+        let result = interpret(&module, "", DEFAULT_STEP_LIMIT).unwrap();
+        assert_eq!(result.output, "2015-4");
+        // 0xffffffff / 15 = 0x11111111
+        assert_eq!(result.ret, 0x1111_1111 % 7);
+    }
+
+    #[test]
+    fn test_hex_float() {
+        assert_eq!(hex_float(1.0), "0x1p+0");
+        assert_eq!(hex_float(-0.1), "-0x1.99999ap-4");
+        assert_eq!(hex_float(60.0), "0x1.ep+5");
+        assert_eq!(hex_float(0.0), "0x0p+0");
+        assert_eq!(hex_float(1e-40), "0x1.16c2p-133");
+        assert_eq!(hex_float(3.4e38), "0x1.ff933cp+127");
+    }
+}
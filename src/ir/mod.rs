@@ -4,19 +4,30 @@ use crate::utility::ObjPtr;
 pub mod analysis;
 pub mod basicblock;
 mod dump_ir;
+mod emit_llvm;
 pub mod function;
 pub mod instruction;
+mod interpreter;
 pub mod ir_type;
 pub mod module;
+mod parse_ir;
+#[cfg(test)]
+pub(crate) mod test_util;
 mod tools;
 mod transform;
 pub mod user;
 pub mod value;
 
 pub use analysis::call_map::{call_map_gen, CallMap};
-pub use dump_ir::dump_now;
+pub use dump_ir::{dump_module, dump_now};
+pub use emit_llvm::emit_llvm;
+pub use interpreter::{interpret, ExecError, ExecResult, DEFAULT_STEP_LIMIT};
+pub(crate) use interpreter::{hex_float, trap, ProgramInput};
+pub use parse_ir::{parse_ir, ParseError};
 pub use transform::add_interface;
 pub use transform::optimizer_run;
+pub use transform::PASSES;
+pub use transform::{verify_module, VerifyError};
 
 /// 侵入式链表
 #[derive(Debug, Clone)]
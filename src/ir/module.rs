@@ -68,6 +68,14 @@ impl Module {
             .clone()
     }
 
+    /// 根据名字查找函数,不存在时返回None
+    pub fn find_function(&self, name: &str) -> Option<ObjPtr<Function>> {
+        self.function
+            .iter()
+            .find(|(n, _)| n.as_str() == name)
+            .map(|(_, func)| *func)
+    }
+
     /// 用于遍历所有全局变量
     pub fn get_all_var(&self) -> Vec<(&String, ObjPtr<Inst>)> {
         self.global_variable
@@ -0,0 +1,1058 @@
+//! 读取dump_ir生成的文本形式的IR,重新构造Module
+//!
+//! 文本格式与dump_module的输出一致,额外约定:
+//! * 基本块按支配顺序排列,除phi外,值必须先定义后使用
+//! * `%xxx_add = icmp ne`形式的指令是分支和select条件的包装,直接引用其操作数
+//! * 一元运算通过注释`;pos`、`;neg`、`;not`区分
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::utility::{ObjPool, ObjPtr};
+
+use super::{
+    basicblock::BasicBlock, function::Function, instruction::Inst, instruction::InstKind,
+    ir_type::IrType, module::Module,
+};
+
+/// 文本IR的解析错误
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    /// 出错的行号,从1开始
+    pub line: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+}
+
+type ParseResult<T> = Result<T, ParseError>;
+
+/// 解析文本形式的IR,将其中的全局变量和函数加入module
+pub fn parse_ir(
+    text: &str,
+    module: &mut Module,
+    pool_inst: &mut ObjPool<Inst>,
+    pool_bb: &mut ObjPool<BasicBlock>,
+    pool_func: &mut ObjPool<Function>,
+) -> Result<(), ParseError> {
+    let mut parser = Parser {
+        module,
+        pool_inst,
+        pool_bb,
+        pool_func,
+        line: 0,
+        globals: HashMap::new(),
+        declares: HashMap::new(),
+        callees: Vec::new(),
+        func: None,
+    };
+    for (index, line) in text.lines().enumerate() {
+        parser.line = index + 1;
+        parser.parse_line(line)?;
+    }
+    parser.finish()
+}
+
+/// phi指令及其(值,前继块)列表和所在的行
+type PendingPhi = (ObjPtr<Inst>, Vec<(String, String)>, usize);
+
+/// 数组及其初始化段中的(是否由变量初始化,值)
+type ArrayInit = (ObjPtr<Inst>, Vec<(bool, String)>);
+
+/// 正在解析的函数
+struct FuncState {
+    name: String,
+    func: ObjPtr<Function>,
+    locals: HashMap<String, ObjPtr<Inst>>,
+    blocks: HashMap<String, ObjPtr<BasicBlock>>,
+    /// 按出现顺序排列的已定义的块
+    defined: Vec<ObjPtr<BasicBlock>>,
+    current: Option<ObjPtr<BasicBlock>>,
+    /// 函数结束后再填入操作数的phi
+    phis: Vec<PendingPhi>,
+    /// 正在解析的数组初始化段
+    init: Option<ArrayInit>,
+}
+
+struct Parser<'a> {
+    module: &'a mut Module,
+    pool_inst: &'a mut ObjPool<Inst>,
+    pool_bb: &'a mut ObjPool<BasicBlock>,
+    pool_func: &'a mut ObjPool<Function>,
+    line: usize,
+    globals: HashMap<String, ObjPtr<Inst>>,
+    /// declare声明的外部函数: 返回类型和参数类型
+    declares: HashMap<String, (IrType, Vec<IrType>)>,
+    /// 被调用的函数及调用所在的行
+    callees: Vec<(String, usize)>,
+    func: Option<FuncState>,
+}
+
+impl Parser<'_> {
+    fn error<T>(&self, msg: impl Into<String>) -> ParseResult<T> {
+        Err(ParseError {
+            line: self.line,
+            msg: msg.into(),
+        })
+    }
+
+    fn parse_line(&mut self, line: &str) -> ParseResult<()> {
+        let (code, comment) = match line.find(';') {
+            Some(index) => (line[..index].trim(), line[index + 1..].trim()),
+            None => (line.trim(), ""),
+        };
+
+        if code.is_empty() {
+            return match comment {
+                "init array begin!!!!" => self.init_begin(),
+                "init array end!!!!" => self.init_end(),
+                _ => Ok(()),
+            };
+        }
+
+        if self.func.is_none() {
+            if code.starts_with("declare ") {
+                self.parse_declare(code)
+            } else if code.starts_with('@') {
+                self.parse_global(code)
+            } else if code.starts_with("define ") {
+                self.parse_define(code)
+            } else {
+                self.error(format!("unexpected `{}` outside of function", code))
+            }
+        } else if code == "}" {
+            self.finish_function()
+        } else if let Some(label) = code.strip_suffix(':') {
+            self.parse_label(label)
+        } else if self.func.as_ref().unwrap().init.is_some() {
+            self.parse_init_store(code, comment)
+        } else {
+            self.parse_inst(code, comment)
+        }
+    }
+
+    fn finish(mut self) -> ParseResult<()> {
+        if let Some(state) = &self.func {
+            let msg = format!("unexpected end of input in function `{}`", state.name);
+            return self.error(msg);
+        }
+
+        // 只为被调用到的外部函数建立声明
+        for (name, line) in std::mem::take(&mut self.callees) {
+            if self.module.find_function(name.as_str()).is_some() {
+                continue;
+            }
+            self.line = line;
+            let (return_type, params) = match self.declares.get(&name) {
+                Some(decl) => decl.clone(),
+                None => return self.error(format!("call to undeclared function `@{}`", name)),
+            };
+            let mut func = self.pool_func.new_function();
+            func.set_return_type(return_type);
+            for (index, ty) in params.into_iter().enumerate() {
+                let param = self.pool_inst.make_param(ty);
+                func.set_parameter(((b'a' + index as u8) as char).to_string(), param);
+            }
+            self.module.push_function(name, func);
+        }
+        Ok(())
+    }
+
+    /// declare void @putarray(i32, ptr)
+    fn parse_declare(&mut self, code: &str) -> ParseResult<()> {
+        let (head, params) = self.split_call(code)?;
+        let (return_type, name) = self.split_callee(&head["declare".len()..])?;
+        let mut types = Vec::new();
+        for param in params.split(',').map(|p| p.trim()) {
+            match param {
+                "" | "i8*" | "..." => {}
+                "i32" => types.push(IrType::Int),
+                "float" => types.push(IrType::Float),
+                "ptr" | "i32*" => types.push(IrType::IntPtr),
+                "float*" => types.push(IrType::FloatPtr),
+                _ => return self.error(format!("unknown parameter type `{}`", param)),
+            }
+        }
+        self.declares.insert(name, (return_type, types));
+        Ok(())
+    }
+
+    /// @a = dso_local global i32 1, align 4
+    fn parse_global(&mut self, code: &str) -> ParseResult<()> {
+        let (name, rest) = match code[1..].split_once(" = ") {
+            Some(pair) => pair,
+            None => return self.error("expected `=` in global definition"),
+        };
+        let rest = rest.trim_start_matches("dso_local ");
+        let rest = rest.strip_suffix(", align 4").unwrap_or(rest);
+        let (is_const, rest) = if let Some(rest) = rest.strip_prefix("constant ") {
+            (true, rest)
+        } else if let Some(rest) = rest.strip_prefix("global ") {
+            (false, rest)
+        } else {
+            return self.error("expected `global` or `constant`");
+        };
+
+        let rest = rest.trim_start();
+        let inst = if let Some(value) = rest.strip_prefix("i32 ") {
+            let value = self.parse_int(value)?;
+            if is_const {
+                self.pool_inst.make_global_int_const(value)
+            } else {
+                self.pool_inst.make_global_int(value)
+            }
+        } else if let Some(value) = rest.strip_prefix("float ") {
+            let value = self.parse_float(value)?;
+            if is_const {
+                self.pool_inst.make_global_float_const(value)
+            } else {
+                self.pool_inst.make_global_float(value)
+            }
+        } else {
+            self.parse_global_array(rest)?
+        };
+
+        if self.globals.insert(name.to_string(), inst).is_some() {
+            return self.error(format!("redefinition of `@{}`", name));
+        }
+        self.module.push_var(name.to_string(), inst);
+        Ok(())
+    }
+
+    /// [4 x i32] [ i32 1, i32 2, i32 3, i32 4]
+    /// <{ i32, [3 x i32] }> <{ i32 1, [3 x i32] zeroinitializer}>
+    fn parse_global_array(&mut self, rest: &str) -> ParseResult<ObjPtr<Inst>> {
+        let (elem_type, length, values) = if rest.starts_with('[') {
+            let (array_type, init) = match rest.split_once("] [") {
+                Some(pair) => pair,
+                None => return self.error(format!("malformed global array `{}`", rest)),
+            };
+            let (length, elem_type) = self.parse_array_type(&array_type[1..])?;
+            let init = init.strip_suffix(']').unwrap_or(init);
+            let values: Vec<&str> = init
+                .split(',')
+                .map(|v| v.trim())
+                .filter(|v| !v.is_empty())
+                .collect();
+            (elem_type, length, values)
+        } else if rest.starts_with("<{") {
+            let init = match rest.split_once("}> <{") {
+                Some((_, init)) => init.strip_suffix("}>").unwrap_or(init),
+                None => return self.error(format!("malformed global array `{}`", rest)),
+            };
+            let mut values = Vec::new();
+            let mut elem_type = None;
+            let mut length = 0;
+            for value in init.split(',').map(|v| v.trim()) {
+                if let Some(zeros) = value.strip_suffix(" zeroinitializer") {
+                    let zeros = zeros.trim_start_matches('[').trim_end_matches(']');
+                    let (len, ty) = self.parse_array_type(zeros)?;
+                    length += len;
+                    elem_type = Some(ty);
+                } else {
+                    values.push(value);
+                    length += 1;
+                }
+            }
+            match elem_type {
+                Some(ty) => (ty, length, values),
+                None => return self.error("expected zeroinitializer in packed global array"),
+            }
+        } else {
+            return self.error(format!("unknown global type `{}`", rest));
+        };
+
+        let inst = match elem_type {
+            IrType::Int => {
+                let mut init = Vec::new();
+                for value in values {
+                    let value = value.trim_start_matches("i32").trim();
+                    init.push((false, self.parse_int(value)?));
+                }
+                self.pool_inst.make_int_array(length, true, init)
+            }
+            _ => {
+                let mut init = Vec::new();
+                for value in values {
+                    let value = value.trim_start_matches("float").trim();
+                    init.push((false, self.parse_float(value)?));
+                }
+                self.pool_inst.make_float_array(length, true, init)
+            }
+        };
+        Ok(inst)
+    }
+
+    /// define dso_local signext i32 @f(i32 noundef signext %val_0, i32* noundef %val_1) #0 {
+    fn parse_define(&mut self, code: &str) -> ParseResult<()> {
+        let (head, params) = self.split_call(code)?;
+        let head = head["define".len()..].trim_start();
+        let head = head.strip_prefix("dso_local").unwrap_or(head);
+        let (return_type, name) = self.split_callee(head)?;
+        if self.module.find_function(name.as_str()).is_some() {
+            return self.error(format!("redefinition of function `@{}`", name));
+        }
+
+        let mut func = self.pool_func.new_function();
+        func.set_return_type(return_type);
+        let mut locals = HashMap::new();
+        for param in params
+            .split(',')
+            .map(|p| p.trim())
+            .filter(|p| !p.is_empty())
+        {
+            let tokens: Vec<&str> = param.split_whitespace().collect();
+            let ty = self.parse_type(tokens[0])?;
+            let param_name = tokens[tokens.len() - 1];
+            if !param_name.starts_with('%') {
+                return self.error(format!("expected parameter name, found `{}`", param_name));
+            }
+            let inst = self.pool_inst.make_param(ty);
+            func.set_parameter(param_name[1..].to_string(), inst);
+            locals.insert(param_name.to_string(), inst);
+        }
+
+        self.func = Some(FuncState {
+            name,
+            func,
+            locals,
+            blocks: HashMap::new(),
+            defined: Vec::new(),
+            current: None,
+            phis: Vec::new(),
+            init: None,
+        });
+        Ok(())
+    }
+
+    /// bb_xxx:
+    fn parse_label(&mut self, label: &str) -> ParseResult<()> {
+        let bb = self.block(label);
+        let state = self.func.as_mut().unwrap();
+        if state.defined.contains(&bb) {
+            return self.error(format!("redefinition of label `{}`", label));
+        }
+        state.defined.push(bb);
+        state.current = Some(bb);
+        Ok(())
+    }
+
+    /// 函数结束,连接phi的操作数并设置入口块
+    fn finish_function(&mut self) -> ParseResult<()> {
+        let state = self.func.as_ref().unwrap();
+        if state.init.is_some() {
+            return self.error("unterminated array initializer");
+        }
+        if state.defined.is_empty() {
+            return self.error(format!("function `{}` has no basic block", state.name));
+        }
+        for (label, bb) in state.blocks.iter() {
+            if !state.defined.contains(bb) {
+                return self.error(format!("use of undefined label `%{}`", label));
+            }
+        }
+
+        // 按phi中前继块的顺序排列up_bb,同一个块中的phi必须一致
+        let phis = state.phis.clone();
+        let mut ordered: HashMap<ObjPtr<BasicBlock>, Vec<String>> = HashMap::new();
+        for (phi, incoming, line) in phis.iter() {
+            self.line = *line;
+            let mut bb = phi.get_parent_bb();
+            let preds: Vec<String> = incoming.iter().map(|(_, pred)| pred.clone()).collect();
+            if let Some(order) = ordered.get(&bb) {
+                if order != &preds {
+                    return self.error("phi predecessors differ from an earlier phi in the block");
+                }
+                continue;
+            }
+            let mut up_bb = Vec::new();
+            for pred in preds.iter() {
+                match self
+                    .func
+                    .as_ref()
+                    .unwrap()
+                    .blocks
+                    .get(pred.trim_start_matches('%'))
+                {
+                    Some(pred_bb) if !up_bb.contains(pred_bb) => up_bb.push(*pred_bb),
+                    _ => return self.error(format!("invalid phi predecessor `{}`", pred)),
+                }
+            }
+            let edges = bb.get_up_bb();
+            if edges.len() != up_bb.len() || edges.iter().any(|pred| !up_bb.contains(pred)) {
+                return self.error(format!(
+                    "phi predecessors do not match the incoming edges of `bb_{}`",
+                    bb.get_name()
+                ));
+            }
+            bb.set_up_bb(up_bb);
+            ordered.insert(bb, preds);
+        }
+
+        for (mut phi, incoming, line) in phis {
+            self.line = line;
+            let ty = phi.get_ir_type();
+            for (index, (value, _)) in incoming.iter().enumerate() {
+                let operand = if value.starts_with('%') || value.starts_with('@') {
+                    self.lookup(value, ty)?
+                } else {
+                    // 常量放在对应前继块的跳转指令之前
+                    let constant = self.constant(value, ty)?;
+                    let pred = phi.get_parent_bb().get_up_bb()[index];
+                    pred.get_tail_inst().as_mut().insert_before(constant);
+                    constant
+                };
+                phi.add_operand(operand);
+            }
+        }
+
+        let state = self.func.take().unwrap();
+        let mut func = state.func;
+        func.insert_first_bb(state.defined[0]);
+        self.module.push_function(state.name, func);
+        Ok(())
+    }
+
+    /// ; init array begin!!!!
+    fn init_begin(&mut self) -> ParseResult<()> {
+        let alloca = match &self.func {
+            Some(state) => match state.current {
+                Some(bb) if !bb.is_empty() => Some(bb.get_tail_inst()),
+                _ => None,
+            },
+            None => None,
+        };
+        match alloca {
+            Some(alloca) if matches!(alloca.get_kind(), InstKind::Alloca(_)) => {
+                self.func.as_mut().unwrap().init = Some((alloca, Vec::new()));
+                Ok(())
+            }
+            _ => self.error("array initializer must follow an alloca"),
+        }
+    }
+
+    /// ; init array end!!!!
+    fn init_end(&mut self) -> ParseResult<()> {
+        let (mut alloca, values) = match self.func.as_mut().and_then(|state| state.init.take()) {
+            Some(init) => init,
+            None => return self.error("unmatched end of array initializer"),
+        };
+        if let IrType::IntPtr = alloca.get_ir_type() {
+            let mut init = Vec::new();
+            for (by_var, value) in values {
+                init.push((by_var, self.parse_int(value.as_str())?));
+            }
+            alloca.set_int_init(true, init);
+        } else {
+            let mut init = Vec::new();
+            for (by_var, value) in values {
+                init.push((by_var, self.parse_float(value.as_str())?));
+            }
+            alloca.set_float_init(true, init);
+        }
+        Ok(())
+    }
+
+    /// 初始化段中的gep只用于定位,store记录初始值
+    fn parse_init_store(&mut self, code: &str, comment: &str) -> ParseResult<()> {
+        let code = match code.split_once(" = ") {
+            Some((_, rest)) => rest,
+            None => code,
+        };
+        if code.starts_with("getelementptr ") {
+            return Ok(());
+        }
+        let value = match code.strip_prefix("store ") {
+            Some(rest) => rest.split(',').next().unwrap().split_whitespace().nth(1),
+            None => None,
+        };
+        match value {
+            Some(value) => {
+                let init = &mut self.func.as_mut().unwrap().init.as_mut().unwrap().1;
+                init.push((comment == "variable", value.to_string()));
+                Ok(())
+            }
+            None => self.error(format!("unexpected `{}` in array initializer", code)),
+        }
+    }
+
+    fn parse_inst(&mut self, code: &str, comment: &str) -> ParseResult<()> {
+        if self.func.as_ref().unwrap().current.is_none() {
+            return self.error("instruction outside of basic block");
+        }
+        let (dest, code) = match code.split_once(" = ") {
+            Some((dest, rest)) if dest.starts_with('%') => (Some(dest), rest),
+            _ => (None, code),
+        };
+        let opcode = code.split_whitespace().next().unwrap();
+        let parts: Vec<&str> = code.split(", ").map(|p| p.trim()).collect();
+        let words: Vec<&str> = parts[0].split_whitespace().collect();
+
+        let inst = match opcode {
+            "alloca" => {
+                let array = code["alloca".len()..].trim_start();
+                let array = array.split(']').next().unwrap().trim_start_matches('[');
+                let (length, ty) = self.parse_array_type(array)?;
+                match ty {
+                    IrType::Int => self.pool_inst.make_int_array(length, false, vec![]),
+                    _ => self.pool_inst.make_float_array(length, false, vec![]),
+                }
+            }
+            "getelementptr" => {
+                if parts.len() < 3 {
+                    return self.error("malformed getelementptr");
+                }
+                let ty = if parts[0].contains("float") {
+                    IrType::FloatPtr
+                } else {
+                    IrType::IntPtr
+                };
+                let ptr = self.operand(parts[1].split_whitespace().last().unwrap(), ty)?;
+                let offset = self.operand(
+                    parts[parts.len() - 1].split_whitespace().last().unwrap(),
+                    IrType::Int,
+                )?;
+                self.pool_inst.make_gep(ptr, offset)
+            }
+            "load" => {
+                if parts.len() < 2 || words.len() < 2 {
+                    return self.error("malformed load");
+                }
+                let ptr_name = parts[1].split_whitespace().last().unwrap();
+                match words[1] {
+                    "ptr" => {
+                        let ptr = self.global(ptr_name)?;
+                        match ptr.get_ir_type() {
+                            IrType::IntPtr => self.pool_inst.make_global_int_array_load(ptr),
+                            IrType::FloatPtr => self.pool_inst.make_global_float_array_load(ptr),
+                            _ => return self.error(format!("`{}` is not an array", ptr_name)),
+                        }
+                    }
+                    "i32" if ptr_name.starts_with('@') => {
+                        let ptr = self.lookup(ptr_name, IrType::Int)?;
+                        self.pool_inst.make_global_int_load(ptr)
+                    }
+                    "float" if ptr_name.starts_with('@') => {
+                        let ptr = self.lookup(ptr_name, IrType::Float)?;
+                        self.pool_inst.make_global_float_load(ptr)
+                    }
+                    "i32" => {
+                        let ptr = self.lookup(ptr_name, IrType::IntPtr)?;
+                        self.pool_inst.make_int_load(ptr)
+                    }
+                    "float" => {
+                        let ptr = self.lookup(ptr_name, IrType::FloatPtr)?;
+                        self.pool_inst.make_float_load(ptr)
+                    }
+                    ty => return self.error(format!("unknown load type `{}`", ty)),
+                }
+            }
+            "store" => {
+                if parts.len() < 2 || words.len() < 3 {
+                    return self.error("malformed store");
+                }
+                let ty = self.parse_type(words[1])?;
+                let ptr_name = parts[1].split_whitespace().last().unwrap();
+                let ptr_type = match (ptr_name.starts_with('@'), ty) {
+                    (true, ty) => ty,
+                    (false, IrType::Int) => IrType::IntPtr,
+                    (false, _) => IrType::FloatPtr,
+                };
+                let dest = self.lookup(ptr_name, ptr_type)?;
+                let value = self.operand(words[2], ty)?;
+                match ty {
+                    IrType::Int => self.pool_inst.make_int_store(dest, value),
+                    _ => self.pool_inst.make_float_store(dest, value),
+                }
+            }
+            "add" | "fadd" | "sub" | "fsub" | "mul" | "fmul" | "sdiv" | "fdiv" | "srem"
+            | "frem" | "udiv" | "urem" | "and" | "or" | "xor" | "shl" | "lshr" | "ashr" => {
+                if parts.len() < 2 || words.len() < 3 {
+                    return self.error(format!("malformed {}", opcode));
+                }
+                let ty = self.parse_type(words[1])?;
+                match comment {
+                    "pos" | "neg" => {
+                        let value = self.operand(parts[1], ty)?;
+                        if comment == "pos" {
+                            self.pool_inst.make_pos(value)
+                        } else {
+                            self.pool_inst.make_neg(value)
+                        }
+                    }
+                    _ => {
+                        let lhs = self.operand(words[2], ty)?;
+                        let rhs = self.operand(parts[1], ty)?;
+                        match opcode {
+                            "add" | "fadd" => self.pool_inst.make_add(lhs, rhs),
+                            "sub" | "fsub" => self.pool_inst.make_sub(lhs, rhs),
+                            "mul" | "fmul" => self.pool_inst.make_mul(lhs, rhs),
+                            "sdiv" | "fdiv" => self.pool_inst.make_div(lhs, rhs),
+                            "udiv" => self.pool_inst.make_udiv(lhs, rhs),
+                            "urem" => self.pool_inst.make_urem(lhs, rhs),
+                            "and" => self.pool_inst.make_and(lhs, rhs),
+                            "or" => self.pool_inst.make_or(lhs, rhs),
+                            "xor" => self.pool_inst.make_xor(lhs, rhs),
+                            "shl" => self.pool_inst.make_shl(lhs, rhs),
+                            "lshr" => self.pool_inst.make_lshr(lhs, rhs),
+                            "ashr" => self.pool_inst.make_ashr(lhs, rhs),
+                            _ => self.pool_inst.make_rem(lhs, rhs),
+                        }
+                    }
+                }
+            }
+            "icmp" | "fcmp" => {
+                if parts.len() < 2 || words.len() < 4 {
+                    return self.error(format!("malformed {}", opcode));
+                }
+                let ty = self.parse_type(words[2])?;
+                let lhs = self.operand(words[3], ty)?;
+                if comment == "not" {
+                    self.pool_inst.make_not(lhs)
+                } else if dest.map_or(false, |d| d.ends_with("_add")) {
+                    // 分支和select条件的包装,直接使用原来的值
+                    return self.define(dest.unwrap(), lhs);
+                } else {
+                    let rhs = self.operand(parts[1], ty)?;
+                    match words[1] {
+                        "eq" | "oeq" => self.pool_inst.make_eq(lhs, rhs),
+                        "ne" | "one" => self.pool_inst.make_ne(lhs, rhs),
+                        "slt" | "olt" => self.pool_inst.make_lt(lhs, rhs),
+                        "sle" | "ole" => self.pool_inst.make_le(lhs, rhs),
+                        "sgt" | "ogt" => self.pool_inst.make_gt(lhs, rhs),
+                        "sge" | "oge" => self.pool_inst.make_ge(lhs, rhs),
+                        pred => return self.error(format!("unknown predicate `{}`", pred)),
+                    }
+                }
+            }
+            "select" => {
+                if parts.len() != 3 || words.len() != 3 {
+                    return self.error("malformed select");
+                }
+                let cond = self.operand(words[2], IrType::Int)?;
+                let (ty, true_value) = match parts[1].split_once(' ') {
+                    Some((ty, value)) => (self.parse_type(ty)?, value),
+                    None => return self.error("malformed select"),
+                };
+                let false_value = match parts[2].split_once(' ') {
+                    Some((_, value)) => value,
+                    None => return self.error("malformed select"),
+                };
+                let true_value = self.operand(true_value, ty)?;
+                let false_value = self.operand(false_value, ty)?;
+                self.pool_inst.make_select(cond, true_value, false_value)
+            }
+            "fptosi" | "sitofp" => {
+                let words: Vec<&str> = code.split_whitespace().collect();
+                if words.len() < 3 {
+                    return self.error(format!("malformed {}", opcode));
+                }
+                if opcode == "fptosi" {
+                    let value = self.operand(words[2], IrType::Float)?;
+                    self.pool_inst.make_float_to_int(value)
+                } else {
+                    let value = self.operand(words[2], IrType::Int)?;
+                    self.pool_inst.make_int_to_float(value)
+                }
+            }
+            "call" => {
+                let (head, params) = self.split_call(code)?;
+                let (return_type, callee) = self.split_callee(&head["call".len()..])?;
+                let mut args = Vec::new();
+                for param in params
+                    .split(',')
+                    .map(|p| p.trim())
+                    .filter(|p| !p.is_empty())
+                {
+                    let tokens: Vec<&str> = param.split_whitespace().collect();
+                    let ty = self.parse_type(tokens[0])?;
+                    args.push(self.operand(tokens[tokens.len() - 1], ty)?);
+                }
+                self.callees.push((callee.clone(), self.line));
+                match return_type {
+                    IrType::Int => self.pool_inst.make_int_call(callee, args),
+                    IrType::Float => self.pool_inst.make_float_call(callee, args),
+                    _ => self.pool_inst.make_void_call(callee, args),
+                }
+            }
+            "ret" => match words.get(1) {
+                Some(&"void") => self.pool_inst.make_return_void(),
+                Some(ty) if words.len() == 3 => {
+                    let ty = self.parse_type(ty)?;
+                    let value = self.operand(words[2], ty)?;
+                    self.pool_inst.make_return(value)
+                }
+                _ => return self.error("malformed ret"),
+            },
+            "br" => {
+                let mut bb = self.func.as_ref().unwrap().current.unwrap();
+                if let Some(label) = code.strip_prefix("br label ") {
+                    let target = self.block(label.trim().trim_start_matches('%'));
+                    bb.add_next_bb(target);
+                    self.pool_inst.make_jmp()
+                } else if parts.len() == 3 && words.len() == 3 {
+                    // 条件可以是int或float
+                    let local = self.func.as_ref().unwrap().locals.get(words[2]).copied();
+                    let cond = match local {
+                        Some(cond) => cond,
+                        None => self.operand(words[2], IrType::Int)?,
+                    };
+                    let true_bb = self.block(Self::label_of(parts[1]));
+                    let false_bb = self.block(Self::label_of(parts[2]));
+                    // next_bb的顺序为[false, true]
+                    bb.add_next_bb(false_bb);
+                    bb.add_next_bb(true_bb);
+                    self.pool_inst.make_br(cond)
+                } else {
+                    return self.error("malformed br");
+                }
+            }
+            "phi" => {
+                if words.len() < 2 {
+                    return self.error("malformed phi");
+                }
+                let ty = self.parse_type(words[1])?;
+                let mut incoming = Vec::new();
+                for pair in code.split('[').skip(1) {
+                    let pair = pair.trim().trim_end_matches(',').trim_end_matches(']');
+                    match pair.split_once(',') {
+                        Some((value, pred)) => incoming
+                            .push((value.trim().to_string(), Self::label_of(pred).to_string())),
+                        None => return self.error(format!("malformed phi operand `{}`", pair)),
+                    }
+                }
+                let phi = self.pool_inst.make_phi(ty);
+                let line = self.line;
+                self.func.as_mut().unwrap().phis.push((phi, incoming, line));
+                phi
+            }
+            _ => return self.error(format!("unknown instruction `{}`", opcode)),
+        };
+
+        self.func.as_ref().unwrap().current.unwrap().push_back(inst);
+        match dest {
+            Some(dest) => self.define(dest, inst),
+            None => Ok(()),
+        }
+    }
+
+    /// 获得名字对应的块,不存在时新建
+    fn block(&mut self, label: &str) -> ObjPtr<BasicBlock> {
+        let state = self.func.as_mut().unwrap();
+        match state.blocks.get(label) {
+            Some(bb) => *bb,
+            None => {
+                let name = label.strip_prefix("bb_").unwrap_or(label);
+                let bb = self.pool_bb.new_basic_block(name.to_string());
+                state.blocks.insert(label.to_string(), bb);
+                bb
+            }
+        }
+    }
+
+    /// `label %bb_1`或`%bb_1 ]`中的块名
+    fn label_of(text: &str) -> &str {
+        let text = text.trim().trim_end_matches(']').trim();
+        let text = text.strip_prefix("label").unwrap_or(text).trim();
+        text.trim_start_matches('%')
+    }
+
+    fn define(&mut self, name: &str, inst: ObjPtr<Inst>) -> ParseResult<()> {
+        let state = self.func.as_mut().unwrap();
+        if state.locals.insert(name.to_string(), inst).is_some() {
+            return self.error(format!("redefinition of `{}`", name));
+        }
+        Ok(())
+    }
+
+    /// 获得操作数,常量会被放在当前块中
+    fn operand(&mut self, token: &str, ty: IrType) -> ParseResult<ObjPtr<Inst>> {
+        if token.starts_with('%') || token.starts_with('@') {
+            return self.lookup(token, ty);
+        }
+        let constant = self.constant(token, ty)?;
+        self.func
+            .as_ref()
+            .unwrap()
+            .current
+            .unwrap()
+            .push_back(constant);
+        Ok(constant)
+    }
+
+    /// 查找已定义的值,并检查类型
+    fn lookup(&mut self, name: &str, ty: IrType) -> ParseResult<ObjPtr<Inst>> {
+        let value = if name.starts_with('@') {
+            self.global(name)?
+        } else {
+            match self.func.as_ref().unwrap().locals.get(name) {
+                Some(value) => *value,
+                None => return self.error(format!("use of undefined value `{}`", name)),
+            }
+        };
+        let found = value.get_ir_type();
+        if found != ty {
+            return self.error(format!(
+                "`{}` has type {:?}, expected {:?}",
+                name, found, ty
+            ));
+        }
+        Ok(value)
+    }
+
+    fn global(&self, name: &str) -> ParseResult<ObjPtr<Inst>> {
+        match self.globals.get(name.trim_start_matches('@')) {
+            Some(value) => Ok(*value),
+            None => self.error(format!("use of undefined global `{}`", name)),
+        }
+    }
+
+    /// 创建一个还未放入块中的常量
+    fn constant(&mut self, token: &str, ty: IrType) -> ParseResult<ObjPtr<Inst>> {
+        match ty {
+            IrType::Int => {
+                let value = self.parse_int(token)?;
+                Ok(self.pool_inst.make_int_const(value))
+            }
+            IrType::Float => {
+                let value = self.parse_float(token)?;
+                Ok(self.pool_inst.make_float_const(value))
+            }
+            _ => self.error(format!("expected a pointer, found `{}`", token)),
+        }
+    }
+
+    fn parse_int(&self, token: &str) -> ParseResult<i32> {
+        match token.trim().parse() {
+            Ok(value) => Ok(value),
+            Err(_) => self.error(format!("invalid integer `{}`", token)),
+        }
+    }
+
+    fn parse_float(&self, token: &str) -> ParseResult<f32> {
+        match token.trim().parse() {
+            Ok(value) => Ok(value),
+            Err(_) => self.error(format!("invalid float `{}`", token)),
+        }
+    }
+
+    fn parse_type(&self, token: &str) -> ParseResult<IrType> {
+        match token {
+            "i32" => Ok(IrType::Int),
+            "float" => Ok(IrType::Float),
+            "i32*" => Ok(IrType::IntPtr),
+            "float*" => Ok(IrType::FloatPtr),
+            _ => self.error(format!("unknown type `{}`", token)),
+        }
+    }
+
+    /// `4 x i32`
+    fn parse_array_type(&self, text: &str) -> ParseResult<(i32, IrType)> {
+        let tokens: Vec<&str> = text.split_whitespace().collect();
+        match tokens.as_slice() {
+            [length, "x", ty] => {
+                let length = self.parse_int(length)?;
+                match self.parse_type(ty)? {
+                    IrType::Int => Ok((length, IrType::Int)),
+                    IrType::Float => Ok((length, IrType::Float)),
+                    _ => self.error(format!("invalid array type `{}`", text)),
+                }
+            }
+            _ => self.error(format!("invalid array type `{}`", text)),
+        }
+    }
+
+    /// 将`xxx(args)`拆成括号前和括号内的部分
+    fn split_call<'b>(&self, code: &'b str) -> ParseResult<(&'b str, &'b str)> {
+        match (code.find('('), code.rfind(')')) {
+            (Some(open), Some(close)) if open < close => {
+                Ok((code[..open].trim(), &code[open + 1..close]))
+            }
+            _ => self.error("expected parenthesized argument list"),
+        }
+    }
+
+    /// 将`signext i32 @f`拆成返回类型和函数名
+    fn split_callee(&self, head: &str) -> ParseResult<(IrType, String)> {
+        let (ty, name) = match head.split_once('@') {
+            Some(pair) => pair,
+            None => return self.error("expected function name"),
+        };
+        let ty = match ty.split_whitespace().last() {
+            Some("void") => IrType::Void,
+            Some("i32") => IrType::Int,
+            Some("float") => IrType::Float,
+            _ => return self.error(format!("unknown return type `{}`", ty.trim())),
+        };
+        Ok((ty, name.trim().to_string()))
+    }
+}
+
+#[cfg(test)]
+mod parse_ir_test {
+    use super::parse_ir;
+    use crate::ir::{
+        basicblock::BasicBlock, dump_module, function::Function, instruction::Inst,
+        instruction::InstKind, module::Module, test_util::parse_module,
+    };
+    use crate::utility::ObjPool;
+
+    const IR: &str = "
+@g = dso_local global i32 3, align 4
+@c = dso_local constant float 1.5, align 4
+@arr = dso_local global [3 x i32] [ i32 1, i32 2, i32 3], align 4
+@farr = dso_local global <{ float, [3 x float] }> <{ float 2.5, [3 x float] zeroinitializer}>, align 4
+
+define dso_local signext i32 @sum(i32* noundef %p, i32 noundef signext %n) #0 {
+bb_entry:
+  br label %bb_loop
+
+bb_loop:
+  %i = phi i32 [ %i2, %bb_body ], [ 0, %bb_entry ]
+  %s = phi i32 [ %s2, %bb_body ], [ 0, %bb_entry ]
+  %cmp = icmp slt i32 %i, %n
+  br i1 %cmp, label %bb_body, label %bb_exit
+
+bb_body:
+  %ptr = getelementptr inbounds i32, ptr %p, i32 %i
+  %v = load i32, i32* %ptr, align 4
+  %s2 = add i32 %s, %v
+  %i2 = add i32 %i, 1
+  br label %bb_loop
+
+bb_exit:
+  ret i32 %s
+}
+
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %a = alloca [4 x i32], align 4
+  ; init array begin!!!!
+  %val_0 = getelementptr inbounds [4 x i32], [4 x i32]* %a, i32 0, i32 0
+  store i32 7, i32* %val_0, align 4
+  %val_1 = getelementptr inbounds [4 x i32], [4 x i32]* %a, i32 0, i32 1
+  store i32 0, i32* %val_1, align 4 ; variable
+  ; init array end!!!!
+  %x = load i32, i32* @g, align 4
+  %y = sub i32 0, %x ;neg
+  %q = getelementptr inbounds [4 x i32], [4 x i32]* %a, i32 0, i32 1
+  store i32 %y, i32* %q, align 4
+  %r = call i32 @sum(i32* noundef %q, i32 noundef 3)
+  %garr = load ptr, ptr @arr, align 8
+  %e = getelementptr inbounds [3 x i32], [3 x i32]* %garr, i32 0, i32 2
+  %ev = load i32, i32* %e, align 4
+  call void @putint(i32 noundef %ev)
+  %f = sitofp i32 %r to float
+  %t = fmul float %f, 2.5
+  %r_add = icmp ne i32 %r, 0
+  br i1 %r_add, label %bb_then, label %bb_end
+
+bb_then:
+  %nt = fcmp one float %t, 0.0 ;not
+  br i1 %nt, label %bb_end, label %bb_end2
+
+bb_end2:
+  %back = fptosi float %t to i32
+  ret i32 %back
+
+bb_end:
+  ret i32 0
+}
+
+declare void @putint(i32)
+";
+
+    #[test]
+    fn test_round_trip() {
+        let module = parse_module(IR).0;
+        let text = dump_module(&module).unwrap();
+        let again = dump_module(&parse_module(&text).0).unwrap();
+        assert_eq!(text, again);
+    }
+
+    #[test]
+    fn test_float_rem() {
+        // 前端在浮点数表达式中生成的浮点数取余按frem输出,并且可以读回
+        let (module, _) = parse_module(
+            "
+define dso_local float @main() #0 {
+bb_entry:
+  %x = frem float 7.5, 2.0
+  ret float %x
+}
+",
+        );
+        let text = dump_module(&module).unwrap();
+        assert!(text.contains("frem float"));
+        assert_eq!(text, dump_module(&parse_module(&text).0).unwrap());
+    }
+
+    #[test]
+    fn test_structure() {
+        let module = parse_module(IR).0;
+        assert!(module.find_function("putint").is_some());
+
+        // up_bb按phi中前继块的顺序排列,phi中的常量放在前继块中
+        let sum = module.get_function("sum");
+        assert_eq!(sum.get_parameter_list().len(), 2);
+        let entry = sum.get_head();
+        assert_eq!(entry.get_name(), "entry");
+        let loop_bb = entry.get_next_bb()[0];
+        let preds: Vec<&str> = loop_bb.get_up_bb().iter().map(|bb| bb.get_name()).collect();
+        assert_eq!(preds, vec!["body", "entry"]);
+        assert_eq!(entry.get_head_inst().get_kind(), InstKind::ConstInt(0));
+        let br = loop_bb.get_tail_inst();
+        assert_eq!(br.get_true_bb().get_name(), "body");
+        assert_eq!(br.get_false_bb().get_name(), "exit");
+        let phi = loop_bb.get_head_inst();
+        assert_eq!(phi.get_operands().len(), 2);
+        assert_eq!(phi.get_phi_predecessor(1), entry);
+
+        // 数组初始化和分支条件的包装
+        let main = module.get_function("main");
+        let alloca = main.get_head().get_head_inst();
+        assert_eq!(alloca.get_int_init(), &(true, vec![(false, 7), (true, 0)]));
+        let cond = main.get_head().get_tail_inst().get_br_cond();
+        assert_eq!(cond.get_kind(), InstKind::Call("sum".to_string()));
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut module = Module::new();
+        let mut pool_inst: ObjPool<Inst> = ObjPool::new();
+        let mut pool_bb: ObjPool<BasicBlock> = ObjPool::new();
+        let mut pool_func: ObjPool<Function> = ObjPool::new();
+        let mut check = |text: &str, line: usize, msg: &str| {
+            let err = parse_ir(
+                text,
+                &mut module,
+                &mut pool_inst,
+                &mut pool_bb,
+                &mut pool_func,
+            )
+            .unwrap_err();
+            assert_eq!(err.line, line);
+            assert!(err.msg.contains(msg), "{}", err);
+        };
+        check(
+            "define dso_local void @f() #0 {\nbb_0:\n  store i32 1, i32* %p, align 4\n}\n",
+            3,
+            "undefined value",
+        );
+        check(
+            "define dso_local void @g() #0 {\nbb_0:\n  br label %bb_1\n}\n",
+            4,
+            "undefined label",
+        );
+        check(
+            "define dso_local void @h() #0 {\nbb_0:\n  br label %bb_1\nbb_1:\n  \
+             %x = phi i32 [ 0, %bb_2 ]\n  ret void\nbb_2:\n  ret void\n}\n",
+            5,
+            "do not match",
+        );
+    }
+}
@@ -0,0 +1,40 @@
+//! 单元测试共用的工具
+
+use super::{basicblock::BasicBlock, function::Function, instruction::Inst, module::Module};
+use crate::{ir::parse_ir, utility::ObjPool};
+
+/// SysY运行库函数的声明,附加在测试的IR之后,测试中可以直接调用而不用逐个声明
+const RUNTIME_DECLARES: &str = "
+declare i32 @getint()
+declare i32 @getch()
+declare float @getfloat()
+declare i32 @getarray(ptr)
+declare i32 @getfarray(ptr)
+declare void @putint(i32)
+declare void @putch(i32)
+declare void @putfloat(float)
+declare void @putarray(i32, ptr)
+declare void @putfarray(i32, ptr)
+";
+
+/// 测试用的对象池
+///
+/// 模块中的指令和基本块都指向池中的内存,池在测试结束前不能释放,因此直接泄漏
+pub struct TestPools {
+    pub inst: &'static mut ObjPool<Inst>,
+    pub bb: &'static mut ObjPool<BasicBlock>,
+    pub func: &'static mut ObjPool<Function>,
+}
+
+/// 解析文本形式的IR,返回模块和解析用的对象池,解析失败时直接panic
+pub fn parse_module(text: &str) -> (Module, TestPools) {
+    let mut module = Module::new();
+    let pools = TestPools {
+        inst: Box::leak(Box::new(ObjPool::new())),
+        bb: Box::leak(Box::new(ObjPool::new())),
+        func: Box::leak(Box::new(ObjPool::new())),
+    };
+    let text = format!("{}{}", text, RUNTIME_DECLARES);
+    parse_ir(&text, &mut module, pools.inst, pools.bb, pools.func).unwrap();
+    (module, pools)
+}
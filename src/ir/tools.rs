@@ -117,6 +117,28 @@ where
     }
 }
 
+/// 从head开始可达的基本块的逆后序,除回边外每个块都排在它的前继之后
+/// # Arguments
+/// * `head` - 遍历的起始点
+pub fn reverse_post_order(head: ObjPtr<BasicBlock>) -> Vec<ObjPtr<BasicBlock>> {
+    let mut visited = HashSet::new();
+    let mut order = vec![];
+    let mut stack = vec![(head, 0)];
+    visited.insert(head);
+    while let Some((bb, index)) = stack.pop() {
+        if let Some(&next) = bb.get_next_bb().get(index) {
+            stack.push((bb, index + 1));
+            if visited.insert(next) {
+                stack.push((next, 0));
+            }
+        } else {
+            order.push(bb);
+        }
+    }
+    order.reverse();
+    order
+}
+
 /// 对每一个函数进行处理，但不处理外部函数
 /// 对每一个函数进行处理
 /// # Arguments
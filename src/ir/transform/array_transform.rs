@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::{
     ir::{instruction::InstKind, ir_type::IrType},
@@ -248,12 +248,189 @@ fn global_array_transform(
         })
 }
 
+/// 元素个数不超过这个值的局部数组才会被标量化
+const LOCAL_ARRAY_LIMIT: i32 = 64;
+
+/// 将索引都是常量的小局部数组的每个元素提升为SSA值
 fn local_array_transform(
-    _module: &mut Module,
-    _pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
+    module: &mut Module,
+    pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
+) {
+    func_process(module, |_, func| {
+        let mut arrays = Vec::new();
+        bfs_inst_process(func.get_head(), |inst| {
+            if inst.is_local_array() && local_array_analyze(inst) {
+                arrays.push(inst);
+            }
+        });
+        for array in arrays {
+            local_inst_transform(func.get_head(), array, pools);
+        }
+    })
+}
+
+/// 数组只通过常量索引的gep被load和store时可以标量化
+fn local_array_analyze(inst: ObjPtr<Inst>) -> bool {
+    let length = inst.get_array_length();
+    length <= LOCAL_ARRAY_LIMIT
+        && inst.get_use_list().iter().all(|gep| {
+            gep.get_kind() == InstKind::Gep
+                && gep.get_gep_ptr() == inst
+                && gep.get_gep_offset().is_int_const()
+                && (0..length).contains(&gep.get_gep_offset().get_int_bond())
+                && gep.get_use_list().iter().all(|user| match user.get_kind() {
+                    InstKind::Load => true,
+                    InstKind::Store => user.get_value() != *gep,
+                    _ => false,
+                })
+        })
+}
+
+/// 一个元素的所有load和store
+type ElementAccess = (Vec<ObjPtr<Inst>>, HashSet<ObjPtr<Inst>>);
+
+fn local_inst_transform(
+    head: ObjPtr<BasicBlock>,
+    array: ObjPtr<Inst>,
+    pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
 ) {
-    // TODO: 本地数组优化
-    // 目前发现这个东西不值得做
+    // 按元素分组load和store
+    let mut elements: HashMap<i32, ElementAccess> = HashMap::new();
+    let geps = array.get_use_list().clone();
+    for gep in geps.iter() {
+        let element = elements
+            .entry(gep.get_gep_offset().get_int_bond())
+            .or_default();
+        for user in gep.get_use_list() {
+            if user.get_kind() == InstKind::Load {
+                element.0.push(*user);
+            } else {
+                element.1.insert(*user);
+            }
+        }
+    }
+
+    // 同一元素的load按照插phi、填phi的方式找到对应的值
+    let mut values = HashMap::new();
+    for (index, (loads, stores)) in elements.iter() {
+        let mut builder = ElementBuilder {
+            array,
+            index: *index as usize,
+            stores,
+            head,
+            entry_values: HashMap::new(),
+            visiting: HashSet::new(),
+            pool: pools.1,
+        };
+        for load in loads {
+            let value = builder.read_before(*load);
+            values.insert(*load, value);
+        }
+    }
+
+    // load的值可能是另一个load,替换时沿着映射找到最终的值
+    for load in values.keys().cloned().collect::<Vec<_>>() {
+        let mut value = values[&load];
+        while let Some(next) = values.get(&value) {
+            value = *next;
+        }
+        replace_inst(load, value);
+    }
+    for (_, (_, stores)) in elements {
+        stores.into_iter().for_each(|mut store| store.remove_self());
+    }
+    for mut gep in geps {
+        gep.remove_self();
+    }
+    array.as_mut().remove_self();
+}
+
+/// 为数组的一个元素构造SSA值
+struct ElementBuilder<'a> {
+    array: ObjPtr<Inst>,
+    index: usize,
+    stores: &'a HashSet<ObjPtr<Inst>>,
+    head: ObjPtr<BasicBlock>,
+    /// 每个块入口处元素的值
+    entry_values: HashMap<ObjPtr<BasicBlock>, ObjPtr<Inst>>,
+    /// 正在向前继求值的单前继块,经过多前继块时清空
+    visiting: HashSet<ObjPtr<BasicBlock>>,
+    pool: &'a mut ObjPool<Inst>,
+}
+
+impl ElementBuilder<'_> {
+    /// 求inst之前元素的值
+    fn read_before(&mut self, inst: ObjPtr<Inst>) -> ObjPtr<Inst> {
+        let mut prev = inst.get_prev();
+        // 块内第一条指令的前一条是Head
+        while !prev.is_tail() {
+            if self.stores.contains(&prev) {
+                return prev.get_value();
+            }
+            prev = prev.get_prev();
+        }
+        self.read_entry(inst.get_parent_bb())
+    }
+
+    /// 求块出口处元素的值
+    fn read_end(&mut self, bb: ObjPtr<BasicBlock>) -> ObjPtr<Inst> {
+        self.read_before(bb.get_tail_inst())
+    }
+
+    /// 求块入口处元素的值,多个前继时插phi,再到前继中找值填phi
+    fn read_entry(&mut self, mut bb: ObjPtr<BasicBlock>) -> ObjPtr<Inst> {
+        if let Some(value) = self.entry_values.get(&bb) {
+            return *value;
+        }
+        let value = if bb == self.head || bb.get_up_bb().is_empty() {
+            let value = self.init_value();
+            self.head.push_front(value);
+            self.entry_values.insert(bb, value);
+            value
+        } else if bb.get_up_bb().len() == 1 {
+            // 只有一个前继的块不插phi,值直接来自前继。
+            // 只经过单前继块又回到自身的环从入口不可达,这时直接取初值
+            if !self.visiting.insert(bb) {
+                let init = self.init_value();
+                self.head.push_front(init);
+                return init;
+            }
+            let value = self.read_end(bb.get_up_bb()[0]);
+            self.visiting.remove(&bb);
+            self.entry_values.insert(bb, value);
+            value
+        } else {
+            let element_type = match self.array.get_ir_type() {
+                IrType::IntPtr => IrType::Int,
+                _ => IrType::Float,
+            };
+            let mut phi = self.pool.make_phi(element_type);
+            bb.push_front(phi);
+            self.entry_values.insert(bb, phi);
+            // 经过phi回到的单前继块不在只有单前继块的环上
+            let visiting = std::mem::take(&mut self.visiting);
+            for pred in bb.get_up_bb().clone() {
+                let value = self.read_end(pred);
+                phi.add_operand(value);
+            }
+            self.visiting = visiting;
+            phi
+        };
+        value
+    }
+
+    /// 元素的初始值
+    fn init_value(&mut self) -> ObjPtr<Inst> {
+        if let IrType::IntPtr = self.array.get_ir_type() {
+            let init = self.array.get_int_init().1.get(self.index);
+            let value = init.filter(|(is_var, _)| !is_var).map_or(0, |(_, v)| *v);
+            self.pool.make_int_const(value)
+        } else {
+            let init = self.array.get_float_init().1.get(self.index);
+            let value = init.filter(|(is_var, _)| !is_var).map_or(0.0, |(_, v)| *v);
+            self.pool.make_float_const(value)
+        }
+    }
 }
 
 fn array_analyze(inst: ObjPtr<Inst>) -> bool {
@@ -325,6 +502,18 @@ fn global_inst_transform(
             })
         }
 
+        // 数组从module中删除之后,不再使用的gep和数组地址的load不能继续引用它
+        for gep in gep_user.iter_mut() {
+            if gep.get_use_list().is_empty() {
+                gep.remove_self();
+            }
+        }
+        for mut load in inst.get_use_list().clone() {
+            if load.get_use_list().is_empty() {
+                load.remove_self();
+            }
+        }
+
         // 将申请的全局变量放入module中
         let var: Vec<_> = var
             .iter()
@@ -372,3 +561,144 @@ fn variable_get(
         }
     }
 }
+
+#[cfg(test)]
+mod array_transform_test {
+    use super::{global_array_transform, local_array_transform};
+    use crate::ir::{
+        dump_module, instruction::InstKind, interpret, test_util::parse_module,
+        tools::bfs_inst_process, verify_module, DEFAULT_STEP_LIMIT,
+    };
+
+    #[test]
+    fn test_local_array() {
+        // %a的索引都是常量,被提升为SSA值;%b的索引是变量,保留
+        let (mut module, pools) = parse_module(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %a = alloca [2 x i32], align 4
+  %b = alloca [8 x i32], align 4
+  %a0 = getelementptr inbounds [2 x i32], [2 x i32]* %a, i32 0, i32 0
+  %a1 = getelementptr inbounds [2 x i32], [2 x i32]* %a, i32 0, i32 1
+  store i32 1, i32* %a0, align 4
+  %n = call i32 @getint()
+  %bn = getelementptr inbounds [8 x i32], [8 x i32]* %b, i32 0, i32 %n
+  store i32 %n, i32* %bn, align 4
+  br label %bb_loop
+
+bb_loop:
+  %i = phi i32 [ 0, %bb_entry ], [ %i2, %bb_body ]
+  %c = icmp slt i32 %i, %n
+  br i1 %c, label %bb_body, label %bb_exit
+
+bb_body:
+  %x = load i32, i32* %a0, align 4
+  %y = load i32, i32* %a1, align 4
+  store i32 %x, i32* %a1, align 4
+  %x2 = add i32 %x, %y
+  store i32 %x2, i32* %a0, align 4
+  %i2 = add i32 %i, 1
+  br label %bb_loop
+
+bb_exit:
+  %r = load i32, i32* %a0, align 4
+  %s = load i32, i32* %bn, align 4
+  %t = add i32 %r, %s
+  ret i32 %t
+}
+
+declare i32 @getint()
+",
+        );
+        local_array_transform(&mut module, &mut (pools.bb, pools.inst));
+        assert_eq!(verify_module(&module), vec![]);
+
+        let mut memory_insts = 0;
+        bfs_inst_process(module.get_function("main").get_head(), |inst| {
+            match inst.get_kind() {
+                InstKind::Alloca(_) | InstKind::Load | InstKind::Store => memory_insts += 1,
+                _ => {}
+            }
+        });
+        assert_eq!(memory_insts, 3);
+        // a0按斐波那契数列增长
+        assert_eq!(interpret(&module, "1", DEFAULT_STEP_LIMIT).unwrap().ret, 2);
+        assert_eq!(interpret(&module, "5", DEFAULT_STEP_LIMIT).unwrap().ret, 13);
+    }
+
+    #[test]
+    fn test_read_in_loop() {
+        // p1在分支中被写,在循环中只被读,循环头中p1的phi在回边上取它自己
+        let (mut module, pools) = parse_module(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %p = alloca [2 x i32], align 4
+  %p0 = getelementptr inbounds [2 x i32], [2 x i32]* %p, i32 0, i32 0
+  %p1 = getelementptr inbounds [2 x i32], [2 x i32]* %p, i32 0, i32 1
+  store i32 1, i32* %p0, align 4
+  store i32 2, i32* %p1, align 4
+  %n = call i32 @getint()
+  %c = icmp sgt i32 %n, 2
+  br i1 %c, label %bb_then, label %bb_loop
+
+bb_then:
+  store i32 5, i32* %p1, align 4
+  br label %bb_loop
+
+bb_loop:
+  %j = phi i32 [ 0, %bb_entry ], [ 0, %bb_then ], [ %j2, %bb_body ]
+  %c2 = icmp slt i32 %j, 3
+  br i1 %c2, label %bb_body, label %bb_exit
+
+bb_body:
+  %x = load i32, i32* %p0, align 4
+  %y = load i32, i32* %p1, align 4
+  %x2 = add i32 %x, %y
+  store i32 %x2, i32* %p0, align 4
+  %j2 = add i32 %j, 1
+  br label %bb_loop
+
+bb_exit:
+  %r = load i32, i32* %p0, align 4
+  ret i32 %r
+}
+
+declare i32 @getint()
+",
+        );
+        local_array_transform(&mut module, &mut (pools.bb, pools.inst));
+        assert_eq!(verify_module(&module), vec![]);
+        assert_eq!(interpret(&module, "5", DEFAULT_STEP_LIMIT).unwrap().ret, 16);
+        assert_eq!(interpret(&module, "0", DEFAULT_STEP_LIMIT).unwrap().ret, 7);
+    }
+
+    #[test]
+    fn test_global_array() {
+        // 全局数组的索引都是常量时被拆成全局变量,之后输出的IR中不能再引用这个数组
+        let (mut module, pools) = parse_module(
+            "
+@g = dso_local global [4 x i32] [ i32 1, i32 2, i32 3, i32 4], align 4
+
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %ga = load ptr, ptr @g, align 8
+  %g1 = getelementptr inbounds [4 x i32], [4 x i32]* %ga, i32 0, i32 1
+  %g2 = getelementptr inbounds [4 x i32], [4 x i32]* %ga, i32 0, i32 2
+  %x = load i32, i32* %g1, align 4
+  store i32 %x, i32* %g2, align 4
+  %y = load i32, i32* %g2, align 4
+  %r = add i32 %x, %y
+  ret i32 %r
+}
+",
+        );
+        global_array_transform(&mut module, &mut (pools.bb, pools.inst));
+        assert_eq!(verify_module(&module), vec![]);
+
+        let text = dump_module(&module).unwrap();
+        assert!(!text.contains("@g ="), "{}", text);
+        assert_eq!(interpret(&module, "", DEFAULT_STEP_LIMIT).unwrap().ret, 4);
+    }
+}
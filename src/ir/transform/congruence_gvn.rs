@@ -0,0 +1,465 @@
+//! 基于同余类的乐观全局值编号
+//!
+//! 一开始假设所有指令的值都还没有确定(Top),按逆后序反复给每条指令求值直到编号不再变化。
+//! 指令的值由操作数的编号构成的表达式决定,表达式相同的指令属于同一个同余类。
+//! phi忽略还没有确定的操作数,因此循环中互相依赖的phi也能被证明相等,
+//! 例如初值和步长都相同的两个归纳变量。
+//!
+//! 求值时先做代数化简(常量折叠、x+0、x-x等),再把可交换运算的操作数排序、
+//! 把大于比较转换为交换操作数的小于比较,使等价的写法得到相同的表达式。
+//! 最后用支配自己的同类指令或常量替换每条指令。
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    ir::{
+        analysis::{
+            call_optimize::call_optimize,
+            dominator_tree::{calculate_dominator, DominatorTree},
+        },
+        basicblock::BasicBlock,
+        instruction::{BinOp, Inst, InstKind, UnOp},
+        ir_type::IrType,
+        module::Module,
+        tools::{func_process, inst_process_in_bb, replace_inst, reverse_post_order},
+    },
+    utility::{ObjPool, ObjPtr},
+};
+
+use super::{delete_redundant_load_store::load_store_opt, sccp::fold_int};
+
+/// 值编号
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Value {
+    /// 还没有确定的值
+    Top,
+    Int(i32),
+    /// 按位比较,区分0.0和-0.0
+    Float(u32),
+    /// 同余类的代表指令
+    Inst(ObjPtr<Inst>),
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Expression {
+    Binary(BinOp, Value, Value),
+    Unary(UnOp, Value),
+    Select(Value, Value, Value),
+    Gep(Value, Value),
+    FtoI(Value),
+    ItoF(Value),
+    /// 只给纯函数的调用编号
+    Call(String, Vec<Value>),
+    /// 同一个块中对应操作数同余的phi同余
+    Phi(ObjPtr<BasicBlock>, Vec<Value>),
+}
+
+pub fn congruence_gvn(
+    module: &mut Module,
+    pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
+    opt_option: bool,
+) {
+    if !opt_option {
+        return;
+    }
+    loop {
+        let mut changed = false;
+        let pure_funcs = call_optimize(module);
+        func_process(module, |_, func| {
+            let mut gvn = CongruenceGvn::new(func.get_head(), pure_funcs.clone());
+            gvn.solve();
+            changed |= gvn.rewrite(func.get_head(), pools.1);
+        });
+        // 删除冗余的load后可能出现新的同余指令
+        changed |= load_store_opt(module);
+        if !changed {
+            break;
+        }
+    }
+}
+
+struct CongruenceGvn {
+    /// 按逆后序排列的指令
+    insts: Vec<ObjPtr<Inst>>,
+    /// 指令的值编号
+    values: HashMap<ObjPtr<Inst>, Value>,
+    /// 本轮中每个表达式的代表指令
+    expressions: HashMap<Expression, ObjPtr<Inst>>,
+    /// 给可交换运算的操作数排序用的序号
+    order: HashMap<ObjPtr<Inst>, usize>,
+    pure_funcs: HashSet<String>,
+}
+
+impl CongruenceGvn {
+    fn new(head: ObjPtr<BasicBlock>, pure_funcs: HashSet<String>) -> CongruenceGvn {
+        let mut insts = vec![];
+        let mut order = HashMap::new();
+        for bb in reverse_post_order(head) {
+            inst_process_in_bb(bb.get_head_inst(), |inst| {
+                for &operand in inst.get_operands() {
+                    let index = order.len();
+                    order.entry(operand).or_insert(index);
+                }
+                let index = order.len();
+                order.entry(inst).or_insert(index);
+                insts.push(inst);
+            });
+        }
+        CongruenceGvn {
+            insts,
+            values: HashMap::new(),
+            expressions: HashMap::new(),
+            order,
+            pure_funcs,
+        }
+    }
+
+    fn solve(&mut self) {
+        loop {
+            // 每一轮重新建立表达式表,上一轮的编号只通过回边上的phi操作数影响本轮
+            self.expressions.clear();
+            let mut changed = false;
+            for index in 0..self.insts.len() {
+                let inst = self.insts[index];
+                let value = self.eval(inst);
+                if self.get_value(inst) != value {
+                    self.values.insert(inst, value);
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+    }
+
+    fn eval(&mut self, inst: ObjPtr<Inst>) -> Value {
+        match inst.get_kind() {
+            InstKind::ConstInt(_) | InstKind::ConstFloat(_) => self.get_value(inst),
+            InstKind::Binary(op) => self.eval_binary(inst, op),
+            InstKind::Unary(op) => match (op, self.get_value(inst.get_unary_operand())) {
+                (_, Value::Top) => Value::Top,
+                (UnOp::Pos, value) => value,
+                (UnOp::Neg, Value::Int(value)) => Value::Int(value.wrapping_neg()),
+                (UnOp::Not, Value::Int(value)) => Value::Int((value == 0) as i32),
+                (op, value) => self.lookup(Expression::Unary(op, value), inst),
+            },
+            InstKind::Select => {
+                let true_value = self.get_value(inst.get_true_value());
+                let false_value = self.get_value(inst.get_false_value());
+                match self.get_value(inst.get_select_cond()) {
+                    Value::Top => Value::Top,
+                    Value::Int(0) => false_value,
+                    Value::Int(_) => true_value,
+                    _ if true_value == false_value => true_value,
+                    _ if true_value == Value::Top || false_value == Value::Top => Value::Top,
+                    cond => self.lookup(Expression::Select(cond, true_value, false_value), inst),
+                }
+            }
+            InstKind::Gep => {
+                let ptr = self.get_value(inst.get_gep_ptr());
+                let offset = self.get_value(inst.get_gep_offset());
+                if ptr == Value::Top || offset == Value::Top {
+                    return Value::Top;
+                }
+                self.lookup(Expression::Gep(ptr, offset), inst)
+            }
+            InstKind::FtoI => match self.get_value(inst.get_float_to_int_value()) {
+                Value::Top => Value::Top,
+                value => self.lookup(Expression::FtoI(value), inst),
+            },
+            InstKind::ItoF => match self.get_value(inst.get_int_to_float_value()) {
+                Value::Top => Value::Top,
+                value => self.lookup(Expression::ItoF(value), inst),
+            },
+            InstKind::Call(callee) if self.pure_funcs.contains(&callee) => {
+                let args: Vec<Value> = inst
+                    .get_args()
+                    .iter()
+                    .map(|arg| self.get_value(*arg))
+                    .collect();
+                if args.contains(&Value::Top) {
+                    return Value::Top;
+                }
+                self.lookup(Expression::Call(callee, args), inst)
+            }
+            InstKind::Phi => {
+                let args: Vec<Value> = inst
+                    .get_operands()
+                    .iter()
+                    .map(|arg| self.get_value(*arg))
+                    .collect();
+                // 乐观地忽略还没有确定的操作数
+                let mut known = args.iter().filter(|arg| **arg != Value::Top);
+                match known.next() {
+                    None => Value::Top,
+                    Some(&first) if known.all(|arg| *arg == first) => first,
+                    _ => self.lookup(Expression::Phi(inst.get_parent_bb(), args), inst),
+                }
+            }
+            // load、参数、有副作用的调用等只和自己同余
+            _ => Value::Inst(inst),
+        }
+    }
+
+    fn eval_binary(&mut self, inst: ObjPtr<Inst>, op: BinOp) -> Value {
+        let lhs = self.get_value(inst.get_lhs());
+        let rhs = self.get_value(inst.get_rhs());
+        if lhs == Value::Top || rhs == Value::Top {
+            return Value::Top;
+        }
+        let is_int = inst.get_lhs().get_ir_type() == IrType::Int;
+        if is_int {
+            if let Some(value) = simplify_int(op, lhs, rhs) {
+                return value;
+            }
+        }
+
+        // 规范化: x-c变为x+(-c),大于比较变为小于比较,可交换运算的操作数按序号排序
+        let (op, lhs, rhs) = match (op, rhs) {
+            (BinOp::Sub, Value::Int(value)) if is_int => {
+                (BinOp::Add, lhs, Value::Int(value.wrapping_neg()))
+            }
+            (BinOp::Gt, _) => (BinOp::Lt, rhs, lhs),
+            (BinOp::Ge, _) => (BinOp::Le, rhs, lhs),
+            _ => (op, lhs, rhs),
+        };
+        let (lhs, rhs) = match op {
+            BinOp::Add
+            | BinOp::Mul
+            | BinOp::And
+            | BinOp::Or
+            | BinOp::Xor
+            | BinOp::Eq
+            | BinOp::Ne
+                if self.rank(rhs) < self.rank(lhs) =>
+            {
+                (rhs, lhs)
+            }
+            _ => (lhs, rhs),
+        };
+        self.lookup(Expression::Binary(op, lhs, rhs), inst)
+    }
+
+    /// 查找表达式所在的同余类,没有时以inst为代表建立新的同余类
+    fn lookup(&mut self, expression: Expression, inst: ObjPtr<Inst>) -> Value {
+        Value::Inst(*self.expressions.entry(expression).or_insert(inst))
+    }
+
+    fn get_value(&self, inst: ObjPtr<Inst>) -> Value {
+        match inst.get_kind() {
+            InstKind::ConstInt(value) => Value::Int(value),
+            InstKind::ConstFloat(value) => Value::Float(value.to_bits()),
+            _ if inst.is_global_var_or_param() => Value::Inst(inst),
+            _ => *self.values.get(&inst).unwrap_or(&Value::Top),
+        }
+    }
+
+    /// 可交换运算的操作数顺序: 常量在前,指令按逆后序
+    fn rank(&self, value: Value) -> (u8, i64) {
+        match value {
+            Value::Int(value) => (0, value as i64),
+            Value::Float(value) => (1, value as i64),
+            Value::Inst(inst) => (2, *self.order.get(&inst).unwrap_or(&0) as i64),
+            Value::Top => (3, 0),
+        }
+    }
+
+    /// 用支配自己的同类指令替换每条指令,值为常量的指令替换为常量
+    fn rewrite(&self, mut head: ObjPtr<BasicBlock>, pool: &mut ObjPool<Inst>) -> bool {
+        let dominator_tree = calculate_dominator(head);
+        // 每个同余类中已经确定保留的指令,按逆后序排列
+        let mut leaders: HashMap<Value, Vec<ObjPtr<Inst>>> = HashMap::new();
+        let mut replaced = vec![];
+        for &inst in self.insts.iter() {
+            let value = self.get_value(inst);
+            match value {
+                Value::Top => continue,
+                Value::Inst(leader) if leader == inst => {
+                    leaders.entry(value).or_default().push(inst);
+                    continue;
+                }
+                Value::Inst(leader) if leader.is_global_var_or_param() => {
+                    replaced.push((inst, leader));
+                    continue;
+                }
+                _ => {}
+            }
+            let members = leaders.entry(value).or_default();
+            match find_leader(members, inst, &dominator_tree) {
+                Some(leader) => replaced.push((inst, leader)),
+                None if inst.is_const() || matches!(value, Value::Inst(_)) => members.push(inst),
+                None => {
+                    // 常量放在头块开头,支配所有使用
+                    let inst_const = match value {
+                        Value::Int(value) => pool.make_int_const(value),
+                        Value::Float(value) => pool.make_float_const(f32::from_bits(value)),
+                        _ => unreachable!(),
+                    };
+                    head.push_front(inst_const);
+                    members.insert(0, inst_const);
+                    replaced.push((inst, inst_const));
+                }
+            }
+        }
+        for (inst, leader) in replaced.iter() {
+            replace_inst(*inst, *leader);
+        }
+        !replaced.is_empty()
+    }
+}
+
+/// 在同余类中找支配inst的指令,同一个块中排在前面的指令支配后面的指令
+fn find_leader(
+    members: &[ObjPtr<Inst>],
+    inst: ObjPtr<Inst>,
+    dominator_tree: &DominatorTree,
+) -> Option<ObjPtr<Inst>> {
+    members
+        .iter()
+        .find(|member| dominator_tree.is_dominate(&member.get_parent_bb(), &inst.get_parent_bb()))
+        .copied()
+}
+
+/// 整数运算的代数化简
+fn simplify_int(op: BinOp, lhs: Value, rhs: Value) -> Option<Value> {
+    if let (Value::Int(lhs), Value::Int(rhs)) = (lhs, rhs) {
+        return fold_int(op, lhs, rhs).map(Value::Int);
+    }
+    let same = lhs == rhs;
+    let value = match (op, lhs, rhs) {
+        (BinOp::Add, Value::Int(0), value)
+        | (BinOp::Add, value, Value::Int(0))
+        | (BinOp::Sub, value, Value::Int(0))
+        | (BinOp::Mul, Value::Int(1), value)
+        | (BinOp::Mul, value, Value::Int(1))
+        | (BinOp::Div, value, Value::Int(1))
+        | (BinOp::UDiv, value, Value::Int(1))
+        | (BinOp::And, Value::Int(-1), value)
+        | (BinOp::And, value, Value::Int(-1))
+        | (BinOp::Or, Value::Int(0), value)
+        | (BinOp::Or, value, Value::Int(0))
+        | (BinOp::Xor, Value::Int(0), value)
+        | (BinOp::Xor, value, Value::Int(0))
+        | (BinOp::Shl, value, Value::Int(0))
+        | (BinOp::LShr, value, Value::Int(0))
+        | (BinOp::AShr, value, Value::Int(0)) => value,
+        (BinOp::Mul, Value::Int(0), _)
+        | (BinOp::Mul, _, Value::Int(0))
+        | (BinOp::And, Value::Int(0), _)
+        | (BinOp::And, _, Value::Int(0))
+        | (BinOp::Rem, _, Value::Int(1))
+        | (BinOp::URem, _, Value::Int(1))
+        | (BinOp::Shl, Value::Int(0), _)
+        | (BinOp::LShr, Value::Int(0), _)
+        | (BinOp::AShr, Value::Int(0), _) => Value::Int(0),
+        (BinOp::Or, Value::Int(-1), _) | (BinOp::Or, _, Value::Int(-1)) => Value::Int(-1),
+        (BinOp::And, value, _) | (BinOp::Or, value, _) if same => value,
+        (BinOp::Sub, _, _) | (BinOp::Xor, _, _) if same => Value::Int(0),
+        (BinOp::Eq, _, _) | (BinOp::Le, _, _) | (BinOp::Ge, _, _) if same => Value::Int(1),
+        (BinOp::Ne, _, _) | (BinOp::Lt, _, _) | (BinOp::Gt, _, _) if same => Value::Int(0),
+        _ => return None,
+    };
+    Some(value)
+}
+
+#[cfg(test)]
+mod congruence_gvn_test {
+    use super::congruence_gvn;
+    use crate::ir::{
+        instruction::InstKind,
+        interpret,
+        module::Module,
+        test_util::parse_module,
+        tools::{bfs_bb_proceess, inst_process_in_bb},
+        verify_module, DEFAULT_STEP_LIMIT,
+    };
+
+    /// 解析IR并运行gvn,返回main中剩下的各类指令的数量
+    fn run_gvn(text: &str) -> (Module, usize, usize) {
+        let (mut module, pools) = parse_module(text);
+        congruence_gvn(&mut module, &mut (pools.bb, pools.inst), true);
+        assert_eq!(verify_module(&module), vec![]);
+        let mut phis = 0;
+        let mut binaries = 0;
+        bfs_bb_proceess(module.get_function("main").get_head(), |bb| {
+            inst_process_in_bb(bb.get_head_inst(), |inst| match inst.get_kind() {
+                InstKind::Phi => phis += 1,
+                InstKind::Binary(_) => binaries += 1,
+                _ => {}
+            })
+        });
+        (module, phis, binaries)
+    }
+
+    #[test]
+    fn test_induction_variables() {
+        // %i和%j初值和步长相同,只能乐观地假设它们相等才能证明
+        let (module, phis, binaries) = run_gvn(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %n = call i32 @getint()
+  br label %bb_loop
+
+bb_loop:
+  %i = phi i32 [ 0, %bb_entry ], [ %i2, %bb_loop ]
+  %j = phi i32 [ 0, %bb_entry ], [ %j2, %bb_loop ]
+  %s = phi i32 [ 0, %bb_entry ], [ %s2, %bb_loop ]
+  %d = sub i32 %i, %j
+  %s2 = add i32 %s, %d
+  %i2 = add i32 %i, 1
+  %j2 = add i32 1, %j
+  %c = icmp slt i32 %i2, %n
+  br i1 %c, label %bb_loop, label %bb_exit
+
+bb_exit:
+  %r = add i32 %s2, %j2
+  ret i32 %r
+}
+",
+        );
+        // %j被%i替换,%d恒为0所以%s恒为0,%r就是%i2
+        assert_eq!(phis, 1);
+        assert_eq!(binaries, 2);
+        let result = interpret(&module, "5", DEFAULT_STEP_LIMIT).unwrap();
+        assert_eq!(result.ret, 5);
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        // 交换操作数的加法和方向相反的比较在不同的分支中,只有支配使用的指令被保留
+        let (module, phis, binaries) = run_gvn(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %a = call i32 @getint()
+  %b = call i32 @getint()
+  %x = add i32 %a, %b
+  %c = icmp sgt i32 %a, %b
+  br i1 %c, label %bb_then, label %bb_else
+
+bb_then:
+  %y = add i32 %b, %a
+  %c2 = icmp slt i32 %b, %a
+  %z = sub i32 %b, -3
+  br label %bb_end
+
+bb_else:
+  %w = add i32 %b, 3
+  br label %bb_end
+
+bb_end:
+  %p = phi i32 [ %y, %bb_then ], [ %x, %bb_else ]
+  %q = phi i32 [ %z, %bb_then ], [ %w, %bb_else ]
+  %r = add i32 %p, %q
+  ret i32 %r
+}
+",
+        );
+        // %y、%c2被替换,%p就是%x; %z和%w同余但互不支配,%q保留
+        assert_eq!(phis, 1);
+        assert_eq!(binaries, 5);
+        let result = interpret(&module, "4 -1", DEFAULT_STEP_LIMIT).unwrap();
+        assert_eq!(result.ret, 5);
+    }
+}
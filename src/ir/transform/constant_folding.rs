@@ -414,6 +414,16 @@ pub fn fold_inst(inst_old: ObjPtr<Inst>, pool: &mut ObjPool<Inst>) -> bool {
                                         pool.make_float_const(val_left % val_right),
                                     );
                                 }
+                                BinOp::UDiv
+                                | BinOp::URem
+                                | BinOp::And
+                                | BinOp::Or
+                                | BinOp::Xor
+                                | BinOp::Shl
+                                | BinOp::LShr
+                                | BinOp::AShr => {
+                                    unreachable!("No float bitwise operation in ir")
+                                }
                                 BinOp::Eq => {
                                     if val_left == val_right {
                                         replace_inst_with_new(inst_old, pool.make_int_const(1));
@@ -495,6 +505,48 @@ pub fn fold_inst(inst_old: ObjPtr<Inst>, pool: &mut ObjPool<Inst>) -> bool {
                                         pool.make_int_const(val_left % val_right),
                                     );
                                 }
+                                BinOp::UDiv | BinOp::URem if val_right == 0 => {
+                                    return false;
+                                }
+                                BinOp::UDiv => {
+                                    replace_inst_with_new(
+                                        inst_old,
+                                        pool.make_int_const((val_left as u32 / val_right as u32) as i32),
+                                    );
+                                }
+                                BinOp::URem => {
+                                    replace_inst_with_new(
+                                        inst_old,
+                                        pool.make_int_const((val_left as u32 % val_right as u32) as i32),
+                                    );
+                                }
+                                BinOp::And => {
+                                    replace_inst_with_new(inst_old, pool.make_int_const(val_left & val_right));
+                                }
+                                BinOp::Or => {
+                                    replace_inst_with_new(inst_old, pool.make_int_const(val_left | val_right));
+                                }
+                                BinOp::Xor => {
+                                    replace_inst_with_new(inst_old, pool.make_int_const(val_left ^ val_right));
+                                }
+                                BinOp::Shl => {
+                                    replace_inst_with_new(
+                                        inst_old,
+                                        pool.make_int_const(val_left.wrapping_shl(val_right as u32)),
+                                    );
+                                }
+                                BinOp::LShr => {
+                                    replace_inst_with_new(
+                                        inst_old,
+                                        pool.make_int_const((val_left as u32).wrapping_shr(val_right as u32) as i32),
+                                    );
+                                }
+                                BinOp::AShr => {
+                                    replace_inst_with_new(
+                                        inst_old,
+                                        pool.make_int_const(val_left.wrapping_shr(val_right as u32)),
+                                    );
+                                }
                                 BinOp::Eq => {
                                     if val_left == val_right {
                                         replace_inst_with_new(inst_old, pool.make_int_const(1));
@@ -595,6 +647,21 @@ pub fn fold_inst(inst_old: ObjPtr<Inst>, pool: &mut ObjPool<Inst>) -> bool {
                 }
             }
         }
+        InstKind::Select => {
+            // 条件为常量或两个值相同时直接使用选中的值
+            let chosen = match get_iconstant(inst_old.get_select_cond()) {
+                Some(0) => Some(inst_old.get_false_value()),
+                Some(_) => Some(inst_old.get_true_value()),
+                None if inst_old.get_true_value() == inst_old.get_false_value() => {
+                    Some(inst_old.get_true_value())
+                }
+                None => None,
+            };
+            if let Some(value) = chosen {
+                replace_inst(inst_old, value);
+                return true;
+            }
+        }
         InstKind::FtoI => {
             let operands = inst_old.get_operands();
             if let Some(val) = get_fconstant(operands[0]) {
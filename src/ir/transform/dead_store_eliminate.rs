@@ -0,0 +1,183 @@
+//! 删除不会被读取的内存
+//!
+//! 从标量全局变量、全局数组和局部数组出发,沿gep、取全局数组地址的load和phi找到所有派生的指针。
+//! 如果这些指针都没有被load读取,也没有作为实参传给可能读它的函数,那么写入这块内存的值不会被观察到:
+//! 删除所有对它的store和hitsz_memset,剩下的gep、alloca和全局变量交给死代码删除。
+use std::collections::HashSet;
+
+use crate::{
+    ir::{
+        analysis::alias::AliasAnalysis,
+        instruction::{Inst, InstKind},
+        module::Module,
+        tools::{bfs_inst_process, func_process},
+    },
+    utility::ObjPtr,
+};
+
+pub fn dead_store_eliminate(module: &mut Module, optimize_flag: bool) {
+    if !optimize_flag {
+        return;
+    }
+
+    let alias = AliasAnalysis::new(module);
+    let mut objects: Vec<ObjPtr<Inst>> = module
+        .get_all_var()
+        .iter()
+        .map(|(_, var)| *var)
+        .filter(|var| {
+            !matches!(
+                var.get_kind(),
+                InstKind::GlobalConstInt(_) | InstKind::GlobalConstFloat(_)
+            )
+        })
+        .collect();
+    func_process(module, |_, func| {
+        bfs_inst_process(func.get_head(), |inst| {
+            if let InstKind::Alloca(_) = inst.get_kind() {
+                objects.push(inst);
+            }
+        })
+    });
+
+    for object in objects {
+        if let Some(writes) = get_unread_writes(object, &alias) {
+            for mut write in writes {
+                write.remove_self();
+            }
+        }
+    }
+}
+
+/// 内存没有被读取时返回所有写它的指令,否则返回None
+fn get_unread_writes(object: ObjPtr<Inst>, alias: &AliasAnalysis) -> Option<HashSet<ObjPtr<Inst>>> {
+    let mut writes = HashSet::new();
+    let mut visited = HashSet::new();
+    let mut stack = vec![object];
+    while let Some(ptr) = stack.pop() {
+        if !visited.insert(ptr) {
+            continue;
+        }
+        for user in ptr.get_use_list().iter() {
+            match user.get_kind() {
+                InstKind::Gep if user.get_gep_ptr() == ptr => stack.push(*user),
+                InstKind::Load if user.is_global_array_load() => stack.push(*user),
+                InstKind::Phi => stack.push(*user),
+                InstKind::Store if user.get_value() != ptr => {
+                    writes.insert(*user);
+                }
+                InstKind::Call(callee) => {
+                    let indexes = user
+                        .get_args()
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, arg)| **arg == ptr)
+                        .map(|(index, _)| index);
+                    // 被调用函数只写这块内存时调用仍然保留
+                    match alias.get_summary(&callee) {
+                        Some(summary) => {
+                            if summary.ref_all
+                                || indexes
+                                    .into_iter()
+                                    .any(|index| summary.ref_params.contains(&index))
+                            {
+                                return None;
+                            }
+                        }
+                        None if callee == "hitsz_memset" => {
+                            writes.insert(*user);
+                        }
+                        None => return None,
+                    }
+                }
+                _ => return None,
+            }
+        }
+    }
+    Some(writes)
+}
+
+#[cfg(test)]
+mod dead_store_test {
+    use super::dead_store_eliminate;
+    use crate::ir::{
+        instruction::InstKind,
+        interpret,
+        test_util::parse_module,
+        tools::bfs_inst_process,
+        transform::dead_code_eliminate::{dead_code_eliminate, global_eliminate},
+        verify_module, DEFAULT_STEP_LIMIT,
+    };
+
+    #[test]
+    fn test_dead_array() {
+        // %a和@g只被写,%b被f读取,@h被set写但在main中被读取
+        let (mut module, _) = parse_module(
+            "
+@g = dso_local global i32 0, align 4
+@h = dso_local global [4 x i32] [ i32 0], align 4
+
+define dso_local signext i32 @f(i32* noundef %p) #0 {
+bb_entry:
+  %v = load i32, i32* %p, align 4
+  ret i32 %v
+}
+
+define dso_local void @set(i32* noundef %p, i32 noundef signext %v) #0 {
+bb_entry:
+  store i32 %v, i32* %p, align 4
+  ret void
+}
+
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %a = alloca [4 x i32], align 4
+  %b = alloca [4 x i32], align 4
+  %a0 = getelementptr inbounds [4 x i32], [4 x i32]* %a, i32 0, i32 0
+  call void @hitsz_memset(i32* noundef %a0, i32 noundef 0, i32 noundef 16)
+  %n = call i32 @getint()
+  %ai = getelementptr inbounds [4 x i32], [4 x i32]* %a, i32 0, i32 %n
+  store i32 %n, i32* %ai, align 4
+  store i32 %n, i32* @g, align 4
+  %bi = getelementptr inbounds [4 x i32], [4 x i32]* %b, i32 0, i32 %n
+  store i32 %n, i32* %bi, align 4
+  %harr = load ptr, ptr @h, align 8
+  %h0 = getelementptr inbounds [4 x i32], [4 x i32]* %harr, i32 0, i32 0
+  call void @set(i32* noundef %h0, i32 noundef %n)
+  %x = call i32 @f(i32* noundef %bi)
+  %y = load i32, i32* %h0, align 4
+  %z = add i32 %x, %y
+  ret i32 %z
+}
+
+declare i32 @getint()
+declare void @hitsz_memset(ptr, i32, i32)
+",
+        );
+        dead_store_eliminate(&mut module, true);
+        dead_code_eliminate(&mut module, true);
+        global_eliminate(&mut module);
+        assert_eq!(verify_module(&module), vec![]);
+
+        let mut insts = vec![];
+        bfs_inst_process(module.get_function("main").get_head(), |inst| {
+            match inst.get_kind() {
+                InstKind::Alloca(_) | InstKind::Store => insts.push(inst.get_kind()),
+                InstKind::Call(callee) => insts.push(InstKind::Call(callee)),
+                _ => {}
+            }
+        });
+        assert_eq!(
+            format!("{:?}", insts),
+            "[Alloca(4), Call(getint), Store, Call(set), Call(f)]"
+        );
+        let mut vars: Vec<String> = module
+            .get_all_var()
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect();
+        vars.sort();
+        assert_eq!(vars, vec!["h"]);
+        assert_eq!(interpret(&module, "2", DEFAULT_STEP_LIMIT).unwrap().ret, 4);
+    }
+}
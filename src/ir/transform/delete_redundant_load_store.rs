@@ -1,253 +1,316 @@
-use std::collections::{HashMap, HashSet};
+//! load,store消除
+//!
+//! 借助别名分析,load可以复用同一位置上此前load的值或store的值;
+//! 一个store在被读取之前被同一位置的store覆盖,则这个store可以删除。
+//! 先在块内线性扫描,再借助MemorySSA跨块转发和删除store
+use std::collections::HashSet;
 
 use crate::{
     ir::{
-        analysis::{store_map::get_store_map, dominator_tree::{self, DominatorTree, calculate_dominator}},
+        analysis::{
+            alias::{AliasAnalysis, AliasResult, ModRef},
+            dominator_tree::calculate_dominator,
+            memory_ssa::{AccessId, AccessKind, MemorySSA},
+        },
+        basicblock::BasicBlock,
         instruction::{Inst, InstKind},
         module::Module,
-        tools::{bfs_bb_proceess, func_process, replace_inst, bfs_inst_process}, basicblock::BasicBlock,
+        tools::{bfs_bb_proceess, func_process, replace_inst},
     },
-    utility::{ObjPtr, ObjPool},
+    utility::ObjPtr,
 };
 
 pub fn load_store_opt(module: &mut Module) -> bool {
-    let mut func_map = get_store_map(module);
+    let alias = AliasAnalysis::new(module);
     let mut changed = false;
-    func_process(module, |_func_name, func| {
+    func_process(module, |func_name, func| {
         bfs_bb_proceess(func.get_head(), |bb| {
-            let mut map = HashMap::new();
+            let mut state = BlockState::default();
             let mut inst = bb.get_head_inst();
             while !inst.is_tail() {
                 let next = inst.get_next();
-                changed |= delete_inst(&mut func_map, &mut map, inst);
+                changed |= state.delete_inst(&alias, inst);
                 inst = next;
             }
         });
+        changed |= memory_ssa_opt(func.get_head(), &alias, func_name == "main");
     });
     changed
 }
 
-pub fn load_store_opt_plus(module: &mut Module,pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>)){
-    // todo:收集gep信息
-    println!("load_store_opt_plus");
-    func_process(module, |func_name, func| {
-        let dominator_tree = calculate_dominator(func.get_head());
-        let mut flag = true;
-        if !(func_name=="main".to_string()){
-            return;
+/// 跨块的load,store消除,main返回后程序结束,内存不会再被读取
+fn memory_ssa_opt(head: ObjPtr<BasicBlock>, alias: &AliasAnalysis, is_main: bool) -> bool {
+    let mssa = MemorySSA::new(head, alias);
+    let dominator_tree = calculate_dominator(head);
+    let insts = mssa.get_insts();
+    let mut changed = false;
+
+    // 先求出所有死store再删除,被删除的store的覆盖者也会覆盖它覆盖的store
+    let dead_stores: Vec<ObjPtr<Inst>> = insts
+        .iter()
+        .filter(|inst| inst.get_kind() == InstKind::Store && is_dead_store(&mssa, **inst, is_main))
+        .copied()
+        .collect();
+
+    // 广度优先的顺序中支配者在前
+    let mut loads: Vec<(AccessId, ObjPtr<Inst>)> = vec![];
+    for inst in insts {
+        if inst.get_kind() != InstKind::Load || inst.is_global_array_load() {
+            continue;
         }
-        bfs_inst_process(func.get_head(), |inst|
-        { // 所有gep偏移均为常量
-            if inst.get_kind()==InstKind::Gep{
-                println!("gep");
-                let offset = inst.get_gep_offset();
-                if !offset.is_int_const(){
-                    flag = false;
-                }
+        let (ptr, bb) = (inst.get_ptr(), inst.get_parent_bb());
+        let clobber = mssa.get_clobber(inst, ptr);
+        if let AccessKind::Def(store) = mssa.get_memory_access(clobber).kind {
+            if store.get_kind() == InstKind::Store
+                && mssa.alias(store.get_dest(), ptr) == AliasResult::MustAlias
+                && dominator_tree.is_dominate(&store.get_parent_bb(), &bb)
+            {
+                replace_inst(inst, store.get_value());
+                changed = true;
+                continue;
             }
         }
-        );
-        if flag {
-            let mut val_map:HashMap<ObjPtr<Inst>, Vec<ObjPtr<Inst>>> = HashMap::new();
-            bfs_inst_process(func.get_head(), |inst|
-        {
-            if inst.get_kind()==InstKind::Store{
-                println!("{:?}",inst);
-            }
-            if inst.get_kind()==InstKind::Store||inst.get_kind()==InstKind::Load{//遍历,存储每一个地址的load,store操作
-                // println!("{:?}",inst);
-                let inst_gep = inst.get_operand(0);
-                if let Some(vec_tmp) = val_map.get_mut(&inst_gep){
-                    vec_tmp.push(inst);
-                }else{
-                    val_map.insert(inst_gep, vec![inst]);
-                }
-            }
-        });
-            for (_,vec_l_s) in &mut val_map{
-                println!("zheli");
-                if vec_l_s.len()==2&&vec_l_s[0].get_kind()==InstKind::Store&&vec_l_s[1].get_kind()==InstKind::Load{
-                    // println!("zheli");
-                    if dominator_tree.is_dominate(&vec_l_s[0].get_parent_bb(), &vec_l_s[1].get_parent_bb()){
-                        println!("删除指令");
-                        replace_inst(vec_l_s[1], vec_l_s[0].get_operand(1));//检查store值是不是operand1
-                        vec_l_s.remove(1);
-                    }
-                }
-            }
-            for (_,vec_l_s) in val_map{
-                if vec_l_s.len()==1&&vec_l_s[0].get_kind()==InstKind::Store{
-                    if vec_l_s[0].get_use_list().len()==0{
-                        vec_l_s[0].as_mut().remove_self();
-                    }
-                }
-            }
-
+        // 块内的load已经在线性扫描中合并
+        if let Some((_, load)) = loads.iter().find(|(load_clobber, load)| {
+            *load_clobber == clobber
+                && load.get_parent_bb() != bb
+                && dominator_tree.is_dominate(&load.get_parent_bb(), &bb)
+                && mssa.alias(load.get_ptr(), ptr) == AliasResult::MustAlias
+        }) {
+            replace_inst(inst, *load);
+            changed = true;
+            continue;
         }
-    });
+        loads.push((clobber, inst));
+    }
+
+    for store in dead_stores {
+        store.as_mut().remove_self();
+        changed = true;
+    }
+    changed
 }
 
-pub fn get_global_array_ptr(inst: ObjPtr<Inst>) -> Option<ObjPtr<Inst>> {
-    match inst.get_kind() {
-        InstKind::Store => {
-            let operands = inst.get_operands();
-            match operands[0].get_kind() {
-                InstKind::Gep => {
-                    let operands2 = operands[0].get_operands();
-                    match operands2[0].get_kind() {
-                        InstKind::Load => {
-                            let inst_vec = operands2[0].get_operands();
-                            return Some(inst_vec[0]);
-                        }
-                        _ => {}
-                    }
-                }
-                _ => {}
-            }
+/// 判断store写入的值是否在被读取之前就被覆盖或者再也不会被读取
+fn is_dead_store(mssa: &MemorySSA, store: ObjPtr<Inst>, is_main: bool) -> bool {
+    let ptr = store.get_dest();
+    let mut visited = HashSet::new();
+    let mut stack = mssa.get_users(mssa.get_access(store).unwrap()).clone();
+    while let Some(access) = stack.pop() {
+        if !visited.insert(access) {
+            continue;
         }
-        InstKind::Gep => {
-            let operands2 = inst.get_operands();
-            match operands2[0].get_kind() {
-                InstKind::Load => {
-                    let inst_vec = operands2[0].get_operands();
-                    return Some(inst_vec[0]);
-                }
-                _ => {}
+        match mssa.get_memory_access(access).kind {
+            AccessKind::Use(inst) if is_main && inst.get_kind() == InstKind::Return => {}
+            AccessKind::Use(inst) | AccessKind::Def(inst)
+                if mssa.get_mod_ref(inst, ptr).is_ref() =>
+            {
+                return false;
             }
+            AccessKind::Def(inst)
+                if inst.get_kind() == InstKind::Store
+                    && mssa.alias(inst.get_dest(), ptr) == AliasResult::MustAlias => {}
+            AccessKind::Use(_) => {}
+            _ => stack.extend(mssa.get_users(access).iter().copied()),
         }
-        InstKind::Load => {
-            let operands = inst.get_operands();
-            match operands[0].get_kind() {
-                InstKind::Gep => {
-                    let operands2 = operands[0].get_operands();
-                    match operands2[0].get_kind() {
-                        InstKind::Load => {
-                            let inst_vec = operands2[0].get_operands();
-                            return Some(inst_vec[0]);
-                        }
-                        _ => {}
-                    }
-                }
-                _ => {}
-            }
-        }
-        _ => {}
     }
-    None
+    true
 }
 
-pub fn delete_inst(
-    func_map: &mut HashMap<String, HashSet<ObjPtr<Inst>>>,
-    map: &mut HashMap<ObjPtr<Inst>, ObjPtr<Inst>>,
-    inst: ObjPtr<Inst>,
-) -> bool {
-    match inst.get_kind() {
-        InstKind::Load => {
-            let operands = inst.get_operands();
-            if let Some(inst_old) = map.get(&operands[0]) {
-                let operands_temp = inst_old.get_operands();
-                match inst_old.get_kind() {
-                    InstKind::Load => {
-                        replace_inst(inst, inst_old.clone());
-                        return true;
-                    }
-                    InstKind::Store => {
-                        replace_inst(inst, operands_temp[1]);
-                        return true;
-                    }
-                    _ => unreachable!(),
+#[derive(Default)]
+struct BlockState {
+    /// 已知的(地址,值)
+    values: Vec<(ObjPtr<Inst>, ObjPtr<Inst>)>,
+    /// 还没有被读过的store
+    stores: Vec<ObjPtr<Inst>>,
+}
+
+impl BlockState {
+    fn get_value(&self, alias: &AliasAnalysis, ptr: ObjPtr<Inst>) -> Option<ObjPtr<Inst>> {
+        self.values
+            .iter()
+            .find(|(addr, _)| alias.alias(*addr, ptr) == AliasResult::MustAlias)
+            .map(|(_, value)| *value)
+    }
+
+    fn delete_inst(&mut self, alias: &AliasAnalysis, inst: ObjPtr<Inst>) -> bool {
+        match inst.get_kind() {
+            InstKind::Load if inst.is_global_array_load() => {}
+            InstKind::Load => {
+                let ptr = inst.get_ptr();
+                if let Some(value) = self.get_value(alias, ptr) {
+                    replace_inst(inst, value);
+                    return true;
                 }
-            } else {
-                map.insert(operands[0], inst);
+                self.values.push((ptr, inst));
+                self.stores
+                    .retain(|store| alias.alias(store.get_dest(), ptr) == AliasResult::NoAlias);
             }
-        }
-        InstKind::Store => {
-            let operands = inst.get_operands();
-            if let Some(inst_old) = map.get(&operands[0]) {
-                match inst_old.get_kind() {
-                    InstKind::Load => {
-                        map.insert(operands[0], inst);
-                        return false;
-                    }
-                    InstKind::Store => {
-                        replace_inst(inst_old.clone(), inst);
-                        map.insert(operands[0], inst);
-                        return true;
-                    }
-                    _ => unreachable!(),
+            InstKind::Store => {
+                let (ptr, value) = (inst.get_dest(), inst.get_value());
+                // 存入的值和内存中的值相同
+                if self.get_value(alias, ptr) == Some(value) {
+                    inst.as_mut().remove_self();
+                    return true;
                 }
-            } else {
-                map.insert(operands[0], inst);
-            }
-        }
-        InstKind::Call(funcname) => {
-            let args = inst.get_args();
-            for arg in args {
-                match arg.get_kind() {
-                    InstKind::Gep => {
-                        let oprs = arg.get_operands();
-                        let ptr = oprs[0];
-                        match ptr.get_kind() {
-                            InstKind::Alloca(_) => {
-                                //todo:对于局部数组
-                                // println!("该参数为局部数组");
-                                for (tgep, _) in map.clone() {
-                                    if tgep.get_kind() == InstKind::Gep {
-                                        let operands_temp = tgep.get_operands();
-                                        if operands_temp[0] == ptr {
-                                            map.remove(&tgep);
-                                        }
-                                    }
-                                }
-                            }
-                            InstKind::Load => {
-                                for (tgep, _) in map.clone() {
-                                    if tgep.get_kind() == InstKind::Gep {
-                                        let operands_temp = tgep.get_operands();
-                                        if operands_temp[0] == ptr {
-                                            map.remove(&tgep);
-                                        }
-                                    }
-                                }
-                            }
-                            InstKind::Parameter => {
-                                for (tgep, _) in map.clone() {
-                                    if tgep.get_kind() == InstKind::Gep {
-                                        let operands_temp = tgep.get_operands();
-                                        if operands_temp[0] == ptr {
-                                            map.remove(&tgep);
-                                        }
-                                    }
-                                }
-                            }
-                            _ => {
-                                unreachable!()
-                            }
-                        }
+                let mut changed = false;
+                self.stores.retain(|store| {
+                    if alias.alias(store.get_dest(), ptr) == AliasResult::MustAlias {
+                        store.as_mut().remove_self();
+                        changed = true;
+                        false
+                    } else {
+                        true
                     }
-                    _ => {}
-                }
+                });
+                self.values
+                    .retain(|(addr, _)| alias.alias(*addr, ptr) == AliasResult::NoAlias);
+                self.values.push((ptr, value));
+                self.stores.push(inst);
+                return changed;
             }
-            if let Some(set) = func_map.get(&funcname) {
-                //对于全局变量
-                for i in set {
-                    if let Some(_) = map.remove(i) {
-                        continue;
-                    }
-                    if let Some(ptr) = get_global_array_ptr(*i) {
-                        //对于全局数组
-                        for (pptr, j) in map.clone() {
-                            if let Some(ptr2) = get_global_array_ptr(j) {
-                                if ptr2 == ptr {
-                                    map.remove(&pptr);
-                                }
-                            }
-                        }
-                    }
-                }
+            InstKind::Call(_) => {
+                self.values
+                    .retain(|(addr, _)| !alias.get_mod_ref(inst, *addr).is_mod());
+                self.stores
+                    .retain(|store| alias.get_mod_ref(inst, store.get_dest()) == ModRef::NoModRef);
             }
+            _ => {}
         }
-        _ => {}
+        false
+    }
+}
+
+#[cfg(test)]
+mod load_store_test {
+    use super::load_store_opt;
+    use crate::ir::{
+        instruction::InstKind, interpret, module::Module, test_util::parse_module,
+        tools::bfs_inst_process, verify_module, DEFAULT_STEP_LIMIT,
+    };
+
+    /// 解析IR并运行load,store消除,返回main中剩下的load和store个数
+    fn run_opt(text: &str) -> (Module, usize, usize) {
+        let (mut module, _) = parse_module(text);
+        load_store_opt(&mut module);
+        assert_eq!(verify_module(&module), vec![]);
+        let (mut loads, mut stores) = (0, 0);
+        bfs_inst_process(module.get_function("main").get_head(), |inst| {
+            match inst.get_kind() {
+                InstKind::Load => loads += 1,
+                InstKind::Store => stores += 1,
+                _ => {}
+            }
+        });
+        (module, loads, stores)
+    }
+
+    #[test]
+    fn test_may_alias_load() {
+        // %p和%q可能指向同一位置,第一个store被读过,不能删除;f不访问数组,%p的值可以转发,
+        // 之后局部数组不再被读取,第二个store被删除
+        let (module, loads, stores) = run_opt(
+            "
+define dso_local signext i32 @f(i32 noundef signext %x) #0 {
+bb_entry:
+  %y = add i32 %x, 1
+  ret i32 %y
+}
+
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %a = alloca [4 x i32], align 4
+  %i = call i32 @getint()
+  %p = getelementptr inbounds [4 x i32], [4 x i32]* %a, i32 0, i32 1
+  %q = getelementptr inbounds [4 x i32], [4 x i32]* %a, i32 0, i32 %i
+  store i32 5, i32* %p, align 4
+  %x = load i32, i32* %q, align 4
+  store i32 7, i32* %p, align 4
+  %r = call i32 @f(i32 noundef %x)
+  %y = load i32, i32* %p, align 4
+  %z = add i32 %r, %y
+  ret i32 %z
+}
+",
+        );
+        assert_eq!((loads, stores), (1, 1));
+        assert_eq!(interpret(&module, "1", DEFAULT_STEP_LIMIT).unwrap().ret, 13);
+    }
+
+    #[test]
+    fn test_dead_store() {
+        // 第一个store在被读取之前被覆盖
+        let (module, loads, stores) = run_opt(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %a = alloca [4 x i32], align 4
+  %i = call i32 @getint()
+  %p = getelementptr inbounds [4 x i32], [4 x i32]* %a, i32 0, i32 %i
+  %q = getelementptr inbounds [4 x i32], [4 x i32]* %a, i32 0, i32 3
+  store i32 5, i32* %p, align 4
+  store i32 6, i32* %q, align 4
+  store i32 7, i32* %p, align 4
+  %x = load i32, i32* %q, align 4
+  ret i32 %x
+}
+",
+        );
+        assert_eq!((loads, stores), (1, 2));
+        assert_eq!(interpret(&module, "3", DEFAULT_STEP_LIMIT).unwrap().ret, 7);
+    }
+
+    #[test]
+    fn test_across_blocks() {
+        // 循环前的store转发给循环中和循环后的load,两个分支都覆盖的store被删除
+        let (module, loads, stores) = run_opt(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %a = alloca [4 x i32], align 4
+  %p0 = getelementptr inbounds [4 x i32], [4 x i32]* %a, i32 0, i32 0
+  %p1 = getelementptr inbounds [4 x i32], [4 x i32]* %a, i32 0, i32 1
+  store i32 1, i32* %p0, align 4
+  store i32 2, i32* %p1, align 4
+  %n = call i32 @getint()
+  br label %bb_loop
+
+bb_loop:
+  %i = phi i32 [ 0, %bb_entry ], [ %i2, %bb_body ]
+  %c = icmp slt i32 %i, %n
+  br i1 %c, label %bb_body, label %bb_exit
+
+bb_body:
+  %x = load i32, i32* %p0, align 4
+  %x2 = add i32 %x, %i
+  store i32 %x2, i32* %p1, align 4
+  %i2 = add i32 %i, 1
+  br label %bb_loop
+
+bb_exit:
+  store i32 9, i32* %p0, align 4
+  %c2 = icmp sgt i32 %n, 5
+  br i1 %c2, label %bb_then, label %bb_else
+
+bb_then:
+  store i32 6, i32* %p0, align 4
+  br label %bb_end
+
+bb_else:
+  store i32 7, i32* %p0, align 4
+  br label %bb_end
+
+bb_end:
+  %y = load i32, i32* %p0, align 4
+  %z = load i32, i32* %p1, align 4
+  %r = add i32 %y, %z
+  ret i32 %r
+}
+",
+        );
+        assert_eq!((loads, stores), (2, 5));
+        assert_eq!(interpret(&module, "3", DEFAULT_STEP_LIMIT).unwrap().ret, 10);
+        assert_eq!(interpret(&module, "8", DEFAULT_STEP_LIMIT).unwrap().ret, 14);
+        assert_eq!(interpret(&module, "0", DEFAULT_STEP_LIMIT).unwrap().ret, 9);
     }
-    false
 }
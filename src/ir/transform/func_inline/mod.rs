@@ -14,8 +14,8 @@ use crate::{
     utility::{ObjPool, ObjPtr},
 };
 
-mod copy_func;
-mod get_optimizate;
+pub(super) mod copy_func;
+pub(super) mod get_optimizate;
 mod inline_operation;
 
 use self::{copy_func::copy_func, inline_operation::delete_uncalled_func};
@@ -23,6 +23,16 @@ use self::{get_optimizate::gep_optimize, inline_operation::inline_func};
 
 use super::{bfs_inst_process, inst_process_in_bb};
 
+/// 指令数超过这个值的函数不内联,ipcp只特化不会被内联的函数,也按这个值判断
+pub const INLINE_SIZE_LIMIT: usize = 300;
+
+/// 函数中的指令数
+pub fn func_size(func: ObjPtr<Function>) -> usize {
+    let mut size = 0;
+    bfs_inst_process(func.get_head(), |_| size += 1);
+    size
+}
+
 pub fn inline_run(module: &mut Module, pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>)) {
     let mut call_map = call_map_gen(module);
 
@@ -55,6 +65,10 @@ fn inline_no_self_call(
                 if call_map.contains_edge(succ, succ) {
                     break;
                 }
+                let callee = module.get_function(succ);
+                if func_size(callee) > INLINE_SIZE_LIMIT {
+                    continue;
+                }
 
                 changed = true;
 
@@ -67,7 +81,6 @@ fn inline_no_self_call(
 
                 // 内联函数
                 let caller = module.get_function(&func_name);
-                let callee = module.get_function(succ);
                 inline_func(caller, callee, succ, module.get_all_var(), pools);
             }
         }
@@ -101,8 +114,11 @@ fn inline_no_succ(
         let mut delete_list = Vec::new();
         for (func_name, succs) in call_map.iter() {
             if succs.is_empty() && !func_name.eq("main") {
-                changed = true;
                 let callee = module.get_function(func_name);
+                if func_size(callee) > INLINE_SIZE_LIMIT {
+                    continue;
+                }
+                changed = true;
                 let callers = call_map.find_predecessors(func_name);
                 for caller_name in callers {
                     let caller = module.get_function(&caller_name);
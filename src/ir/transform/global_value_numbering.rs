@@ -30,6 +30,8 @@ pub struct CongruenceClass {
     mul_congruence: Congruence,
     div_congruence: Congruence,
     rem_congruence: Congruence,
+    bit_congruence: Congruence,
+    select_congruence: Congruence,
     ne_congruence: Congruence,
     cmp_congruence: Congruence,
     call_congruence: Congruence,
@@ -50,6 +52,8 @@ impl CongruenceClass {
             mul_congruence: Congruence::new(),
             div_congruence: Congruence::new(),
             rem_congruence: Congruence::new(),
+            bit_congruence: Congruence::new(),
+            select_congruence: Congruence::new(),
             ne_congruence: Congruence::new(),
             cmp_congruence: Congruence::new(),
             call_congruence: Congruence::new(),
@@ -62,6 +66,8 @@ impl CongruenceClass {
         vec.push(&mut self.mul_congruence);
         vec.push(&mut self.div_congruence);
         vec.push(&mut self.rem_congruence);
+        vec.push(&mut self.bit_congruence);
+        vec.push(&mut self.select_congruence);
         vec.push(&mut self.ne_congruence);
         vec.push(&mut self.cmp_congruence);
         vec.push(&mut self.call_congruence);
@@ -88,6 +94,14 @@ impl CongruenceClass {
                 BinOp::Mul => Some(&self.mul_congruence),
                 BinOp::Div => Some(&self.div_congruence),
                 BinOp::Rem => Some(&self.rem_congruence),
+                BinOp::UDiv
+                | BinOp::URem
+                | BinOp::And
+                | BinOp::Or
+                | BinOp::Xor
+                | BinOp::Shl
+                | BinOp::LShr
+                | BinOp::AShr => Some(&self.bit_congruence),
                 BinOp::Ne => Some(&self.ne_congruence),
             },
             InstKind::Unary(unop) => match unop {
@@ -99,6 +113,7 @@ impl CongruenceClass {
             InstKind::ConstFloat(_) => Some(&self.float_congruence),
             InstKind::FtoI => Some(&self.ftoi_congruence),
             InstKind::ItoF => Some(&self.itof_congruence),
+            InstKind::Select => Some(&self.select_congruence),
             InstKind::Call(_) => Some(&self.call_congruence),
             _ => {
                 // println!("kind:{:?}", inst.get_kind());
@@ -119,6 +134,14 @@ impl CongruenceClass {
                 BinOp::Mul => Some(&mut self.mul_congruence),
                 BinOp::Div => Some(&mut self.div_congruence),
                 BinOp::Rem => Some(&mut self.rem_congruence),
+                BinOp::UDiv
+                | BinOp::URem
+                | BinOp::And
+                | BinOp::Or
+                | BinOp::Xor
+                | BinOp::Shl
+                | BinOp::LShr
+                | BinOp::AShr => Some(&mut self.bit_congruence),
                 BinOp::Ne => Some(&mut self.ne_congruence),
             },
             InstKind::Unary(unop) => match unop {
@@ -130,6 +153,7 @@ impl CongruenceClass {
             InstKind::ConstFloat(_) => Some(&mut self.float_congruence),
             InstKind::FtoI => Some(&mut self.ftoi_congruence),
             InstKind::ItoF => Some(&mut self.itof_congruence),
+            InstKind::Select => Some(&mut self.select_congruence),
             InstKind::Call(_) => Some(&mut self.call_congruence),
             _ => None,
         }
@@ -499,11 +523,24 @@ pub fn compare_two_inst(
                 let operands2 = inst2.get_operands();
                 return compare_two_inst_with_index(operands1[0], operands2[0], congrunce_class);
             }
+            InstKind::Select => {
+                let operands1 = inst1.get_operands();
+                let operands2 = inst2.get_operands();
+                return operands1.iter().zip(operands2.iter()).all(|(op1, op2)| {
+                    compare_two_inst_with_index(*op1, *op2, congrunce_class)
+                });
+            }
             InstKind::Binary(binop1) => match inst2.get_kind() {
                 InstKind::Binary(binop2) => {
                     if binop1 == binop2 && inst1.get_ir_type() == IrType::Int {
                         match binop1 {
-                            BinOp::Add | BinOp::Eq | BinOp::Mul | BinOp::Ne => {
+                            BinOp::Add
+                            | BinOp::Eq
+                            | BinOp::Mul
+                            | BinOp::Ne
+                            | BinOp::And
+                            | BinOp::Or
+                            | BinOp::Xor => {
                                 let operands1 = inst1.get_operands();
                                 let operands2 = inst2.get_operands();
                                 return compare_two_operands(operands1, operands2, congrunce_class);
@@ -563,10 +600,11 @@ pub fn compare_two_inst(
                 InstKind::Binary(binop2) => {
                     let operands1 = inst1.get_operands();
                     let operands2 = inst2.get_operands();
-                    if (binop1 == BinOp::Ge && binop2 == BinOp::Lt)
-                        || (binop1 == BinOp::Gt && binop2 == BinOp::Le)
-                        || (binop1 == BinOp::Le && binop2 == BinOp::Gt)
-                        || (binop1 == BinOp::Lt && binop2 == BinOp::Ge)
+                    // a>=b等价于b<=a,a>b等价于b<a
+                    if (binop1 == BinOp::Ge && binop2 == BinOp::Le)
+                        || (binop1 == BinOp::Gt && binop2 == BinOp::Lt)
+                        || (binop1 == BinOp::Le && binop2 == BinOp::Ge)
+                        || (binop1 == BinOp::Lt && binop2 == BinOp::Gt)
                     {
                         return compare_two_inst_with_index(
                             operands1[0],
@@ -0,0 +1,273 @@
+//! 将短小且没有副作用的分支转换为select
+//!
+//! 处理以下两种结构, bb以条件分支结尾, join中的phi在两条路径上取不同的值:
+//! * 菱形: bb -> (then, else) -> join
+//! * 三角形: bb -> then -> join, bb -> join
+//!
+//! then和else中的指令被提前到bb中无条件执行, join中的phi改为bb中的select,
+//! bb直接跳转到join。被提前的指令必须没有副作用也不会出错, 所以只转换很小的块。
+
+use crate::{
+    ir::{
+        basicblock::BasicBlock,
+        instruction::{Inst, InstKind},
+        ir_type::IrType,
+        module::Module,
+        tools::{bfs_bb_proceess, func_process, inst_process_in_bb},
+    },
+    utility::{ObjPool, ObjPtr},
+};
+
+use super::loop_operation::is_movable;
+
+/// 两条路径上被提前执行的指令总数上限(不含常量)
+const MAX_SPECULATED_INSTS: usize = 6;
+/// 一次转换生成的select数量上限
+const MAX_SELECTS: usize = 3;
+
+pub fn if_conversion(
+    module: &mut Module,
+    pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
+) {
+    func_process(module, |_, func| loop {
+        let mut bbs = vec![];
+        bfs_bb_proceess(func.get_head(), |bb| bbs.push(bb));
+        // 内层的分支转换后外层的分支可能变得可以转换
+        let mut changed = false;
+        for bb in bbs {
+            changed |= convert(bb, pools.1);
+        }
+        if !changed {
+            break;
+        }
+    });
+}
+
+/// 尝试转换以bb结尾的分支
+fn convert(mut bb: ObjPtr<BasicBlock>, pool: &mut ObjPool<Inst>) -> bool {
+    // 已经被转换掉的then和else是空块
+    if bb.is_empty() {
+        return false;
+    }
+    let branch = bb.get_tail_inst();
+    if branch.get_kind() != InstKind::Branch || branch.is_br_jmp() {
+        return false;
+    }
+    let cond = branch.get_br_cond();
+    if cond.get_ir_type() != IrType::Int {
+        return false;
+    }
+    let false_bb = bb.get_next_bb()[0];
+    let true_bb = bb.get_next_bb()[1];
+    if false_bb == true_bb {
+        return false;
+    }
+
+    // then/else为None表示这条路径直接到达join
+    let (then_bb, else_bb, join) = if is_arm(true_bb, bb) && is_arm(false_bb, bb) {
+        let join = true_bb.get_next_bb()[0];
+        if join != false_bb.get_next_bb()[0] {
+            return false;
+        }
+        (Some(true_bb), Some(false_bb), join)
+    } else if is_arm(true_bb, bb) && true_bb.get_next_bb()[0] == false_bb {
+        (Some(true_bb), None, false_bb)
+    } else if is_arm(false_bb, bb) && false_bb.get_next_bb()[0] == true_bb {
+        (None, Some(false_bb), true_bb)
+    } else {
+        return false;
+    };
+    if join == bb {
+        return false;
+    }
+
+    // 两条路径到达join时所在的块
+    let true_pred = then_bb.unwrap_or(bb);
+    let false_pred = else_bb.unwrap_or(bb);
+    let true_index = join.get_up_bb().iter().position(|x| *x == true_pred);
+    let false_index = join.get_up_bb().iter().position(|x| *x == false_pred);
+    let (true_index, false_index) = match (true_index, false_index) {
+        (Some(true_index), Some(false_index)) => (true_index, false_index),
+        _ => return false,
+    };
+
+    // 收集需要select的phi,select只支持int
+    let mut phis = vec![];
+    let mut selects = 0;
+    let mut legal = true;
+    inst_process_in_bb(join.get_head_inst(), |inst| {
+        if !inst.is_phi() {
+            return;
+        }
+        let true_value = inst.get_operand(true_index);
+        let false_value = inst.get_operand(false_index);
+        if true_value != false_value {
+            legal &= inst.get_ir_type() == IrType::Int;
+            selects += 1;
+        }
+        phis.push(inst);
+    });
+    let speculated = [then_bb, else_bb]
+        .iter()
+        .flatten()
+        .map(|arm| arm_size(*arm))
+        .sum::<usize>();
+    if !legal || selects > MAX_SELECTS || speculated > MAX_SPECULATED_INSTS {
+        return false;
+    }
+
+    // 把then和else中的指令提前到分支之前
+    for arm in [then_bb, else_bb].iter().flatten() {
+        arm.get_tail_inst().as_mut().remove_self();
+        if arm.is_empty() {
+            continue;
+        }
+        inst_process_in_bb(arm.get_head_inst(), |inst| {
+            inst.as_mut().move_self();
+            branch.as_mut().insert_before(inst);
+        });
+    }
+
+    // phi在bb对应的位置使用select的结果,三角形中bb本身就是join的前继
+    let (index, removed) = if false_pred == bb {
+        (false_index, true_pred)
+    } else {
+        (true_index, false_pred)
+    };
+    for mut phi in phis {
+        let true_value = phi.get_operand(true_index);
+        let false_value = phi.get_operand(false_index);
+        let value = if true_value == false_value {
+            true_value
+        } else {
+            let select = pool.make_select(cond, true_value, false_value);
+            branch.as_mut().insert_before(select);
+            select
+        };
+        phi.set_operand(value, index);
+    }
+
+    // 重新连接cfg: bb直接跳转到join
+    let mut join = join;
+    if true_pred != bb && false_pred != bb {
+        join.replace_up_bb(true_pred, bb);
+    }
+    join.remove_up_bb(removed);
+    for mut arm in [then_bb, else_bb].into_iter().flatten() {
+        arm.clear_up_bb();
+        arm.clear_next_bb();
+    }
+    branch.as_mut().remove_self();
+    bb.push_back(pool.make_jmp());
+    bb.set_next_bb(vec![join]);
+    true
+}
+
+/// bb是否是只从pred进入、直接跳转出去的短小的块
+fn is_arm(bb: ObjPtr<BasicBlock>, pred: ObjPtr<BasicBlock>) -> bool {
+    if bb.get_up_bb().len() != 1 || bb.get_up_bb()[0] != pred || bb.get_next_bb().len() != 1 {
+        return false;
+    }
+    let mut movable = true;
+    inst_process_in_bb(bb.get_head_inst(), |inst| {
+        movable &= inst.get_kind() == InstKind::Branch || is_movable(inst);
+    });
+    movable
+}
+
+/// 块中需要执行的指令数
+fn arm_size(bb: ObjPtr<BasicBlock>) -> usize {
+    let mut size = 0;
+    inst_process_in_bb(bb.get_head_inst(), |inst| {
+        if !inst.is_const() && inst.get_kind() != InstKind::Branch {
+            size += 1;
+        }
+    });
+    size
+}
+
+#[cfg(test)]
+mod if_conversion_test {
+    use super::if_conversion;
+    use crate::ir::{
+        dump_module,
+        instruction::InstKind,
+        interpret,
+        test_util::parse_module,
+        tools::{bfs_bb_proceess, inst_process_in_bb},
+        verify_module, DEFAULT_STEP_LIMIT,
+    };
+
+    #[test]
+    fn test_if_conversion() {
+        // 菱形求绝对值,三角形求最大值,含除法的分支不能转换
+        let (mut module, pools) = parse_module(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %a = call i32 @getint()
+  %b = call i32 @getint()
+  %c = icmp slt i32 %a, 0
+  br i1 %c, label %bb_neg, label %bb_pos
+
+bb_neg:
+  %n = sub i32 0, %a
+  br label %bb_abs
+
+bb_pos:
+  %p = add i32 %a, 0
+  br label %bb_abs
+
+bb_abs:
+  %abs = phi i32 [ %n, %bb_neg ], [ %p, %bb_pos ]
+  %c2 = icmp sgt i32 %b, %abs
+  br i1 %c2, label %bb_big, label %bb_max
+
+bb_big:
+  br label %bb_max
+
+bb_max:
+  %max = phi i32 [ %b, %bb_big ], [ %abs, %bb_abs ]
+  %c3 = icmp ne i32 %b, 0
+  br i1 %c3, label %bb_div, label %bb_exit
+
+bb_div:
+  %d = sdiv i32 %max, %b
+  br label %bb_exit
+
+bb_exit:
+  %r = phi i32 [ %d, %bb_div ], [ %max, %bb_max ]
+  ret i32 %r
+}
+
+declare i32 @getint()
+",
+        );
+        if_conversion(&mut module, &mut (pools.bb, pools.inst));
+        assert_eq!(verify_module(&module), vec![]);
+
+        let mut branches = 0;
+        let mut selects = 0;
+        bfs_bb_proceess(module.get_function("main").get_head(), |bb| {
+            if bb.get_next_bb().len() == 2 {
+                branches += 1;
+            }
+            inst_process_in_bb(bb.get_head_inst(), |inst| {
+                if inst.get_kind() == InstKind::Select {
+                    selects += 1;
+                }
+            });
+        });
+        assert_eq!(branches, 1);
+        assert_eq!(selects, 2);
+
+        // select可以被读回
+        let (module, _) = parse_module(&dump_module(&module).unwrap());
+        for (input, ret) in [("-7 3", 2), ("5 9", 1), ("-4 0", 4)] {
+            assert_eq!(
+                interpret(&module, input, DEFAULT_STEP_LIMIT).unwrap().ret,
+                ret
+            );
+        }
+    }
+}
@@ -0,0 +1,1019 @@
+//! 过程间常量与范围传播
+//!
+//! 在整个程序上求每个值的格:整数用区间表示,常量就是上下界相同的区间,浮点数只区分常量和非常量。
+//! 调用点实参的值合并到被调用函数的形参中,return的值合并到所有调用点,
+//! 在所有函数上迭代到不动点,同一个值扩大太多次时把变化的边界直接放宽到i32的边界,保证迭代终止。
+//! 求解后把值为常量的形参、调用结果和计算替换为常量,区间能确定结果的比较也随之折叠,
+//! 再由sccp折叠分支、删除不可达的块。
+//!
+//! 同一函数在不同调用点有不同的常量实参时,为每组常量实参克隆一份函数,调用点改为调用克隆,
+//! 克隆的形参只会收到同一个常量,下一轮就会被替换。以常量n调用的递归函数会被逐层特化,
+//! 递归到底的一层返回常量之后,整个调用链都会折叠为常量。
+//!
+//! 实参是全局数组加常量偏移的指针时也按数组和偏移克隆,克隆中的形参直接替换为这个数组,
+//! 之后的分析可以知道访问的是哪个数组。递归调用传下去的还是同一个数组时会调用克隆自身。
+//! 能被内联的函数在内联之后自然会按实参折叠,只克隆内联不会处理的函数,即递归函数和超过内联大小上限的函数,
+//! 克隆出的指令总数有上限,避免代码膨胀。
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    ir::{
+        analysis::{call_map::CallMap, value_range::Range},
+        basicblock::BasicBlock,
+        call_map_gen,
+        function::Function,
+        instruction::{BinOp, Inst, InstKind, UnOp},
+        ir_type::IrType,
+        module::Module,
+        tools::{
+            bfs_bb_proceess, bfs_inst_process, func_process, inst_process_in_bb, replace_inst,
+        },
+    },
+    utility::{ObjPool, ObjPtr},
+};
+
+use super::{
+    dead_code_eliminate,
+    func_inline::{
+        copy_func::copy_func, func_size, get_optimizate::gep_optimize, INLINE_SIZE_LIMIT,
+    },
+    sccp,
+};
+
+/// 同一个值的格变化超过这个次数之后进行放宽
+const WIDEN_LIMIT: usize = 3;
+/// 一次运行中克隆出的指令总数的上限
+const CLONE_BUDGET: usize = 1000;
+
+/// 克隆时按实参区分的模式
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum Pattern {
+    /// 常量实参
+    Const(Value),
+    /// 全局数组加常量偏移
+    Array(ObjPtr<Inst>, i32),
+}
+
+/// 被克隆的函数名和每个实参的模式,不特化的形参为None
+type CloneKey = (String, Vec<Option<Pattern>>);
+
+/// 值的格,Top表示还没有确定的值,整数的区间用i64保存上下界
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum Value {
+    Top,
+    Int(i64, i64),
+    /// 按位比较,区分0.0和-0.0
+    Float(u32),
+    Bottom,
+}
+
+impl Value {
+    /// 任意的i32
+    fn full() -> Value {
+        Value::Int(i32::MIN as i64, i32::MAX as i64)
+    }
+
+    /// 构造区间,超出i32时运算会回绕,只能是任意值
+    fn int(lo: i64, hi: i64) -> Value {
+        if lo < i32::MIN as i64 || hi > i32::MAX as i64 {
+            Value::full()
+        } else {
+            Value::Int(lo, hi)
+        }
+    }
+
+    fn bool(lo: bool, hi: bool) -> Value {
+        Value::Int(lo as i64, hi as i64)
+    }
+
+    fn float(value: f32) -> Value {
+        Value::Float(value.to_bits())
+    }
+
+    /// 不知道任何信息的值
+    fn unknown(ir_type: IrType) -> Value {
+        match ir_type {
+            IrType::Int => Value::full(),
+            _ => Value::Bottom,
+        }
+    }
+
+    fn join(self, other: Value) -> Value {
+        match (self, other) {
+            (Value::Top, value) | (value, Value::Top) => value,
+            (Value::Int(lo1, hi1), Value::Int(lo2, hi2)) => Value::Int(lo1.min(lo2), hi1.max(hi2)),
+            (lhs, rhs) if lhs == rhs => lhs,
+            _ => Value::Bottom,
+        }
+    }
+
+    /// 放宽变化的边界
+    fn widen(self, new: Value) -> Value {
+        match (self, new) {
+            (Value::Int(lo, hi), Value::Int(new_lo, new_hi)) => Value::Int(
+                if new_lo < lo { i32::MIN as i64 } else { lo },
+                if new_hi > hi { i32::MAX as i64 } else { hi },
+            ),
+            _ => new,
+        }
+    }
+
+    fn is_const(self) -> bool {
+        match self {
+            Value::Int(lo, hi) => lo == hi,
+            Value::Float(_) => true,
+            _ => false,
+        }
+    }
+
+    /// 为常量值构造常量指令
+    fn make_const(self, pool: &mut ObjPool<Inst>) -> Option<ObjPtr<Inst>> {
+        match self {
+            Value::Int(lo, hi) if lo == hi => Some(pool.make_int_const(lo as i32)),
+            Value::Float(value) => Some(pool.make_float_const(f32::from_bits(value))),
+            _ => None,
+        }
+    }
+}
+
+pub fn ipcp(
+    module: &mut Module,
+    pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
+    pool_func: &mut ObjPool<Function>,
+) {
+    let mut clones: HashMap<CloneKey, String> = HashMap::new();
+    let mut budget = CLONE_BUDGET;
+    loop {
+        let call_map = call_map_gen(module);
+        let mut solver = Ipcp::new();
+        let cloned: HashSet<String> = clones.keys().map(|(name, _)| name.clone()).collect();
+        solver.solve(module, &call_map, &cloned);
+        if solver.rewrite(module, pools.1) {
+            // 折叠分支,删除结果不再使用的无副作用调用
+            sccp::sccp(module, pools);
+            dead_code_eliminate::dead_code_eliminate(module, true);
+        } else if !specialize(module, pools, pool_func, &mut clones, &mut budget) {
+            break;
+        }
+    }
+    restore_unfolded_calls(module, &clones);
+    delete_unreachable_func(module);
+}
+
+struct Ipcp {
+    values: HashMap<ObjPtr<Inst>, Value>,
+    returns: HashMap<String, Value>,
+    params: HashMap<String, Vec<ObjPtr<Inst>>>,
+    /// 每个值和返回值的格变化的次数
+    changes: HashMap<ObjPtr<Inst>, usize>,
+    return_changes: HashMap<String, usize>,
+    changed: bool,
+}
+
+impl Ipcp {
+    fn new() -> Ipcp {
+        Ipcp {
+            values: HashMap::new(),
+            returns: HashMap::new(),
+            params: HashMap::new(),
+            changes: HashMap::new(),
+            return_changes: HashMap::new(),
+            changed: false,
+        }
+    }
+
+    fn solve(&mut self, module: &mut Module, call_map: &CallMap, cloned: &HashSet<String>) {
+        let mut funcs = vec![];
+        func_process(module, |name, func| {
+            // main和没有调用者的函数的形参可能是任意值,
+            // 被克隆过的函数之后可能重新被特化的调用点调用,也不能依赖调用者
+            if name == "main"
+                || call_map.find_predecessors(&name).is_empty()
+                || cloned.contains(&name)
+            {
+                for &param in func.get_parameter_list().iter() {
+                    self.values
+                        .insert(param, Value::unknown(param.get_ir_type()));
+                }
+            }
+            self.returns.insert(name.clone(), Value::Top);
+            self.params
+                .insert(name.clone(), func.get_parameter_list().clone());
+            let mut blocks = vec![];
+            bfs_bb_proceess(func.get_head(), |bb| blocks.push(bb));
+            funcs.push((name, blocks));
+        });
+
+        loop {
+            self.changed = false;
+            for (name, blocks) in funcs.iter() {
+                for bb in blocks.iter() {
+                    inst_process_in_bb(bb.get_head_inst(), |inst| self.visit_inst(name, inst));
+                }
+            }
+            if !self.changed {
+                break;
+            }
+        }
+    }
+
+    fn visit_inst(&mut self, func_name: &str, inst: ObjPtr<Inst>) {
+        let value = match inst.get_kind() {
+            InstKind::Phi => inst
+                .get_operands()
+                .iter()
+                .fold(Value::Top, |value, &op| value.join(self.get_value(op))),
+            InstKind::Binary(op) => self.eval_binary(
+                op,
+                self.get_value(inst.get_lhs()),
+                self.get_value(inst.get_rhs()),
+                inst.get_ir_type(),
+            ),
+            InstKind::Unary(op) => eval_unary(op, self.get_value(inst.get_unary_operand())),
+            InstKind::Select => match self.get_value(inst.get_select_cond()) {
+                Value::Top => Value::Top,
+                Value::Int(0, 0) => self.get_value(inst.get_false_value()),
+                Value::Int(lo, hi) if lo > 0 || hi < 0 => self.get_value(inst.get_true_value()),
+                _ => self
+                    .get_value(inst.get_true_value())
+                    .join(self.get_value(inst.get_false_value())),
+            },
+            InstKind::FtoI => match self.get_value(inst.get_float_to_int_value()) {
+                Value::Top => Value::Top,
+                Value::Float(value) => {
+                    let value = f32::from_bits(value);
+                    // 超出范围时各平台的转换结果不同,不折叠
+                    if value.is_finite() && value.abs() < 2147483648.0 {
+                        Value::int(value as i64, value as i64)
+                    } else {
+                        Value::full()
+                    }
+                }
+                _ => Value::full(),
+            },
+            InstKind::ItoF => match self.get_value(inst.get_int_to_float_value()) {
+                Value::Top => Value::Top,
+                Value::Int(lo, hi) if lo == hi => Value::float(lo as f32),
+                _ => Value::Bottom,
+            },
+            InstKind::Call(callee) => {
+                if !self.returns.contains_key(&callee) {
+                    // 外部函数
+                    Value::unknown(inst.get_ir_type())
+                } else {
+                    let params = self.params[&callee].clone();
+                    for (&arg, &param) in inst.get_args().iter().zip(params.iter()) {
+                        let value = self.get_value(arg);
+                        self.update(param, value);
+                    }
+                    self.returns[&callee]
+                }
+            }
+            InstKind::Return => {
+                if !inst.is_void_return() {
+                    let value = self.get_value(inst.get_return_value());
+                    let old = self.returns[func_name];
+                    let count = self
+                        .return_changes
+                        .entry(func_name.to_string())
+                        .or_insert(0);
+                    if let Some(new) = next_value(old, value, count) {
+                        self.returns.insert(func_name.to_string(), new);
+                        self.changed = true;
+                    }
+                }
+                return;
+            }
+            InstKind::ConstInt(value) => Value::int(value as i64, value as i64),
+            InstKind::ConstFloat(value) => Value::float(value),
+            _ => Value::unknown(inst.get_ir_type()),
+        };
+        self.update(inst, value);
+    }
+
+    /// 合并新的值,格变化时记录下来
+    fn update(&mut self, inst: ObjPtr<Inst>, value: Value) {
+        let old = self.get_value(inst);
+        let count = self.changes.entry(inst).or_insert(0);
+        if let Some(new) = next_value(old, value, count) {
+            self.values.insert(inst, new);
+            self.changed = true;
+        }
+    }
+
+    fn get_value(&self, inst: ObjPtr<Inst>) -> Value {
+        match inst.get_kind() {
+            InstKind::ConstInt(value) | InstKind::GlobalConstInt(value) => {
+                Value::int(value as i64, value as i64)
+            }
+            InstKind::ConstFloat(value) | InstKind::GlobalConstFloat(value) => Value::float(value),
+            InstKind::Parameter => *self.values.get(&inst).unwrap_or(&Value::Top),
+            _ if inst.is_global_var_or_param() => Value::unknown(inst.get_ir_type()),
+            _ => *self.values.get(&inst).unwrap_or(&Value::Top),
+        }
+    }
+
+    fn eval_binary(&self, op: BinOp, lhs: Value, rhs: Value, ir_type: IrType) -> Value {
+        match (lhs, rhs) {
+            (Value::Top, _) | (_, Value::Top) => Value::Top,
+            (Value::Int(lo1, hi1), Value::Int(lo2, hi2)) => {
+                let range = Range::eval_binary(op, Range::new(lo1, hi1), Range::new(lo2, hi2));
+                Value::Int(range.lo, range.hi)
+            }
+            (Value::Float(lhs), Value::Float(rhs)) => {
+                let (lhs, rhs) = (f32::from_bits(lhs), f32::from_bits(rhs));
+                let bool_value = |value: bool| Value::bool(value, value);
+                match op {
+                    BinOp::Add => Value::float(lhs + rhs),
+                    BinOp::Sub => Value::float(lhs - rhs),
+                    BinOp::Mul => Value::float(lhs * rhs),
+                    BinOp::Div => Value::float(lhs / rhs),
+                    BinOp::Rem => Value::float(lhs % rhs),
+                    BinOp::UDiv
+                    | BinOp::URem
+                    | BinOp::And
+                    | BinOp::Or
+                    | BinOp::Xor
+                    | BinOp::Shl
+                    | BinOp::LShr
+                    | BinOp::AShr => Value::Bottom,
+                    BinOp::Gt => bool_value(lhs > rhs),
+                    BinOp::Lt => bool_value(lhs < rhs),
+                    BinOp::Ge => bool_value(lhs >= rhs),
+                    BinOp::Le => bool_value(lhs <= rhs),
+                    BinOp::Eq => bool_value(lhs == rhs),
+                    BinOp::Ne => bool_value(lhs != rhs),
+                }
+            }
+            _ => Value::unknown(ir_type),
+        }
+    }
+
+    /// 用常量替换值为常量的形参、调用结果和计算,返回是否有修改
+    fn rewrite(&self, module: &mut Module, pool: &mut ObjPool<Inst>) -> bool {
+        let mut changed = false;
+        func_process(module, |_, func| {
+            let mut head = func.get_head();
+            let mut consts = HashMap::new();
+            let mut get_const = |value: Value, pool: &mut ObjPool<Inst>| match consts.get(&value) {
+                Some(&inst_const) => inst_const,
+                None => {
+                    let inst_const = value.make_const(pool).unwrap();
+                    // 常量放在头块开头,支配所有使用
+                    head.push_front(inst_const);
+                    consts.insert(value, inst_const);
+                    inst_const
+                }
+            };
+
+            for &param in func.get_parameter_list().iter() {
+                let value = self.get_value(param);
+                if value.is_const() && !param.get_use_list().is_empty() {
+                    replace_uses(param, get_const(value, pool));
+                    changed = true;
+                }
+            }
+
+            let mut insts = vec![];
+            bfs_inst_process(func.get_head(), |inst| match inst.get_kind() {
+                InstKind::Binary(_)
+                | InstKind::Unary(_)
+                | InstKind::Select
+                | InstKind::FtoI
+                | InstKind::ItoF
+                | InstKind::Phi
+                | InstKind::Call(_) => insts.push(inst),
+                _ => {}
+            });
+            for inst in insts {
+                let value = self.get_value(inst);
+                if !value.is_const() || inst.get_use_list().is_empty() {
+                    continue;
+                }
+                let inst_const = get_const(value, pool);
+                if let InstKind::Call(_) = inst.get_kind() {
+                    // 调用可能有副作用,只替换其结果
+                    replace_uses(inst, inst_const);
+                } else {
+                    replace_inst(inst, inst_const);
+                }
+                changed = true;
+            }
+        });
+        changed
+    }
+}
+
+/// 合并新的值,超过次数时放宽,格没有变化时返回None
+fn next_value(old: Value, value: Value, count: &mut usize) -> Option<Value> {
+    let mut new = old.join(value);
+    if new == old {
+        return None;
+    }
+    *count += 1;
+    if *count > WIDEN_LIMIT {
+        new = old.widen(new);
+    }
+    Some(new)
+}
+
+fn eval_unary(op: UnOp, value: Value) -> Value {
+    match (op, value) {
+        (_, Value::Top) => Value::Top,
+        (UnOp::Pos, value) => value,
+        (UnOp::Neg, Value::Int(lo, hi)) => Value::int(-hi, -lo),
+        (UnOp::Neg, Value::Float(value)) => Value::float(-f32::from_bits(value)),
+        (UnOp::Not, Value::Int(lo, hi)) => Value::bool(lo == 0 && hi == 0, lo <= 0 && hi >= 0),
+        (UnOp::Not, Value::Float(value)) => {
+            Value::float((f32::from_bits(value) == 0.0) as i32 as f32)
+        }
+        (UnOp::Not, _) => Value::Bottom,
+        (UnOp::Neg, _) => Value::Bottom,
+    }
+}
+
+/// 把inst的所有使用替换为new,inst本身保留
+fn replace_uses(inst: ObjPtr<Inst>, new: ObjPtr<Inst>) {
+    let use_list = inst.get_use_list().clone();
+    for user in use_list {
+        let index = user.get_operand_index(inst);
+        user.as_mut().set_operand(new, index);
+    }
+}
+
+/// 对有常量实参或全局数组实参的调用点按实参的模式克隆被调用函数,返回是否有修改。
+/// budget是还能克隆的指令数
+fn specialize(
+    module: &mut Module,
+    pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
+    pool_func: &mut ObjPool<Function>,
+    clones: &mut HashMap<CloneKey, String>,
+    budget: &mut usize,
+) -> bool {
+    let clone_names: HashSet<String> = clones.values().cloned().collect();
+    let call_map = call_map_gen(module);
+    let mut sites = vec![];
+    func_process(module, |_, func| {
+        bfs_inst_process(func.get_head(), |inst| {
+            if let InstKind::Call(callee) = inst.get_kind() {
+                sites.push((inst, callee));
+            }
+        })
+    });
+
+    let mut changed = false;
+    for (mut call, callee) in sites {
+        // 克隆只会从特化的调用点调用,形参会在下一轮替换为常量,不需要再克隆
+        let callee_func = match module.find_function(&callee) {
+            Some(func) if !func.is_empty_bb() && callee != "main" => func,
+            _ => continue,
+        };
+        if clone_names.contains(&callee) {
+            continue;
+        }
+        // 只特化仍然被使用的形参
+        let key: Vec<Option<Pattern>> = call
+            .get_args()
+            .iter()
+            .zip(callee_func.get_parameter_list().iter())
+            .map(|(&arg, param)| match arg.get_kind() {
+                _ if param.get_use_list().is_empty() => None,
+                InstKind::ConstInt(value) => {
+                    Some(Pattern::Const(Value::int(value as i64, value as i64)))
+                }
+                InstKind::ConstFloat(value) => Some(Pattern::Const(Value::float(value))),
+                InstKind::Gep => {
+                    array_base(arg).map(|(array, offset)| Pattern::Array(array, offset))
+                }
+                _ => None,
+            })
+            .collect();
+        if key.iter().all(|value| value.is_none()) {
+            continue;
+        }
+
+        let key = (callee.clone(), key);
+        let name = match clones.get(&key) {
+            Some(name) => name.clone(),
+            None => {
+                let size = func_size(callee_func);
+                if size > *budget || !can_clone(&call_map, &callee, callee_func, size) {
+                    continue;
+                }
+                *budget -= size;
+                let name = clone_func(module, &callee, callee_func, pools, pool_func);
+                bind_array_params(module.get_function(&name), &key.1, pools);
+                clones.insert(key, name.clone());
+                name
+            }
+        };
+        call.set_callee(name);
+        changed = true;
+    }
+    changed
+}
+
+/// 指针是从全局数组加载的地址加常量偏移时返回数组和偏移
+fn array_base(mut ptr: ObjPtr<Inst>) -> Option<(ObjPtr<Inst>, i32)> {
+    let mut offset = 0i32;
+    while ptr.get_kind() == InstKind::Gep {
+        match ptr.get_gep_offset().get_kind() {
+            InstKind::ConstInt(value) | InstKind::GlobalConstInt(value) => {
+                offset = offset.checked_add(value)?
+            }
+            _ => return None,
+        }
+        ptr = ptr.get_gep_ptr();
+    }
+    if ptr.get_kind() == InstKind::Load && ptr.is_global_array_load() {
+        Some((ptr.get_ptr(), offset))
+    } else {
+        None
+    }
+}
+
+/// 把克隆中按全局数组特化的形参替换为在头块中重新加载的数组地址上的gep
+fn bind_array_params(
+    func: ObjPtr<Function>,
+    patterns: &[Option<Pattern>],
+    pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
+) {
+    let mut head = func.get_head();
+    for (&param, pattern) in func.get_parameter_list().iter().zip(patterns.iter()) {
+        if let Some(Pattern::Array(array, offset)) = *pattern {
+            let load = match array.get_ir_type() {
+                IrType::IntPtr => pools.1.make_global_int_array_load(array),
+                _ => pools.1.make_global_float_array_load(array),
+            };
+            let offset = pools.1.make_int_const(offset);
+            let gep = pools.1.make_gep(load, offset);
+            head.push_front(gep);
+            head.push_front(offset);
+            head.push_front(load);
+            replace_uses(param, gep);
+        }
+    }
+    // 形参上的gep变成了嵌套的gep,合并为一个
+    gep_optimize(head, pools);
+}
+
+/// 只克隆内联不会处理的函数:递归函数,或者指令数超过内联上限的函数。函数还需要有出口块
+fn can_clone(call_map: &CallMap, name: &str, func: ObjPtr<Function>, size: usize) -> bool {
+    if size <= INLINE_SIZE_LIMIT && !is_recursive(call_map, name) {
+        return false;
+    }
+    let mut has_exit = false;
+    bfs_bb_proceess(func.get_head(), |bb| has_exit |= bb.is_exit());
+    has_exit
+}
+
+/// 函数能否经过调用链调用到自身
+fn is_recursive(call_map: &CallMap, name: &str) -> bool {
+    let mut visited = HashSet::new();
+    let mut stack: Vec<&String> = call_map.get_succs(name).iter().collect();
+    while let Some(callee) = stack.pop() {
+        if callee == name {
+            return true;
+        }
+        if visited.insert(callee) {
+            stack.extend(call_map.get_succs(callee).iter());
+        }
+    }
+    false
+}
+
+/// 复制一份函数加入module中,返回新函数的名字
+fn clone_func(
+    module: &mut Module,
+    name: &str,
+    func: ObjPtr<Function>,
+    pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
+    pool_func: &mut ObjPool<Function>,
+) -> String {
+    let mut index = 0;
+    let clone_name = loop {
+        let clone_name = format!("{}_spec_{}", name, index);
+        if module.find_function(&clone_name).is_none() {
+            break clone_name;
+        }
+        index += 1;
+    };
+
+    let mut clone = pool_func.new_function();
+    clone.set_return_type(func.get_return_type());
+    let mut params = vec![];
+    for &param in func.get_parameter_list().iter() {
+        let new_param = pools.1.make_param(param.get_ir_type());
+        clone.set_parameter(func.get_parameter_name(param).to_string(), new_param);
+        params.push(new_param);
+    }
+    let (head, _) = copy_func(&clone_name, func, module.get_all_var(), params, pools);
+    clone.insert_first_bb(head);
+    module.push_function(clone_name.clone(), clone);
+    clone_name
+}
+
+/// 克隆次数用完时递归可能还没有到底,这样的克隆链没有收益,
+/// 克隆与以同样实参调用原函数等价,把调用点改回原函数。
+/// 克隆调用自身时递归的实参模式没有变化,不是没有到底的克隆链
+fn restore_unfolded_calls(module: &mut Module, clones: &HashMap<CloneKey, String>) {
+    let origin: HashMap<&String, &String> = clones
+        .iter()
+        .map(|((name, _), clone)| (clone, name))
+        .collect();
+    let get_origin = |name: &String| {
+        origin
+            .get(name)
+            .map_or(name.clone(), |name| name.to_string())
+    };
+
+    // 已经折叠掉的克隆调用不到,其中剩下的调用不用考虑
+    let reachable = reachable_func(module);
+    let mut unfolded = HashSet::new();
+    for (clone_name, name) in origin.iter() {
+        if reachable.contains(*clone_name) {
+            let clone = module.get_function(clone_name);
+            bfs_inst_process(clone.get_head(), |inst| {
+                if let InstKind::Call(callee) = inst.get_kind() {
+                    if get_origin(&callee) == **name && callee != **clone_name {
+                        unfolded.insert(name.to_string());
+                    }
+                }
+            });
+        }
+    }
+
+    func_process(module, |_, func| {
+        bfs_inst_process(func.get_head(), |mut inst| {
+            if let InstKind::Call(callee) = inst.get_kind() {
+                let name = get_origin(&callee);
+                if name != callee && unfolded.contains(&name) {
+                    inst.set_callee(name);
+                }
+            }
+        });
+    });
+}
+
+/// 删除从main开始调用不到的函数
+fn delete_unreachable_func(module: &mut Module) {
+    if module.find_function("main").is_none() {
+        return;
+    }
+    let reachable = reachable_func(module);
+    for name in call_map_gen(module).get_all_func() {
+        if !reachable.contains(&name) {
+            module.delete_function(&name);
+        }
+    }
+}
+
+/// 从main开始能调用到的函数
+fn reachable_func(module: &mut Module) -> HashSet<String> {
+    let call_map = call_map_gen(module);
+    let funcs: HashSet<String> = call_map.get_all_func().into_iter().collect();
+    let mut reachable = HashSet::new();
+    let mut stack = vec!["main".to_string()];
+    while let Some(name) = stack.pop() {
+        if funcs.contains(&name) && reachable.insert(name.clone()) {
+            stack.extend(call_map.get_succs(&name).iter().cloned());
+        }
+    }
+    reachable
+}
+
+#[cfg(test)]
+mod ipcp_test {
+    use super::ipcp;
+    use crate::ir::{
+        instruction::InstKind, interpret, module::Module, test_util::parse_module,
+        tools::bfs_inst_process, transform::func_inline::inline_run, verify_module,
+        DEFAULT_STEP_LIMIT,
+    };
+
+    /// 解析IR并运行ipcp,返回剩下的函数名
+    fn run_ipcp(text: &str) -> (Module, Vec<String>) {
+        let (mut module, pools) = parse_module(text);
+        ipcp(&mut module, &mut (pools.bb, pools.inst), pools.func);
+        assert_eq!(verify_module(&module), vec![]);
+        let funcs = func_names(&module);
+        (module, funcs)
+    }
+
+    /// 模块中有函数体的函数名,按字典序排列
+    fn func_names(module: &Module) -> Vec<String> {
+        let mut funcs: Vec<String> = module
+            .get_all_func()
+            .iter()
+            .filter(|(_, func)| !func.is_empty_bb())
+            .map(|(name, _)| name.to_string())
+            .collect();
+        funcs.sort();
+        funcs
+    }
+
+    #[test]
+    fn test_recursive_fold() {
+        // 以常量调用的递归函数被逐层特化,最后整个调用折叠为常量
+        let (module, funcs) = run_ipcp(
+            "
+define dso_local i32 @fib(i32 %n) {
+bb_entry:
+  %c = icmp slt i32 %n, 2
+  br i1 %c, label %bb_exit, label %bb_rec
+
+bb_rec:
+  %n1 = sub i32 %n, 1
+  %f1 = call i32 @fib(i32 %n1)
+  %n2 = sub i32 %n, 2
+  %f2 = call i32 @fib(i32 %n2)
+  %s = add i32 %f1, %f2
+  br label %bb_exit
+
+bb_exit:
+  %r = phi i32 [ %n, %bb_entry ], [ %s, %bb_rec ]
+  ret i32 %r
+}
+
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %f = call i32 @fib(i32 10)
+  ret i32 %f
+}
+",
+        );
+        assert_eq!(funcs, vec!["main".to_string()]);
+        let result = interpret(&module, "", DEFAULT_STEP_LIMIT).unwrap();
+        assert_eq!(result.ret, 55);
+    }
+
+    #[test]
+    fn test_clone_and_range() {
+        // 递归函数不会被内联,不同的常量实参得到不同的克隆,克隆中的比较随之折叠
+        let (module, funcs) = run_ipcp(
+            "
+define dso_local i32 @f(i32 %x, i32 %k) {
+bb_entry:
+  %c = icmp sgt i32 %k, 100
+  br i1 %c, label %bb_big, label %bb_small
+
+bb_big:
+  %k1 = sub i32 %k, 100
+  %b = call i32 @f(i32 %x, i32 %k1)
+  br label %bb_exit
+
+bb_small:
+  %m = mul i32 %x, %k
+  br label %bb_exit
+
+bb_exit:
+  %r = phi i32 [ %b, %bb_big ], [ %m, %bb_small ]
+  ret i32 %r
+}
+
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %x = call i32 @getint()
+  %a = call i32 @f(i32 %x, i32 3)
+  %b = call i32 @f(i32 %x, i32 5)
+  %s = add i32 %a, %b
+  ret i32 %s
+}
+",
+        );
+        assert_eq!(
+            funcs,
+            vec![
+                "f_spec_0".to_string(),
+                "f_spec_1".to_string(),
+                "main".to_string()
+            ]
+        );
+        // 克隆中只剩一个块,递归调用随着不可达的块删除
+        for name in ["f_spec_0", "f_spec_1"] {
+            let head = module.get_function(name).get_head();
+            assert!(head.is_exit(), "{}", name);
+        }
+        let result = interpret(&module, "4", DEFAULT_STEP_LIMIT).unwrap();
+        assert_eq!(result.ret, 32);
+    }
+
+    #[test]
+    fn test_inline_not_clone() {
+        // 小的非递归函数留给内联处理,ipcp不克隆,内联之后实参同样会被折叠
+        let (mut module, pools) = parse_module(
+            "
+define dso_local i32 @f(i32 %x, i32 %k) {
+bb_entry:
+  %c = icmp sgt i32 %k, 100
+  br i1 %c, label %bb_big, label %bb_small
+
+bb_big:
+  %b = call i32 @getint()
+  br label %bb_exit
+
+bb_small:
+  %m = mul i32 %x, %k
+  br label %bb_exit
+
+bb_exit:
+  %r = phi i32 [ %b, %bb_big ], [ %m, %bb_small ]
+  ret i32 %r
+}
+
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %x = call i32 @getint()
+  %a = call i32 @f(i32 %x, i32 3)
+  %b = call i32 @f(i32 %x, i32 5)
+  %s = add i32 %a, %b
+  ret i32 %s
+}
+",
+        );
+        let mut ir_pools = (pools.bb, pools.inst);
+        ipcp(&mut module, &mut ir_pools, pools.func);
+        assert_eq!(verify_module(&module), vec![]);
+        assert_eq!(
+            func_names(&module),
+            vec!["f".to_string(), "main".to_string()]
+        );
+
+        // 内联之后main中不再调用f
+        inline_run(&mut module, &mut ir_pools);
+        assert_eq!(verify_module(&module), vec![]);
+        bfs_inst_process(module.get_function("main").get_head(), |inst| {
+            if let InstKind::Call(callee) = inst.get_kind() {
+                assert_ne!(callee, "f");
+            }
+        });
+        let result = interpret(&module, "4", DEFAULT_STEP_LIMIT).unwrap();
+        assert_eq!(result.ret, 32);
+    }
+
+    #[test]
+    fn test_clone_budget() {
+        // 逐层特化1000层的递归会超过克隆的指令数上限,递归到不了底,调用点改回原函数
+        let (module, funcs) = run_ipcp(
+            "
+define dso_local i32 @sum(i32 %n) {
+bb_entry:
+  %c = icmp eq i32 %n, 0
+  br i1 %c, label %bb_exit, label %bb_rec
+
+bb_rec:
+  %n1 = sub i32 %n, 1
+  %s = call i32 @sum(i32 %n1)
+  %t = add i32 %n, %s
+  br label %bb_exit
+
+bb_exit:
+  %r = phi i32 [ 0, %bb_entry ], [ %t, %bb_rec ]
+  ret i32 %r
+}
+
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %s = call i32 @sum(i32 1000)
+  ret i32 %s
+}
+",
+        );
+        assert_eq!(funcs, vec!["main".to_string(), "sum".to_string()]);
+        let result = interpret(&module, "", DEFAULT_STEP_LIMIT).unwrap();
+        assert_eq!(result.ret, 500500);
+    }
+
+    #[test]
+    fn test_range() {
+        // 实参的区间传入被调用函数,%a不会大于100
+        let (module, _) = run_ipcp(
+            "
+define dso_local i32 @g(i32 %a) {
+bb_entry:
+  %c = icmp sgt i32 %a, 100
+  br i1 %c, label %bb_big, label %bb_exit
+
+bb_big:
+  %b = call i32 @getint()
+  br label %bb_exit
+
+bb_exit:
+  %r = phi i32 [ %b, %bb_big ], [ %a, %bb_entry ]
+  ret i32 %r
+}
+
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %x = call i32 @getint()
+  %a = srem i32 %x, 50
+  %r = call i32 @g(i32 %a)
+  ret i32 %r
+}
+",
+        );
+        assert!(module.get_function("g").get_head().is_exit());
+        let result = interpret(&module, "123", DEFAULT_STEP_LIMIT).unwrap();
+        assert_eq!(result.ret, 23);
+    }
+
+    #[test]
+    fn test_restore_unfolded() {
+        // %b不是常量时递归不能折叠,调用点改回原函数,原函数的形参不能按克隆的调用点替换
+        let (module, funcs) = run_ipcp(
+            "
+define dso_local i32 @pw(i32 %b, i32 %e) {
+bb_entry:
+  %c = icmp eq i32 %e, 0
+  br i1 %c, label %bb_exit, label %bb_rec
+
+bb_rec:
+  %e1 = sub i32 %e, 1
+  %p = call i32 @pw(i32 %b, i32 %e1)
+  %m = mul i32 %b, %p
+  br label %bb_exit
+
+bb_exit:
+  %r = phi i32 [ 1, %bb_entry ], [ %m, %bb_rec ]
+  ret i32 %r
+}
+
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %x = call i32 @getint()
+  %a = call i32 @pw(i32 %x, i32 5)
+  %b = call i32 @pw(i32 2, i32 10)
+  %s = add i32 %a, %b
+  ret i32 %s
+}
+",
+        );
+        assert_eq!(funcs, vec!["main".to_string(), "pw".to_string()]);
+        let result = interpret(&module, "3", DEFAULT_STEP_LIMIT).unwrap();
+        assert_eq!(result.ret, 1267);
+    }
+
+    #[test]
+    fn test_array_base() {
+        // 以全局数组调用的递归函数按数组克隆,递归调用传下去的是同一个数组,调用克隆自身
+        let (module, funcs) = run_ipcp(
+            "
+@g = dso_local global [4 x i32] [ i32 1, i32 2, i32 3, i32 4], align 4
+@h = dso_local global [4 x i32] [ i32 5, i32 6, i32 7, i32 8], align 4
+
+define dso_local i32 @sum(i32* %a, i32 %n) {
+bb_entry:
+  %c = icmp eq i32 %n, 0
+  br i1 %c, label %bb_exit, label %bb_rec
+
+bb_rec:
+  %n1 = sub i32 %n, 1
+  %p = getelementptr inbounds i32, ptr %a, i32 %n1
+  %v = load i32, i32* %p, align 4
+  %a1 = getelementptr inbounds i32, ptr %a, i32 0
+  %s = call i32 @sum(i32* %a1, i32 %n1)
+  %t = add i32 %v, %s
+  br label %bb_exit
+
+bb_exit:
+  %r = phi i32 [ 0, %bb_entry ], [ %t, %bb_rec ]
+  ret i32 %r
+}
+
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %x = call i32 @getint()
+  %ga = load ptr, ptr @g, align 8
+  %pg = getelementptr inbounds [4 x i32], [4 x i32]* %ga, i32 0, i32 0
+  %a = call i32 @sum(i32* %pg, i32 %x)
+  %ha = load ptr, ptr @h, align 8
+  %ph = getelementptr inbounds [4 x i32], [4 x i32]* %ha, i32 0, i32 1
+  %b = call i32 @sum(i32* %ph, i32 %x)
+  %s = add i32 %a, %b
+  ret i32 %s
+}
+",
+        );
+        assert_eq!(
+            funcs,
+            vec![
+                "main".to_string(),
+                "sum_spec_0".to_string(),
+                "sum_spec_1".to_string()
+            ]
+        );
+        // 克隆中不再使用指针形参,递归调用的是克隆自身
+        for name in ["sum_spec_0", "sum_spec_1"] {
+            let func = module.get_function(name);
+            assert!(func.get_parameter_list()[0].get_use_list().is_empty());
+            bfs_inst_process(func.get_head(), |inst| {
+                if let InstKind::Call(callee) = inst.get_kind() {
+                    assert_eq!(callee, name);
+                }
+            });
+        }
+        // 1+2+3 + 6+7+8
+        let result = interpret(&module, "3", DEFAULT_STEP_LIMIT).unwrap();
+        assert_eq!(result.ret, 27);
+    }
+}
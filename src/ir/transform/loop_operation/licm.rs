@@ -1,6 +1,10 @@
 use crate::{
     ir::{
-        analysis::loop_tree::{LoopInfo, LoopList},
+        analysis::{
+            alias::{get_location, AliasAnalysis, MemBase, MemOffset},
+            loop_tree::{LoopInfo, LoopList},
+            memory_ssa::MemorySSA,
+        },
         instruction::InstKind,
         tools::inst_process_in_bb,
     },
@@ -11,6 +15,8 @@ use super::*;
 
 pub fn licm_run(
     loop_list: &mut LoopList,
+    head: ObjPtr<BasicBlock>,
+    alias: &AliasAnalysis,
     _pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
 ) {
     loop {
@@ -18,8 +24,9 @@ pub fn licm_run(
         // 先把当前循环的循环不变量放到循环的preheader中
         loop {
             let mut changed = false;
+            let mssa = MemorySSA::new(head, alias);
             for loop_info in loop_list.get_loop_list() {
-                changed |= licm_one_loop(loop_info.clone());
+                changed |= licm_one_loop(*loop_info, &mssa);
             }
 
             if !changed {
@@ -34,7 +41,7 @@ pub fn licm_run(
     }
 }
 
-fn licm_one_loop(loop_info: ObjPtr<LoopInfo>) -> bool {
+fn licm_one_loop(loop_info: ObjPtr<LoopInfo>, mssa: &MemorySSA) -> bool {
     let preheader = loop_info.get_preheader();
     let mut changed = false;
     let mut tail_inst = preheader.get_tail_inst();
@@ -53,7 +60,11 @@ fn licm_one_loop(loop_info: ObjPtr<LoopInfo>) -> bool {
                     | InstKind::Phi
                     | InstKind::Call(_) => {}
                     InstKind::Load => {
-                        if inst.is_global_array_load() {
+                        if inst.is_global_array_load()
+                            || (is_memory_invariant(inst, loop_info, mssa)
+                                && (bb == loop_info.get_header()
+                                    || is_safe_to_load(inst.get_ptr())))
+                        {
                             changed = true;
                             inst.move_self();
                             tail_inst.insert_before(inst);
@@ -83,3 +94,25 @@ fn is_loop_invariant(inst: ObjPtr<Inst>, loop_info: ObjPtr<LoopInfo>) -> bool {
             }
     })
 }
+
+/// 循环中没有写load的位置的指令,即load的clobber在循环外
+fn is_memory_invariant(load: ObjPtr<Inst>, loop_info: ObjPtr<LoopInfo>, mssa: &MemorySSA) -> bool {
+    let clobber = mssa.get_clobber(load, load.get_ptr());
+    match mssa.get_memory_access(clobber).block {
+        Some(bb) => !loop_info.is_in_loop(&bb),
+        None => true,
+    }
+}
+
+/// 不在循环头中的load被外提后可能在原本不会执行的路径上执行,只有地址一定合法时才能外提
+fn is_safe_to_load(ptr: ObjPtr<Inst>) -> bool {
+    let loc = get_location(ptr);
+    match (loc.base, loc.offset) {
+        (MemBase::Global(global), MemOffset::Const(offset))
+        | (MemBase::Local(global), MemOffset::Const(offset)) => match global.get_kind() {
+            InstKind::Alloca(length) => 0 <= offset && offset < length as i64,
+            _ => true,
+        },
+        _ => false,
+    }
+}
@@ -3,9 +3,10 @@ use std::{collections::HashSet, hash::Hash};
 use crate::ir::{
     analysis::{
         call_optimize::call_optimize,
+        dominator_tree::{calculate_dominator, DominatorTree},
         scev::{scevexp::SCEVExp, SCEVAnalyzer},
     },
-    instruction::InstKind,
+    instruction::{BinOp, InstKind},
 };
 
 use super::{livo::parse_scev_exp, *};
@@ -15,7 +16,8 @@ pub fn loop_elimination(
     pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
 ) {
     let call_op_set = call_optimize(module);
-    func_process(module, |name, _| {
+    func_process(module, |name, func| {
+        let dominator_tree = calculate_dominator(func.get_head());
         let mut delete_list = vec![];
         let looplist = loop_map.get_mut(&name).unwrap();
         let mut analyzer = SCEVAnalyzer::new();
@@ -23,7 +25,7 @@ pub fn loop_elimination(
         looplist.get_loop_list().iter().for_each(|loop_info| {
             if let Some(exit) = check_one_exiting(*loop_info) {
                 loop_induct(*loop_info, &mut analyzer, exit, pools);
-                loop_store_eliminate(*loop_info, &mut analyzer, exit, pools);
+                loop_store_eliminate(*loop_info, &mut analyzer, exit, &dominator_tree, pools);
                 loop_dead_code_eliminate(*loop_info, &call_op_set);
                 if loop_eliminate(*loop_info, &call_op_set, exit) {
                     delete_list.push(*loop_info);
@@ -88,12 +90,7 @@ fn loop_induct(
         return;
     }
 
-    let round = parse_round(
-        analyzer,
-        loop_info,
-        exiting.get_tail_inst().get_br_cond(),
-        pools,
-    );
+    let round = parse_round(analyzer, loop_info, exiting, pools);
     if round.is_none() {
         return;
     }
@@ -102,112 +99,127 @@ fn loop_induct(
     for (inst, mut users) in inst_list {
         let inst_scev = analyzer.analyze(&inst);
         if users.len() > 0 && inst_scev.is_scev_rec() {
-            let new_inst = parse_inst(
+            if let Some(new_inst) = parse_inst(
                 loop_info,
                 loop_info.get_preheader().get_tail_inst(),
                 round,
                 &inst_scev.get_operands(),
                 pools,
-            );
-            users.iter_mut().for_each(|user| {
-                let index = user.get_operand_index(inst);
-                user.set_operand(new_inst, index);
-            })
+            ) {
+                users.iter_mut().for_each(|user| {
+                    let index = user.get_operand_index(inst);
+                    user.set_operand(new_inst, index);
+                })
+            }
         }
     }
 }
 
+/// 每次迭代都向a[i]写入同一个常量时,改为在preheader中用memset一次写完。
+/// 循环中读取了数组、对数组有多次写入、或者有函数调用时不处理
 fn loop_store_eliminate(
     loop_info: ObjPtr<LoopInfo>,
     analyzer: &mut SCEVAnalyzer,
     exit: [ObjPtr<BasicBlock>; 2],
+    dominator_tree: &DominatorTree,
     pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
 ) {
-    let mut array_store: HashMap<ObjPtr<Inst>, ObjPtr<Inst>> = HashMap::new();
-    let mut insts: Vec<ObjPtr<Inst>> = vec![];
-    let bbs = loop_info.get_current_loop_bb();
-    for bb in bbs {
-        inst_process_in_bb(bb.get_head_inst(), |inst| {
-            if inst.is_global_array_load() {
-                let dest = inst.get_dest();
-                if let Some(store) = array_store.get(&dest) {
-                    if let Some(index) = insts.iter().position(|x| x == store) {
-                        insts.remove(index);
-                    }
-                } else {
-                    array_store.insert(dest, inst);
-                }
-            } else if inst.is_array_store() {
-                let dest = inst.get_dest();
-                if let Some(store) = array_store.get(&dest) {
-                    if let Some(index) = insts.iter().position(|x| x == store) {
-                        insts.remove(index);
+    // 数组元素的地址对应的数组,不能确定时为None
+    let get_array = |ptr: ObjPtr<Inst>| -> Option<ObjPtr<Inst>> {
+        if ptr.get_kind() != InstKind::Gep {
+            return None;
+        }
+        let base = ptr.get_gep_ptr();
+        if base.is_array() || base.is_param() {
+            Some(base)
+        } else if base.is_global_array_load() {
+            Some(base.get_ptr())
+        } else {
+            None
+        }
+    };
+
+    let mut stores: HashMap<ObjPtr<Inst>, Vec<ObjPtr<Inst>>> = HashMap::new();
+    let mut read = HashSet::new();
+    let mut flag = true;
+    for bb in loop_info.get_current_loop_bb() {
+        inst_process_in_bb(bb.get_head_inst(), |inst| match inst.get_kind() {
+            InstKind::Load if inst.get_ptr().get_kind() == InstKind::Gep => {
+                match get_array(inst.get_ptr()) {
+                    Some(array) => {
+                        read.insert(array);
                     }
-                } else {
-                    array_store.insert(dest, inst);
-                    insts.push(inst)
+                    None => flag = false,
                 }
             }
+            InstKind::Store if inst.is_array_store() => match get_array(inst.get_dest()) {
+                Some(array) => stores.entry(array).or_default().push(inst),
+                None => flag = false,
+            },
+            InstKind::Call(_) => flag = false,
+            _ => {}
         })
     }
-
-    let mut tail = loop_info.get_preheader().get_tail_inst();
-    let round = parse_round(
-        analyzer,
-        loop_info,
-        exit[0].get_tail_inst().get_br_cond(),
-        pools,
-    );
-
-    let check_value = |value: ObjPtr<Inst>| -> bool {
-        !value.is_global_var()
-            && (value.is_param() || !loop_info.is_in_current_loop(&value.get_parent_bb()))
+    if !flag {
+        return;
+    }
+    let round = match parse_round(analyzer, loop_info, exit[0], pools) {
+        Some(round) => round,
+        None => return,
     };
+    let latchs = loop_info.as_mut().get_latch_blocks();
+    let mut tail = loop_info.get_preheader().get_tail_inst();
 
-    insts.iter_mut().for_each(|store| {
+    for (array, stores) in stores {
+        let mut store = stores[0];
         let gep = store.get_dest();
         let value = store.get_value();
-        if loop_info.is_in_current_loop(&gep.get_parent_bb()) && check_value(value) {
-            if let Some(round) = round {
-                let array = if gep.get_gep_ptr().is_array() || gep.get_gep_ptr().is_param() {
-                    gep.get_gep_ptr()
-                } else {
-                    gep.get_gep_ptr().get_ptr()
-                };
-                let offset = gep.get_gep_offset();
-                let iv = analyzer.analyze(&offset);
-                if iv.is_scev_rec_expr()
-                    && iv.get_operands()[1].is_scev_constant()
-                    && iv.get_operands()[1].get_scev_const() == 1
-                {
-                    let start = if iv.get_operands()[0].is_scev_constant() {
-                        let constx = pools
-                            .1
-                            .make_int_const(iv.get_operands()[0].get_scev_const());
-                        tail.insert_before(constx);
-                        constx
-                    } else {
-                        iv.get_operands()[0].get_bond_inst()
-                    };
-                    let new_gep_start = pools.1.make_gep(array, start);
-                    let const_4 = pools.1.make_int_const(4);
-                    let round_4 = pools.1.make_mul(round, const_4);
-                    let memset = pools.1.make_void_call(
-                        "hitsz_memset".to_string(),
-                        vec![new_gep_start, value, round_4],
-                    );
-                    tail.insert_before(new_gep_start);
-                    tail.insert_before(const_4);
-                    tail.insert_before(round_4);
-                    tail.insert_before(memset);
-                    store.remove_self();
-                }
-            }
-        } else if check_value(value) {
-            store.move_self();
-            tail.insert_before(*store);
+        // 写入要在每次迭代中都执行;memset按字节填充,写入的值的四个字节必须相同
+        if stores.len() != 1
+            || read.contains(&array)
+            || value.get_kind() != InstKind::ConstInt(0)
+            || !value
+                .get_int_bond()
+                .to_ne_bytes()
+                .windows(2)
+                .all(|bytes| bytes[0] == bytes[1])
+            || !latchs
+                .iter()
+                .all(|latch| dominator_tree.is_dominate(&store.get_parent_bb(), latch))
+        {
+            continue;
         }
-    })
+        let iv = analyzer.analyze(&gep.get_gep_offset());
+        if iv.is_scev_rec_expr()
+            && iv.get_in_loop() == Some(loop_info)
+            && iv.get_operands().len() == 2
+            && iv.get_operands()[1].is_scev_constant()
+            && iv.get_operands()[1].get_scev_const() == 1
+        {
+            let start = parse_one_inst(loop_info, iv.get_operands()[0], tail, pools);
+            let value = pools.1.make_int_const(value.get_int_bond());
+            tail.insert_before(value);
+            let base = if gep.get_gep_ptr().is_global_array_load() {
+                let load = pools.1.make_global_int_array_load(array);
+                tail.insert_before(load);
+                load
+            } else {
+                array
+            };
+            let new_gep_start = pools.1.make_gep(base, start);
+            let const_4 = pools.1.make_int_const(4);
+            let round_4 = pools.1.make_mul(round, const_4);
+            let memset = pools.1.make_void_call(
+                "hitsz_memset".to_string(),
+                vec![new_gep_start, value, round_4],
+            );
+            tail.insert_before(new_gep_start);
+            tail.insert_before(const_4);
+            tail.insert_before(round_4);
+            tail.insert_before(memset);
+            store.remove_self();
+        }
+    }
 }
 
 fn loop_eliminate(
@@ -307,12 +319,22 @@ fn loop_dead_code_eliminate(loop_info: ObjPtr<LoopInfo>, call_op_set: &HashSet<S
     delete_list.iter().for_each(|x| x.as_mut().remove_self());
 }
 
+/// 计算循环的迭代次数,只处理header中判断"i < n"或"i <= n"后留在循环中,步长为1的循环。
+/// n比i的初值小时迭代次数为0
 fn parse_round(
     analyzer: &mut SCEVAnalyzer,
     loop_info: ObjPtr<LoopInfo>,
-    cond: ObjPtr<Inst>,
+    exiting: ObjPtr<BasicBlock>,
     pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
 ) -> Option<ObjPtr<Inst>> {
+    let br = exiting.get_tail_inst();
+    if exiting != loop_info.get_header()
+        || !br.is_br_cond()
+        || !loop_info.is_in_current_loop(&br.get_true_bb())
+    {
+        return None;
+    }
+    let cond = br.get_br_cond();
     if !cond.is_cond() {
         return None;
     }
@@ -320,85 +342,84 @@ fn parse_round(
     let rhs = analyzer.analyze(&cond.get_rhs());
 
     let check_current_iv = |op: &ObjPtr<SCEVExp>| -> bool {
-        op.is_scev_rec_expr() && loop_info == op.get_in_loop().unwrap()
-    };
-
-    let parse_answer = |start: ObjPtr<SCEVExp>,
-                        end: ObjPtr<SCEVExp>,
-                        can_be_equal: bool,
-                        pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>)|
-     -> ObjPtr<Inst> {
-        let mut tail = loop_info.get_preheader().get_tail_inst();
-        let start = parse_one_inst(loop_info, start, tail, pools);
-        let end = parse_one_inst(loop_info, end, tail, pools);
-
-        let mut minus = pools.1.make_sub(end, start);
-        tail.insert_before(minus);
-        if can_be_equal {
-            let const_1 = pools.1.make_int_const(1);
-            minus = pools.1.make_add(minus, const_1);
-            tail.insert_before(const_1);
-            tail.insert_before(minus);
-        }
-        minus
+        op.is_scev_rec_expr()
+            && loop_info == op.get_in_loop().unwrap()
+            && op.get_operands().len() == 2
+            && op.get_operands()[1].is_scev_constant()
+            && op.get_operands()[1].get_scev_const() == 1
     };
 
-    let check_eq = |op: InstKind| match op {
-        InstKind::Binary(crate::ir::instruction::BinOp::Le)
-        | InstKind::Binary(crate::ir::instruction::BinOp::Ge)
-        | InstKind::Binary(crate::ir::instruction::BinOp::Eq) => true,
-        _ => false,
+    // 归纳变量在左边时只能是小于(等于),在右边时只能是大于(等于)
+    let (iv, end, can_be_equal) = match (
+        cond.get_kind(),
+        check_current_iv(&lhs),
+        check_current_iv(&rhs),
+    ) {
+        (InstKind::Binary(BinOp::Lt), true, false) => (lhs, rhs, false),
+        (InstKind::Binary(BinOp::Le), true, false) => (lhs, rhs, true),
+        (InstKind::Binary(BinOp::Gt), false, true) => (rhs, lhs, false),
+        (InstKind::Binary(BinOp::Ge), false, true) => (rhs, lhs, true),
+        _ => return None,
     };
+    if end.get_in_loop() == Some(loop_info) {
+        return None;
+    }
 
-    match (check_current_iv(&lhs), check_current_iv(&rhs)) {
-        (true, false) => {
-            if rhs.get_in_loop() == Some(loop_info) {
-                None
-            } else {
-                let start = lhs.get_operands()[0];
-                let step = lhs.get_operands()[1];
-                if step.is_scev_constant() && step.get_scev_const() == 1 {
-                    Some(parse_answer(start, rhs, check_eq(cond.get_kind()), pools))
-                } else {
-                    None
-                }
-            }
-        }
-        (false, true) => {
-            if lhs.get_in_loop() == Some(loop_info) {
-                None
-            } else {
-                let start = rhs.get_operands()[0];
-                let step = rhs.get_operands()[1];
-                if step.is_scev_constant() && step.get_scev_const() == 1 {
-                    Some(parse_answer(start, lhs, check_eq(cond.get_kind()), pools))
-                } else {
-                    None
-                }
-            }
-        }
-        _ => None,
+    let mut tail = loop_info.get_preheader().get_tail_inst();
+    let start = parse_one_inst(loop_info, iv.get_operands()[0], tail, pools);
+    let end = parse_one_inst(loop_info, end, tail, pools);
+    let mut minus = pools.1.make_sub(end, start);
+    tail.insert_before(minus);
+    if can_be_equal {
+        let const_1 = pools.1.make_int_const(1);
+        minus = pools.1.make_add(minus, const_1);
+        tail.insert_before(const_1);
+        tail.insert_before(minus);
     }
+    let const_0 = pools.1.make_int_const(0);
+    let positive = pools.1.make_gt(minus, const_0);
+    let round = pools.1.make_select(positive, minus, const_0);
+    tail.insert_before(const_0);
+    tail.insert_before(positive);
+    tail.insert_before(round);
+    Some(round)
 }
 
+/// 计算归纳变量{a, +, b, +, c}在第round次迭代时的值 a + b * round + c * round * (round - 1) / 2,
+/// 更高次的归纳变量不处理
 fn parse_inst(
     loop_info: ObjPtr<LoopInfo>,
     mut tail: ObjPtr<Inst>,
     round: ObjPtr<Inst>,
     operands: &[ObjPtr<SCEVExp>],
     pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
-) -> ObjPtr<Inst> {
+) -> Option<ObjPtr<Inst>> {
+    if operands.len() > 3 {
+        return None;
+    }
     let start = parse_one_inst(loop_info, operands[0], tail, pools);
-    let step = if operands.len() > 2 {
-        parse_inst(loop_info, tail, round, &operands[1..], pools)
-    } else {
-        parse_one_inst(loop_info, operands[1], tail, pools)
-    };
+    let step = parse_one_inst(loop_info, operands[1], tail, pools);
     let mul = pools.1.make_mul(step, round);
-    let add = pools.1.make_add(start, mul);
+    let mut add = pools.1.make_add(start, mul);
     tail.insert_before(mul);
     tail.insert_before(add);
-    add
+    if let Some(op) = operands.get(2) {
+        // round * (round - 1)是偶数,按无符号数右移不会受溢出的符号影响
+        let step = parse_one_inst(loop_info, *op, tail, pools);
+        let const_1 = pools.1.make_int_const(1);
+        let round_1 = pools.1.make_sub(round, const_1);
+        let product = pools.1.make_mul(round, round_1);
+        let half = pools.1.make_lshr(product, const_1);
+        let mul = pools.1.make_mul(step, half);
+        tail.insert_before(const_1);
+        tail.insert_before(round_1);
+        tail.insert_before(product);
+        tail.insert_before(half);
+        tail.insert_before(mul);
+        add = pools.1.make_add(add, mul);
+        tail.insert_before(add);
+    }
+    Some(add)
 }
 
 fn parse_one_inst(
@@ -415,3 +436,335 @@ fn parse_one_inst(
         vec.last().unwrap().clone()
     }
 }
+
+#[cfg(test)]
+mod loop_elimination_test {
+    use crate::ir::{
+        add_interface, dump_module, interpret, module::Module, optimizer_run,
+        test_util::parse_module, verify_module, DEFAULT_STEP_LIMIT,
+    };
+    use crate::pass_manager::PassManager;
+
+    /// 解析IR并运行优化,检查每组输入的返回值
+    fn check_optimized(text: &str, cases: &[(&str, i32)]) -> Module {
+        let (mut module, pools) = parse_module(text);
+        optimizer_run(
+            &mut module,
+            (pools.bb, pools.inst),
+            pools.func,
+            true,
+            false,
+            &PassManager::new(),
+        );
+        assert_eq!(verify_module(&module), vec![]);
+        for (input, ret) in cases {
+            assert_eq!(
+                interpret(&module, input, DEFAULT_STEP_LIMIT).unwrap().ret,
+                *ret,
+                "input {}",
+                input
+            );
+        }
+        module
+    }
+
+    #[test]
+    fn test_trip_count() {
+        // 循环后的%u = 100 - %i;n小于初值时循环一次也不执行,%i仍是0
+        check_optimized(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %n = call i32 @getint()
+  br label %bb_loop
+
+bb_loop:
+  %i = phi i32 [ 0, %bb_entry ], [ %i2, %bb_body ]
+  %u = sub i32 100, %i
+  %c = icmp slt i32 %i, %n
+  br i1 %c, label %bb_body, label %bb_exit
+
+bb_body:
+  %i2 = add i32 %i, 1
+  br label %bb_loop
+
+bb_exit:
+  ret i32 %u
+}
+",
+            &[("5", 95), ("0", 100), ("-3", 100)],
+        );
+    }
+
+    #[test]
+    fn test_second_order() {
+        // %s是二阶的归纳变量,%t每次减3
+        check_optimized(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %n = call i32 @getint()
+  br label %bb_loop
+
+bb_loop:
+  %i = phi i32 [ 0, %bb_entry ], [ %i2, %bb_body ]
+  %s = phi i32 [ 0, %bb_entry ], [ %s2, %bb_body ]
+  %t = phi i32 [ 0, %bb_entry ], [ %t2, %bb_body ]
+  %c = icmp slt i32 %i, %n
+  br i1 %c, label %bb_body, label %bb_exit
+
+bb_body:
+  %s2 = add i32 %s, %i
+  %t2 = sub i32 %t, 3
+  %i2 = add i32 %i, 1
+  br label %bb_loop
+
+bb_exit:
+  %r = mul i32 %s, 1000
+  %r2 = add i32 %r, %t
+  ret i32 %r2
+}
+",
+            &[("10", 44970), ("1", -3), ("0", 0), ("-4", 0)],
+        );
+    }
+
+    #[test]
+    fn test_product_of_induction_variables() {
+        // %i * %i不是归纳变量,不能用迭代次数直接算出
+        check_optimized(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %n = call i32 @getint()
+  br label %bb_loop
+
+bb_loop:
+  %i = phi i32 [ 0, %bb_entry ], [ %i2, %bb_body ]
+  %m = mul i32 %i, %i
+  %c = icmp slt i32 %i, %n
+  br i1 %c, label %bb_body, label %bb_exit
+
+bb_body:
+  %i2 = add i32 %i, 1
+  br label %bb_loop
+
+bb_exit:
+  ret i32 %m
+}
+",
+            &[("6", 36), ("1", 1), ("0", 0)],
+        );
+    }
+
+    #[test]
+    fn test_without_licm() {
+        // 不经过licm时循环中还留有全局数组地址的load,循环删除不能把它当作store处理
+        let (mut module, mut pools) = parse_module(
+            "
+@g = dso_local global [4 x i32] [ i32 1, i32 2, i32 3, i32 4], align 4
+
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %n = call i32 @getint()
+  %a = alloca [4 x i32], align 4
+  %a1 = getelementptr inbounds [4 x i32], [4 x i32]* %a, i32 0, i32 1
+  store i32 5, i32* %a1, align 4
+  br label %bb_loop
+
+bb_loop:
+  %i = phi i32 [ 0, %bb_entry ], [ %i2, %bb_body ]
+  %s = phi i32 [ 0, %bb_entry ], [ %s2, %bb_body ]
+  %c = icmp slt i32 %i, %n
+  br i1 %c, label %bb_body, label %bb_exit
+
+bb_body:
+  %ga = load ptr, ptr @g, align 8
+  %p = getelementptr inbounds [4 x i32], [4 x i32]* %ga, i32 0, i32 %i
+  %v = load i32, i32* %p, align 4
+  %s2 = add i32 %s, %v
+  %q = getelementptr inbounds [4 x i32], [4 x i32]* %a, i32 0, i32 %i
+  store i32 0, i32* %q, align 4
+  %i2 = add i32 %i, 1
+  br label %bb_loop
+
+bb_exit:
+  %v1 = load i32, i32* %a1, align 4
+  %r = add i32 %s, %v1
+  ret i32 %r
+}
+",
+        );
+        // 循环删除生成的memset调用需要运行库接口
+        add_interface(&mut module, &mut *pools.func, &mut *pools.inst, true, false);
+        let mut passes = PassManager::new();
+        passes.disable("licm").unwrap();
+        optimizer_run(
+            &mut module,
+            (pools.bb, pools.inst),
+            pools.func,
+            true,
+            false,
+            &passes,
+        );
+        assert_eq!(verify_module(&module), vec![]);
+        for (input, ret) in [("4", 10), ("1", 6), ("0", 5)] {
+            assert_eq!(
+                interpret(&module, input, DEFAULT_STEP_LIMIT).unwrap().ret,
+                ret
+            );
+        }
+    }
+
+    #[test]
+    fn test_store_with_load() {
+        // 循环中先读a[2]再写a[i],不能把写入提前为循环前的memset
+        let (mut module, pools) = parse_module(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %n = call i32 @getint()
+  %a = alloca [10 x i32], align 4
+  br label %bb_loop
+
+bb_loop:
+  %i = phi i32 [ 0, %bb_entry ], [ %i2, %bb_body ]
+  %s = phi i32 [ 0, %bb_entry ], [ %s2, %bb_body ]
+  %c = icmp slt i32 %i, %n
+  br i1 %c, label %bb_body, label %bb_exit
+
+bb_body:
+  %p = getelementptr inbounds [10 x i32], [10 x i32]* %a, i32 0, i32 2
+  %v = load i32, i32* %p, align 4
+  %s2 = add i32 %s, %v
+  %q = getelementptr inbounds [10 x i32], [10 x i32]* %a, i32 0, i32 %i
+  store i32 -1, i32* %q, align 4
+  %i2 = add i32 %i, 1
+  br label %bb_loop
+
+bb_exit:
+  %p1 = getelementptr inbounds [10 x i32], [10 x i32]* %a, i32 0, i32 1
+  %v1 = load i32, i32* %p1, align 4
+  %r = mul i32 %s, 10
+  %r2 = add i32 %r, %v1
+  ret i32 %r2
+}
+
+declare i32 @getint()
+",
+        );
+        let mut passes = PassManager::new();
+        passes.disable("licm").unwrap();
+        optimizer_run(
+            &mut module,
+            (pools.bb, pools.inst),
+            pools.func,
+            true,
+            false,
+            &passes,
+        );
+        assert_eq!(verify_module(&module), vec![]);
+        for (input, ret) in [("4", -11), ("3", -1), ("0", 0)] {
+            assert_eq!(
+                interpret(&module, input, DEFAULT_STEP_LIMIT).unwrap().ret,
+                ret
+            );
+        }
+    }
+
+    #[test]
+    fn test_non_splat_value() {
+        // memset按字节填充,b[i] = 7不能改为memset,b[i] = -1可以
+        let (mut module, mut pools) = parse_module(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %n = call i32 @getint()
+  %a = alloca [8 x i32], align 4
+  %b = alloca [8 x i32], align 4
+  %a0 = getelementptr inbounds [8 x i32], [8 x i32]* %a, i32 0, i32 0
+  store i32 1, i32* %a0, align 4
+  %b0 = getelementptr inbounds [8 x i32], [8 x i32]* %b, i32 0, i32 0
+  store i32 2, i32* %b0, align 4
+  br label %bb_loop
+
+bb_loop:
+  %i = phi i32 [ 0, %bb_entry ], [ %i2, %bb_body ]
+  %c = icmp slt i32 %i, %n
+  br i1 %c, label %bb_body, label %bb_exit
+
+bb_body:
+  %p = getelementptr inbounds [8 x i32], [8 x i32]* %a, i32 0, i32 %i
+  store i32 -1, i32* %p, align 4
+  %q = getelementptr inbounds [8 x i32], [8 x i32]* %b, i32 0, i32 %i
+  store i32 7, i32* %q, align 4
+  %i2 = add i32 %i, 1
+  br label %bb_loop
+
+bb_exit:
+  %x = load i32, i32* %a0, align 4
+  %y = load i32, i32* %b0, align 4
+  %r = mul i32 %x, 10
+  %r2 = add i32 %r, %y
+  ret i32 %r2
+}
+",
+        );
+        add_interface(&mut module, &mut *pools.func, &mut *pools.inst, true, false);
+        optimizer_run(
+            &mut module,
+            (pools.bb, pools.inst),
+            pools.func,
+            true,
+            false,
+            &PassManager::new(),
+        );
+        assert_eq!(verify_module(&module), vec![]);
+        let text = dump_module(&module).unwrap();
+        assert_eq!(
+            text.matches("call void @hitsz_memset").count(),
+            1,
+            "{}",
+            text
+        );
+        for (input, ret) in [("3", -3), ("0", 12)] {
+            assert_eq!(
+                interpret(&module, input, DEFAULT_STEP_LIMIT).unwrap().ret,
+                ret
+            );
+        }
+    }
+
+    #[test]
+    fn test_invariant_store() {
+        // 循环中每次都写同一个位置a[k],循环一次也不执行时这个写入不能发生
+        check_optimized(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %k = call i32 @getint()
+  %n = call i32 @getint()
+  %a = alloca [4 x i32], align 4
+  %ak = getelementptr inbounds [4 x i32], [4 x i32]* %a, i32 0, i32 %k
+  store i32 5, i32* %ak, align 4
+  br label %bb_loop
+
+bb_loop:
+  %i = phi i32 [ 0, %bb_entry ], [ %i2, %bb_body ]
+  %c = icmp slt i32 %i, %n
+  br i1 %c, label %bb_body, label %bb_exit
+
+bb_body:
+  store i32 %n, i32* %ak, align 4
+  %i2 = add i32 %i, 1
+  br label %bb_loop
+
+bb_exit:
+  %v = load i32, i32* %ak, align 4
+  ret i32 %v
+}
+",
+            &[("1 3", 3), ("2 0", 5), ("1 -2", 5)],
+        );
+    }
+}
@@ -0,0 +1,362 @@
+//! 循环合并
+//!
+//! 两个相邻的最内层循环迭代空间相同,之间只有可以移动的计算,且第二个循环的第k次迭代
+//! 不依赖第一个循环第k次之后的迭代时,把第二个循环的循环体接在第一个循环的循环体之后:
+//! 两个循环之间的计算移到第一个循环的preheader中,第二个循环的归纳变量替换为第一个循环的,
+//! 其余phi移到第一个循环的header中
+use crate::ir::{
+    analysis::{dependent_analyse::Direction, scev::SCEVAnalyzer},
+    instruction::InstKind,
+    tools::replace_inst,
+    transform::simplify_cfg::merge_bb,
+};
+
+use super::{loop_nest::*, *};
+
+pub fn loop_fusion(module: &mut Module, loop_map: &mut HashMap<String, LoopList>) {
+    func_process(module, |name, _| {
+        let loop_list = loop_map.get_mut(&name).unwrap();
+        let mut analyzer = SCEVAnalyzer::new();
+        analyzer.set_loop_list(loop_list.get_loop_list().clone());
+        // 合并后的循环可能继续和下一个循环合并
+        while let Some(second) = loop_list
+            .get_loop_list()
+            .clone()
+            .into_iter()
+            .find_map(|first| try_fuse(first, loop_list.get_loop_list(), &mut analyzer))
+        {
+            loop_list.remove_loops(&vec![second]);
+            analyzer.clear();
+        }
+    });
+}
+
+/// 尝试将first和它之后的循环合并,成功时返回被合并掉的循环
+fn try_fuse(
+    first: ObjPtr<LoopInfo>,
+    loops: &[ObjPtr<LoopInfo>],
+    analyzer: &mut SCEVAnalyzer,
+) -> Option<ObjPtr<LoopInfo>> {
+    if !first.get_sub_loops().is_empty() {
+        return None;
+    }
+    let control1 = get_loop_control(first, analyzer)?;
+
+    // 从第一个循环的出口沿着单前继单后继的块找到下一个循环的preheader
+    let mut chain = vec![];
+    let mut bb = control1.exit;
+    let second = loop {
+        if bb.get_up_bb().len() != 1 || bb.get_next_bb().len() != 1 || chain.contains(&bb) {
+            return None;
+        }
+        chain.push(bb);
+        let next = bb.get_next_bb()[0];
+        if let Some(second) = loops.iter().find(|x| x.get_header() == next) {
+            if second.get_preheader() != bb {
+                return None;
+            }
+            break *second;
+        }
+        bb = next;
+    };
+    if !second.get_sub_loops().is_empty() || second.get_parent_loop() != first.get_parent_loop() {
+        return None;
+    }
+    let control2 = get_loop_control(second, analyzer)?;
+    if !is_fusible(first, &control1, second, &control2, &chain) {
+        return None;
+    }
+
+    fuse(first, &control1, second, &control2, &chain);
+    Some(second)
+}
+
+/// 两个值相同,或者是相等的整数常量
+fn is_same_value(lhs: ObjPtr<Inst>, rhs: ObjPtr<Inst>) -> bool {
+    lhs == rhs
+        || lhs.is_int_const() && rhs.is_int_const() && lhs.get_int_bond() == rhs.get_int_bond()
+}
+
+fn is_fusible(
+    first: ObjPtr<LoopInfo>,
+    control1: &LoopControl,
+    second: ObjPtr<LoopInfo>,
+    control2: &LoopControl,
+    chain: &[ObjPtr<BasicBlock>],
+) -> bool {
+    // 迭代空间相同
+    if !is_same_value(control1.start, control2.start)
+        || !is_same_value(control1.end, control2.end)
+        || control1.step != control2.step
+        || control1.inclusive != control2.inclusive
+    {
+        return false;
+    }
+    let header2 = second.get_header();
+    if !control1.latch.get_tail_inst().is_br_jmp()
+        || control2.body.get_up_bb().len() != 1
+        || control2.cond.get_use_list().len() != 1
+    {
+        return false;
+    }
+
+    let defined_in_first = |inst: ObjPtr<Inst>| {
+        inst.get_operands()
+            .iter()
+            .any(|operand| !is_invariant(*operand, first))
+    };
+    let mut flag = true;
+    // 两个循环之间的计算会移到第一个循环之前
+    for bb in chain {
+        inst_process_in_bb(bb.get_head_inst(), |inst| {
+            flag &=
+                inst.get_kind() == InstKind::Branch || is_movable(inst) && !defined_in_first(inst);
+        });
+    }
+    // 第二个循环的header中只有phi和循环控制
+    inst_process_in_bb(header2.get_head_inst(), |inst| {
+        flag &= inst.is_phi()
+            || inst == control2.cond
+            || inst.get_kind() == InstKind::Branch
+            || inst.is_const();
+    });
+    // 第二个循环不能使用第一个循环中的值
+    for bb in second.get_current_loop_bb() {
+        inst_process_in_bb(bb.get_head_inst(), |inst| {
+            flag &= !defined_in_first(inst);
+        });
+    }
+    if !flag {
+        return false;
+    }
+
+    // 合并后第二个循环的第k次迭代提前到第一个循环的第k+1次迭代之前执行
+    let (accesses1, accesses2) = match (
+        get_accesses(first.get_current_loop_bb()),
+        get_accesses(second.get_current_loop_bb()),
+    ) {
+        (Some(accesses1), Some(accesses2)) => (accesses1, accesses2),
+        _ => return false,
+    };
+    !has_dependence(
+        &AccessGroup {
+            accesses: &accesses1,
+            ivs: &[control1.iv],
+            nest: first,
+        },
+        &AccessGroup {
+            accesses: &accesses2,
+            ivs: &[control2.iv],
+            nest: second,
+        },
+        &[Direction::Gt],
+        &[control1.get_distance()],
+    )
+}
+
+fn fuse(
+    mut first: ObjPtr<LoopInfo>,
+    control1: &LoopControl,
+    second: ObjPtr<LoopInfo>,
+    control2: &LoopControl,
+    chain: &[ObjPtr<BasicBlock>],
+) {
+    let (mut header1, mut header2) = (first.get_header(), second.get_header());
+    let (preheader1, mut preheader2) = (first.get_preheader(), second.get_preheader());
+    let (mut latch1, mut latch2) = (control1.latch, control2.latch);
+    let (mut body2, mut exit2) = (control2.body, control2.exit);
+
+    // 两个循环之间的计算移到第一个循环之前
+    for bb in chain {
+        inst_process_in_bb(bb.get_head_inst(), |mut inst| {
+            if inst.get_kind() != InstKind::Branch {
+                inst.move_self();
+                preheader1.get_tail_inst().insert_before(inst);
+            }
+        });
+    }
+
+    // 第二个循环header中的phi按第一个循环header的前继排列
+    let position = |bb: ObjPtr<BasicBlock>, up_bb: ObjPtr<BasicBlock>| {
+        bb.get_up_bb().iter().position(|x| *x == up_bb).unwrap()
+    };
+    let (index1, index2) = (position(header1, preheader1), position(header2, preheader2));
+    replace_inst(control2.iv, control1.iv);
+    inst_process_in_bb(header2.get_head_inst(), |mut inst| {
+        if inst.is_phi() {
+            let (start, update) = (inst.get_operand(index2), inst.get_operand(1 - index2));
+            inst.move_self();
+            header1.push_front(inst);
+            inst.set_operand(start, index1);
+            inst.set_operand(update, 1 - index1);
+        } else if inst.is_const() {
+            inst.move_self();
+            preheader1.get_tail_inst().insert_before(inst);
+        }
+    });
+    header2.get_tail_inst().remove_self();
+    control2.cond.as_mut().remove_self();
+
+    // 第一个循环的latch接第二个循环的循环体,第二个循环的latch回到第一个循环的header
+    latch1.replace_next_bb(header1, body2);
+    body2.replace_up_bb(header2, latch1);
+    latch2.replace_next_bb(header2, header1);
+    header1.replace_up_bb(latch1, latch2);
+    preheader2.replace_next_bb(header2, exit2);
+    exit2.replace_up_bb(header2, preheader2);
+    header2.clear_next_bb();
+    header2.clear_up_bb();
+
+    let mut blocks: Vec<ObjPtr<BasicBlock>> = second
+        .get_current_loop_bb()
+        .iter()
+        .filter(|bb| **bb != header2)
+        .copied()
+        .collect();
+    // 合并成一条直线的块,使合并后的循环仍然可以展开
+    if !body2.get_head_inst().is_phi() {
+        merge_bb(latch1);
+        blocks.retain(|bb| *bb != body2);
+    }
+    first.add_bbs(blocks);
+    first.clear_cache();
+    if let Some(mut parent) = second.get_parent_loop() {
+        parent.remove_sub_loop(second);
+    }
+}
+
+#[cfg(test)]
+mod loop_fusion_test {
+    use crate::ir::{
+        interpret, module::Module, test_util::parse_module, tools::bfs_bb_proceess,
+        transform::loop_operation::fusion, verify_module, DEFAULT_STEP_LIMIT,
+    };
+
+    /// 运行循环合并,返回剩下的条件跳转个数
+    fn run_fusion(text: &str) -> (Module, usize) {
+        let (mut module, pools) = parse_module(text);
+        fusion(&mut module, &mut (pools.bb, pools.inst));
+        assert_eq!(verify_module(&module), vec![]);
+        let mut count = 0;
+        bfs_bb_proceess(module.get_function("main").get_head(), |bb| {
+            if bb.get_next_bb().len() == 2 {
+                count += 1;
+            }
+        });
+        (module, count)
+    }
+
+    #[test]
+    fn test_fusion() {
+        // 三个循环依次写a[i],读a[i]写b[i],读b[i]求和,可以合并为一个循环
+        let (module, count) = run_fusion(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %a = alloca [16 x i32], align 4
+  %b = alloca [16 x i32], align 4
+  %n = call i32 @getint()
+  br label %bb_h1
+
+bb_h1:
+  %i = phi i32 [ 0, %bb_entry ], [ %i2, %bb_b1 ]
+  %c1 = icmp slt i32 %i, %n
+  br i1 %c1, label %bb_b1, label %bb_m1
+
+bb_b1:
+  %pa = getelementptr inbounds [16 x i32], [16 x i32]* %a, i32 0, i32 %i
+  %v = mul i32 %i, 2
+  store i32 %v, i32* %pa, align 4
+  %i2 = add i32 %i, 1
+  br label %bb_h1
+
+bb_m1:
+  %k = add i32 %n, 3
+  br label %bb_h2
+
+bb_h2:
+  %j = phi i32 [ 0, %bb_m1 ], [ %j2, %bb_b2 ]
+  %c2 = icmp slt i32 %j, %n
+  br i1 %c2, label %bb_b2, label %bb_m2
+
+bb_b2:
+  %qa = getelementptr inbounds [16 x i32], [16 x i32]* %a, i32 0, i32 %j
+  %qb = getelementptr inbounds [16 x i32], [16 x i32]* %b, i32 0, i32 %j
+  %x = load i32, i32* %qa, align 4
+  %y = add i32 %x, %k
+  store i32 %y, i32* %qb, align 4
+  %j2 = add i32 %j, 1
+  br label %bb_h2
+
+bb_m2:
+  br label %bb_h3
+
+bb_h3:
+  %l = phi i32 [ 0, %bb_m2 ], [ %l2, %bb_b3 ]
+  %s = phi i32 [ %n, %bb_m2 ], [ %s2, %bb_b3 ]
+  %c3 = icmp slt i32 %l, %n
+  br i1 %c3, label %bb_b3, label %bb_exit
+
+bb_b3:
+  %rb = getelementptr inbounds [16 x i32], [16 x i32]* %b, i32 0, i32 %l
+  %z = load i32, i32* %rb, align 4
+  %s2 = add i32 %s, %z
+  %l2 = add i32 %l, 1
+  br label %bb_h3
+
+bb_exit:
+  %r = add i32 %s, %i
+  ret i32 %r
+}
+",
+        );
+        assert_eq!(count, 1);
+        // s = 4 + (0 + 2 + 4 + 6) + 4 * 7, r = s + 4
+        assert_eq!(interpret(&module, "4", DEFAULT_STEP_LIMIT).unwrap().ret, 48);
+        assert_eq!(interpret(&module, "0", DEFAULT_STEP_LIMIT).unwrap().ret, 0);
+    }
+
+    #[test]
+    fn test_fusion_dependence() {
+        // 第二个循环读a[j + 1],合并后会读到第一个循环还没有写入的值
+        let (_, count) = run_fusion(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %a = alloca [16 x i32], align 4
+  %n = call i32 @getint()
+  br label %bb_h1
+
+bb_h1:
+  %i = phi i32 [ 0, %bb_entry ], [ %i2, %bb_b1 ]
+  %c1 = icmp slt i32 %i, %n
+  br i1 %c1, label %bb_b1, label %bb_h2
+
+bb_b1:
+  %pa = getelementptr inbounds [16 x i32], [16 x i32]* %a, i32 0, i32 %i
+  store i32 %i, i32* %pa, align 4
+  %i2 = add i32 %i, 1
+  br label %bb_h1
+
+bb_h2:
+  %j = phi i32 [ 0, %bb_h1 ], [ %j2, %bb_b2 ]
+  %s = phi i32 [ 0, %bb_h1 ], [ %s2, %bb_b2 ]
+  %c2 = icmp slt i32 %j, %n
+  br i1 %c2, label %bb_b2, label %bb_exit
+
+bb_b2:
+  %j1 = add i32 %j, 1
+  %qa = getelementptr inbounds [16 x i32], [16 x i32]* %a, i32 0, i32 %j1
+  %x = load i32, i32* %qa, align 4
+  %s2 = add i32 %s, %x
+  %j2 = add i32 %j, 1
+  br label %bb_h2
+
+bb_exit:
+  ret i32 %s
+}
+",
+        );
+        assert_eq!(count, 2);
+    }
+}
@@ -0,0 +1,225 @@
+//! 循环交换
+//!
+//! 对两层的完美循环嵌套,如果交换后最内层循环访存的跨度更小,且不存在方向为(<,>)的依赖,
+//! 就交换内外层循环的控制:两个header中的归纳变量和比较互换位置,latch中重新生成步进,
+//! 外层循环中只依赖外层归纳变量的计算移到内层循环头中
+use crate::ir::{
+    analysis::{
+        dependent_analyse::{AffineExpr, Direction},
+        scev::SCEVAnalyzer,
+    },
+    instruction::InstKind,
+};
+
+use super::{loop_nest::*, *};
+
+/// 一次访存的跨度超过这个值后不再区分
+const MAX_STRIDE: i32 = 16;
+
+pub fn loop_interchange(
+    module: &mut Module,
+    loop_map: &mut HashMap<String, LoopList>,
+    pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
+) {
+    func_process(module, |name, _| {
+        let loop_list = loop_map.get(&name).unwrap();
+        let mut analyzer = SCEVAnalyzer::new();
+        analyzer.set_loop_list(loop_list.get_loop_list().clone());
+        for outer in loop_list.get_loop_list().iter() {
+            if let Some(nest) = get_perfect_nest(*outer, &mut analyzer) {
+                if should_interchange(&nest) {
+                    interchange(&nest, pools);
+                    analyzer.clear();
+                }
+            }
+        }
+    });
+}
+
+/// 交换后内层循环的访存跨度更小,并且交换不会改变依赖
+fn should_interchange(nest: &PerfectNest) -> bool {
+    let accesses = match get_accesses(&get_loop_blocks(nest.inner)) {
+        Some(accesses) => accesses,
+        None => return false,
+    };
+    let ivs = [nest.outer_control.iv, nest.inner_control.iv];
+    let exprs: Vec<AffineExpr> = accesses
+        .iter()
+        .filter_map(|access| get_affine(access.ptr, &ivs, nest.outer))
+        .collect();
+    let stride = |level: usize| -> i32 {
+        exprs
+            .iter()
+            .map(|expr| expr.coefs[level].abs().min(MAX_STRIDE))
+            .sum()
+    };
+    if stride(0) >= stride(1) {
+        return false;
+    }
+
+    let group = AccessGroup {
+        accesses: &accesses,
+        ivs: &ivs,
+        nest: nest.outer,
+    };
+    !has_dependence(
+        &group,
+        &group,
+        &[Direction::Lt, Direction::Gt],
+        &[
+            nest.outer_control.get_distance(),
+            nest.inner_control.get_distance(),
+        ],
+    )
+}
+
+fn interchange(nest: &PerfectNest, pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>)) {
+    let (mut outer_header, mut inner_header) = (nest.outer.get_header(), nest.inner.get_header());
+    let (outer, inner) = (&nest.outer_control, &nest.inner_control);
+    let mut moved = vec![];
+    inst_process_in_bb(outer_header.get_head_inst(), |inst| {
+        if inst != outer.iv && inst != outer.cond && inst.get_kind() != InstKind::Branch {
+            moved.push(inst);
+        }
+    });
+    for bb in nest.before.iter() {
+        inst_process_in_bb(bb.get_head_inst(), |inst| {
+            if inst.get_kind() != InstKind::Branch {
+                moved.push(inst);
+            }
+        });
+    }
+
+    for mut inst in [outer.iv, inner.iv, inner.cond] {
+        inst.move_self();
+    }
+    outer_header.push_front(inner.iv);
+    outer_header.get_tail_inst().insert_before(inner.cond);
+    inner_header.push_front(outer.iv);
+    let mut pos = outer.iv;
+    for mut inst in std::iter::once(outer.cond).chain(moved) {
+        inst.move_self();
+        pos.insert_after(inst);
+        pos = inst;
+    }
+    outer_header.get_tail_inst().set_operand(inner.cond, 0);
+    inner_header.get_tail_inst().set_operand(outer.cond, 0);
+
+    // 归纳变量的操作数按新的header的前继排列,步进在新的latch中重新计算
+    let reset_iv = |mut iv: ObjPtr<Inst>,
+                    start: ObjPtr<Inst>,
+                    step: i32,
+                    loop_info: ObjPtr<LoopInfo>,
+                    latch: ObjPtr<BasicBlock>,
+                    pool: &mut ObjPool<Inst>| {
+        let step = pool.make_int_const(step);
+        let update = pool.make_add(iv, step);
+        latch.get_tail_inst().insert_before(step);
+        latch.get_tail_inst().insert_before(update);
+        let index = loop_info
+            .get_header()
+            .get_up_bb()
+            .iter()
+            .position(|bb| *bb == loop_info.get_preheader())
+            .unwrap();
+        iv.set_operand(start, index);
+        iv.set_operand(update, 1 - index);
+    };
+    reset_iv(
+        outer.iv,
+        outer.start,
+        outer.step,
+        nest.inner,
+        inner.latch,
+        pools.1,
+    );
+    reset_iv(
+        inner.iv,
+        inner.start,
+        inner.step,
+        nest.outer,
+        outer.latch,
+        pools.1,
+    );
+}
+
+#[cfg(test)]
+mod loop_interchange_test {
+    use crate::ir::{
+        instruction::{BinOp, InstKind},
+        interpret,
+        test_util::parse_module,
+        tools::bfs_bb_proceess,
+        transform::loop_operation::interchange,
+        verify_module, DEFAULT_STEP_LIMIT,
+    };
+
+    #[test]
+    fn test_interchange() {
+        // 内层循环按列访问%a,交换后内层循环的归纳变量是%i,访存跨度为1
+        let (mut module, pools) = parse_module(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %a = alloca [64 x i32], align 4
+  %n = call i32 @getint()
+  br label %bb_outer
+
+bb_outer:
+  %i = phi i32 [ 0, %bb_entry ], [ %i2, %bb_latch ]
+  %c = icmp slt i32 %i, 8
+  br i1 %c, label %bb_pre, label %bb_exit
+
+bb_pre:
+  %t = add i32 %i, %n
+  br label %bb_inner
+
+bb_inner:
+  %j = phi i32 [ 0, %bb_pre ], [ %j2, %bb_body ]
+  %c2 = icmp slt i32 %j, 8
+  br i1 %c2, label %bb_body, label %bb_latch
+
+bb_body:
+  %row = mul i32 %j, 8
+  %o = add i32 %row, %i
+  %p = getelementptr inbounds [64 x i32], [64 x i32]* %a, i32 0, i32 %o
+  %v = mul i32 %t, %j
+  store i32 %v, i32* %p, align 4
+  %j2 = add i32 %j, 1
+  br label %bb_inner
+
+bb_latch:
+  %i2 = add i32 %i, 1
+  br label %bb_outer
+
+bb_exit:
+  %p1 = getelementptr inbounds [64 x i32], [64 x i32]* %a, i32 0, i32 29
+  %p2 = getelementptr inbounds [64 x i32], [64 x i32]* %a, i32 0, i32 63
+  %x = load i32, i32* %p1, align 4
+  %y = load i32, i32* %p2, align 4
+  %r = add i32 %x, %y
+  ret i32 %r
+}
+
+declare i32 @getint()
+",
+        );
+        interchange(&mut module, &mut (pools.bb, pools.inst));
+        assert_eq!(verify_module(&module), vec![]);
+
+        // 内层循环头中的归纳变量直接作为下标的低位
+        let mut inner_iv = None;
+        bfs_bb_proceess(module.get_function("main").get_head(), |bb| {
+            if bb.get_name() == "inner" {
+                inner_iv = Some(bb.get_head_inst());
+            }
+        });
+        let inner_iv = inner_iv.unwrap();
+        assert!(inner_iv.is_phi());
+        assert!(inner_iv.get_use_list().iter().any(|user| {
+            user.get_kind() == InstKind::Binary(BinOp::Add) && user.get_rhs() == inner_iv
+        }));
+        // a[29] = (5 + n) * 3, a[63] = (7 + n) * 7
+        assert_eq!(interpret(&module, "1", DEFAULT_STEP_LIMIT).unwrap().ret, 74);
+    }
+}
@@ -0,0 +1,347 @@
+//! 循环嵌套变换(交换、分块、合并)共用的循环控制识别和依赖检查
+use crate::ir::{
+    analysis::{
+        alias::{alias_location, get_location, AliasResult},
+        dependent_analyse::{direction_check, parse_affine, AffineExpr, Direction},
+        scev::SCEVAnalyzer,
+    },
+    instruction::{BinOp, InstKind},
+    ir_type::IrType,
+};
+
+use super::*;
+
+/// 形如 for (i = start; i < end; i += step) 的循环控制,header是循环唯一的出口
+pub struct LoopControl {
+    /// 归纳变量
+    pub iv: ObjPtr<Inst>,
+    pub start: ObjPtr<Inst>,
+    pub end: ObjPtr<Inst>,
+    pub step: i32,
+    /// 条件为 i <= end 时为true
+    pub inclusive: bool,
+    pub cond: ObjPtr<Inst>,
+    /// header在循环中的后继
+    pub body: ObjPtr<BasicBlock>,
+    /// header在循环外的后继
+    pub exit: ObjPtr<BasicBlock>,
+    pub latch: ObjPtr<BasicBlock>,
+}
+
+impl LoopControl {
+    /// 归纳变量取值的最大差,边界不是常数时返回None
+    pub fn get_distance(&self) -> Option<i32> {
+        let count = self.get_trip_count()?;
+        Some((count.max(1) - 1) * self.step)
+    }
+
+    /// 常数的迭代次数,边界不是常数时返回None
+    pub fn get_trip_count(&self) -> Option<i32> {
+        if !self.start.is_int_const() || !self.end.is_int_const() {
+            return None;
+        }
+        let start = self.start.get_int_bond() as i64;
+        let last = self.end.get_int_bond() as i64 - if self.inclusive { 0 } else { 1 };
+        if last < start {
+            Some(0)
+        } else {
+            i32::try_from((last - start) / self.step as i64 + 1).ok()
+        }
+    }
+}
+
+/// 识别循环的控制结构,要求循环已经规范化
+pub fn get_loop_control(
+    mut loop_info: ObjPtr<LoopInfo>,
+    analyzer: &mut SCEVAnalyzer,
+) -> Option<LoopControl> {
+    let header = loop_info.get_header();
+    let preheader = loop_info.get_preheader();
+    if header.get_up_bb().len() != 2
+        || !header.get_up_bb().contains(&preheader)
+        || loop_info.get_exit_blocks() != vec![header]
+        || header.get_next_bb().len() != 2
+    {
+        return None;
+    }
+    let latch = *header.get_up_bb().iter().find(|bb| **bb != preheader)?;
+    // next_bb[0]是条件为假时的后继
+    let (exit, body) = (header.get_next_bb()[0], header.get_next_bb()[1]);
+    if loop_info.is_in_loop(&exit) || !loop_info.is_in_loop(&body) {
+        return None;
+    }
+
+    let cond = header.get_tail_inst().get_br_cond();
+    let (iv, end, inclusive) = match cond.get_kind() {
+        InstKind::Binary(BinOp::Lt) => (cond.get_lhs(), cond.get_rhs(), false),
+        InstKind::Binary(BinOp::Le) => (cond.get_lhs(), cond.get_rhs(), true),
+        InstKind::Binary(BinOp::Gt) => (cond.get_rhs(), cond.get_lhs(), false),
+        InstKind::Binary(BinOp::Ge) => (cond.get_rhs(), cond.get_lhs(), true),
+        _ => return None,
+    };
+    if !iv.is_phi()
+        || iv.get_parent_bb() != header
+        || iv.get_ir_type() != IrType::Int
+        || !is_invariant(end, loop_info)
+    {
+        return None;
+    }
+
+    // 由SCEV确定归纳变量的步长
+    let scev = analyzer.analyze(&iv);
+    if !scev.is_scev_rec_expr()
+        || scev.get_operands().len() != 2
+        || !scev.get_operands()[1].is_scev_constant()
+    {
+        return None;
+    }
+    let index = header
+        .get_up_bb()
+        .iter()
+        .position(|bb| *bb == preheader)
+        .unwrap();
+    let start = iv.get_operand(index);
+    let step = scev.get_operands()[1].get_scev_const();
+    if iv.get_operand(1 - index).get_kind() != InstKind::Binary(BinOp::Add) || step <= 0 {
+        return None;
+    }
+
+    Some(LoopControl {
+        iv,
+        start,
+        end,
+        step,
+        inclusive,
+        cond,
+        body,
+        exit,
+        latch,
+    })
+}
+
+/// 判断一个值在循环中是否不变
+pub fn is_invariant(inst: ObjPtr<Inst>, loop_info: ObjPtr<LoopInfo>) -> bool {
+    inst.is_const() || inst.is_global_var_or_param() || !loop_info.is_in_loop(&inst.get_parent_bb())
+}
+
+/// 没有副作用也不会出错,可以移动到其他位置执行的指令
+pub fn is_movable(inst: ObjPtr<Inst>) -> bool {
+    match inst.get_kind() {
+        InstKind::Binary(BinOp::Div | BinOp::Rem | BinOp::UDiv | BinOp::URem) => false,
+        InstKind::Binary(_)
+        | InstKind::Unary(_)
+        | InstKind::Select
+        | InstKind::Gep
+        | InstKind::FtoI
+        | InstKind::ItoF => true,
+        InstKind::Load => inst.is_global_array_load(),
+        _ => inst.is_const(),
+    }
+}
+
+/// 获得循环及其子循环的所有块
+pub fn get_loop_blocks(loop_info: ObjPtr<LoopInfo>) -> Vec<ObjPtr<BasicBlock>> {
+    let mut blocks = loop_info.get_current_loop_bb().clone();
+    for sub_loop in loop_info.get_sub_loops() {
+        blocks.extend(get_loop_blocks(*sub_loop));
+    }
+    blocks
+}
+
+/// 循环中的一次访存
+pub struct MemAccess {
+    pub ptr: ObjPtr<Inst>,
+    pub is_write: bool,
+}
+
+/// 收集块中的load和store,有函数调用时返回None
+pub fn get_accesses(blocks: &[ObjPtr<BasicBlock>]) -> Option<Vec<MemAccess>> {
+    let mut accesses = vec![];
+    let mut has_call = false;
+    for bb in blocks {
+        inst_process_in_bb(bb.get_head_inst(), |inst| match inst.get_kind() {
+            InstKind::Load if !inst.is_global_array_load() => accesses.push(MemAccess {
+                ptr: inst.get_ptr(),
+                is_write: false,
+            }),
+            InstKind::Store => accesses.push(MemAccess {
+                ptr: inst.get_dest(),
+                is_write: true,
+            }),
+            InstKind::Call(_) => has_call = true,
+            _ => {}
+        });
+    }
+    if has_call {
+        None
+    } else {
+        Some(accesses)
+    }
+}
+
+/// 访问地址相对于数组基址的仿射偏移
+pub fn get_affine(
+    ptr: ObjPtr<Inst>,
+    ivs: &[ObjPtr<Inst>],
+    nest: ObjPtr<LoopInfo>,
+) -> Option<AffineExpr> {
+    match ptr.get_kind() {
+        InstKind::Gep => {
+            // 只处理直接以数组为基址的gep
+            let base = ptr.get_gep_ptr();
+            if matches!(base.get_kind(), InstKind::Alloca(_) | InstKind::Parameter)
+                || base.is_global_array_load()
+            {
+                parse_affine(ptr.get_gep_offset(), ivs, nest)
+            } else {
+                None
+            }
+        }
+        _ => Some(AffineExpr::new(ivs.len())),
+    }
+}
+
+/// 一组访存和它们所在循环的归纳变量
+pub struct AccessGroup<'a> {
+    pub accesses: &'a [MemAccess],
+    pub ivs: &'a [ObjPtr<Inst>],
+    pub nest: ObjPtr<LoopInfo>,
+}
+
+/// 判断两组访存之间是否可能存在指定方向的依赖,两次访问中至少有一次是写
+pub fn has_dependence(
+    firsts: &AccessGroup,
+    seconds: &AccessGroup,
+    directions: &[Direction],
+    distances: &[Option<i32>],
+) -> bool {
+    for first in firsts.accesses {
+        for second in seconds.accesses {
+            if !first.is_write && !second.is_write {
+                continue;
+            }
+            let (lhs, rhs) = (get_location(first.ptr), get_location(second.ptr));
+            if alias_location(lhs.whole(), rhs.whole()) == AliasResult::NoAlias {
+                continue;
+            }
+            // 基址不同但可能别名时无法比较下标
+            if lhs.base != rhs.base {
+                return true;
+            }
+            match (
+                get_affine(first.ptr, firsts.ivs, firsts.nest),
+                get_affine(second.ptr, seconds.ivs, seconds.nest),
+            ) {
+                (Some(lhs), Some(rhs)) => {
+                    if direction_check(&lhs, &rhs, directions, distances) {
+                        return true;
+                    }
+                }
+                _ => return true,
+            }
+        }
+    }
+    false
+}
+
+/// 两层的完美循环嵌套:外层循环中除内层循环外只有循环控制和可以移动的计算
+pub struct PerfectNest {
+    pub outer: ObjPtr<LoopInfo>,
+    pub inner: ObjPtr<LoopInfo>,
+    pub outer_control: LoopControl,
+    pub inner_control: LoopControl,
+    /// 外层循环中从外层header到内层preheader之间的块
+    pub before: Vec<ObjPtr<BasicBlock>>,
+}
+
+/// 识别以outer为外层循环的完美循环嵌套,内层循环的边界在外层循环中不变
+pub fn get_perfect_nest(
+    outer: ObjPtr<LoopInfo>,
+    analyzer: &mut SCEVAnalyzer,
+) -> Option<PerfectNest> {
+    if outer.get_sub_loops().len() != 1 {
+        return None;
+    }
+    let inner = outer.get_sub_loops()[0];
+    if !inner.get_sub_loops().is_empty() {
+        return None;
+    }
+    let outer_control = get_loop_control(outer, analyzer)?;
+    let inner_control = get_loop_control(inner, analyzer)?;
+    if !is_invariant(inner_control.start, outer) || !is_invariant(inner_control.end, outer) {
+        return None;
+    }
+
+    // 两段块都是只有一个前继和一个后继的链
+    let chain = |mut bb: ObjPtr<BasicBlock>, end: ObjPtr<BasicBlock>| {
+        let mut blocks = vec![];
+        loop {
+            if !outer.is_in_current_loop(&bb)
+                || bb.get_up_bb().len() != 1
+                || bb.get_next_bb().len() != 1
+                || blocks.contains(&bb)
+            {
+                return None;
+            }
+            blocks.push(bb);
+            if bb == end {
+                return Some(blocks);
+            }
+            bb = bb.get_next_bb()[0];
+        }
+    };
+    let before = chain(outer_control.body, inner.get_preheader())?;
+    let after = chain(inner_control.exit, outer_control.latch)?;
+    if before.len() + after.len() + 1 != outer.get_current_loop_bb().len() {
+        return None;
+    }
+
+    let header = outer.get_header();
+    let mut flag = true;
+    inst_process_in_bb(header.get_head_inst(), |inst| {
+        flag &= inst == outer_control.iv
+            || inst == outer_control.cond
+            || inst.get_kind() == InstKind::Branch
+            || is_movable(inst) && !inst.is_phi();
+    });
+    inst_process_in_bb(inner.get_header().get_head_inst(), |inst| {
+        flag &= inst == inner_control.iv || !inst.is_phi();
+    });
+    for bb in before.iter() {
+        inst_process_in_bb(bb.get_head_inst(), |inst| {
+            flag &= inst.get_kind() == InstKind::Branch || is_movable(inst);
+        });
+    }
+    // 内层循环之后的计算只能被外层归纳变量的phi使用
+    for bb in after.iter() {
+        inst_process_in_bb(bb.get_head_inst(), |inst| {
+            flag &= inst.get_kind() == InstKind::Branch
+                || is_movable(inst)
+                    && inst.get_use_list().iter().all(|user| {
+                        *user == outer_control.iv || after.contains(&user.get_parent_bb())
+                    });
+        });
+    }
+    // 嵌套中定义的值不能在嵌套之外使用
+    for bb in get_loop_blocks(outer) {
+        inst_process_in_bb(bb.get_head_inst(), |inst| {
+            flag &= inst.is_const()
+                || inst
+                    .get_use_list()
+                    .iter()
+                    .all(|user| outer.is_in_loop(&user.get_parent_bb()));
+        });
+    }
+
+    if flag {
+        Some(PerfectNest {
+            outer,
+            inner,
+            outer_control,
+            inner_control,
+            before,
+        })
+    } else {
+        None
+    }
+}
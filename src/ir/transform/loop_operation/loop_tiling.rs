@@ -0,0 +1,243 @@
+//! 循环分块
+//!
+//! 对两层的完美循环嵌套(i, j),如果内层循环访问的某个数组在j方向跨度很大而在i方向连续,
+//! 就把j分成长度为TILE_SIZE的块,块循环jj放到最外层:
+//! for jj: for i: for j in jj..jj+TILE_SIZE,使一块内用到的数组行在i循环中保持在缓存里。
+//! 这等价于交换i和jj,合法性条件和循环交换相同
+use crate::ir::analysis::{dependent_analyse::Direction, scev::SCEVAnalyzer};
+
+use super::{loop_nest::*, *};
+
+/// 分块的大小
+const TILE_SIZE: i32 = 32;
+/// 跨度不小于一个缓存行(16个int)的访存才需要分块
+const MIN_STRIDE: i32 = 16;
+
+pub fn loop_tiling(
+    module: &mut Module,
+    loop_map: &mut HashMap<String, LoopList>,
+    pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
+) {
+    func_process(module, |name, _| {
+        let loop_list = loop_map.get_mut(&name).unwrap();
+        let mut analyzer = SCEVAnalyzer::new();
+        analyzer.set_loop_list(loop_list.get_loop_list().clone());
+        for outer in loop_list.get_loop_list().clone() {
+            if let Some(nest) = get_perfect_nest(outer, &mut analyzer) {
+                if should_tile(&nest) {
+                    tile(&nest, loop_list, pools);
+                    analyzer.clear();
+                }
+            }
+        }
+    });
+}
+
+fn should_tile(nest: &PerfectNest) -> bool {
+    let inner = &nest.inner_control;
+    if !inner.start.is_int_const() || inner.step != 1 {
+        return false;
+    }
+    match inner.get_trip_count() {
+        Some(count) if count >= 2 * TILE_SIZE && count % TILE_SIZE == 0 => {}
+        _ => return false,
+    }
+
+    let accesses = match get_accesses(&get_loop_blocks(nest.inner)) {
+        Some(accesses) => accesses,
+        None => return false,
+    };
+    let ivs = [nest.outer_control.iv, inner.iv];
+    let strided = accesses.iter().any(|access| {
+        get_affine(access.ptr, &ivs, nest.outer).map_or(false, |expr| {
+            expr.coefs[0].abs() == 1 && expr.coefs[1].abs() >= MIN_STRIDE
+        })
+    });
+    if !strided {
+        return false;
+    }
+
+    let group = AccessGroup {
+        accesses: &accesses,
+        ivs: &ivs,
+        nest: nest.outer,
+    };
+    !has_dependence(
+        &group,
+        &group,
+        &[Direction::Lt, Direction::Gt],
+        &[nest.outer_control.get_distance(), inner.get_distance()],
+    )
+}
+
+/// 在外层循环外加一层块循环,内层循环只遍历一块
+fn tile(
+    nest: &PerfectNest,
+    loop_list: &mut LoopList,
+    pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
+) {
+    let (outer, inner) = (&nest.outer_control, &nest.inner_control);
+    let mut outer_header = nest.outer.get_header();
+    let mut preheader = nest.outer.get_preheader();
+    let mut exit = outer.exit;
+    let name = outer_header.get_name().to_string();
+    let mut tile_header = pools.0.new_basic_block(format!("tile_{}", name));
+    let mut tile_preheader = pools.0.new_basic_block(format!("tile_preheader_{}", name));
+    let mut tile_latch = pools.0.new_basic_block(format!("tile_latch_{}", name));
+
+    // 块循环: for (jj = start; jj < start + count; jj += TILE_SIZE)
+    let start = inner.start.get_int_bond();
+    let end = start + inner.get_trip_count().unwrap();
+    let start = pools.1.make_int_const(start);
+    let end = pools.1.make_int_const(end);
+    preheader.get_tail_inst().insert_before(start);
+    preheader.get_tail_inst().insert_before(end);
+    let mut tile_iv = pools.1.make_int_phi();
+    let cond = pools.1.make_lt(tile_iv, end);
+    tile_header.push_back(tile_iv);
+    tile_header.push_back(cond);
+    tile_header.push_back(pools.1.make_br(cond));
+
+    let size = pools.1.make_int_const(TILE_SIZE);
+    let update = pools.1.make_add(tile_iv, size);
+    tile_latch.push_back(size);
+    tile_latch.push_back(update);
+    tile_latch.push_back(pools.1.make_jmp());
+    tile_iv.add_operand(start);
+    tile_iv.add_operand(update);
+
+    // 内层循环从jj开始,到jj + TILE_SIZE结束
+    let size = pools.1.make_int_const(TILE_SIZE - inner.inclusive as i32);
+    let tile_end = pools.1.make_add(tile_iv, size);
+    tile_preheader.push_back(size);
+    tile_preheader.push_back(tile_end);
+    tile_preheader.push_back(pools.1.make_jmp());
+    let mut inner_iv = inner.iv;
+    let index = inner
+        .iv
+        .get_parent_bb()
+        .get_up_bb()
+        .iter()
+        .position(|bb| *bb == nest.inner.get_preheader())
+        .unwrap();
+    inner_iv.set_operand(tile_iv, index);
+    let mut cond = inner.cond;
+    let index = cond.get_operand_index(inner.end);
+    cond.set_operand(tile_end, index);
+
+    // preheader -> tile_header -> tile_preheader -> outer_header -> tile_latch -> tile_header
+    preheader.replace_next_bb(outer_header, tile_header);
+    outer_header.replace_up_bb(preheader, tile_preheader);
+    outer_header.replace_next_bb(exit, tile_latch);
+    exit.replace_up_bb(outer_header, tile_header);
+    tile_header.set_up_bb(vec![preheader, tile_latch]);
+    tile_header.set_next_bb(vec![exit, tile_preheader]);
+    tile_preheader.set_up_bb(vec![tile_header]);
+    tile_preheader.set_next_bb(vec![outer_header]);
+    tile_latch.set_up_bb(vec![outer_header]);
+    tile_latch.set_next_bb(vec![tile_header]);
+
+    // 块循环成为原外层循环的父循环
+    let parent = nest.outer.get_parent_loop();
+    let tile_loop = loop_list.add_loop(LoopInfo::new_loop(
+        parent,
+        Some(preheader),
+        tile_header,
+        None,
+        None,
+        vec![tile_header, tile_latch],
+        vec![nest.outer],
+    ));
+    if let Some(mut parent) = parent {
+        parent.remove_sub_loop(nest.outer);
+        parent.add_sub_loop(tile_loop);
+    }
+    let mut outer_loop = nest.outer;
+    outer_loop.set_parent_loop(Some(tile_loop));
+    outer_loop.set_pre_header(tile_preheader);
+    outer_loop.clear_cache();
+}
+
+#[cfg(test)]
+mod loop_tiling_test {
+    use crate::ir::{
+        interpret, test_util::parse_module, tools::bfs_bb_proceess,
+        transform::loop_operation::tiling, verify_module, DEFAULT_STEP_LIMIT,
+    };
+
+    #[test]
+    fn test_tiling() {
+        // 转置: a[i][j] = b[j][i] + 1,b在内层循环中的跨度为64,j循环被分成两块
+        let (mut module, pools) = parse_module(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %a = alloca [4096 x i32], align 4
+  %b = alloca [4096 x i32], align 4
+  %n = call i32 @getint()
+  %b1 = getelementptr inbounds [4096 x i32], [4096 x i32]* %b, i32 0, i32 %n
+  store i32 %n, i32* %b1, align 4
+  br label %bb_outer
+
+bb_outer:
+  %i = phi i32 [ 0, %bb_entry ], [ %i2, %bb_latch ]
+  %c = icmp slt i32 %i, 64
+  br i1 %c, label %bb_pre, label %bb_exit
+
+bb_pre:
+  %row = mul i32 %i, 64
+  br label %bb_inner
+
+bb_inner:
+  %j = phi i32 [ 0, %bb_pre ], [ %j2, %bb_body ]
+  %c2 = icmp slt i32 %j, 64
+  br i1 %c2, label %bb_body, label %bb_latch
+
+bb_body:
+  %col = mul i32 %j, 64
+  %ob = add i32 %col, %i
+  %pb = getelementptr inbounds [4096 x i32], [4096 x i32]* %b, i32 0, i32 %ob
+  %x = load i32, i32* %pb, align 4
+  %y = add i32 %x, 1
+  %oa = add i32 %row, %j
+  %pa = getelementptr inbounds [4096 x i32], [4096 x i32]* %a, i32 0, i32 %oa
+  store i32 %y, i32* %pa, align 4
+  %j2 = add i32 %j, 1
+  br label %bb_inner
+
+bb_latch:
+  %i2 = add i32 %i, 1
+  br label %bb_outer
+
+bb_exit:
+  %m = mul i32 %n, 64
+  %p = getelementptr inbounds [4096 x i32], [4096 x i32]* %a, i32 0, i32 %m
+  %r = load i32, i32* %p, align 4
+  ret i32 %r
+}
+
+declare i32 @getint()
+",
+        );
+        tiling(&mut module, &mut (pools.bb, pools.inst));
+        assert_eq!(verify_module(&module), vec![]);
+
+        let mut count = 0;
+        bfs_bb_proceess(module.get_function("main").get_head(), |bb| {
+            if bb.get_next_bb().len() == 2 {
+                count += 1;
+            }
+        });
+        assert_eq!(count, 3);
+        // a[n][0] = b[0][n] + 1 = n + 1
+        assert_eq!(interpret(&module, "0", DEFAULT_STEP_LIMIT).unwrap().ret, 1);
+        assert_eq!(
+            interpret(&module, "40", DEFAULT_STEP_LIMIT).unwrap().ret,
+            41
+        );
+        assert_eq!(
+            interpret(&module, "63", DEFAULT_STEP_LIMIT).unwrap().ret,
+            64
+        );
+    }
+}
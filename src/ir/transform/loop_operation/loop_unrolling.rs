@@ -1,9 +1,18 @@
-use super::*;
+use std::collections::HashSet;
+
+use super::{loop_nest::get_loop_control, *};
 use crate::ir::{
     analysis::scev::{scevexp::SCEVExp, SCEVAnalyzer},
     instruction::InstKind,
 };
 
+/// 部分展开的最大倍数,依次尝试8、4、2
+const MAX_UNROLL_FACTOR: usize = 8;
+/// 部分展开后循环体的最大指令数
+const MAX_UNROLLED_SIZE: usize = 64;
+/// 部分展开后同时活跃的值的上限,超过后寄存器分配会产生溢出
+const MAX_REGISTER_PRESSURE: usize = 24;
+
 /// 尝试对循环进行展开
 pub fn loop_unrolling(
     module: &mut Module,
@@ -39,6 +48,18 @@ pub fn loop_unrolling(
             loop_list.remove_loops(&vec![remove_list.unwrap()]);
         }
     });
+
+    // 迭代次数在运行时才能确定的循环按代价模型部分展开
+    func_process(module, |name, _| {
+        let loop_list = loop_map.get(&name).unwrap();
+        let mut analyzer = SCEVAnalyzer::new();
+        analyzer.set_loop_list(loop_list.get_loop_list().clone());
+        for loop_info in loop_list.get_loop_list().iter() {
+            if runtime_loop_unrolling(&mut analyzer, *loop_info, pools) {
+                analyzer.clear();
+            }
+        }
+    });
 }
 
 enum IVC {
@@ -346,3 +367,333 @@ fn one_block_loop_full_unrolling(
         parent.remove_sub_loop(loop_info);
     }
 }
+
+/// 对迭代次数在运行时才能确定的循环进行部分展开:
+/// 循环体复制factor份,循环条件改为 i + (factor - 1) * step < end,
+/// 剩下的不足factor次的迭代由循环后的factor - 1份带条件的循环体完成
+fn runtime_loop_unrolling(
+    analyzer: &mut SCEVAnalyzer,
+    mut loop_info: ObjPtr<LoopInfo>,
+    pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
+) -> bool {
+    if !loop_info.get_sub_loops().is_empty() || loop_info.get_current_loop_bb().len() != 2 {
+        return false;
+    }
+    let control = match get_loop_control(loop_info, analyzer) {
+        Some(control) => control,
+        None => return false,
+    };
+    // 迭代次数是常数的循环由完全展开处理
+    if control.get_trip_count().is_some() {
+        return false;
+    }
+    let mut header = loop_info.get_header();
+    let body = control.body;
+    if body == header
+        || body != control.latch
+        || body.get_up_bb().len() != 1
+        || control.cond.get_use_list().len() != 1
+    {
+        return false;
+    }
+    let mut flag = true;
+    let mut phis = vec![];
+    inst_process_in_bb(header.get_head_inst(), |inst| {
+        if inst.is_phi() {
+            phis.push(inst);
+        } else {
+            flag &= inst == control.cond || inst.is_br() || inst.is_const();
+        }
+    });
+    if !flag {
+        return false;
+    }
+    let factor = get_unroll_factor(loop_info, body, &phis);
+    if factor < 2 {
+        return false;
+    }
+
+    // 循环体中的指令和每个phi在回边上的值
+    let mut insts = vec![];
+    inst_process_in_bb(body.get_head_inst(), |inst| {
+        if !inst.is_br() {
+            insts.push(inst);
+        }
+    });
+    let index = header
+        .get_up_bb()
+        .iter()
+        .position(|bb| *bb == control.latch)
+        .unwrap();
+    let latch_values: Vec<ObjPtr<Inst>> = phis.iter().map(|phi| phi.get_operand(index)).collect();
+    // 在循环外使用phi的指令,之后改为使用余下迭代结束后的值
+    let outside_users: Vec<Vec<ObjPtr<Inst>>> = phis
+        .iter()
+        .map(|phi| {
+            phi.get_use_list()
+                .iter()
+                .filter(|user| !loop_info.is_in_loop(&user.get_parent_bb()))
+                .copied()
+                .collect()
+        })
+        .collect();
+
+    // 余下的迭代: check -> body_1 -> ... -> body_{factor-1} -> join
+    let exit = control.exit;
+    let name = header.get_name().to_string();
+    let mut blocks = vec![];
+    let mut values: Vec<Vec<ObjPtr<Inst>>> = vec![phis.clone()];
+    let mut check = pools.0.new_basic_block(format!("unroll_check_{}", name));
+    let mut join = pools.0.new_basic_block(format!("unroll_exit_{}", name));
+    let mut map: HashMap<ObjPtr<Inst>, ObjPtr<Inst>> = HashMap::new();
+    let cond = copy_inst(control.cond, &map, pools.1);
+    check.push_back(cond);
+    check.push_back(pools.1.make_br(cond));
+    blocks.push(check);
+    let mut last = check;
+    for k in 1..factor {
+        let mut bb = pools
+            .0
+            .new_basic_block(format!("unroll_rest_{}_{}", name, k));
+        map = phis
+            .iter()
+            .copied()
+            .zip(values[k - 1].iter().copied())
+            .collect();
+        for inst in insts.iter() {
+            let new_inst = copy_inst(*inst, &map, pools.1);
+            bb.push_back(new_inst);
+            map.insert(*inst, new_inst);
+        }
+        values.push(
+            latch_values
+                .iter()
+                .map(|x| *map.get(x).unwrap_or(x))
+                .collect(),
+        );
+        if k + 1 < factor {
+            map = phis
+                .iter()
+                .copied()
+                .zip(values[k].iter().copied())
+                .collect();
+            let cond = copy_inst(control.cond, &map, pools.1);
+            bb.push_back(cond);
+            bb.push_back(pools.1.make_br(cond));
+        } else {
+            bb.push_back(pools.1.make_jmp());
+        }
+        // 条件为假时跳到join
+        last.set_next_bb(vec![join, bb]);
+        bb.set_up_bb(vec![last]);
+        blocks.push(bb);
+        last = bb;
+    }
+    last.set_next_bb(vec![join]);
+    join.set_up_bb(blocks.clone());
+    join.push_back(pools.1.make_jmp());
+    join.set_next_bb(vec![exit]);
+    for ((phi, users), k) in phis.iter().zip(outside_users.iter()).zip(0..) {
+        let mut new_phi = pools.1.make_phi(phi.get_ir_type());
+        values
+            .iter()
+            .for_each(|value| new_phi.add_operand(value[k]));
+        join.push_front(new_phi);
+        for user in users {
+            while user.get_operands().contains(phi) {
+                let index = user.get_operand_index(*phi);
+                user.as_mut().set_operand(new_phi, index);
+            }
+        }
+    }
+    exit.as_mut().replace_up_bb(header, join);
+    header.replace_next_bb(exit, check);
+    check.set_up_bb(vec![header]);
+    blocks.push(join);
+
+    // 循环体复制factor份,phi在回边上的值改为最后一份的值
+    let mut current = latch_values.clone();
+    let mut tail = body.get_tail_inst();
+    for _ in 1..factor {
+        map = phis.iter().copied().zip(current.iter().copied()).collect();
+        for inst in insts.iter() {
+            let new_inst = copy_inst(*inst, &map, pools.1);
+            tail.insert_before(new_inst);
+            map.insert(*inst, new_inst);
+        }
+        current = latch_values
+            .iter()
+            .map(|x| *map.get(x).unwrap_or(x))
+            .collect();
+    }
+    for (mut phi, value) in phis.into_iter().zip(current) {
+        phi.set_operand(value, index);
+    }
+
+    // 循环条件改为 i < end - (factor - 1) * step
+    let preheader = loop_info.get_preheader();
+    let offset = pools.1.make_int_const((factor as i32 - 1) * control.step);
+    let limit = pools.1.make_sub(control.end, offset);
+    preheader.get_tail_inst().insert_before(offset);
+    preheader.get_tail_inst().insert_before(limit);
+    let mut cond = control.cond;
+    let index = cond.get_operand_index(control.end);
+    cond.set_operand(limit, index);
+
+    if let Some(mut parent) = loop_info.get_parent_loop() {
+        parent.add_bbs(blocks);
+        parent.clear_cache();
+    }
+    loop_info.clear_cache();
+    true
+}
+
+/// 代价模型:展开后的循环体不超过MAX_UNROLLED_SIZE条指令,
+/// 循环不变量、phi和每一份循环体中同时活跃的值之和不超过MAX_REGISTER_PRESSURE
+fn get_unroll_factor(
+    loop_info: ObjPtr<LoopInfo>,
+    body: ObjPtr<BasicBlock>,
+    phis: &[ObjPtr<Inst>],
+) -> usize {
+    let mut insts = vec![];
+    let mut flag = true;
+    inst_process_in_bb(body.get_head_inst(), |inst| {
+        // 调用的开销远大于循环控制
+        flag &= !inst.is_phi() && !matches!(inst.get_kind(), InstKind::Call(_));
+        if !inst.is_br() && !inst.is_const() {
+            insts.push(inst);
+        }
+    });
+    if !flag || insts.is_empty() || insts.len() * 2 > MAX_UNROLLED_SIZE {
+        return 1;
+    }
+
+    let mut invariants = HashSet::new();
+    for inst in insts.iter() {
+        for operand in inst.get_operands() {
+            if !operand.is_const()
+                && !operand.is_global_var_or_param()
+                && !loop_info.is_in_loop(&operand.get_parent_bb())
+            {
+                invariants.insert(*operand);
+            }
+        }
+    }
+    // 一份循环体中同时活跃的临时值的最大个数
+    let mut live = 0;
+    for index in 0..insts.len() {
+        let count = insts[..index]
+            .iter()
+            .filter(|inst| {
+                inst.get_use_list()
+                    .iter()
+                    .any(|user| user.get_parent_bb() != body || insts[index..].contains(user))
+            })
+            .count();
+        live = live.max(count);
+    }
+
+    let mut factor = MAX_UNROLL_FACTOR;
+    while factor >= 2 {
+        if insts.len() * factor <= MAX_UNROLLED_SIZE
+            && invariants.len() + phis.len() + live * factor <= MAX_REGISTER_PRESSURE
+        {
+            return factor;
+        }
+        factor /= 2;
+    }
+    1
+}
+
+/// 复制一条指令,操作数按map替换
+fn copy_inst(
+    inst: ObjPtr<Inst>,
+    map: &HashMap<ObjPtr<Inst>, ObjPtr<Inst>>,
+    pool: &mut ObjPool<Inst>,
+) -> ObjPtr<Inst> {
+    let mut new_inst = pool.put(inst.as_ref().clone());
+    new_inst.set_operands(
+        inst.get_operands()
+            .iter()
+            .map(|x| *map.get(x).unwrap_or(x))
+            .collect(),
+    );
+    new_inst.set_users(vec![]);
+    new_inst
+        .get_operands()
+        .iter()
+        .for_each(|x| x.as_mut().add_user(new_inst.as_ref()));
+    new_inst
+}
+
+#[cfg(test)]
+mod loop_unrolling_test {
+    use crate::ir::{
+        instruction::InstKind,
+        interpret,
+        test_util::parse_module,
+        tools::{bfs_bb_proceess, inst_process_in_bb},
+        transform::loop_operation::unroll,
+        verify_module, DEFAULT_STEP_LIMIT,
+    };
+
+    #[test]
+    fn test_runtime_unrolling() {
+        // 迭代次数为运行时输入的n,展开后剩余的迭代在循环后完成
+        let (mut module, pools) = parse_module(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %a = alloca [64 x i32], align 4
+  %n = call i32 @getint()
+  br label %bb_loop
+
+bb_loop:
+  %i = phi i32 [ 0, %bb_entry ], [ %i2, %bb_body ]
+  %s = phi i32 [ 0, %bb_entry ], [ %s2, %bb_body ]
+  %c = icmp slt i32 %i, %n
+  br i1 %c, label %bb_body, label %bb_exit
+
+bb_body:
+  %p = getelementptr inbounds [64 x i32], [64 x i32]* %a, i32 0, i32 %i
+  %x = mul i32 %i, 3
+  store i32 %x, i32* %p, align 4
+  %s2 = add i32 %s, %x
+  %i2 = add i32 %i, 1
+  br label %bb_loop
+
+bb_exit:
+  %p2 = getelementptr inbounds [64 x i32], [64 x i32]* %a, i32 0, i32 5
+  %y = load i32, i32* %p2, align 4
+  %r = add i32 %s, %y
+  ret i32 %r
+}
+
+declare i32 @getint()
+",
+        );
+        unroll(&mut module, 100, &mut (pools.bb, pools.inst));
+        assert_eq!(verify_module(&module), vec![]);
+
+        // 循环体中有多份store
+        let mut stores = 0;
+        bfs_bb_proceess(module.get_function("main").get_head(), |bb| {
+            if bb.get_name() == "body" {
+                inst_process_in_bb(bb.get_head_inst(), |inst| {
+                    if inst.get_kind() == InstKind::Store {
+                        stores += 1;
+                    }
+                });
+            }
+        });
+        assert!(stores > 1);
+        // sum(3i, i < n) + (n > 5 ? 15 : 0)
+        for n in 0..12 {
+            let expected = 3 * n * (n - 1).max(0) / 2 + if n > 5 { 15 } else { 0 };
+            let ret = interpret(&module, &n.to_string(), DEFAULT_STEP_LIMIT)
+                .unwrap()
+                .ret;
+            assert_eq!(ret, expected);
+        }
+    }
+}
@@ -0,0 +1,514 @@
+//! 循环判断外提
+//!
+//! 最内层循环中某个分支的条件在循环中不变时,把循环复制一份:preheader按条件跳到两份循环之一,
+//! 原循环中的分支固定走真分支,复制的循环中固定走假分支,两份循环中不会再执行到的块直接删除。
+//! 复制会使代码变大,只处理指令数不超过MAX_LOOP_SIZE的循环
+use std::collections::HashSet;
+
+use crate::ir::instruction::InstKind;
+
+use super::{loop_nest::is_invariant, *};
+use crate::ir::{
+    analysis::loop_tree::loop_recognize::loop_recognize_in_function, tools::bfs_bb_proceess,
+};
+
+/// 被复制的循环的最大指令数
+const MAX_LOOP_SIZE: usize = 100;
+/// 一个函数中因外提复制的指令总数的上限
+const MAX_FUNC_GROWTH: usize = 400;
+
+pub fn loop_unswitching(
+    module: &mut Module,
+    loop_map: &mut HashMap<String, LoopList>,
+    pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
+) {
+    func_process(module, |name, func| {
+        let loop_list = loop_map.get_mut(&name).unwrap();
+        let mut names = HashSet::new();
+        bfs_bb_proceess(func.get_head(), |bb| {
+            names.insert(bb.get_name().to_string());
+        });
+        let mut growth = 0;
+        for loop_info in loop_list.get_loop_list().clone() {
+            let blocks = get_blocks(loop_info);
+            let size = blocks
+                .iter()
+                .map(|bb| {
+                    let mut size = 0;
+                    inst_process_in_bb(bb.get_head_inst(), |_| size += 1);
+                    size
+                })
+                .sum::<usize>();
+            if size > MAX_LOOP_SIZE || growth + size > MAX_FUNC_GROWTH {
+                continue;
+            }
+            if let Some((branch, exit)) = get_unswitch_branch(loop_info, &blocks) {
+                unswitch(loop_info, &blocks, branch, exit, &mut names, pools);
+                growth += size;
+            }
+        }
+        // 复制出的循环和外层循环的块都变了,重新识别函数中的循环
+        if growth > 0 {
+            *loop_list = loop_recognize_in_function(func);
+            loop_simplify_run(loop_list, pools);
+        }
+    });
+}
+
+/// 循环自身的块,去掉重复
+fn get_blocks(loop_info: ObjPtr<LoopInfo>) -> Vec<ObjPtr<BasicBlock>> {
+    let mut visited = HashSet::new();
+    loop_info
+        .get_current_loop_bb()
+        .iter()
+        .filter(|bb| visited.insert(**bb))
+        .copied()
+        .collect()
+}
+
+/// 找到条件在循环中不变的分支,返回分支所在的块和循环唯一的出口目标
+fn get_unswitch_branch(
+    loop_info: ObjPtr<LoopInfo>,
+    blocks: &[ObjPtr<BasicBlock>],
+) -> Option<(ObjPtr<BasicBlock>, ObjPtr<BasicBlock>)> {
+    let header = loop_info.get_header();
+    let preheader = loop_info.get_preheader();
+    if !loop_info.get_sub_loops().is_empty()
+        || header.get_up_bb().len() != 2
+        || !header.get_up_bb().contains(&preheader)
+        || !preheader.get_tail_inst().is_br_jmp()
+    {
+        return None;
+    }
+    let latch = *header.get_up_bb().iter().find(|bb| **bb != preheader)?;
+
+    // 循环只能跳到同一个块,外提后这个块中为两份循环的出口加phi
+    let mut exit = None;
+    let mut flag = true;
+    for bb in blocks {
+        inst_process_in_bb(bb.get_head_inst(), |inst| {
+            flag &= !matches!(inst.get_kind(), InstKind::Alloca(_));
+        });
+        let targets: Vec<_> = bb
+            .get_next_bb()
+            .iter()
+            .filter(|next| !blocks.contains(next))
+            .copied()
+            .collect();
+        for target in targets.iter() {
+            flag &= targets.len() == 1 && exit.map_or(true, |exit| exit == *target);
+            exit = Some(*target);
+        }
+    }
+    let exit = exit?;
+    if !flag {
+        return None;
+    }
+    // 循环中的值在出口块以外使用时要在出口块中加phi,这要求出口块只能从循环中到达
+    let used_outside = blocks.iter().any(|bb| {
+        let mut used = false;
+        inst_process_in_bb(bb.get_head_inst(), |inst| {
+            used |= !inst.is_const()
+                && inst
+                    .get_use_list()
+                    .iter()
+                    .any(|user| is_outside_user(*user, blocks, exit));
+        });
+        used
+    });
+    if used_outside && exit.get_up_bb().iter().any(|bb| !blocks.contains(bb)) {
+        return None;
+    }
+
+    blocks
+        .iter()
+        .copied()
+        .find(|bb| {
+            let tail = bb.get_tail_inst();
+            if !tail.is_br_cond() {
+                return false;
+            }
+            let cond = tail.get_br_cond();
+            let next = bb.get_next_bb();
+            !cond.is_const()
+            && is_invariant(cond, loop_info)
+            && next[0] != next[1]
+            && blocks.contains(&next[0])
+            && blocks.contains(&next[1])
+            // 两份循环中都还能回到header
+            && get_reachable(header, blocks, (*bb, next[0])).contains(&latch)
+            && get_reachable(header, blocks, (*bb, next[1])).contains(&latch)
+        })
+        .map(|bb| (bb, exit))
+}
+
+/// 使用者在循环外,并且不是出口块中的phi
+fn is_outside_user(
+    user: ObjPtr<Inst>,
+    blocks: &[ObjPtr<BasicBlock>],
+    exit: ObjPtr<BasicBlock>,
+) -> bool {
+    let bb = user.get_parent_bb();
+    !(blocks.contains(&bb) || user.is_phi() && bb == exit)
+}
+
+/// 不经过边removed时从header能到达的循环中的块
+fn get_reachable(
+    header: ObjPtr<BasicBlock>,
+    blocks: &[ObjPtr<BasicBlock>],
+    removed: (ObjPtr<BasicBlock>, ObjPtr<BasicBlock>),
+) -> HashSet<ObjPtr<BasicBlock>> {
+    let mut visited = HashSet::new();
+    let mut stack = vec![header];
+    while let Some(bb) = stack.pop() {
+        if !visited.insert(bb) {
+            continue;
+        }
+        for next in bb.get_next_bb() {
+            if blocks.contains(next) && (bb, *next) != removed {
+                stack.push(*next);
+            }
+        }
+    }
+    visited
+}
+
+fn unswitch(
+    loop_info: ObjPtr<LoopInfo>,
+    blocks: &[ObjPtr<BasicBlock>],
+    branch: ObjPtr<BasicBlock>,
+    mut exit: ObjPtr<BasicBlock>,
+    names: &mut HashSet<String>,
+    pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
+) {
+    let mut header = loop_info.get_header();
+    let mut preheader = loop_info.get_preheader();
+    let cond = branch.get_tail_inst().get_br_cond();
+
+    // 循环中在出口块以外使用的值先经过出口块中的phi
+    for bb in blocks {
+        inst_process_in_bb(bb.get_head_inst(), |inst| {
+            if inst.is_const() {
+                return;
+            }
+            let users: Vec<_> = inst
+                .get_use_list()
+                .iter()
+                .filter(|user| is_outside_user(**user, blocks, exit))
+                .copied()
+                .collect();
+            if users.is_empty() {
+                return;
+            }
+            let phi = pools
+                .1
+                .make_phi_with_operands(inst.get_ir_type(), vec![inst; exit.get_up_bb().len()]);
+            exit.push_front(phi);
+            for mut user in users {
+                while user.get_operands().contains(&inst) {
+                    let index = user.get_operand_index(inst);
+                    user.set_operand(phi, index);
+                }
+            }
+        });
+    }
+
+    // 复制循环中的块和指令,新块名的后缀取函数中还没有用过的最小编号
+    let name = header.get_name().to_string();
+    let new_names = |suffix: usize| {
+        let mut new_names: Vec<_> = blocks
+            .iter()
+            .map(|bb| format!("{}_unswitch_{}", bb.get_name(), suffix))
+            .collect();
+        new_names.push(format!("unswitch_true_{}_{}", name, suffix));
+        new_names.push(format!("unswitch_false_{}_{}", name, suffix));
+        new_names
+    };
+    let suffix = (0..)
+        .find(|suffix| new_names(*suffix).iter().all(|name| !names.contains(name)))
+        .unwrap();
+    names.extend(new_names(suffix));
+    let mut bb_map = HashMap::new();
+    let mut inst_map = HashMap::new();
+    for bb in blocks {
+        let mut new_bb = pools
+            .0
+            .new_basic_block(format!("{}_unswitch_{}", bb.get_name(), suffix));
+        inst_process_in_bb(bb.get_head_inst(), |inst| {
+            let mut new_inst = pools.1.put(inst.as_ref().clone());
+            new_inst.set_users(vec![]);
+            new_bb.push_back(new_inst);
+            inst_map.insert(inst, new_inst);
+        });
+        bb_map.insert(*bb, new_bb);
+    }
+    for (inst, new_inst) in inst_map.iter() {
+        let mut new_inst = *new_inst;
+        new_inst.set_operands(
+            inst.get_operands()
+                .iter()
+                .map(|x| *inst_map.get(x).unwrap_or(x))
+                .collect(),
+        );
+        new_inst
+            .get_operands()
+            .iter()
+            .for_each(|x| x.as_mut().add_user(new_inst.as_ref()));
+    }
+
+    // preheader按条件跳到两份循环: 真 -> pre_true -> 原循环, 假 -> pre_false -> 复制的循环
+    let mut pre_true = pools
+        .0
+        .new_basic_block(format!("unswitch_true_{}_{}", name, suffix));
+    let mut pre_false = pools
+        .0
+        .new_basic_block(format!("unswitch_false_{}_{}", name, suffix));
+    let new_header = bb_map[&header];
+    preheader.get_tail_inst().remove_self();
+    preheader.push_back(pools.1.make_br(cond));
+    preheader.set_next_bb(vec![pre_false, pre_true]);
+    pre_true.push_back(pools.1.make_jmp());
+    pre_true.set_up_bb(vec![preheader]);
+    pre_true.set_next_bb(vec![header]);
+    pre_false.push_back(pools.1.make_jmp());
+    pre_false.set_up_bb(vec![preheader]);
+    pre_false.set_next_bb(vec![new_header]);
+
+    let map_bb = |bb: &ObjPtr<BasicBlock>| {
+        if *bb == preheader {
+            pre_false
+        } else {
+            *bb_map.get(bb).unwrap_or(bb)
+        }
+    };
+    for bb in blocks {
+        let mut new_bb = bb_map[bb];
+        new_bb.set_up_bb(bb.get_up_bb().iter().map(map_bb).collect());
+        new_bb.set_next_bb(bb.get_next_bb().iter().map(map_bb).collect());
+    }
+    header.replace_up_bb(preheader, pre_true);
+
+    // 复制的循环跳到出口块的边,phi的参数按原循环对应的边复制
+    let preds: Vec<usize> = (0..exit.get_up_bb().len())
+        .filter(|index| blocks.contains(&exit.get_up_bb()[*index]))
+        .collect();
+    for index in preds.iter() {
+        let pred = bb_map[&exit.get_up_bb()[*index]];
+        exit.add_up_bb(pred.as_ref());
+    }
+    let mut inst = exit.get_head_inst();
+    while inst.is_phi() {
+        for index in preds.iter() {
+            let operand = inst.get_operand(*index);
+            inst.add_operand(*inst_map.get(&operand).unwrap_or(&operand));
+        }
+        inst = inst.get_next();
+    }
+
+    // 原循环中分支只走真分支,复制的循环中只走假分支
+    fold_branch(branch, 1, pools.1);
+    fold_branch(bb_map[&branch], 0, pools.1);
+    let new_blocks: Vec<_> = blocks.iter().map(|bb| bb_map[bb]).collect();
+    remove_unreachable(header, blocks, pre_true);
+    remove_unreachable(new_header, &new_blocks, pre_false);
+}
+
+/// 把条件跳转改为只跳到next_bb[taken]
+fn fold_branch(mut bb: ObjPtr<BasicBlock>, taken: usize, pool: &mut ObjPool<Inst>) {
+    let dead = bb.get_next_bb()[1 - taken];
+    bb.remove_next_bb(dead);
+    bb.get_tail_inst().remove_self();
+    bb.push_back(pool.make_jmp());
+}
+
+/// 删除从header到达不了的块,还有使用者的常量移到preheader中
+fn remove_unreachable(
+    header: ObjPtr<BasicBlock>,
+    blocks: &[ObjPtr<BasicBlock>],
+    preheader: ObjPtr<BasicBlock>,
+) {
+    let reachable = get_reachable(header, blocks, (header, header));
+    let dead: Vec<_> = blocks
+        .iter()
+        .filter(|bb| !reachable.contains(bb))
+        .copied()
+        .collect();
+    for mut bb in dead.iter().copied() {
+        for next in bb.get_next_bb().clone() {
+            if !dead.contains(&next) {
+                bb.remove_next_bb(next);
+            }
+        }
+        bb.clear_up_bb();
+    }
+    let mut consts = vec![];
+    for bb in dead.iter() {
+        inst_process_in_bb(bb.get_head_inst(), |mut inst| {
+            if inst.is_const() {
+                consts.push(inst);
+            } else {
+                inst.remove_self();
+            }
+        });
+    }
+    for mut inst in consts {
+        if inst.get_use_list().is_empty() {
+            inst.remove_self();
+        } else {
+            inst.move_self();
+            preheader.get_tail_inst().insert_before(inst);
+        }
+    }
+}
+
+#[cfg(test)]
+mod loop_unswitching_test {
+    use crate::ir::{
+        interpret, optimizer_run, test_util::parse_module, tools::bfs_bb_proceess,
+        transform::loop_operation::unswitch, verify_module, DEFAULT_STEP_LIMIT,
+    };
+    use crate::pass_manager::PassManager;
+
+    #[test]
+    fn test_unswitch() {
+        // %f在循环中不变,外提后两份循环中都没有对%f的判断
+        let (mut module, pools) = parse_module(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %n = call i32 @getint()
+  %f = call i32 @getint()
+  %c = icmp ne i32 %f, 0
+  br label %bb_loop
+
+bb_loop:
+  %i = phi i32 [ 0, %bb_entry ], [ %i2, %bb_latch ]
+  %s = phi i32 [ 0, %bb_entry ], [ %s3, %bb_latch ]
+  %c2 = icmp slt i32 %i, %n
+  br i1 %c2, label %bb_body, label %bb_exit
+
+bb_body:
+  %t = mul i32 %i, 2
+  br i1 %c, label %bb_then, label %bb_else
+
+bb_then:
+  %s1 = add i32 %s, %t
+  br label %bb_latch
+
+bb_else:
+  %s2 = sub i32 %s, %i
+  br label %bb_latch
+
+bb_latch:
+  %s3 = phi i32 [ %s1, %bb_then ], [ %s2, %bb_else ]
+  %i2 = add i32 %i, 1
+  br label %bb_loop
+
+bb_exit:
+  %r = add i32 %s, %i
+  ret i32 %r
+}
+
+declare i32 @getint()
+",
+        );
+        unswitch(&mut module, &mut (pools.bb, pools.inst));
+        assert_eq!(verify_module(&module), vec![]);
+
+        // preheader中的判断和两个循环头
+        let mut count = 0;
+        bfs_bb_proceess(module.get_function("main").get_head(), |bb| {
+            if bb.get_next_bb().len() == 2 {
+                count += 1;
+            }
+        });
+        assert_eq!(count, 3);
+        assert_eq!(
+            interpret(&module, "10 1", DEFAULT_STEP_LIMIT).unwrap().ret,
+            100
+        );
+        assert_eq!(
+            interpret(&module, "10 0", DEFAULT_STEP_LIMIT).unwrap().ret,
+            -35
+        );
+        assert_eq!(
+            interpret(&module, "0 1", DEFAULT_STEP_LIMIT).unwrap().ret,
+            0
+        );
+    }
+
+    #[test]
+    fn test_optimizer() {
+        // 外提后的两份循环还要经过循环归纳和删除等优化,这些优化使用的循环信息要和外提后的cfg一致
+        let (mut module, pools) = parse_module(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %n = call i32 @getint()
+  %c = icmp sgt i32 %n, 5
+  br label %bb_outer
+
+bb_outer:
+  %i = phi i32 [ 0, %bb_entry ], [ %i2, %bb_outer_latch ]
+  %s = phi i32 [ 0, %bb_entry ], [ %s1, %bb_outer_latch ]
+  %t = phi i32 [ 0, %bb_entry ], [ %t1, %bb_outer_latch ]
+  %ci = icmp slt i32 %i, 4
+  br i1 %ci, label %bb_inner_pre, label %bb_exit
+
+bb_inner_pre:
+  br label %bb_inner
+
+bb_inner:
+  %j = phi i32 [ 0, %bb_inner_pre ], [ %j2, %bb_inner_latch ]
+  %s1 = phi i32 [ %s, %bb_inner_pre ], [ %s3, %bb_inner_latch ]
+  %t1 = phi i32 [ %t, %bb_inner_pre ], [ %t3, %bb_inner_latch ]
+  %cj = icmp slt i32 %j, %n
+  br i1 %cj, label %bb_body, label %bb_outer_latch
+
+bb_body:
+  br i1 %c, label %bb_then, label %bb_else
+
+bb_then:
+  %s2 = add i32 %s1, %j
+  br label %bb_inner_latch
+
+bb_else:
+  %t2 = sub i32 %t1, %j
+  br label %bb_inner_latch
+
+bb_inner_latch:
+  %s3 = phi i32 [ %s2, %bb_then ], [ %s1, %bb_else ]
+  %t3 = phi i32 [ %t1, %bb_then ], [ %t2, %bb_else ]
+  %j2 = add i32 %j, 1
+  br label %bb_inner
+
+bb_outer_latch:
+  %i2 = add i32 %i, 1
+  br label %bb_outer
+
+bb_exit:
+  %r = mul i32 %s, 1000
+  %r2 = add i32 %r, %t
+  ret i32 %r2
+}
+
+declare i32 @getint()
+",
+        );
+        optimizer_run(
+            &mut module,
+            (pools.bb, pools.inst),
+            pools.func,
+            true,
+            false,
+            &PassManager::new(),
+        );
+        assert_eq!(verify_module(&module), vec![]);
+        for (input, ret) in [("23", 1012000), ("3", -12), ("-5", 0), ("6", 60000)] {
+            assert_eq!(
+                interpret(&module, input, DEFAULT_STEP_LIMIT).unwrap().ret,
+                ret
+            );
+        }
+    }
+}
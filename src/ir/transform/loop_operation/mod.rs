@@ -2,7 +2,10 @@ use std::collections::HashMap;
 
 use crate::{
     ir::{
-        analysis::loop_tree::{loop_recognize::loop_recognize, LoopInfo, LoopList},
+        analysis::{
+            alias::AliasAnalysis,
+            loop_tree::{loop_recognize::loop_recognize, LoopInfo, LoopList},
+        },
         basicblock::BasicBlock,
         dump_now,
         function::Function,
@@ -10,61 +13,209 @@ use crate::{
         module::Module,
         tools::{func_process, inst_process_in_bb},
     },
+    pass_manager::PassManager,
     utility::{ObjPool, ObjPtr},
 };
 
 use self::{
     auto_parallelization::auto_paralellization, licm::licm_run, livo::livo_run,
-    loop_elimination::loop_elimination, loop_simplify::loop_simplify_run,
-    loop_unrolling::loop_unrolling,
+    loop_elimination::loop_elimination, loop_fusion::loop_fusion,
+    loop_interchange::loop_interchange, loop_simplify::loop_simplify_run, loop_tiling::loop_tiling,
+    loop_unrolling::loop_unrolling, loop_unswitching::loop_unswitching,
 };
 
+pub use self::loop_nest::is_movable;
+
 mod auto_parallelization;
 mod licm;
 mod livo;
 mod loop_elimination;
+mod loop_fusion;
+mod loop_interchange;
+mod loop_nest;
 mod loop_simplify;
+mod loop_tiling;
 mod loop_unrolling;
+mod loop_unswitching;
 
 pub fn loop_optimize(
     module: &mut Module,
     max_loop_unrolling: usize,
     pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
     para: bool,
+    passes: &PassManager,
 ) {
     let mut loop_map = loop_recognize(module);
-    func_process(module, |name, _| {
-        loop_simplify_run(loop_map.get_mut(&name).unwrap(), pools);
+    passes.run("loop-simplify", module, pools, |module, pools| {
+        func_process(module, |name, _| {
+            loop_simplify_run(loop_map.get_mut(&name).unwrap(), pools);
+        });
     });
 
     // 循环尾指令上提
-    super::sink::sink_opt(module, pools, true);
-    super::functional_optimizer(module, pools, false);
+    passes.run("loop-sink", module, pools, |module, pools| {
+        super::sink::sink_opt(module, pools, true)
+    });
+    super::functional_optimizer(module, pools, false, passes);
 
     // 循环不变量外提
-    func_process(module, |name, _| {
-        licm_run(loop_map.get_mut(&name).unwrap(), pools);
+    passes.run("licm", module, pools, |module, pools| {
+        let alias = AliasAnalysis::new(module);
+        func_process(module, |name, func| {
+            licm_run(
+                loop_map.get_mut(&name).unwrap(),
+                func.get_head(),
+                &alias,
+                pools,
+            );
+        });
+    });
+
+    // 循环判断外提
+    passes.run("unswitch", module, pools, |module, pools| {
+        loop_unswitching(module, &mut loop_map, pools)
     });
 
     // 循环归纳和删除
-    loop_elimination(module, &mut loop_map, pools);
-    super::functional_optimizer(module, pools, true);
+    passes.run("loop-elim", module, pools, |module, pools| {
+        loop_elimination(module, &mut loop_map, pools)
+    });
+    super::functional_optimizer(module, pools, true, passes);
+
+    // 循环合并
+    passes.run("fusion", module, pools, |module, _| {
+        loop_fusion(module, &mut loop_map)
+    });
+
+    // 循环交换
+    passes.run("interchange", module, pools, |module, pools| {
+        loop_interchange(module, &mut loop_map, pools)
+    });
+
+    // 循环分块
+    passes.run("tiling", module, pools, |module, pools| {
+        loop_tiling(module, &mut loop_map, pools)
+    });
+    super::functional_optimizer(module, pools, false, passes);
 
     // 循环展开
-    loop_unrolling(module, &mut loop_map, max_loop_unrolling, pools);
-    super::functional_optimizer(module, pools, false);
+    passes.run("unroll", module, pools, |module, pools| {
+        loop_unrolling(module, &mut loop_map, max_loop_unrolling, pools)
+    });
+    super::functional_optimizer(module, pools, false, passes);
 
     if para {
         // 自动并行化
-        auto_paralellization(module, &mut loop_map, pools);
-        super::functional_optimizer(module, pools, false);
+        passes.run("auto-para", module, pools, |module, pools| {
+            auto_paralellization(module, &mut loop_map, pools)
+        });
+        super::functional_optimizer(module, pools, false, passes);
 
         // 归纳变量强度削减
-
-        func_process(module, |name, func| {
-            let dominator_tree =
-                crate::ir::analysis::dominator_tree::calculate_dominator(func.get_head());
-            livo_run(dominator_tree, loop_map.get_mut(&name).unwrap(), pools);
+        passes.run("livo", module, pools, |module, pools| {
+            func_process(module, |name, func| {
+                let dominator_tree =
+                    crate::ir::analysis::dominator_tree::calculate_dominator(func.get_head());
+                livo_run(dominator_tree, loop_map.get_mut(&name).unwrap(), pools);
+            });
         });
     }
 }
+
+/// 识别并规范化所有循环,供单独运行的循环优化使用
+fn simplified_loops(
+    module: &mut Module,
+    pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
+) -> HashMap<String, LoopList> {
+    let mut loop_map = loop_recognize(module);
+    func_process(module, |name, _| {
+        loop_simplify_run(loop_map.get_mut(&name).unwrap(), pools);
+    });
+    loop_map
+}
+
+/// 单独运行循环规范化
+pub fn loop_simplify(
+    module: &mut Module,
+    pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
+) {
+    simplified_loops(module, pools);
+}
+
+/// 单独运行循环不变量外提
+pub fn licm(module: &mut Module, pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>)) {
+    let mut loop_map = simplified_loops(module, pools);
+    let alias = AliasAnalysis::new(module);
+    func_process(module, |name, func| {
+        licm_run(
+            loop_map.get_mut(&name).unwrap(),
+            func.get_head(),
+            &alias,
+            pools,
+        );
+    });
+}
+
+/// 单独运行循环归纳和删除
+pub fn loop_elim(module: &mut Module, pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>)) {
+    let mut loop_map = simplified_loops(module, pools);
+    loop_elimination(module, &mut loop_map, pools);
+}
+
+/// 单独运行循环合并
+pub fn fusion(module: &mut Module, pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>)) {
+    let mut loop_map = simplified_loops(module, pools);
+    super::phi_optimizer::phi_run(module);
+    loop_fusion(module, &mut loop_map);
+}
+
+/// 单独运行循环交换
+pub fn interchange(
+    module: &mut Module,
+    pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
+) {
+    let mut loop_map = simplified_loops(module, pools);
+    // 规范化产生的单前继phi会挡住内层循环的边界
+    super::phi_optimizer::phi_run(module);
+    loop_interchange(module, &mut loop_map, pools);
+}
+
+/// 单独运行循环分块
+pub fn tiling(module: &mut Module, pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>)) {
+    let mut loop_map = simplified_loops(module, pools);
+    super::phi_optimizer::phi_run(module);
+    loop_tiling(module, &mut loop_map, pools);
+}
+
+/// 单独运行循环判断外提
+pub fn unswitch(module: &mut Module, pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>)) {
+    let mut loop_map = simplified_loops(module, pools);
+    super::phi_optimizer::phi_run(module);
+    loop_unswitching(module, &mut loop_map, pools);
+}
+
+/// 单独运行循环展开
+pub fn unroll(
+    module: &mut Module,
+    max_loop_unrolling: usize,
+    pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
+) {
+    let mut loop_map = simplified_loops(module, pools);
+    loop_unrolling(module, &mut loop_map, max_loop_unrolling, pools);
+}
+
+/// 单独运行自动并行化
+pub fn auto_para(module: &mut Module, pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>)) {
+    let mut loop_map = simplified_loops(module, pools);
+    auto_paralellization(module, &mut loop_map, pools);
+}
+
+/// 单独运行归纳变量强度削减
+pub fn livo(module: &mut Module, pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>)) {
+    let mut loop_map = simplified_loops(module, pools);
+    func_process(module, |name, func| {
+        let dominator_tree =
+            crate::ir::analysis::dominator_tree::calculate_dominator(func.get_head());
+        livo_run(dominator_tree, loop_map.get_mut(&name).unwrap(), pools);
+    });
+}
@@ -3,72 +3,287 @@ use self::gvn_hoist::hoist_to_loop_head;
 use super::function::Function;
 use super::{basicblock::BasicBlock, instruction::Inst, module::Module};
 use super::{dump_now, tools::*};
+use crate::pass_manager::{IrPass, PassManager};
 use crate::utility::ObjPool;
 
 mod array_transform;
 mod condition_transform;
+mod congruence_gvn;
 mod constant_folding;
 mod dead_code_eliminate;
+mod dead_store_eliminate;
 mod delete_empty_block;
 mod delete_redundant_load_store;
 mod func_inline;
 mod global_value_numbering;
 mod global_var_transform;
 mod gvn_hoist;
+mod if_conversion;
+mod ipcp;
 mod loop_operation;
 mod meaningless_insts_folding;
 mod partial_redundancy_elimination;
 mod phi_optimizer;
 mod return_unused;
+mod sccp;
 mod simplify_cfg;
 mod sink;
 mod tail_call_optimize;
 mod verify;
+pub use verify::{verify_module, VerifyError};
+
+/// 所有可以在--passes中使用的中端遍
+pub const PASSES: &[IrPass] = &[
+    IrPass {
+        name: "cleanup",
+        desc: "phi优化、常量折叠、gvn、死代码删除等组成的清理流程",
+        run: |module, pools, _, passes| functional_optimizer(module, pools, true, passes),
+    },
+    IrPass {
+        name: "phi-opt",
+        desc: "删除冗余的phi",
+        run: |module, _, _, _| phi_optimizer::phi_run(module),
+    },
+    IrPass {
+        name: "const-fold",
+        desc: "常量折叠",
+        run: |module, pools, _, _| constant_folding::constant_folding(module, pools, true),
+    },
+    IrPass {
+        name: "dce",
+        desc: "死代码删除",
+        run: |module, _, _, _| dead_code_eliminate::dead_code_eliminate(module, true),
+    },
+    IrPass {
+        name: "global-dce",
+        desc: "删除未使用的全局变量和函数",
+        run: |module, _, _, _| dead_code_eliminate::global_eliminate(module),
+    },
+    IrPass {
+        name: "dse",
+        desc: "删除不会被读取的store、数组和全局变量",
+        run: |module, _, _, _| dead_store_eliminate::dead_store_eliminate(module, true),
+    },
+    IrPass {
+        name: "inst-fold",
+        desc: "消除无意义的指令",
+        run: |module, pools, _, _| {
+            meaningless_insts_folding::meaningless_inst_folding(module, pools)
+        },
+    },
+    IrPass {
+        name: "gvn",
+        desc: "基于同余类的乐观全局值编号",
+        run: |module, pools, _, _| congruence_gvn::congruence_gvn(module, pools, true),
+    },
+    IrPass {
+        name: "array-opt",
+        desc: "数组优化",
+        run: |module, pools, _, _| array_transform::array_optimize(module, pools, true),
+    },
+    IrPass {
+        name: "global-var",
+        desc: "全局变量转换为局部值",
+        run: |module, pools, _, _| global_var_transform::global_var_transform(module, pools, true),
+    },
+    IrPass {
+        name: "return-unused",
+        desc: "删除未被使用的返回值",
+        run: |module, _, _, _| return_unused::return_unused(module),
+    },
+    IrPass {
+        name: "simplify-cfg",
+        desc: "简化控制流图",
+        run: |module, pools, _, _| simplify_cfg::simplify_cfg_run(module, pools),
+    },
+    IrPass {
+        name: "ipcp",
+        desc: "过程间常量与范围传播,按常量实参克隆函数",
+        run: |module, pools, pool_func, _| ipcp::ipcp(module, pools, pool_func),
+    },
+    IrPass {
+        name: "sccp",
+        desc: "稀疏条件常量传播",
+        run: |module, pools, _, _| sccp::sccp(module, pools),
+    },
+    IrPass {
+        name: "pre",
+        desc: "基于lazy code motion的部分冗余消除",
+        run: |module, pools, _, _| partial_redundancy_elimination::pre(module, true, pools),
+    },
+    IrPass {
+        name: "gvn-hoist",
+        desc: "将相同的指令上提到循环头(不在默认流水线中)",
+        run: |module, pools, _, _| hoist_to_loop_head(module, pools),
+    },
+    IrPass {
+        name: "loop-opt",
+        desc: "完整的循环优化流程,不含自动并行化",
+        run: |module, pools, _, passes| {
+            loop_operation::loop_optimize(module, 100, pools, false, passes)
+        },
+    },
+    IrPass {
+        name: "loop-simplify",
+        desc: "为循环插入preheader并规范化出口",
+        run: |module, pools, _, _| loop_operation::loop_simplify(module, pools),
+    },
+    IrPass {
+        name: "loop-sink",
+        desc: "循环尾指令上提",
+        run: |module, pools, _, _| sink::sink_opt(module, pools, true),
+    },
+    IrPass {
+        name: "licm",
+        desc: "循环不变量外提",
+        run: |module, pools, _, _| loop_operation::licm(module, pools),
+    },
+    IrPass {
+        name: "unswitch",
+        desc: "把循环中不变的判断外提到循环外,复制循环",
+        run: |module, pools, _, _| loop_operation::unswitch(module, pools),
+    },
+    IrPass {
+        name: "loop-elim",
+        desc: "循环归纳和删除",
+        run: |module, pools, _, _| loop_operation::loop_elim(module, pools),
+    },
+    IrPass {
+        name: "fusion",
+        desc: "合并迭代空间相同的相邻循环",
+        run: |module, pools, _, _| loop_operation::fusion(module, pools),
+    },
+    IrPass {
+        name: "interchange",
+        desc: "交换两层完美循环嵌套以减小内层循环的访存跨度",
+        run: |module, pools, _, _| loop_operation::interchange(module, pools),
+    },
+    IrPass {
+        name: "tiling",
+        desc: "对访存跨度大的两层循环嵌套分块",
+        run: |module, pools, _, _| loop_operation::tiling(module, pools),
+    },
+    IrPass {
+        name: "unroll",
+        desc: "循环展开",
+        run: |module, pools, _, _| loop_operation::unroll(module, 100, pools),
+    },
+    IrPass {
+        name: "auto-para",
+        desc: "循环自动并行化",
+        run: |module, pools, _, _| loop_operation::auto_para(module, pools),
+    },
+    IrPass {
+        name: "livo",
+        desc: "归纳变量强度削减",
+        run: |module, pools, _, _| loop_operation::livo(module, pools),
+    },
+    IrPass {
+        name: "if-convert",
+        desc: "将短小的分支转换为select",
+        run: |module, pools, _, _| if_conversion::if_conversion(module, pools),
+    },
+    IrPass {
+        name: "sink",
+        desc: "指令下沉",
+        run: |module, pools, _, _| sink::sink(module, pools),
+    },
+    IrPass {
+        name: "tail-call",
+        desc: "尾递归优化",
+        run: |module, pools, _, _| tail_call_optimize::tail_call_optimize(module, pools),
+    },
+    IrPass {
+        name: "inline",
+        desc: "函数内联",
+        run: |module, pools, _, _| func_inline::inline_run(module, pools),
+    },
+];
 
 pub fn optimizer_run(
     module: &mut Module,
     mut pools: (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
+    pool_func: &mut ObjPool<Function>,
     optimize_flag: bool,
     para: bool,
+    passes: &PassManager,
 ) {
+    // 指定了流水线时只运行指定的遍
+    if passes.get_pipeline().is_some() {
+        passes.run_pipeline(module, &mut pools, pool_func);
+        return;
+    }
+
     // 在功能点上对phi指令进行优化
-    functional_optimizer(module, &mut pools, optimize_flag);
+    passes.run("cleanup", module, &mut pools, |module, pools| {
+        functional_optimizer(module, pools, optimize_flag, passes)
+    });
 
     if optimize_flag {
+        // 过程间常量与范围传播
+        passes.run("ipcp", module, &mut pools, |module, pools| {
+            ipcp::ipcp(module, pools, pool_func)
+        });
+        // 稀疏条件常量传播
+        passes.run("sccp", module, &mut pools, sccp::sccp);
         // 简化cfg
-        simplify_cfg::simplify_cfg_run(module, &mut pools);
-        functional_optimizer(module, &mut pools, optimize_flag);
+        passes.run("simplify-cfg", module, &mut pools, simplify_cfg::simplify_cfg_run);
+        passes.run("cleanup", module, &mut pools, |module, pools| {
+            functional_optimizer(module, pools, optimize_flag, passes)
+        });
 
-        // 局部冗余消除 指令上提
-        // partial_redundancy_elimination::pre(module, optimize_flag, &mut pools);
+        // 部分冗余消除
+        passes.run("pre", module, &mut pools, |module, pools| {
+            partial_redundancy_elimination::pre(module, optimize_flag, pools)
+        });
 
         // 循环优化
-        loop_operation::loop_optimize(module, 100, &mut pools, para);
-        simplify_cfg::simplify_cfg_run(module, &mut pools);
-        functional_optimizer(module, &mut pools, optimize_flag);
+        passes.run("loop-opt", module, &mut pools, |module, pools| {
+            loop_operation::loop_optimize(module, 100, pools, para, passes)
+        });
+        passes.run("simplify-cfg", module, &mut pools, simplify_cfg::simplify_cfg_run);
+        passes.run("cleanup", module, &mut pools, |module, pools| {
+            functional_optimizer(module, pools, optimize_flag, passes)
+        });
 
         // 指令下沉
-        sink::sink(module, &mut pools);
+        passes.run("sink", module, &mut pools, sink::sink);
 
         // 尾递归优化
-        tail_call_optimize::tail_call_optimize(module, &mut pools);
+        passes.run("tail-call", module, &mut pools, tail_call_optimize::tail_call_optimize);
         // 函数内联
-        func_inline::inline_run(module, &mut pools);
-        functional_optimizer(module, &mut pools, optimize_flag);
+        passes.run("inline", module, &mut pools, func_inline::inline_run);
+        passes.run("cleanup", module, &mut pools, |module, pools| {
+            functional_optimizer(module, pools, optimize_flag, passes)
+        });
+        // 稀疏条件常量传播
+        passes.run("sccp", module, &mut pools, sccp::sccp);
         // 简化cfg
-        simplify_cfg::simplify_cfg_run(module, &mut pools);
-        functional_optimizer(module, &mut pools, optimize_flag);
+        passes.run("simplify-cfg", module, &mut pools, simplify_cfg::simplify_cfg_run);
+        passes.run("cleanup", module, &mut pools, |module, pools| {
+            functional_optimizer(module, pools, optimize_flag, passes)
+        });
 
-        // 局部冗余消除 指令上提
-        //partial_redundancy_elimination::pre(module, optimize_flag, &mut pools);
+        // 部分冗余消除
+        passes.run("pre", module, &mut pools, |module, pools| {
+            partial_redundancy_elimination::pre(module, optimize_flag, pools)
+        });
 
         // 循环优化
-        loop_operation::loop_optimize(module, 100, &mut pools, false);
-        simplify_cfg::simplify_cfg_run(module, &mut pools);
-        functional_optimizer(module, &mut pools, optimize_flag);
+        passes.run("loop-opt", module, &mut pools, |module, pools| {
+            loop_operation::loop_optimize(module, 100, pools, false, passes)
+        });
+        passes.run("simplify-cfg", module, &mut pools, simplify_cfg::simplify_cfg_run);
+
+        // 分支转换为select,之后合并变为直线的块
+        passes.run("if-convert", module, &mut pools, if_conversion::if_conversion);
+        passes.run("simplify-cfg", module, &mut pools, simplify_cfg::simplify_cfg_run);
+        passes.run("cleanup", module, &mut pools, |module, pools| {
+            functional_optimizer(module, pools, optimize_flag, passes)
+        });
 
         // 指令下沉
-        sink::sink(module, &mut pools);
+        passes.run("sink", module, &mut pools, sink::sink);
     }
 }
 
@@ -84,93 +299,138 @@ pub fn add_interface(
         return;
     }
 
+    // 从文本形式读入的module中已经有被调用到的接口函数,不再重复添加
+    let exist = |module: &Module, name: &str| module.find_function(name).is_some();
+
     if pa_flag {
         // 增加自动并行化接口
 
         // void hitsz_thread_init();
         // 初始化线程池，在main函数中调用一次即可
-        let thread_init = func_pool.new_function();
-        module.push_function("hitsz_thread_init".to_string(), thread_init);
-        // 将这个函数插入到main函数的开头
-        let thread_init_call = inst_pool.make_void_call("hitsz_thread_init".to_string(), vec![]);
-        module
-            .get_function("main")
-            .get_head()
-            .push_front(thread_init_call);
+        if !exist(module, "hitsz_thread_init") {
+            let thread_init = func_pool.new_function();
+            module.push_function("hitsz_thread_init".to_string(), thread_init);
+            // 将这个函数插入到main函数的开头
+            let thread_init_call =
+                inst_pool.make_void_call("hitsz_thread_init".to_string(), vec![]);
+            module
+                .get_function("main")
+                .get_head()
+                .push_front(thread_init_call);
+        }
 
         // int hitsz_thread_create();
         // 创建一个新的线程，返回线程id
-        let mut thread_create = func_pool.new_function();
-        thread_create.set_return_type(super::ir_type::IrType::Int);
-        module.push_function("hitsz_thread_create".to_string(), thread_create);
+        if !exist(module, "hitsz_thread_create") {
+            let mut thread_create = func_pool.new_function();
+            thread_create.set_return_type(super::ir_type::IrType::Int);
+            module.push_function("hitsz_thread_create".to_string(), thread_create);
+        }
 
         // void hitsz_thread_join();
         // 等待线程结束
-        let thread_join = func_pool.new_function();
-        module.push_function("hitsz_thread_join".to_string(), thread_join);
+        if !exist(module, "hitsz_thread_join") {
+            let thread_join = func_pool.new_function();
+            module.push_function("hitsz_thread_join".to_string(), thread_join);
+        }
 
         // int hitsz_get_thread_num();
         // 获取当前线程id
-        let mut get_thread_num = func_pool.new_function();
-        get_thread_num.set_return_type(super::ir_type::IrType::Int);
-        module.push_function("hitsz_get_thread_num".to_string(), get_thread_num);
+        if !exist(module, "hitsz_get_thread_num") {
+            let mut get_thread_num = func_pool.new_function();
+            get_thread_num.set_return_type(super::ir_type::IrType::Int);
+            module.push_function("hitsz_get_thread_num".to_string(), get_thread_num);
+        }
     }
 
     // void hitsz_memset(intptr array, int value, int n);
-    let mut memset = func_pool.new_function();
-    let array = inst_pool.make_param(super::ir_type::IrType::IntPtr);
-    let value = inst_pool.make_param(super::ir_type::IrType::Int);
-    let n = inst_pool.make_param(super::ir_type::IrType::Int);
-    memset.set_parameter("array".to_string(), array);
-    memset.set_parameter("value".to_string(), value);
-    memset.set_parameter("n".to_string(), n);
-    module.push_function("hitsz_memset".to_string(), memset);
+    if !exist(module, "hitsz_memset") {
+        let mut memset = func_pool.new_function();
+        let array = inst_pool.make_param(super::ir_type::IrType::IntPtr);
+        let value = inst_pool.make_param(super::ir_type::IrType::Int);
+        let n = inst_pool.make_param(super::ir_type::IrType::Int);
+        memset.set_parameter("array".to_string(), array);
+        memset.set_parameter("value".to_string(), value);
+        memset.set_parameter("n".to_string(), n);
+        module.push_function("hitsz_memset".to_string(), memset);
+    }
 
     // void hitsz_memcopy(intptr dst, intptr src, int n);
-    let mut hitsz_memcopy = func_pool.new_function();
-    let dst = inst_pool.make_param(super::ir_type::IrType::IntPtr);
-    let src = inst_pool.make_param(super::ir_type::IrType::IntPtr);
-    let n = inst_pool.make_param(super::ir_type::IrType::Int);
-    hitsz_memcopy.set_parameter("dst".to_string(), dst);
-    hitsz_memcopy.set_parameter("src".to_string(), src);
-    hitsz_memcopy.set_parameter("n".to_string(), n);
-    module.push_function("hitsz_memcopy".to_string(), hitsz_memcopy);
+    if !exist(module, "hitsz_memcopy") {
+        let mut hitsz_memcopy = func_pool.new_function();
+        let dst = inst_pool.make_param(super::ir_type::IrType::IntPtr);
+        let src = inst_pool.make_param(super::ir_type::IrType::IntPtr);
+        let n = inst_pool.make_param(super::ir_type::IrType::Int);
+        hitsz_memcopy.set_parameter("dst".to_string(), dst);
+        hitsz_memcopy.set_parameter("src".to_string(), src);
+        hitsz_memcopy.set_parameter("n".to_string(), n);
+        module.push_function("hitsz_memcopy".to_string(), hitsz_memcopy);
+    }
 }
 
 fn functional_optimizer(
     module: &mut Module,
     pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
     optimize_flag: bool,
+    passes: &PassManager,
 ) {
     debug_assert!(verify::verify_run(module));
     // phi优化
-    phi_optimizer::phi_run(module);
+    passes.run("phi-opt", module, pools, |module, _| phi_optimizer::phi_run(module));
 
     // 常量折叠
-    constant_folding::constant_folding(module, pools, optimize_flag);
+    passes.run("const-fold", module, pools, |module, pools| {
+        constant_folding::constant_folding(module, pools, optimize_flag)
+    });
     // 死代码删除
-    dead_code_eliminate::dead_code_eliminate(module, optimize_flag);
+    passes.run("dce", module, pools, |module, _| {
+        dead_code_eliminate::dead_code_eliminate(module, optimize_flag)
+    });
 
     // 消除不必要的指令
-    meaningless_insts_folding::meaningless_inst_folding(module, pools);
+    passes.run(
+        "inst-fold",
+        module,
+        pools,
+        meaningless_insts_folding::meaningless_inst_folding,
+    );
 
     // gvn
-    global_value_numbering::gvn(module, optimize_flag);
+    passes.run("gvn", module, pools, |module, pools| {
+        congruence_gvn::congruence_gvn(module, pools, optimize_flag)
+    });
 
     // 常量折叠
-    constant_folding::constant_folding(module, pools, optimize_flag);
+    passes.run("const-fold", module, pools, |module, pools| {
+        constant_folding::constant_folding(module, pools, optimize_flag)
+    });
 
     // 数组优化
-    array_transform::array_optimize(module, pools, optimize_flag);
+    passes.run("array-opt", module, pools, |module, pools| {
+        array_transform::array_optimize(module, pools, optimize_flag)
+    });
 
     // 全局变量转换
-    global_var_transform::global_var_transform(module, pools, optimize_flag);
+    passes.run("global-var", module, pools, |module, pools| {
+        global_var_transform::global_var_transform(module, pools, optimize_flag)
+    });
 
     // 函数返回值优化
-    return_unused::return_unused(module);
+    passes.run("return-unused", module, pools, |module, _| {
+        return_unused::return_unused(module)
+    });
+
+    // 删除不会被读取的内存
+    passes.run("dse", module, pools, |module, _| {
+        dead_store_eliminate::dead_store_eliminate(module, optimize_flag)
+    });
 
     // 死代码删除
-    dead_code_eliminate::dead_code_eliminate(module, optimize_flag);
+    passes.run("dce", module, pools, |module, _| {
+        dead_code_eliminate::dead_code_eliminate(module, optimize_flag)
+    });
     // 全局死代码删除
-    dead_code_eliminate::global_eliminate(module);
+    passes.run("global-dce", module, pools, |module, _| {
+        dead_code_eliminate::global_eliminate(module)
+    });
 }
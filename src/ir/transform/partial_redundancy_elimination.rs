@@ -1,15 +1,18 @@
-use std::collections::HashMap;
+//! 基于lazy code motion的部分冗余消除
+//!
+//! 以gvn得到的同余类为基础,把操作数完全相同的一组纯计算指令视为一个表达式。
+//! 在ssa中操作数不会被重新定义,操作数的定义点就相当于传统lcm中对表达式的kill,
+//! 因此可预期的插入点一定被所有操作数的定义支配。
+//! 插入只发生在表达式在所有后续路径上都会被计算的位置,不会引入新的除零等异常。
+use std::collections::{HashMap, HashSet};
 
 use crate::{
     ir::{
-        analysis::{
-            dominator_tree::{calculate_dominator, DominatorTree},
-            downstream_tree::DownStreamTree,
-        },
         basicblock::BasicBlock,
-        instruction::{Inst, InstKind},
+        instruction::{BinOp, Inst, InstKind},
+        ir_type::IrType,
         module::Module,
-        tools::{func_process, replace_inst},
+        tools::{bfs_bb_proceess, func_process, inst_process_in_bb, replace_inst},
         transform::{
             dead_code_eliminate::{dead_code_eliminate, global_eliminate},
             phi_optimizer::phi_run,
@@ -19,9 +22,9 @@ use crate::{
 };
 
 use super::{
-    delete_empty_block::{block_opt, replace_bb_with_bbs},
-    global_value_numbering::{self, Congruence},
-    gvn_hoist::{hoist_group, make_same_inst},
+    delete_empty_block::block_opt,
+    global_value_numbering::{self, CongruenceClass},
+    gvn_hoist::make_same_inst,
 };
 
 pub struct PreContext {
@@ -48,11 +51,15 @@ pub fn pre(
 ) {
     block_opt(module, pools, opt_option);
     let mut vec_congruence_class = global_value_numbering::gvn(module, opt_option).unwrap();
+    let mut pre_context = PreContext { index: 0 };
     let mut index = 0;
     func_process(module, |_, func| {
-        for congruence in vec_congruence_class[index].get_all_congruence_mut() {
-            pre_congruence(congruence, func.get_head(), pools);
-        }
+        pre_func(
+            &mut pre_context,
+            func.get_head(),
+            &mut vec_congruence_class[index],
+            pools,
+        );
         index += 1;
     });
     phi_run(module);
@@ -60,202 +67,538 @@ pub fn pre(
     global_eliminate(module);
 }
 
-pub fn pre_congruence(
-    congruence: &mut Congruence,
+/// 对一个函数做部分冗余消除,返回是否有指令被删除
+pub fn pre_func(
+    pre_context: &mut PreContext,
     head: ObjPtr<BasicBlock>,
+    congruence_class: &mut CongruenceClass,
     pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
-) {
-    let mut pre_context = PreContext { index: 0 };
-    let mut dominator_tree = calculate_dominator(head);
-    let mut downstream_tree = DownStreamTree::make_downstream_tree(head, &dominator_tree);
-    for index_class in 0..congruence.vec_class.len() {
-        loop {
-            let mut changed = false;
-            // changed |= pre_group(
-            //     &mut pre_context,
-            //     head,
-            //     &mut dominator_tree,
-            //     &mut downstream_tree,
-            //     congruence,
-            //     index_class,
-            //     pools,
-            // );
-            changed |= hoist_group(congruence, index_class, &dominator_tree, pools.1);
-            if !changed {
-                break;
+) -> bool {
+    if !head.get_up_bb().is_empty() {
+        // 入口块有前继时没有可以插入的虚拟入口边,不处理
+        return false;
+    }
+    let new_blocks = split_critical_edges(pre_context, head, pools);
+    let mut changed = false;
+    if let Some(cfg) = Cfg::new(head) {
+        for congruence in congruence_class.get_all_congruence_mut() {
+            for index_class in 0..congruence.vec_class.len() {
+                for expr in split_exprs(&congruence.vec_class[index_class]) {
+                    changed |= lcm(&cfg, &expr, pools.1);
+                }
             }
         }
     }
+    // 没有用来插入指令的关键边恢复原状
+    for bb in new_blocks {
+        if bb.get_head_inst().get_next().is_tail() {
+            merge_split_block(bb);
+        }
+    }
+    changed
+}
+
+/// 函数中可达的基本块组成的控制流图,块按bfs顺序编号,入口块为0
+struct Cfg {
+    blocks: Vec<ObjPtr<BasicBlock>>,
+    index: HashMap<ObjPtr<BasicBlock>, usize>,
+    /// 前继的顺序与up_bb相同,即与phi的参数顺序相同
+    preds: Vec<Vec<usize>>,
+    succs: Vec<Vec<usize>>,
 }
 
-pub fn pre_group(
+impl Cfg {
+    /// 存在不可达的前继时返回None
+    fn new(head: ObjPtr<BasicBlock>) -> Option<Cfg> {
+        let mut blocks = vec![];
+        bfs_bb_proceess(head, |bb| blocks.push(bb));
+        let index: HashMap<_, _> = blocks.iter().enumerate().map(|(i, &bb)| (bb, i)).collect();
+        let mut preds = vec![];
+        let mut succs = vec![];
+        for bb in blocks.iter() {
+            let mut up = vec![];
+            for pred in bb.get_up_bb() {
+                up.push(*index.get(pred)?);
+            }
+            preds.push(up);
+            succs.push(bb.get_next_bb().iter().map(|next| index[next]).collect());
+        }
+        Some(Cfg {
+            blocks,
+            index,
+            preds,
+            succs,
+        })
+    }
+}
+
+/// 在所有关键边上插入只含跳转的新块,返回插入的块
+fn split_critical_edges(
     pre_context: &mut PreContext,
     head: ObjPtr<BasicBlock>,
-    dominator_tree: &mut DominatorTree,
-    downstream_tree: &mut DownStreamTree,
-    congruence: &mut Congruence,
-    index_class: usize,
     pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
-) -> bool {
-    let mut changed = false;
-    loop {
-        let mut flag = false;
-        if congruence.vec_class[index_class].len() > 0 {
-            match congruence.vec_class[index_class][0].get_kind() {
-                InstKind::Gep => {
-                    break;
-                }
-                _ => {}
+) -> Vec<ObjPtr<BasicBlock>> {
+    let mut blocks = vec![];
+    bfs_bb_proceess(head, |bb| blocks.push(bb));
+    let mut new_blocks = vec![];
+    for bb in blocks {
+        let nexts = bb.get_next_bb().clone();
+        if nexts.len() < 2 {
+            continue;
+        }
+        for next in nexts.iter() {
+            // 两个后继相同时无法区分两条边,不拆分
+            if next.get_up_bb().len() < 2 || nexts.iter().filter(|x| *x == next).count() > 1 {
+                continue;
             }
+            let newb = pre_context.get_new_block(bb, pools.0);
+            let inst_jmp = pools.1.make_jmp();
+            newb.as_mut().push_back(inst_jmp);
+            bb.as_mut().replace_next_bb(*next, newb);
+            next.as_mut().replace_up_bb(bb, newb);
+            newb.as_mut().set_up_bb(vec![bb]);
+            newb.as_mut().set_next_bb(vec![*next]);
+            new_blocks.push(newb);
         }
-        for i in 0..congruence.vec_class[index_class].len() {
-            for j in i + 1..congruence.vec_class[index_class].len() {
-                if downstream_tree.is_upstream(
-                    congruence.vec_class[index_class][j].get_parent_bb(),
-                    congruence.vec_class[index_class][i].get_parent_bb(),
-                ) {
-                    // 其中一个指令所在块是另一个块的上游，且不互为上下游(不在同一个循环体中)
-                    let down = congruence.vec_class[index_class][i].get_parent_bb();
-                    let pres = down.get_up_bb();
-                    if pres.len() == 1 {
-                        if pres[0].get_next_bb().len() > 1 {
-                            continue;
-                        }
-                    }
-                    flag |= true;
-                    changed |= true;
-                    if insert_inst_in_pre(
-                        index_class,
-                        congruence,
-                        congruence.vec_class[index_class].clone(),
-                        pre_context,
-                        congruence.vec_class[index_class][i],
-                        &dominator_tree,
-                        pools.1,
-                        pools.0,
-                    ) {
-                        *dominator_tree = calculate_dominator(head);
-                        *downstream_tree =
-                            DownStreamTree::make_downstream_tree(head, dominator_tree);
-                    }
-                    break; //替换过指令，刷新，从头开始比较
-                }
+    }
+    new_blocks
+}
+
+/// 删除拆分关键边时插入的空块,把前继和后继重新连起来
+fn merge_split_block(bb: ObjPtr<BasicBlock>) {
+    let up = bb.get_up_bb()[0];
+    let next = bb.get_next_bb()[0];
+    up.as_mut().replace_next_bb(bb, next);
+    next.as_mut().replace_up_bb(bb, up);
+    bb.as_mut().clear_up_bb();
+    bb.as_mut().clear_next_bb();
+}
+
+/// 把同余类拆分为若干个操作数完全相同的表达式
+fn split_exprs(class: &[ObjPtr<Inst>]) -> Vec<Vec<ObjPtr<Inst>>> {
+    let mut exprs: Vec<Vec<ObjPtr<Inst>>> = vec![];
+    for &inst in class {
+        if !is_pre_candidate(inst) {
+            continue;
+        }
+        match exprs.iter_mut().find(|expr| is_same_expr(expr[0], inst)) {
+            Some(expr) => expr.push(inst),
+            None => exprs.push(vec![inst]),
+        }
+    }
+    exprs
+}
+
+/// 只有没有副作用且不访问内存的计算可以移动
+fn is_pre_candidate(inst: ObjPtr<Inst>) -> bool {
+    matches!(
+        inst.get_kind(),
+        InstKind::Binary(_)
+            | InstKind::Unary(_)
+            | InstKind::Select
+            | InstKind::Gep
+            | InstKind::FtoI
+            | InstKind::ItoF
+    )
+}
+
+/// 两条指令计算的是否是同一个表达式(操作数为同一组值)
+fn is_same_expr(inst1: ObjPtr<Inst>, inst2: ObjPtr<Inst>) -> bool {
+    if inst1.get_kind() != inst2.get_kind() || inst1.get_ir_type() != inst2.get_ir_type() {
+        return false;
+    }
+    let operands1 = inst1.get_operands();
+    let operands2 = inst2.get_operands();
+    if operands1 == operands2 {
+        return true;
+    }
+    match inst1.get_kind() {
+        InstKind::Binary(BinOp::Add | BinOp::Mul | BinOp::Eq | BinOp::Ne) => {
+            operands1[0] == operands2[1] && operands1[1] == operands2[0]
+        }
+        _ => false,
+    }
+}
+
+/// 对一个表达式做lazy code motion,返回是否有指令被删除
+fn lcm(cfg: &Cfg, expr: &[ObjPtr<Inst>], pool: &mut ObjPool<Inst>) -> bool {
+    let n = cfg.blocks.len();
+
+    // 定义了操作数的块对表达式不透明
+    let mut transp = vec![true; n];
+    for op in expr[0].get_operands() {
+        if op.is_global_var_or_param() {
+            continue;
+        }
+        match cfg.index.get(&op.get_parent_bb()) {
+            Some(&b) => transp[b] = false,
+            None => return false,
+        }
+    }
+
+    // 每个块中的计算按指令顺序排列
+    let set: HashSet<ObjPtr<Inst>> = expr.iter().copied().collect();
+    let mut comps = vec![vec![]; n];
+    let bbs: HashSet<usize> = expr
+        .iter()
+        .filter_map(|inst| cfg.index.get(&inst.get_parent_bb()).copied())
+        .collect();
+    for b in bbs {
+        inst_process_in_bb(cfg.blocks[b].get_head_inst(), |inst| {
+            if set.contains(&inst) {
+                comps[b].push(inst);
+            }
+        });
+    }
+    let comp: Vec<bool> = comps.iter().map(|c| !c.is_empty()).collect();
+    // 操作数在块内定义时,块内的计算都在定义之后,不是向上暴露的
+    let antloc: Vec<bool> = (0..n).map(|b| comp[b] && transp[b]).collect();
+
+    // 可用性
+    let mut avout = vec![true; n];
+    loop {
+        let mut changed = false;
+        for b in 0..n {
+            let avin = b != 0 && cfg.preds[b].iter().all(|&p| avout[p]);
+            let out = comp[b] || (avin && transp[b]);
+            changed |= out != avout[b];
+            avout[b] = out;
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    // 可预期性
+    let mut antin = vec![true; n];
+    let mut antout = vec![false; n];
+    loop {
+        let mut changed = false;
+        for b in (0..n).rev() {
+            antout[b] = !cfg.succs[b].is_empty() && cfg.succs[b].iter().all(|&s| antin[s]);
+            let ant = antloc[b] || (transp[b] && antout[b]);
+            changed |= ant != antin[b];
+            antin[b] = ant;
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let earliest = |i: usize, j: usize| antin[j] && !avout[i] && (!transp[i] || !antout[i]);
+
+    // 插入点尽量推迟,入口块的虚拟入口边上earliest即为antin
+    let mut laterin = vec![true; n];
+    laterin[0] = antin[0];
+    loop {
+        let mut changed = false;
+        for b in 1..n {
+            let later = cfg.preds[b]
+                .iter()
+                .all(|&p| earliest(p, b) || (laterin[p] && !antloc[p]));
+            changed |= later != laterin[b];
+            laterin[b] = later;
+        }
+        if !changed {
+            break;
+        }
+    }
+    let later = |i: usize, j: usize| earliest(i, j) || (laterin[i] && !antloc[i]);
+
+    let delete: Vec<bool> = (0..n).map(|b| antloc[b] && !laterin[b]).collect();
+    if !delete.iter().any(|&d| d) {
+        return false;
+    }
+
+    // 边上的插入放在后继块开头或前继块末尾,关键边已经被拆分
+    let mut insert_top = vec![false; n];
+    let mut insert_end = vec![false; n];
+    for (i, succs) in cfg.succs.iter().enumerate() {
+        for &j in succs.iter() {
+            if !later(i, j) || laterin[j] {
+                continue;
             }
-            if flag {
-                //替换过指令，刷新，从头开始比较
-                break;
+            if cfg.preds[j].len() == 1 {
+                insert_top[j] = true;
+            } else if succs.len() == 1 {
+                insert_end[i] = true;
+            } else {
+                return false;
             }
         }
-        if !flag {
+    }
+
+    // 被删除的计算处表达式的值必须在所有路径上都已经算出
+    let has_def: Vec<bool> = (0..n)
+        .map(|b| insert_top[b] || insert_end[b] || comp[b])
+        .collect();
+    let mut value_out = vec![true; n];
+    let mut value_in = vec![false; n];
+    loop {
+        let mut changed = false;
+        for b in 0..n {
+            value_in[b] = b != 0 && cfg.preds[b].iter().all(|&p| value_out[p]);
+            let out = has_def[b] || value_in[b];
+            changed |= out != value_out[b];
+            value_out[b] = out;
+        }
+        if !changed {
             break;
         }
     }
-    changed
-}
-// todo:循环中的pre需要另外处理
+    if (0..n).any(|b| delete[b] && !insert_top[b] && !value_in[b]) {
+        return false;
+    }
 
-pub fn insert_inst_in_pre(
-    index: usize,
-    congruence: &mut Congruence,
-    gp: Vec<ObjPtr<Inst>>,
-    pre_context: &mut PreContext,
-    inst_old: ObjPtr<Inst>,
-    dominator_tree: &DominatorTree,
-    pool_inst: &mut ObjPool<Inst>,
-    pool_block: &mut ObjPool<BasicBlock>,
-) -> bool {
-    let bb = inst_old.get_parent_bb();
-    let mut pres = bb.get_up_bb().clone();
-    let mut vec_index = vec![]; // 记录自己所支配前继的索引
-    let mut vec_operands_phi = vec![];
-    let mut flag = false;
-    //todo:只有一个前继的情况需要特殊处理
-    //只有一个前继且被自己所支配的情况不予考虑
-    //只有一个前继且前继有多个后继,不处理
-    //只有一个前继且前继只有当前块一个后继，直接把指令塞上去
-    if pres.len() == 1 {
-        if pres[0].get_next_bb().len() == 1 {
-            let inst_new = make_same_inst(inst_old, pool_inst);
-            pres[0].get_tail_inst().as_mut().insert_before(inst_new);
-            replace_inst(inst_old, inst_new);
-            congruence.remove_inst(inst_old); // congruence删除旧指令
-            congruence.add_inst(inst_new, index);
-            return flag;
+    let mut rewriter = Rewriter {
+        cfg,
+        ir_type: expr[0].get_ir_type(),
+        comps: &comps,
+        delete: &delete,
+        start: vec![None; n],
+        end: vec![None; n],
+    };
+    for b in 0..n {
+        if insert_top[b] {
+            let inst_new = make_same_inst(expr[0], pool);
+            first_non_phi(cfg.blocks[b])
+                .as_mut()
+                .insert_before(inst_new);
+            rewriter.start[b] = Some(inst_new);
+        }
+        if insert_end[b] {
+            let inst_new = make_same_inst(expr[0], pool);
+            cfg.blocks[b]
+                .get_tail_inst()
+                .as_mut()
+                .insert_before(inst_new);
+            rewriter.end[b] = Some(inst_new);
         }
-        unreachable!()
     }
-    congruence.remove_inst(inst_old); // congruence删除旧指令
-    let mut index_temp = 0;
-    for i in 0..bb.get_up_bb().len() {
-        if dominator_tree.is_dominate(&bb, &pres[index_temp]) {
-            //前继是自己所支配的，相应插的phi参数为phi本身
-            pres.remove(index_temp);
-            vec_index.push(i);
+
+    // 先确定所有计算的替代值,再统一替换
+    let mut replacements = vec![];
+    for b in 0..n {
+        if comps[b].is_empty() {
             continue;
         }
-        index_temp += 1;
+        let value = match delete[b] {
+            true => {
+                let value = rewriter.value_at_start(b, pool);
+                replacements.push((comps[b][0], value));
+                value
+            }
+            false => comps[b][0],
+        };
+        // 块内后面的计算是局部冗余
+        for &inst in comps[b].iter().skip(1) {
+            replacements.push((inst, value));
+        }
     }
-    for i in 0..pres.len() {
-        if let Some(inst_temp) = bb_contains_inst(pres[i], gp.clone()) {
-            vec_operands_phi.push((i, inst_temp));
-            continue;
+    for (inst, value) in replacements {
+        replace_inst(inst, value);
+    }
+    true
+}
+
+/// 按需插入phi,求表达式在各个块开头和末尾的值
+struct Rewriter<'a> {
+    cfg: &'a Cfg,
+    ir_type: IrType,
+    comps: &'a [Vec<ObjPtr<Inst>>],
+    delete: &'a [bool],
+    start: Vec<Option<ObjPtr<Inst>>>,
+    end: Vec<Option<ObjPtr<Inst>>>,
+}
+
+impl Rewriter<'_> {
+    fn value_at_start(&mut self, b: usize, pool: &mut ObjPool<Inst>) -> ObjPtr<Inst> {
+        if let Some(value) = self.start[b] {
+            return value;
         }
-        if pres[i].get_next_bb().len() == 1 {
-            // 只有当前块一个后继,不需要插块
-            let inst_new = make_same_inst(inst_old, pool_inst);
-            pres[i].get_tail_inst().as_mut().insert_before(inst_new);
-            congruence.add_inst(inst_new, index); //congruence加入新指令
-            vec_operands_phi.push((i, inst_new));
-        } else if pres[i].get_next_bb().len() > 1 {
-            // 不只有当前块一个后继,需要插块
-            flag = true;
-            let newb = pre_context.get_new_block(pres[i], pool_block);
-            let inst_jmp = pool_inst.make_jmp();
-            newb.as_mut().push_back(inst_jmp);
-            let inst_new = make_same_inst(inst_old, pool_inst);
-            newb.get_tail_inst().as_mut().insert_before(inst_new);
-            congruence.add_inst(inst_new, index); //congruence加入新指令
-            pres[i].as_mut().replace_next_bb(bb, newb);
-            replace_bb_with_bbs(bb, pres[i], vec![newb]);
-            newb.as_mut().set_next_bb(vec![bb]);
-            newb.as_mut().set_up_bb(vec![pres[i]]);
-            vec_operands_phi.push((i, inst_new));
-        } else {
-            unreachable!("前继块没后继,upbb和nextbb管理出错")
+        let preds = &self.cfg.preds[b];
+        if preds.len() == 1 {
+            let value = self.value_at_end(preds[0], pool);
+            self.start[b] = Some(value);
+            return value;
+        }
+        // 先记录phi再求参数,循环中的回边会用到phi自身
+        let phi = pool.make_phi(self.ir_type);
+        self.cfg.blocks[b].as_mut().push_front(phi);
+        self.start[b] = Some(phi);
+        for p in self.cfg.preds[b].clone() {
+            let value = self.value_at_end(p, pool);
+            phi.as_mut().add_operand(value);
+        }
+        phi
+    }
+
+    fn value_at_end(&mut self, b: usize, pool: &mut ObjPool<Inst>) -> ObjPtr<Inst> {
+        if let Some(value) = self.end[b] {
+            return value;
+        }
+        match self.comps[b].first() {
+            Some(&inst) if !self.delete[b] => inst,
+            _ => self.value_at_start(b, pool),
         }
     }
-    insert_phi(vec_index, vec_operands_phi, pool_inst, inst_old);
-    flag
 }
 
-pub fn bb_contains_inst(bb: ObjPtr<BasicBlock>, gp: Vec<ObjPtr<Inst>>) -> Option<ObjPtr<Inst>> {
-    let mut map_bb = HashMap::new();
-    for i in gp {
-        map_bb.insert(i.get_parent_bb(), i);
+/// 块中第一条不是phi的指令
+fn first_non_phi(bb: ObjPtr<BasicBlock>) -> ObjPtr<Inst> {
+    let mut inst = bb.get_head_inst();
+    while let InstKind::Phi = inst.get_kind() {
+        inst = inst.get_next();
     }
-    map_bb.get(&bb).copied()
+    inst
 }
 
-pub fn insert_phi(
-    vec_index: Vec<usize>,
-    vec_operands_phi: Vec<(usize, ObjPtr<Inst>)>,
-    pool: &mut ObjPool<Inst>,
-    inst_old: ObjPtr<Inst>,
-) {
-    //todo:输入参数为需要插phi的块和该块所支配前继的索引
-    let inst_phi = pool.make_phi(inst_old.get_ir_type());
-    let mut map_temp = HashMap::new();
-    for i in vec_index {
-        map_temp.insert(i, inst_phi);
+#[cfg(test)]
+mod pre_test {
+    use super::pre;
+    use crate::ir::{
+        instruction::{BinOp, InstKind},
+        interpret,
+        module::Module,
+        test_util::parse_module,
+        tools::{bfs_bb_proceess, inst_process_in_bb},
+        verify_module, DEFAULT_STEP_LIMIT,
+    };
+
+    /// 解析IR并运行pre
+    fn run_pre(text: &str) -> Module {
+        let (mut module, pools) = parse_module(text);
+        pre(&mut module, true, &mut (pools.bb, pools.inst));
+        assert_eq!(verify_module(&module), vec![]);
+        module
     }
-    for tuple in vec_operands_phi {
-        map_temp.insert(tuple.0, tuple.1);
+
+    /// 统计每个块中某种二元运算的条数
+    fn count_binary(module: &Module, op: BinOp) -> Vec<(String, usize)> {
+        let mut counts = vec![];
+        bfs_bb_proceess(module.get_function("main").get_head(), |bb| {
+            let mut count = 0;
+            inst_process_in_bb(bb.get_head_inst(), |inst| {
+                if inst.get_kind() == InstKind::Binary(op) {
+                    count += 1;
+                }
+            });
+            counts.push((bb.get_name().to_string(), count));
+        });
+        counts.sort();
+        counts
+    }
+
+    fn run(module: &Module, input: &str) -> i32 {
+        interpret(module, input, DEFAULT_STEP_LIMIT).unwrap().ret
     }
-    for i in 0..map_temp.len() {
-        inst_phi
-            .as_mut()
-            .add_operand(map_temp.get(&i).unwrap().clone())
+
+    #[test]
+    fn test_diamond() {
+        // bb_end中的乘法在bb_then中已经算过,插入到bb_else后变为全冗余
+        let module = run_pre(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %n = call i32 @getint()
+  %c = icmp slt i32 %n, 10
+  br i1 %c, label %bb_then, label %bb_else
+
+bb_then:
+  %x = mul i32 %n, 3
+  br label %bb_end
+
+bb_else:
+  %z = add i32 %n, 1
+  br label %bb_end
+
+bb_end:
+  %p = phi i32 [ %x, %bb_then ], [ %z, %bb_else ]
+  %y = mul i32 %n, 3
+  %r = add i32 %p, %y
+  ret i32 %r
+}
+",
+        );
+        assert_eq!(
+            count_binary(&module, BinOp::Mul),
+            vec![
+                ("else".to_string(), 1),
+                ("end".to_string(), 0),
+                ("entry".to_string(), 0),
+                ("then".to_string(), 1),
+            ]
+        );
+        assert_eq!(run(&module, "5"), 30);
+        assert_eq!(run(&module, "20"), 81);
+    }
+
+    #[test]
+    fn test_critical_edge() {
+        // bb_entry到bb_end的边是关键边,需要插块
+        let module = run_pre(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %n = call i32 @getint()
+  %c = icmp slt i32 %n, 10
+  br i1 %c, label %bb_then, label %bb_end
+
+bb_then:
+  %x = sub i32 %n, 7
+  br label %bb_end
+
+bb_end:
+  %p = phi i32 [ 0, %bb_entry ], [ %x, %bb_then ]
+  %y = sub i32 %n, 7
+  %r = add i32 %p, %y
+  ret i32 %r
+}
+",
+        );
+        assert_eq!(
+            count_binary(&module, BinOp::Sub),
+            vec![
+                ("end".to_string(), 0),
+                ("entry".to_string(), 0),
+                ("entry_PRE_0".to_string(), 1),
+                ("then".to_string(), 1),
+            ]
+        );
+        assert_eq!(run(&module, "5"), -4);
+        assert_eq!(run(&module, "20"), 13);
+    }
+
+    #[test]
+    fn test_no_speculation() {
+        // 除法只在一条路径上计算,不能上提到bb_entry
+        let module = run_pre(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %n = call i32 @getint()
+  %c = icmp ne i32 %n, 0
+  br i1 %c, label %bb_then, label %bb_end
+
+bb_then:
+  %x = sdiv i32 100, %n
+  br label %bb_end
+
+bb_end:
+  %p = phi i32 [ 0, %bb_entry ], [ %x, %bb_then ]
+  ret i32 %p
+}
+",
+        );
+        assert_eq!(
+            count_binary(&module, BinOp::Div),
+            vec![
+                ("end".to_string(), 0),
+                ("entry".to_string(), 0),
+                ("then".to_string(), 1),
+            ]
+        );
+        assert_eq!(run(&module, "0"), 0);
+        assert_eq!(run(&module, "4"), 25);
     }
-    inst_old.get_parent_bb().as_mut().push_front(inst_phi);
-    replace_inst(inst_old, inst_phi);
 }
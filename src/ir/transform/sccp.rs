@@ -0,0 +1,415 @@
+//! 稀疏条件常量传播
+//!
+//! 同时维护可执行边和ssa值的格,只沿可执行边传播常量:
+//! phi只合并可执行前继传来的值,条件为常量的分支只有一个后继是可执行的。
+//! 求解后把常量值替换为常量指令,分支的条件随之变为常量,
+//! 再由simplify_cfg折叠这些分支、删除不可达的块并合并phi。
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    ir::{
+        basicblock::BasicBlock,
+        instruction::{BinOp, Inst, InstKind, UnOp},
+        module::Module,
+        tools::{bfs_bb_proceess, func_process, inst_process_in_bb, replace_inst},
+    },
+    utility::{ObjPool, ObjPtr},
+};
+
+use super::simplify_cfg;
+
+/// 值的格,Top表示还没有确定的值,Bottom表示不是常量
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum LatticeValue {
+    Top,
+    Int(i32),
+    /// 按位比较,区分0.0和-0.0
+    Float(u32),
+    Bottom,
+}
+
+impl LatticeValue {
+    fn meet(self, other: LatticeValue) -> LatticeValue {
+        match (self, other) {
+            (LatticeValue::Top, value) | (value, LatticeValue::Top) => value,
+            (lhs, rhs) if lhs == rhs => lhs,
+            _ => LatticeValue::Bottom,
+        }
+    }
+
+    fn float(value: f32) -> LatticeValue {
+        LatticeValue::Float(value.to_bits())
+    }
+
+    /// 为常量值构造常量指令
+    fn make_const(self, pool: &mut ObjPool<Inst>) -> Option<ObjPtr<Inst>> {
+        match self {
+            LatticeValue::Int(value) => Some(pool.make_int_const(value)),
+            LatticeValue::Float(value) => Some(pool.make_float_const(f32::from_bits(value))),
+            _ => None,
+        }
+    }
+}
+
+pub fn sccp(module: &mut Module, pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>)) {
+    func_process(module, |_, func| {
+        let mut solver = Sccp::new();
+        solver.solve(func.get_head());
+        solver.rewrite(func.get_head(), pools.1);
+    });
+    // 删除不可达的块,合并只剩一个前继的phi
+    simplify_cfg::simplify_cfg_run(module, pools);
+}
+
+/// 折叠两个整数常量的二元运算,除零等运行时才能确定结果的情况返回None
+pub fn fold_int(op: BinOp, lhs: i32, rhs: i32) -> Option<i32> {
+    let value = match op {
+        BinOp::Add => lhs.wrapping_add(rhs),
+        BinOp::Sub => lhs.wrapping_sub(rhs),
+        BinOp::Mul => lhs.wrapping_mul(rhs),
+        // 除零在运行时才报错,不折叠
+        BinOp::Div | BinOp::Rem | BinOp::UDiv | BinOp::URem if rhs == 0 => return None,
+        BinOp::Div => lhs.wrapping_div(rhs),
+        BinOp::Rem => lhs.wrapping_rem(rhs),
+        BinOp::UDiv => (lhs as u32 / rhs as u32) as i32,
+        BinOp::URem => (lhs as u32 % rhs as u32) as i32,
+        BinOp::And => lhs & rhs,
+        BinOp::Or => lhs | rhs,
+        BinOp::Xor => lhs ^ rhs,
+        BinOp::Shl => lhs.wrapping_shl(rhs as u32),
+        BinOp::LShr => (lhs as u32).wrapping_shr(rhs as u32) as i32,
+        BinOp::AShr => lhs.wrapping_shr(rhs as u32),
+        BinOp::Gt => (lhs > rhs) as i32,
+        BinOp::Lt => (lhs < rhs) as i32,
+        BinOp::Ge => (lhs >= rhs) as i32,
+        BinOp::Le => (lhs <= rhs) as i32,
+        BinOp::Eq => (lhs == rhs) as i32,
+        BinOp::Ne => (lhs != rhs) as i32,
+    };
+    Some(value)
+}
+
+struct Sccp {
+    values: HashMap<ObjPtr<Inst>, LatticeValue>,
+    executable_bb: HashSet<ObjPtr<BasicBlock>>,
+    executable_edge: HashSet<(ObjPtr<BasicBlock>, ObjPtr<BasicBlock>)>,
+    flow_worklist: Vec<(ObjPtr<BasicBlock>, ObjPtr<BasicBlock>)>,
+    ssa_worklist: Vec<ObjPtr<Inst>>,
+}
+
+impl Sccp {
+    fn new() -> Sccp {
+        Sccp {
+            values: HashMap::new(),
+            executable_bb: HashSet::new(),
+            executable_edge: HashSet::new(),
+            flow_worklist: vec![],
+            ssa_worklist: vec![],
+        }
+    }
+
+    fn solve(&mut self, head: ObjPtr<BasicBlock>) {
+        self.visit_bb(head);
+        loop {
+            if let Some((from, to)) = self.flow_worklist.pop() {
+                if !self.executable_edge.insert((from, to)) {
+                    continue;
+                }
+                if self.executable_bb.contains(&to) {
+                    // 新的可执行边只影响phi
+                    let mut inst = to.get_head_inst();
+                    while let InstKind::Phi = inst.get_kind() {
+                        self.visit_inst(inst);
+                        inst = inst.get_next();
+                    }
+                } else {
+                    self.visit_bb(to);
+                }
+            } else if let Some(inst) = self.ssa_worklist.pop() {
+                if self.executable_bb.contains(&inst.get_parent_bb()) {
+                    self.visit_inst(inst);
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn visit_bb(&mut self, bb: ObjPtr<BasicBlock>) {
+        self.executable_bb.insert(bb);
+        inst_process_in_bb(bb.get_head_inst(), |inst| self.visit_inst(inst));
+    }
+
+    fn visit_inst(&mut self, inst: ObjPtr<Inst>) {
+        let bb = inst.get_parent_bb();
+        let value = match inst.get_kind() {
+            InstKind::Phi => {
+                let mut value = LatticeValue::Top;
+                for (i, pred) in bb.get_up_bb().iter().enumerate() {
+                    if self.executable_edge.contains(&(*pred, bb)) {
+                        value = value.meet(self.get_value(inst.get_operand(i)));
+                    }
+                }
+                value
+            }
+            InstKind::Branch => {
+                // 条件跳转的next_bb[0]为条件为假时的后继
+                let next = bb.get_next_bb().clone();
+                if !inst.is_br_cond() {
+                    self.flow_worklist.push((bb, next[0]));
+                    return;
+                }
+                match self.get_value(inst.get_br_cond()) {
+                    LatticeValue::Top => {}
+                    LatticeValue::Int(cond) => {
+                        self.flow_worklist
+                            .push((bb, if cond != 0 { next[1] } else { next[0] }));
+                    }
+                    LatticeValue::Float(cond) => {
+                        let cond = f32::from_bits(cond) != 0.0;
+                        self.flow_worklist
+                            .push((bb, if cond { next[1] } else { next[0] }));
+                    }
+                    LatticeValue::Bottom => {
+                        self.flow_worklist.push((bb, next[0]));
+                        self.flow_worklist.push((bb, next[1]));
+                    }
+                }
+                return;
+            }
+            InstKind::Binary(op) => self.eval_binary(
+                op,
+                self.get_value(inst.get_lhs()),
+                self.get_value(inst.get_rhs()),
+            ),
+            InstKind::Unary(op) => match (op, self.get_value(inst.get_unary_operand())) {
+                (_, LatticeValue::Top) => LatticeValue::Top,
+                (_, LatticeValue::Bottom) => LatticeValue::Bottom,
+                (UnOp::Pos, value) => value,
+                (UnOp::Neg, LatticeValue::Int(value)) => LatticeValue::Int(value.wrapping_neg()),
+                (UnOp::Neg, LatticeValue::Float(value)) => {
+                    LatticeValue::float(-f32::from_bits(value))
+                }
+                (UnOp::Not, LatticeValue::Int(value)) => LatticeValue::Int((value == 0) as i32),
+                (UnOp::Not, LatticeValue::Float(value)) => {
+                    LatticeValue::float((f32::from_bits(value) == 0.0) as i32 as f32)
+                }
+            },
+            InstKind::Select => match self.get_value(inst.get_select_cond()) {
+                LatticeValue::Top => LatticeValue::Top,
+                LatticeValue::Int(0) => self.get_value(inst.get_false_value()),
+                LatticeValue::Int(_) => self.get_value(inst.get_true_value()),
+                _ => self
+                    .get_value(inst.get_true_value())
+                    .meet(self.get_value(inst.get_false_value())),
+            },
+            InstKind::FtoI => match self.get_value(inst.get_float_to_int_value()) {
+                LatticeValue::Float(value) => {
+                    let value = f32::from_bits(value);
+                    // 超出范围时各平台的转换结果不同,不折叠
+                    if value.is_finite() && value.abs() < 2147483648.0 {
+                        LatticeValue::Int(value as i32)
+                    } else {
+                        LatticeValue::Bottom
+                    }
+                }
+                LatticeValue::Top => LatticeValue::Top,
+                _ => LatticeValue::Bottom,
+            },
+            InstKind::ItoF => match self.get_value(inst.get_int_to_float_value()) {
+                LatticeValue::Int(value) => LatticeValue::float(value as f32),
+                LatticeValue::Top => LatticeValue::Top,
+                _ => LatticeValue::Bottom,
+            },
+            InstKind::ConstInt(value) => LatticeValue::Int(value),
+            InstKind::ConstFloat(value) => LatticeValue::float(value),
+            InstKind::Store | InstKind::Return => return,
+            _ => LatticeValue::Bottom,
+        };
+        self.set_value(inst, value);
+    }
+
+    fn eval_binary(&self, op: BinOp, lhs: LatticeValue, rhs: LatticeValue) -> LatticeValue {
+        let bool_value = |value: bool| LatticeValue::Int(value as i32);
+        match (lhs, rhs) {
+            (LatticeValue::Bottom, _) | (_, LatticeValue::Bottom) => LatticeValue::Bottom,
+            (LatticeValue::Top, _) | (_, LatticeValue::Top) => LatticeValue::Top,
+            (LatticeValue::Int(lhs), LatticeValue::Int(rhs)) => match fold_int(op, lhs, rhs) {
+                Some(value) => LatticeValue::Int(value),
+                None => LatticeValue::Bottom,
+            },
+            (LatticeValue::Float(lhs), LatticeValue::Float(rhs)) => {
+                let (lhs, rhs) = (f32::from_bits(lhs), f32::from_bits(rhs));
+                match op {
+                    BinOp::Add => LatticeValue::float(lhs + rhs),
+                    BinOp::Sub => LatticeValue::float(lhs - rhs),
+                    BinOp::Mul => LatticeValue::float(lhs * rhs),
+                    BinOp::Div => LatticeValue::float(lhs / rhs),
+                    BinOp::Rem => LatticeValue::float(lhs % rhs),
+                    BinOp::UDiv
+                    | BinOp::URem
+                    | BinOp::And
+                    | BinOp::Or
+                    | BinOp::Xor
+                    | BinOp::Shl
+                    | BinOp::LShr
+                    | BinOp::AShr => LatticeValue::Bottom,
+                    BinOp::Gt => bool_value(lhs > rhs),
+                    BinOp::Lt => bool_value(lhs < rhs),
+                    BinOp::Ge => bool_value(lhs >= rhs),
+                    BinOp::Le => bool_value(lhs <= rhs),
+                    BinOp::Eq => bool_value(lhs == rhs),
+                    BinOp::Ne => bool_value(lhs != rhs),
+                }
+            }
+            _ => LatticeValue::Bottom,
+        }
+    }
+
+    fn get_value(&self, inst: ObjPtr<Inst>) -> LatticeValue {
+        match inst.get_kind() {
+            InstKind::ConstInt(value) | InstKind::GlobalConstInt(value) => LatticeValue::Int(value),
+            InstKind::ConstFloat(value) | InstKind::GlobalConstFloat(value) => {
+                LatticeValue::float(value)
+            }
+            _ if inst.is_global_var_or_param() => LatticeValue::Bottom,
+            _ => *self.values.get(&inst).unwrap_or(&LatticeValue::Top),
+        }
+    }
+
+    fn set_value(&mut self, inst: ObjPtr<Inst>, value: LatticeValue) {
+        let old = self.get_value(inst);
+        // 格只能下降
+        let value = old.meet(value);
+        if value != old {
+            self.values.insert(inst, value);
+            self.ssa_worklist
+                .extend(inst.get_use_list().iter().copied());
+        }
+    }
+
+    /// 用常量替换可执行块中值为常量的指令,条件为常量的分支交给simplify_cfg折叠
+    fn rewrite(&self, head: ObjPtr<BasicBlock>, pool: &mut ObjPool<Inst>) {
+        let mut blocks = vec![];
+        bfs_bb_proceess(head, |bb| {
+            if self.executable_bb.contains(&bb) {
+                blocks.push(bb);
+            }
+        });
+        let mut consts = HashMap::new();
+        for &bb in blocks.iter() {
+            inst_process_in_bb(bb.get_head_inst(), |inst| match inst.get_kind() {
+                InstKind::Binary(_)
+                | InstKind::Unary(_)
+                | InstKind::Select
+                | InstKind::FtoI
+                | InstKind::ItoF
+                | InstKind::Phi => {
+                    let value = self.get_value(inst);
+                    let inst_const = match consts.get(&value) {
+                        Some(&inst_const) => inst_const,
+                        None => match value.make_const(pool) {
+                            Some(inst_const) => {
+                                // 常量放在头块开头,支配所有使用
+                                head.as_mut().push_front(inst_const);
+                                consts.insert(value, inst_const);
+                                inst_const
+                            }
+                            None => return,
+                        },
+                    };
+                    replace_inst(inst, inst_const);
+                }
+                _ => {}
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod sccp_test {
+    use super::sccp;
+    use crate::ir::{
+        interpret, module::Module, test_util::parse_module, tools::bfs_bb_proceess, verify_module,
+        DEFAULT_STEP_LIMIT,
+    };
+
+    /// 解析IR并运行sccp,返回main中剩下的块
+    fn run_sccp(text: &str) -> (Module, Vec<String>) {
+        let (mut module, pools) = parse_module(text);
+        sccp(&mut module, &mut (pools.bb, pools.inst));
+        assert_eq!(verify_module(&module), vec![]);
+        let mut blocks = vec![];
+        bfs_bb_proceess(module.get_function("main").get_head(), |bb| {
+            blocks.push(bb.get_name().to_string())
+        });
+        blocks.sort();
+        (module, blocks)
+    }
+
+    #[test]
+    fn test_loop_phi() {
+        // 只有沿可执行边传播才能知道%x恒为1,bb_else不可达
+        let (module, blocks) = run_sccp(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %n = call i32 @getint()
+  br label %bb_loop
+
+bb_loop:
+  %x = phi i32 [ 1, %bb_entry ], [ %z, %bb_latch ]
+  %i = phi i32 [ 0, %bb_entry ], [ %i2, %bb_latch ]
+  %c = icmp eq i32 %x, 1
+  br i1 %c, label %bb_then, label %bb_else
+
+bb_then:
+  br label %bb_latch
+
+bb_else:
+  %w = add i32 %x, 1
+  br label %bb_latch
+
+bb_latch:
+  %z = phi i32 [ %x, %bb_then ], [ %w, %bb_else ]
+  %i2 = add i32 %i, 1
+  %c2 = icmp slt i32 %i2, %n
+  br i1 %c2, label %bb_loop, label %bb_exit
+
+bb_exit:
+  %r = add i32 %x, %i2
+  ret i32 %r
+}
+",
+        );
+        assert!(!blocks.contains(&"else".to_string()), "{:?}", blocks);
+        let result = interpret(&module, "5", DEFAULT_STEP_LIMIT).unwrap();
+        assert_eq!(result.ret, 6);
+    }
+
+    #[test]
+    fn test_entry_branch() {
+        // 入口块中条件为常量的分支也要折叠
+        let (module, blocks) = run_sccp(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %a = add i32 2, 3
+  %c = icmp sgt i32 %a, 4
+  br i1 %c, label %bb_then, label %bb_end
+
+bb_then:
+  %n = call i32 @getint()
+  br label %bb_end
+
+bb_end:
+  %r = phi i32 [ %a, %bb_entry ], [ %n, %bb_then ]
+  ret i32 %r
+}
+",
+        );
+        assert_eq!(blocks, vec!["entry".to_string()]);
+        let result = interpret(&module, "7", DEFAULT_STEP_LIMIT).unwrap();
+        assert_eq!(result.ret, 7);
+    }
+}
@@ -1,9 +1,12 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use crate::{
     ir::{
+        analysis::value_range::{calculate_value_range, Range},
         basicblock::BasicBlock,
+        function::Function,
         instruction::{Inst, InstKind},
+        ir_type::IrType,
         module::Module,
     },
     utility::{ObjPool, ObjPtr},
@@ -14,13 +17,14 @@ use super::{func_process, inst_process_in_bb, phi_optimizer};
 ///! 对于block的优化
 ///! 1. 删除无法到达的block：除头block外没有前继的就是无法到达的
 ///! 2. 合并只有一个后继和这个后继只有一个前继的block
-///! 3. 删除无法到达的分支
+///! 3. 删除无法到达的分支,包括由值范围分析得到条件恒为真或恒为假的分支
 
 pub fn simplify_cfg_run(
     module: &mut Module,
     pools: &mut (&mut ObjPool<BasicBlock>, &mut ObjPool<Inst>),
 ) {
     func_process(module, |_, func| {
+        fold_branch_by_range(func, pools.1);
         remove_unreachable_bb(func.get_head(), pools);
     });
 
@@ -106,13 +110,15 @@ fn remove_unreachable_bb(
     loop {
         let mut changed = false;
         for bb in bb_list.iter() {
-            // 不考虑头和尾
-            if bb.is_entry() || bb.is_exit() {
+            // 不考虑尾
+            if bb.is_exit() {
                 continue;
             }
 
-            // 如果没有前继或者前继都在deleted集里，那么当前bb是无法到达的
-            if (bb.get_up_bb().is_empty() || bb.get_up_bb().iter().all(|bb| deleted.contains(bb)))
+            // 如果没有前继或者前继都在deleted集里，那么当前bb是无法到达的，头块总是可达的
+            if *bb != head
+                && (bb.get_up_bb().is_empty()
+                    || bb.get_up_bb().iter().all(|bb| deleted.contains(bb)))
                 && !deleted.contains(bb)
             {
                 deleted.insert(bb);
@@ -168,6 +174,48 @@ fn remove_bb_self(bb: ObjPtr<BasicBlock>) {
     });
 }
 
+/// 把由值范围能确定结果的分支条件替换为常量,之后由check_bb删除不会走的分支
+fn fold_branch_by_range(func: ObjPtr<Function>, pool: &mut ObjPool<Inst>) {
+    let value_range = calculate_value_range(func);
+    let mut head = func.get_head();
+    let mut consts = HashMap::new();
+    for bb in get_bb_list(head) {
+        if bb.get_next_bb().len() != 2 {
+            continue;
+        }
+        let mut branch = bb.get_tail_inst();
+        let cond = branch.get_br_cond();
+        if cond.is_const() || cond.get_ir_type() != IrType::Int {
+            continue;
+        }
+        // 条件在分支所在的块中重新求值,可以用上支配这个块的分支条件
+        let range = match cond.get_kind() {
+            InstKind::Binary(op) if cond.get_lhs().get_ir_type() == IrType::Int => {
+                Range::eval_binary(
+                    op,
+                    value_range.get_range_at(cond.get_lhs(), bb),
+                    value_range.get_range_at(cond.get_rhs(), bb),
+                )
+            }
+            _ => value_range.get_range_at(cond, bb),
+        };
+        let value = if !range.contains(0) {
+            1
+        } else if range == Range::constant(0) {
+            0
+        } else {
+            continue;
+        };
+        let inst_const = *consts.entry(value).or_insert_with(|| {
+            // 常量放在头块开头,支配所有使用
+            let inst_const = pool.make_int_const(value);
+            head.push_front(inst_const);
+            inst_const
+        });
+        branch.set_br_cond(inst_const);
+    }
+}
+
 /// 检查分支是否无法到达
 /// 如果无法到达，那么删除到达这个分支的路径
 fn check_bb(
@@ -1,126 +1,645 @@
-use std::collections::HashMap;
+//! IR的合法性检查
+//!
+//! 检查SSA的支配关系、phi与前驱的对应、use list的一致性、
+//! 终结指令的位置以及操作数的类型,出错时指出所在的函数、基本块和指令。
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 
 use crate::{
     ir::{
+        analysis::dominator_tree::calculate_dominator,
         basicblock::BasicBlock,
         function::Function,
-        instruction::{Inst, InstKind},
+        instruction::{BinOp, Inst, InstKind},
+        ir_type::IrType,
         module::Module,
-        tools::{bfs_bb_proceess, bfs_inst_process, func_process, inst_process_in_bb},
+        tools::bfs_bb_proceess,
     },
     utility::ObjPtr,
 };
 
-pub fn verify_run(module: &mut Module) -> bool {
-    func_process(module, |name, func| {
-        func_verify(name, func);
-    });
-    true
+/// IR不满足的一条约束
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyError {
+    pub func: String,
+    pub block: Option<String>,
+    /// 指令在块中的序号和种类
+    pub inst: Option<(usize, String)>,
+    pub msg: String,
 }
 
-fn func_verify(_name: String, func: ObjPtr<Function>) {
-    // 构造inst和operands的map
-    let mut inst_map = HashMap::new();
-    bfs_inst_process(func.get_head(), |inst| {
-        inst_map.insert(inst, inst.get_operands().clone());
-    });
-
-    // 构造bb和up bb的map
-    let mut bb_map = HashMap::new();
-    bfs_bb_proceess(func.get_head(), |bb| {
-        bb_map.insert(bb, bb.get_up_bb().clone());
-    });
-
-    bfs_bb_proceess(func.get_head(), |bb| {
-        bb_verify(bb, &mut inst_map, &mut bb_map);
-    });
-
-    // 检查bb_map剩下的bb
-    for (_, up_bb) in bb_map {
-        debug_assert!(up_bb.len() == 0);
-    }
-
-    // 检查inst_map剩下的inst
-    for (inst, operands) in inst_map {
-        for op in operands {
-            debug_assert!(
-                op.is_param() || op.is_global_var(),
-                "inst: {:?} in bb: {}, op: {:?} in bb : {}",
-                inst.get_kind(),
-                inst.get_parent_bb().get_name(),
-                op.get_kind(),
-                op.get_parent_bb().get_name()
-            );
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "in function `{}`", self.func)?;
+        if let Some(block) = &self.block {
+            write!(f, ", block `{}`", block)?;
+        }
+        if let Some((index, kind)) = &self.inst {
+            write!(f, ", instruction #{} ({})", index, kind)?;
         }
+        write!(f, ": {}", self.msg)
     }
 }
 
-fn bb_verify(
-    bb: ObjPtr<BasicBlock>,
-    inst_map: &mut HashMap<ObjPtr<Inst>, Vec<ObjPtr<Inst>>>,
-    bb_map: &mut HashMap<ObjPtr<BasicBlock>, Vec<ObjPtr<BasicBlock>>>,
-) {
-    // 检查next bb 和 up bb会不会有重复的
-    let next_bb = bb.get_next_bb();
-    debug_assert!(
-        next_bb.len() <= 2,
-        "next bb num > 2, len: {}",
-        next_bb.len()
-    );
-    for next in next_bb {
-        let up_bb = bb_map.get_mut(next);
-        debug_assert_ne!(up_bb, None);
-        let up_bb = up_bb.unwrap();
-        let index = up_bb.iter().position(|x| x == &bb);
-        debug_assert!(
-            !index.is_none(),
-            "bb: {:?} not in {}'s up_bb: {:?}",
-            bb.get_name(),
-            next.get_name(),
-            up_bb.iter().map(|x| x.get_name()).collect::<Vec<_>>()
-        );
-        let index = index.unwrap();
-        up_bb.remove(index);
-        if up_bb.len() == 0 {
-            bb_map.remove(next);
+/// 检查整个模块,返回所有发现的错误
+pub fn verify_module(module: &Module) -> Vec<VerifyError> {
+    let mut errors = vec![];
+    let mut funcs = module.get_all_func();
+    funcs.sort_by(|a, b| a.0.cmp(b.0));
+    for (name, func) in funcs {
+        // 外部函数没有函数体
+        if func.is_empty_bb() {
+            continue;
         }
+        FuncVerifier::new(module, name, func, &mut errors).verify();
     }
+    errors
+}
 
-    inst_process_in_bb(bb.get_head_inst(), |inst| {
-        inst_verify(inst, inst_map);
-    });
+/// 在debug模式下检查模块,打印发现的错误
+pub fn verify_run(module: &mut Module) -> bool {
+    let errors = verify_module(module);
+    for err in errors.iter() {
+        eprintln!("{}", err);
+    }
+    errors.is_empty()
 }
 
-fn inst_verify(inst: ObjPtr<Inst>, inst_map: &mut HashMap<ObjPtr<Inst>, Vec<ObjPtr<Inst>>>) {
-    let prev = inst.get_prev();
-    let next = inst.get_next();
-    debug_assert_ne!(prev, inst, "prev == inst: {:?}", inst.get_kind());
-    debug_assert_ne!(next, inst, "next == inst: {:?}", inst.get_kind());
-    debug_assert!(
-        if prev == next {
-            prev.get_kind() == InstKind::Head
-        } else {
-            true
-        },
-        "prev == next: {:?}, prev & inst: {:?}",
-        inst,
-        prev
-    );
-
-    debug_assert_ne!(inst.get_kind(), InstKind::Parameter);
-    debug_assert_ne!(inst.get_kind(), InstKind::GlobalConstInt(0));
-    debug_assert_ne!(inst.get_kind(), InstKind::GlobalConstFloat(0.0));
-    debug_assert_ne!(inst.get_kind(), InstKind::GlobalInt(0));
-    debug_assert_ne!(inst.get_kind(), InstKind::GlobalFloat(0.0));
-
-    for user in inst.get_use_list() {
-        let user_operands = inst_map.get_mut(user);
-        debug_assert_ne!(user_operands, None, "user: {:?}, inst: {:?}", user, inst);
-        let user_operands = user_operands.unwrap();
-        debug_assert!(user_operands.contains(&inst));
-        user_operands.remove(user_operands.iter().position(|x| x == &inst).unwrap());
-        if user_operands.len() == 0 {
-            inst_map.remove(user);
+struct FuncVerifier<'a> {
+    module: &'a Module,
+    name: &'a str,
+    func: ObjPtr<Function>,
+    /// 可达的基本块,按广度优先的顺序
+    blocks: Vec<ObjPtr<BasicBlock>>,
+    /// 指令所在的块和在块中的序号
+    position: HashMap<ObjPtr<Inst>, (ObjPtr<BasicBlock>, usize)>,
+    errors: &'a mut Vec<VerifyError>,
+}
+
+impl<'a> FuncVerifier<'a> {
+    fn new(
+        module: &'a Module,
+        name: &'a str,
+        func: ObjPtr<Function>,
+        errors: &'a mut Vec<VerifyError>,
+    ) -> FuncVerifier<'a> {
+        let mut blocks = vec![];
+        bfs_bb_proceess(func.get_head(), |bb| blocks.push(bb));
+        let mut position = HashMap::new();
+        for bb in blocks.iter() {
+            for (index, inst) in insts_of(*bb).into_iter().enumerate() {
+                position.insert(inst, (*bb, index));
+            }
+        }
+        FuncVerifier {
+            module,
+            name,
+            func,
+            blocks,
+            position,
+            errors,
+        }
+    }
+
+    fn report(&mut self, bb: Option<ObjPtr<BasicBlock>>, inst: Option<ObjPtr<Inst>>, msg: String) {
+        // 序号不计入常量,与输出的IR中的指令对应
+        let inst = inst.map(|inst| {
+            let index = match self.position.get(&inst) {
+                Some((bb, _)) => insts_of(*bb)
+                    .into_iter()
+                    .take_while(|x| *x != inst)
+                    .filter(|x| !x.is_const())
+                    .count(),
+                None => 0,
+            };
+            (index, format!("{:?}", inst.get_kind()))
+        });
+        self.errors.push(VerifyError {
+            func: self.name.to_string(),
+            block: bb.map(|bb| bb.get_name().to_string()),
+            inst,
+            msg,
+        });
+    }
+
+    fn verify(&mut self) {
+        let errors = self.errors.len();
+        for bb in self.blocks.clone() {
+            self.verify_cfg(bb);
+            self.verify_block(bb);
+        }
+        // 控制流图或者指令链表有错误时,支配关系没有意义
+        if self.errors.len() == errors {
+            self.verify_dominance();
+        }
+    }
+
+    /// 检查前驱和后继互相对应
+    fn verify_cfg(&mut self, bb: ObjPtr<BasicBlock>) {
+        for next in bb.get_next_bb().clone() {
+            let forward = count(bb.get_next_bb(), &next);
+            let backward = count(next.get_up_bb(), &bb);
+            if forward != backward {
+                self.report(
+                    Some(bb),
+                    None,
+                    format!(
+                        "successor `{}` lists it {} time(s) as predecessor, expected {}",
+                        next.get_name(),
+                        backward,
+                        forward
+                    ),
+                );
+            }
+        }
+        for up in bb.get_up_bb().clone() {
+            if !up.get_next_bb().contains(&bb) {
+                self.report(
+                    Some(bb),
+                    None,
+                    format!(
+                        "predecessor `{}` does not list it as successor",
+                        up.get_name()
+                    ),
+                );
+            }
+        }
+    }
+
+    /// 检查指令链表、终结指令、phi的位置和每条指令
+    fn verify_block(&mut self, bb: ObjPtr<BasicBlock>) {
+        let insts = insts_of(bb);
+        if insts.is_empty() {
+            self.report(Some(bb), None, "block has no terminator".to_string());
+            return;
+        }
+        let mut phi_allowed = true;
+        for (index, inst) in insts.iter().enumerate() {
+            let inst = *inst;
+            if inst.get_parent_bb() != bb {
+                self.report(
+                    Some(bb),
+                    Some(inst),
+                    format!(
+                        "parent block is `{}` but the instruction is in this block",
+                        inst.get_parent_bb().get_name()
+                    ),
+                );
+            }
+            if inst.is_phi() {
+                if !phi_allowed {
+                    self.report(
+                        Some(bb),
+                        Some(inst),
+                        "phi after a non-phi instruction".to_string(),
+                    );
+                }
+            } else {
+                phi_allowed = false;
+            }
+            let is_terminator = matches!(inst.get_kind(), InstKind::Branch | InstKind::Return);
+            if is_terminator && index + 1 != insts.len() {
+                self.report(
+                    Some(bb),
+                    Some(inst),
+                    "terminator in the middle of the block".to_string(),
+                );
+            }
+            if !is_terminator && index + 1 == insts.len() {
+                self.report(
+                    Some(bb),
+                    Some(inst),
+                    "block does not end with a terminator".to_string(),
+                );
+            }
+            self.verify_inst(bb, inst);
+        }
+    }
+
+    fn verify_inst(&mut self, bb: ObjPtr<BasicBlock>, inst: ObjPtr<Inst>) {
+        for msg in self.check_inst(bb, inst) {
+            self.report(Some(bb), Some(inst), msg);
+        }
+    }
+
+    /// 检查指令的use list、操作数个数和类型,返回发现的问题
+    fn check_inst(&self, bb: ObjPtr<BasicBlock>, inst: ObjPtr<Inst>) -> Vec<String> {
+        let mut msgs = vec![];
+        let mut error = |msg: String| msgs.push(msg);
+        let operands = inst.get_operands().clone();
+        let ty = inst.get_ir_type();
+        let op_ty = |i: usize| operands[i].get_ir_type();
+
+        // use list与操作数一一对应
+        let distinct: HashSet<_> = operands.iter().cloned().collect();
+        for op in distinct.iter() {
+            let uses = count(op.get_use_list(), &inst);
+            let times = count(&operands, op);
+            if uses != times {
+                error(format!(
+                    "uses {:?} {} time(s) but appears {} time(s) in its use list",
+                    op.get_kind(),
+                    times,
+                    uses
+                ));
+            }
+        }
+        let users: HashSet<_> = inst.get_use_list().iter().cloned().collect();
+        for user in users {
+            if !user.get_operands().contains(&inst) {
+                error(format!(
+                    "use list contains {:?}, which does not use it",
+                    user
+                ));
+            }
+        }
+
+        let arity = match inst.get_kind() {
+            InstKind::Alloca(_) | InstKind::Branch | InstKind::Return | InstKind::Phi => None,
+            InstKind::Call(_) => None,
+            InstKind::Gep | InstKind::Store | InstKind::Binary(_) => Some(2),
+            InstKind::Load | InstKind::Unary(_) | InstKind::FtoI | InstKind::ItoF => Some(1),
+            InstKind::Select => Some(3),
+            InstKind::ConstInt(_) | InstKind::ConstFloat(_) => Some(0),
+            kind => {
+                error(format!("{:?} cannot appear in a block", kind));
+                return msgs;
+            }
+        };
+        if let Some(arity) = arity {
+            if operands.len() != arity {
+                error(format!(
+                    "expected {} operand(s), found {}",
+                    arity,
+                    operands.len()
+                ));
+                return msgs;
+            }
         }
+
+        match inst.get_kind() {
+            InstKind::Alloca(_) if !ty.is_pointer() || !operands.is_empty() => {
+                error(format!("alloca of type {:?} with operands", ty));
+            }
+            InstKind::Gep
+                if !op_ty(0).is_pointer() || op_ty(1) != IrType::Int || ty != op_ty(0) =>
+            {
+                error(format!(
+                    "gep of {:?} with offset {:?} has type {:?}",
+                    op_ty(0),
+                    op_ty(1),
+                    ty
+                ));
+            }
+            InstKind::Load => {
+                let ok = match pointee(operands[0]) {
+                    Some(IrType::Int) => ty == IrType::Int || ty == IrType::IntPtr,
+                    Some(IrType::Float) => ty == IrType::Float || ty == IrType::FloatPtr,
+                    _ => false,
+                };
+                if !ok {
+                    error(format!("load of type {:?} from {:?}", ty, op_ty(0)));
+                }
+            }
+            InstKind::Store => {
+                let ok = match pointee(operands[0]) {
+                    Some(elem) => op_ty(1) == elem,
+                    None => false,
+                };
+                if !ok {
+                    error(format!("store of {:?} to {:?}", op_ty(1), op_ty(0)));
+                }
+            }
+            InstKind::Binary(op) => {
+                let expected = match op {
+                    BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Rem => op_ty(0),
+                    _ => IrType::Int,
+                };
+                // 位运算、移位和无符号除法只能用于整数
+                let int_only = matches!(
+                    op,
+                    BinOp::UDiv
+                        | BinOp::URem
+                        | BinOp::And
+                        | BinOp::Or
+                        | BinOp::Xor
+                        | BinOp::Shl
+                        | BinOp::LShr
+                        | BinOp::AShr
+                );
+                if op_ty(0) != op_ty(1)
+                    || !(op_ty(0).is_int() || op_ty(0).is_float())
+                    || int_only && !op_ty(0).is_int()
+                    || ty != expected
+                {
+                    error(format!(
+                        "{:?} of {:?} and {:?} has type {:?}",
+                        op,
+                        op_ty(0),
+                        op_ty(1),
+                        ty
+                    ));
+                }
+            }
+            InstKind::Unary(op) if ty != op_ty(0) => {
+                error(format!("{:?} of {:?} has type {:?}", op, op_ty(0), ty));
+            }
+            InstKind::FtoI if op_ty(0) != IrType::Float || ty != IrType::Int => {
+                error(format!("fptosi from {:?} to {:?}", op_ty(0), ty));
+            }
+            InstKind::ItoF if op_ty(0) != IrType::Int || ty != IrType::Float => {
+                error(format!("sitofp from {:?} to {:?}", op_ty(0), ty));
+            }
+            // select目前只用于整数
+            InstKind::Select
+                if op_ty(0) != IrType::Int
+                    || op_ty(1) != IrType::Int
+                    || op_ty(2) != IrType::Int
+                    || ty != IrType::Int =>
+            {
+                error(format!(
+                    "select on {:?} between {:?} and {:?} has type {:?}",
+                    op_ty(0),
+                    op_ty(1),
+                    op_ty(2),
+                    ty
+                ));
+            }
+            InstKind::Branch => {
+                let succ = bb.get_next_bb().len();
+                match operands.len() {
+                    0 if succ != 1 => error(format!("jump with {} successor(s)", succ)),
+                    1 if succ != 2 => error(format!("branch with {} successor(s)", succ)),
+                    1 if op_ty(0) != IrType::Int => {
+                        error(format!("branch on a {:?} condition", op_ty(0)))
+                    }
+                    0 | 1 => {}
+                    n => error(format!("branch with {} operands", n)),
+                }
+            }
+            InstKind::Return => {
+                let ret_ty = self.func.get_return_type();
+                let ok = match operands.len() {
+                    0 => ret_ty == IrType::Void,
+                    1 => op_ty(0) == ret_ty,
+                    _ => false,
+                };
+                if !ok {
+                    error(format!(
+                        "returns {:?} from a function returning {:?}",
+                        operands
+                            .iter()
+                            .map(|op| op.get_ir_type())
+                            .collect::<Vec<_>>(),
+                        ret_ty
+                    ));
+                }
+                if !bb.get_next_bb().is_empty() {
+                    error("return in a block with successors".to_string());
+                }
+            }
+            InstKind::Call(callee) => {
+                // 外部函数的参数不做检查
+                match self.module.find_function(&callee) {
+                    Some(func) if !func.is_empty_bb() => {
+                        let params: Vec<_> = func
+                            .get_parameter_list()
+                            .iter()
+                            .map(|param| param.get_ir_type())
+                            .collect();
+                        let args: Vec<_> = operands.iter().map(|arg| arg.get_ir_type()).collect();
+                        if params != args {
+                            error(format!(
+                                "call to `{}` with arguments {:?}, expected {:?}",
+                                callee, args, params
+                            ));
+                        }
+                        if func.get_return_type() != ty {
+                            error(format!(
+                                "call to `{}` has type {:?}, but it returns {:?}",
+                                callee,
+                                ty,
+                                func.get_return_type()
+                            ));
+                        }
+                    }
+                    Some(_) => {}
+                    None => error(format!("call to unknown function `{}`", callee)),
+                }
+            }
+            InstKind::Phi => {
+                let preds = bb.get_up_bb().len();
+                if operands.len() != preds {
+                    error(format!(
+                        "phi has {} operand(s) but the block has {} predecessor(s)",
+                        operands.len(),
+                        preds
+                    ));
+                }
+                if operands.iter().any(|op| op.get_ir_type() != ty) {
+                    error(format!(
+                        "phi of type {:?} has operands {:?}",
+                        ty,
+                        operands
+                            .iter()
+                            .map(|op| op.get_ir_type())
+                            .collect::<Vec<_>>()
+                    ));
+                }
+            }
+            _ => {}
+        }
+        msgs
+    }
+
+    /// 检查每个操作数的定义支配其使用,phi的操作数需要支配对应的前驱
+    fn verify_dominance(&mut self) {
+        let dominator = calculate_dominator(self.func.get_head());
+        let params = self.func.get_parameter_list().clone();
+        for bb in self.blocks.clone() {
+            for inst in insts_of(bb) {
+                for (i, op) in inst.get_operands().clone().into_iter().enumerate() {
+                    // 来自不可达前驱的phi操作数不做检查
+                    let pred = match inst.is_phi() {
+                        true => match bb.get_up_bb().get(i) {
+                            Some(pred) if self.blocks.contains(pred) => Some(*pred),
+                            _ => continue,
+                        },
+                        false => None,
+                    };
+                    if op.is_const() || op.is_global_var() {
+                        continue;
+                    }
+                    if op.is_param() {
+                        if !params.contains(&op) {
+                            self.report(
+                                Some(bb),
+                                Some(inst),
+                                format!("operand {} is a parameter of another function", i),
+                            );
+                        }
+                        continue;
+                    }
+                    let (def_bb, def_index) = match self.position.get(&op) {
+                        Some(position) => *position,
+                        None => {
+                            self.report(
+                                Some(bb),
+                                Some(inst),
+                                format!(
+                                    "operand {} ({:?}) is not in a reachable block of the function",
+                                    i, op
+                                ),
+                            );
+                            continue;
+                        }
+                    };
+                    let dominated = if let Some(pred) = pred {
+                        dominator.is_dominate(&def_bb, &pred)
+                    } else if def_bb == bb {
+                        def_index < self.position[&inst].1
+                    } else {
+                        dominator.is_dominate(&def_bb, &bb)
+                    };
+                    if !dominated {
+                        self.report(
+                            Some(bb),
+                            Some(inst),
+                            format!(
+                                "operand {} ({:?}) is defined at #{} of `{}`, which does not dominate this use",
+                                i,
+                                op.get_kind(),
+                                def_index,
+                                def_bb.get_name()
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+        dominator.destroy();
+    }
+}
+
+/// 块中按顺序排列的指令,链表成环时在重复处停止
+fn insts_of(bb: ObjPtr<BasicBlock>) -> Vec<ObjPtr<Inst>> {
+    let mut insts = vec![];
+    if bb.is_empty() {
+        return insts;
+    }
+    let mut visited = HashSet::new();
+    let mut inst = bb.get_head_inst();
+    while !inst.is_tail() && visited.insert(inst) {
+        insts.push(inst);
+        inst = inst.get_next();
+    }
+    insts
+}
+
+/// 指针指向的元素类型,全局的标量变量本身作为地址使用
+fn pointee(ptr: ObjPtr<Inst>) -> Option<IrType> {
+    match ptr.get_kind() {
+        InstKind::GlobalInt(_) | InstKind::GlobalConstInt(_) => Some(IrType::Int),
+        InstKind::GlobalFloat(_) | InstKind::GlobalConstFloat(_) => Some(IrType::Float),
+        _ => match ptr.get_ir_type() {
+            IrType::IntPtr => Some(IrType::Int),
+            IrType::FloatPtr => Some(IrType::Float),
+            _ => None,
+        },
+    }
+}
+
+fn count<T: PartialEq>(list: &[T], item: &T) -> usize {
+    list.iter().filter(|x| *x == item).count()
+}
+
+#[cfg(test)]
+mod verify_test {
+    use super::verify_module;
+    use crate::ir::test_util::parse_module;
+
+    #[test]
+    fn test_valid() {
+        let (module, _) = parse_module(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %n = call i32 @getint()
+  %c = icmp slt i32 %n, 10
+  br i1 %c, label %bb_then, label %bb_end
+
+bb_then:
+  %m = add i32 %n, 1
+  br label %bb_end
+
+bb_end:
+  %r = phi i32 [ %n, %bb_entry ], [ %m, %bb_then ]
+  ret i32 %r
+}
+",
+        );
+        assert_eq!(verify_module(&module), vec![]);
+    }
+
+    #[test]
+    fn test_dominance() {
+        // %m定义在bb_then中,不支配bb_end
+        let (module, _) = parse_module(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %n = call i32 @getint()
+  %c = icmp slt i32 %n, 10
+  br i1 %c, label %bb_then, label %bb_end
+
+bb_then:
+  %m = add i32 %n, 1
+  br label %bb_end
+
+bb_end:
+  %r = add i32 %m, 2
+  ret i32 %r
+}
+",
+        );
+        let errors = verify_module(&module);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].func, "main");
+        assert_eq!(errors[0].block.as_deref(), Some("end"));
+        assert_eq!(errors[0].inst, Some((0, "Binary(Add)".to_string())));
+        assert!(errors[0].msg.contains("does not dominate"), "{}", errors[0]);
+    }
+
+    #[test]
+    fn test_terminator_and_use_list() {
+        let (module, _) = parse_module(
+            "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  %n = call i32 @getint()
+  %m = add i32 %n, 1
+  ret i32 %m
+}
+",
+        );
+        let main = module.get_function("main");
+        let call = main.get_head().get_head_inst();
+        let mut ret = main.get_head().get_tail_inst();
+        ret.remove_self();
+        // 手动破坏use list
+        call.as_mut().add_user(ret.as_ref());
+
+        let errors: Vec<String> = verify_module(&module)
+            .iter()
+            .map(|err| err.to_string())
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "in function `main`, block `entry`, instruction #0 (Call(getint)): use list contains Return in \"GlobalZone\", which does not use it",
+                "in function `main`, block `entry`, instruction #1 (Binary(Add)): block does not end with a terminator",
+            ]
+        );
     }
 }
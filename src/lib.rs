@@ -4,4 +4,5 @@ pub mod config;
 pub mod container;
 pub mod frontend;
 pub mod ir;
+pub mod pass_manager;
 pub mod utility;
@@ -1,13 +1,19 @@
 use lalrpop_util::lalrpop_mod;
 use sysylib::config;
-use sysylib::frontend::preprocess::preprocess;
+use sysylib::frontend::error::Error;
+use sysylib::frontend::preprocess::{Preprocessed, Preprocessor};
 extern crate biheap;
+use std::io::Write;
 // extern crate hexf_parse;
 // extern crate libm;
 use sysylib::backend::module::AsmModule;
 use sysylib::frontend::irgen::irgen;
-use sysylib::ir::dump_now;
+use sysylib::frontend::semantic;
+use sysylib::ir::basicblock::BasicBlock;
+use sysylib::ir::function::Function;
 use sysylib::ir::instruction::Inst;
+use sysylib::ir::{parse_ir, ExecError, ExecResult};
+use sysylib::pass_manager::{list_passes, PassManager};
 use sysylib::{self, backend::generate_asm, ir::module::Module, utility::ObjPool};
 lalrpop_mod! {
   #[allow(clippy::all)]
@@ -31,23 +37,129 @@ fn run_main() {
     use clap::{App, Arg};
     // 获取命令行解析
     let matches = App::new("compiler")
-        .arg(Arg::with_name("filename").required(true))
+        .arg(Arg::with_name("filename").required_unless("list-passes"))
         .arg(Arg::with_name("S").short("S"))
         .arg(Arg::with_name("o").short("o").takes_value(true))
         .arg(Arg::with_name("O1").short("O").takes_value(true))
         .arg(Arg::with_name("Events").short("E").takes_value(true))
+        .arg(
+            Arg::with_name("include")
+                .short("I")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("passes")
+                .long("passes")
+                .takes_value(true)
+                .require_equals(true)
+                .use_delimiter(true)
+                .help("Run only the given comma-separated passes"),
+        )
+        .arg(
+            Arg::with_name("print-after")
+                .long("print-after")
+                .takes_value(true)
+                .require_equals(true)
+                .multiple(true)
+                .use_delimiter(true)
+                .help("Print the IR to stderr after the given passes, or `all`"),
+        )
+        .arg(
+            Arg::with_name("disable-pass")
+                .long("disable-pass")
+                .takes_value(true)
+                .require_equals(true)
+                .multiple(true)
+                .use_delimiter(true)
+                .help("Skip the given passes wherever they run"),
+        )
+        .arg(Arg::with_name("list-passes").long("list-passes"))
+        .arg(
+            Arg::with_name("emit-llvm")
+                .long("emit-llvm")
+                .help("Write the optimized IR as LLVM IR instead of assembly"),
+        )
+        .arg(
+            Arg::with_name("interpret")
+                .long("interpret")
+                .help("Run the optimized IR with the interpreter instead of generating assembly"),
+        )
+        .arg(
+            Arg::with_name("check-passes")
+                .long("check-passes")
+                .help("Run the program before and after every IR pass and compare the results"),
+        )
+        .arg(
+            Arg::with_name("verify-each")
+                .long("verify-each")
+                .help("Verify the IR after the frontend and after every IR pass"),
+        )
+        .arg(
+            Arg::with_name("input")
+                .long("input")
+                .takes_value(true)
+                .require_equals(true)
+                .help("Input of the program for --interpret, --check-passes and --simulate"),
+        )
+        .arg(
+            Arg::with_name("simulate")
+                .long("simulate")
+                .help("Run the generated assembly with the RISC-V simulator"),
+        )
+        .arg(
+            Arg::with_name("expected")
+                .long("expected")
+                .takes_value(true)
+                .require_equals(true)
+                .requires("simulate")
+                .help("Compare the simulated output and exit code with the given .out file"),
+        )
         .get_matches();
 
+    if matches.is_present("list-passes") {
+        print!("{}", list_passes());
+        return;
+    }
+
     // 获取文件名
     let filename = matches.value_of("filename").unwrap();
 
+    // 优化遍的选择
+    let mut passes = pass_options(&matches).unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    });
+
+    // 解释执行时程序的输入,默认为空
+    let program_input = match matches.value_of("input") {
+        Some(path) => std::fs::read_to_string(path).unwrap_or_else(|err| {
+            eprintln!("error: cannot read {}: {}", path, err);
+            std::process::exit(1);
+        }),
+        None => String::new(),
+    };
+    if matches.is_present("check-passes") {
+        passes.check_passes(program_input.clone());
+    }
+    if matches.is_present("verify-each") {
+        passes.verify_each();
+    }
+
     crate::config::init();
     crate::config::set_file_path(&String::from(filename)); //把函数名加载到全局
 
     // 生成汇编的标志
     let _s_option = matches.is_present("S");
+    // 输出LLVM IR而不是汇编
+    let emit_llvm = matches.is_present("emit-llvm");
     // 输出文件名
-    let output = matches.value_of("o").unwrap_or("testcase.s");
+    let output = matches.value_of("o").unwrap_or(if emit_llvm {
+        "testcase.ll"
+    } else {
+        "testcase.s"
+    });
 
     // 是否使用优化
     let o1_option = matches.is_present("O1");
@@ -71,37 +183,85 @@ fn run_main() {
 
     let mut pool_inst: ObjPool<Inst> = ObjPool::new();
 
-    let file_preprocessed = preprocess(file.as_str());
-
-    let mut compunit = SysYRust::CompUnitParser::new()
-        .parse(file_preprocessed.as_str())
-        .unwrap();
+    if filename.ends_with(".ll") {
+        // 直接读取文本形式的IR,跳过前端,便于复现优化中的问题
+        if let Err(err) = parse_ir(
+            file.as_str(),
+            &mut module,
+            &mut pool_inst,
+            &mut pool_bb,
+            &mut pool_func,
+        ) {
+            eprintln!("error: {}\n --> {}:{}", err.msg, filename, err.line);
+            std::process::exit(1);
+        }
+    } else {
+        let include_dirs: Vec<&str> = matches.values_of("include").into_iter().flatten().collect();
+        frontend(
+            filename,
+            file.as_str(),
+            &include_dirs,
+            &mut module,
+            &mut pool_inst,
+            &mut pool_bb,
+            &mut pool_func,
+        );
+    }
 
-    irgen(
-        &mut compunit,
-        &mut module,
-        &mut pool_inst,
-        &mut pool_bb,
-        &mut pool_func,
-    );
-    drop(compunit);
+    if matches.is_present("verify-each") && !passes.verify("frontend", &module) {
+        std::process::exit(1);
+    }
 
     // let is_pa = o1_option;
     let is_pa = true;
-    // ir优化
+    // ir优化,指定了流水线时即使没有-O也运行
     sysylib::ir::add_interface(
         &mut module,
         &mut pool_func,
         &mut pool_inst,
-        o1_option,
+        o1_option || passes.get_pipeline().is_some(),
         is_pa,
     );
     sysylib::ir::optimizer_run(
         &mut module,
         (&mut pool_bb, &mut pool_inst),
+        &mut pool_func,
         o1_option,
         is_pa,
+        &passes,
     );
+
+    let broken = passes.get_broken_passes();
+    if !broken.is_empty() {
+        eprintln!(
+            "{} pass(es) produced invalid IR or changed the behaviour of the program",
+            broken.len()
+        );
+        std::process::exit(1);
+    }
+
+    if matches.is_present("interpret") {
+        match sysylib::ir::interpret(&module, &program_input, sysylib::ir::DEFAULT_STEP_LIMIT) {
+            Ok(result) => {
+                print!("{}", result.output);
+                std::io::stdout().flush().unwrap();
+                std::process::exit(result.exit_code());
+            }
+            Err(err) => {
+                eprintln!("error: {}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if emit_llvm {
+        std::fs::write(output, sysylib::ir::emit_llvm(&module)).unwrap_or_else(|err| {
+            eprintln!("error: cannot write {}: {}", output, err);
+            std::process::exit(1);
+        });
+        return;
+    }
+
     let output2 = "row_asm.log";
 
     // 后端解析
@@ -109,14 +269,23 @@ fn run_main() {
     // let is_opt = true;
     // let is_opt = false;
 
-    generate_asm(
+    let simulate_input = match matches.is_present("simulate") {
+        true => Some(program_input.as_str()),
+        false => None,
+    };
+    let simulated = generate_asm(
         filename,
         output,
         output2,
         &mut AsmModule::new(module),
         is_opt,
         is_pa,
+        &passes,
+        simulate_input,
     );
+    if let Some(result) = simulated {
+        report_simulation(result, matches.value_of("expected"));
+    }
     config::record_event("finish compile");
     // 编译结束后打印记录的属性
     config::dump();
@@ -126,3 +295,150 @@ fn run_main() {
         config::dump_not_log("./performance_eval.txt");
     }
 }
+
+/// 输出模拟执行的结果,给出--expected时与期望输出比较,然后退出
+fn report_simulation(result: Result<ExecResult, ExecError>, expected: Option<&str>) -> ! {
+    let result = result.unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    });
+    let path = match expected {
+        Some(path) => path,
+        None => {
+            print!("{}", result.output);
+            std::io::stdout().flush().unwrap();
+            std::process::exit(result.exit_code());
+        }
+    };
+    let expected = std::fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("error: cannot read {}: {}", path, err);
+        std::process::exit(1);
+    });
+    if result.matches(&expected) {
+        eprintln!(
+            "simulation matches {} ({} instructions)",
+            path, result.steps
+        );
+        std::process::exit(0);
+    }
+    eprintln!("simulation does not match {}", path);
+    eprintln!(
+        "--- expected\n{}--- actual\n{}",
+        expected,
+        result.judge_output()
+    );
+    std::process::exit(1);
+}
+
+/// 根据--passes、--disable-pass和--print-after构造PassManager
+fn pass_options(matches: &clap::ArgMatches) -> Result<PassManager, String> {
+    let mut passes = PassManager::new();
+    if let Some(pipeline) = matches.values_of("passes") {
+        passes.set_pipeline(pipeline.map(|name| name.to_string()).collect())?;
+    }
+    for name in matches.values_of("disable-pass").into_iter().flatten() {
+        passes.disable(name)?;
+    }
+    for name in matches.values_of("print-after").into_iter().flatten() {
+        passes.print_after(name)?;
+    }
+    Ok(passes)
+}
+
+/// 预处理、语法分析、语义分析并生成IR,出错时打印诊断信息并退出
+fn frontend(
+    filename: &str,
+    source: &str,
+    include_dirs: &[&str],
+    module: &mut Module,
+    pool_inst: &mut ObjPool<Inst>,
+    pool_bb: &mut ObjPool<BasicBlock>,
+    pool_func: &mut ObjPool<Function>,
+) {
+    // 预处理
+    let mut preprocessor = Preprocessor::new();
+    for dir in include_dirs {
+        preprocessor.include_dir(dir);
+    }
+    let (preprocessed, preprocess_errors) = preprocessor.process(filename, source);
+    if !preprocess_errors.is_empty() {
+        report_errors(&preprocess_errors, &preprocessed);
+    }
+
+    // 语法分析,出错时尽量恢复并收集所有错误后再退出
+    let mut parse_errors = Vec::new();
+    let parse_result =
+        SysYRust::CompUnitParser::new().parse(&mut parse_errors, preprocessed.text.as_str());
+    let mut syntax_errors: Vec<Error> = parse_errors
+        .iter()
+        .map(|recovery| Error::from(&recovery.error))
+        .collect();
+    if let Err(err) = &parse_result {
+        syntax_errors.push(Error::from(err));
+    }
+    // 规约时才记录错误,外层结构中的错误可能在内层之后记录
+    syntax_errors.sort_by_key(|err| err.span().map(|span| span.start));
+    if !syntax_errors.is_empty() {
+        report_errors(&syntax_errors, &preprocessed);
+    }
+    let mut compunit = parse_result.unwrap();
+
+    // 语义分析,只有通过检查的程序才生成IR
+    let semantic_errors = semantic::check(&mut compunit);
+    if !semantic_errors.is_empty() {
+        report_errors(&semantic_errors, &preprocessed);
+    }
+
+    if let Err(err) = irgen(&mut compunit, module, pool_inst, pool_bb, pool_func) {
+        report_errors(&[err], &preprocessed);
+    }
+}
+
+/// 打印所有诊断信息并退出
+fn report_errors(errors: &[Error], preprocessed: &Preprocessed) -> ! {
+    for err in errors {
+        eprintln!("{}", err.render_with(preprocessed));
+    }
+    eprintln!("{} error(s) generated", errors.len());
+    std::process::exit(1);
+}
+
+#[cfg(test)]
+mod main_test {
+    use super::SysYRust;
+    use sysylib::frontend::error::{line_col, Error};
+
+    #[test]
+    fn test_parse_errors() {
+        // 每个错误都恢复后继续分析,各报告一次
+        let source = "int main() {\n  \
+                      int a = 1;\n  \
+                      if (a { return 1; }\n  \
+                      int b[2] = {1 2}, c;\n  \
+                      while (a) { break }\n  \
+                      return 0;\n\
+                      }\n\
+                      int f(int a,) { return a }\n";
+        let mut errors = Vec::new();
+        let result = SysYRust::CompUnitParser::new().parse(&mut errors, source);
+        assert!(result.is_ok());
+        let errors: Vec<Error> = errors
+            .iter()
+            .map(|recovery| Error::from(&recovery.error))
+            .collect();
+        let mut positions: Vec<(usize, usize)> = errors
+            .iter()
+            .map(|err| line_col(source, err.span().unwrap().start))
+            .collect();
+        positions.sort();
+        assert_eq!(positions, vec![(3, 9), (4, 17), (5, 21), (8, 13), (8, 26)]);
+        assert_eq!(
+            errors[2].to_string(),
+            "syntax error: unexpected token `}`, expected \";\""
+        );
+        assert_eq!(
+            errors[4].to_string(),
+            "syntax error: unexpected token `)`, expected one of \"float\", \"int\""
+        );
+    }
+}
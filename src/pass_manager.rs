@@ -0,0 +1,330 @@
+//! 优化遍的注册表和命令行选项
+//!
+//! 中端的遍在ir::transform::PASSES中注册,可以按任意顺序组成流水线;
+//! 后端的遍在backend::opt::PASSES中注册,只能在生成汇编的固定位置运行,
+//! 流水线中的后端遍只决定其是否运行。
+//! 开启检查时,每个中端的遍前后都会解释执行程序,报告改变了程序行为的遍;
+//! 开启--verify-each时,每个中端的遍之后都会检查IR的合法性。
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+
+use crate::backend::opt::PASSES as BACKEND_PASSES;
+use crate::ir::basicblock::BasicBlock;
+use crate::ir::dump_module;
+use crate::ir::function::Function;
+use crate::ir::instruction::Inst;
+use crate::ir::module::Module;
+use crate::ir::PASSES as IR_PASSES;
+use crate::ir::{interpret, verify_module, ExecError, ExecResult, DEFAULT_STEP_LIMIT};
+use crate::utility::ObjPool;
+
+/// 中端的遍使用的基本块和指令池
+pub type Pools<'a, 'b> = (&'a mut ObjPool<BasicBlock>, &'b mut ObjPool<Inst>);
+
+/// 中端的一个遍,需要新建函数的遍使用传入的函数池
+pub struct IrPass {
+    pub name: &'static str,
+    pub desc: &'static str,
+    pub run: fn(&mut Module, &mut Pools, &mut ObjPool<Function>, &PassManager),
+}
+
+/// 后端的一个遍
+pub struct BackendPassInfo {
+    pub name: &'static str,
+    pub desc: &'static str,
+}
+
+/// 控制运行哪些遍,以及在哪些遍之后打印IR
+#[derive(Default)]
+pub struct PassManager {
+    /// 指定的流水线,为None时使用默认流水线
+    pipeline: Option<Vec<String>>,
+    disabled: HashSet<String>,
+    print_after: HashSet<String>,
+    print_after_all: bool,
+    /// 检查遍时解释执行程序使用的输入
+    check_input: Option<String>,
+    /// 最近一次解释执行的结果
+    last_result: RefCell<Option<Result<ExecResult, ExecError>>>,
+    /// 每个遍之后检查IR的合法性
+    verify_each: bool,
+    /// 改变了程序行为或者产生了不合法IR的遍
+    broken: RefCell<Vec<String>>,
+}
+
+impl PassManager {
+    pub fn new() -> PassManager {
+        PassManager::default()
+    }
+
+    /// 用指定的遍替换默认流水线
+    pub fn set_pipeline(&mut self, passes: Vec<String>) -> Result<(), String> {
+        for name in passes.iter() {
+            check_name(name)?;
+        }
+        self.pipeline = Some(passes);
+        Ok(())
+    }
+
+    /// 获得指定的流水线
+    pub fn get_pipeline(&self) -> Option<&Vec<String>> {
+        self.pipeline.as_ref()
+    }
+
+    /// 禁用一个遍,包括其在其他遍内部的运行
+    pub fn disable(&mut self, name: &str) -> Result<(), String> {
+        check_name(name)?;
+        self.disabled.insert(name.to_string());
+        Ok(())
+    }
+
+    /// 在遍之后打印IR,`all`表示所有中端的遍
+    pub fn print_after(&mut self, name: &str) -> Result<(), String> {
+        if name == "all" {
+            self.print_after_all = true;
+            return Ok(());
+        }
+        check_name(name)?;
+        if !IR_PASSES.iter().any(|pass| pass.name == name) {
+            return Err(format!("cannot print IR after backend pass `{}`", name));
+        }
+        self.print_after.insert(name.to_string());
+        Ok(())
+    }
+
+    /// 在每个中端的遍前后用给定的输入解释执行程序,比较其行为
+    pub fn check_passes(&mut self, input: String) {
+        self.check_input = Some(input);
+    }
+
+    /// 在每个中端的遍之后检查IR的合法性
+    pub fn verify_each(&mut self) {
+        self.verify_each = true;
+    }
+
+    /// 检查IR的合法性,打印发现的错误,`stage`指出刚刚运行的遍
+    pub fn verify(&self, stage: &str, module: &Module) -> bool {
+        let errors = verify_module(module);
+        if errors.is_empty() {
+            return true;
+        }
+        eprintln!("error: invalid IR after `{}`", stage);
+        for err in errors.iter() {
+            eprintln!("  {}", err);
+        }
+        false
+    }
+
+    /// 获得改变了程序行为或者产生了不合法IR的遍
+    pub fn get_broken_passes(&self) -> Vec<String> {
+        self.broken.borrow().clone()
+    }
+
+    /// 判断一个遍是否需要运行
+    /// 指定了流水线时,后端的遍只有出现在流水线中才运行
+    pub fn is_enabled(&self, name: &str) -> bool {
+        debug_assert!(check_name(name).is_ok(), "未注册的遍: {}", name);
+        if self.disabled.contains(name) {
+            return false;
+        }
+        match &self.pipeline {
+            Some(pipeline) if BACKEND_PASSES.iter().any(|pass| pass.name == name) => {
+                pipeline.iter().any(|pass| pass == name)
+            }
+            _ => true,
+        }
+    }
+
+    /// 运行一个中端的遍,被禁用时跳过,需要时在之后打印IR
+    pub fn run<F>(&self, name: &str, module: &mut Module, pools: &mut Pools, pass: F)
+    where
+        F: FnOnce(&mut Module, &mut Pools),
+    {
+        if !self.is_enabled(name) {
+            return;
+        }
+        // 内层的遍会更新最近的结果,这里保留本遍运行前的结果
+        let before = self.check_input.as_ref().map(|input| {
+            self.last_result
+                .borrow_mut()
+                .get_or_insert_with(|| interpret(module, input, DEFAULT_STEP_LIMIT))
+                .clone()
+        });
+        pass(module, pools);
+        if self.print_after_all || self.print_after.contains(name) {
+            match dump_module(module) {
+                Ok(text) => eprintln!("; *** IR Dump After {} ***\n{}", name, text),
+                Err(e) => eprintln!("; *** IR Dump After {} failed: {} ***", name, e),
+            }
+        }
+        if self.verify_each && !self.verify(name, module) {
+            self.mark_broken(name);
+        }
+        if let (Some(input), Some(before)) = (&self.check_input, before) {
+            let after = interpret(module, input, DEFAULT_STEP_LIMIT);
+            if !same_behaviour(&before, &after) {
+                eprintln!(
+                    "error: pass `{}` changed the behaviour of the program\n  before: {}\n  after:  {}",
+                    name,
+                    describe(&before),
+                    describe(&after)
+                );
+                self.mark_broken(name);
+            }
+            *self.last_result.borrow_mut() = Some(after);
+        }
+    }
+
+    fn mark_broken(&self, name: &str) {
+        let mut broken = self.broken.borrow_mut();
+        if !broken.iter().any(|pass| pass == name) {
+            broken.push(name.to_string());
+        }
+    }
+
+    /// 按指定的流水线运行中端的遍
+    pub fn run_pipeline(
+        &self,
+        module: &mut Module,
+        pools: &mut Pools,
+        pool_func: &mut ObjPool<Function>,
+    ) {
+        for name in self.pipeline.iter().flatten() {
+            if let Some(pass) = IR_PASSES.iter().find(|pass| pass.name == name) {
+                self.run(pass.name, module, pools, |module, pools| {
+                    (pass.run)(module, pools, pool_func, self)
+                });
+            }
+        }
+    }
+}
+
+/// 比较两次运行的返回值和输出,不比较执行的指令数
+fn same_behaviour(
+    before: &Result<ExecResult, ExecError>,
+    after: &Result<ExecResult, ExecError>,
+) -> bool {
+    match (before, after) {
+        (Ok(before), Ok(after)) => before.ret == after.ret && before.output == after.output,
+        (Err(before), Err(after)) => before == after,
+        _ => false,
+    }
+}
+
+/// 运行结果的简短描述
+fn describe(result: &Result<ExecResult, ExecError>) -> String {
+    match result {
+        Ok(result) => format!(
+            "returned {} after {} steps, output {:?}",
+            result.ret,
+            result.steps,
+            if result.output.chars().count() > 64 {
+                format!("{}...", result.output.chars().take(64).collect::<String>())
+            } else {
+                result.output.clone()
+            }
+        ),
+        Err(err) => format!("error: {}", err),
+    }
+}
+
+/// 检查遍是否已经注册
+fn check_name(name: &str) -> Result<(), String> {
+    let registered = IR_PASSES.iter().any(|pass| pass.name == name)
+        || BACKEND_PASSES.iter().any(|pass| pass.name == name);
+    if registered {
+        Ok(())
+    } else {
+        Err(format!(
+            "unknown pass `{}`, use --list-passes to see all passes",
+            name
+        ))
+    }
+}
+
+/// 列出所有注册的遍
+pub fn list_passes() -> String {
+    let mut text = String::from("IR passes:\n");
+    for pass in IR_PASSES.iter() {
+        text += format!("  {:<16} {}\n", pass.name, pass.desc).as_str();
+    }
+    text += "Backend passes:\n";
+    for pass in BACKEND_PASSES.iter() {
+        text += format!("  {:<16} {}\n", pass.name, pass.desc).as_str();
+    }
+    text
+}
+
+#[cfg(test)]
+mod pass_manager_test {
+    use super::PassManager;
+    use crate::ir::{
+        basicblock::BasicBlock,
+        function::Function,
+        instruction::{Inst, InstKind},
+        module::Module,
+        parse_ir,
+    };
+    use crate::utility::ObjPool;
+
+    #[test]
+    fn test_options() {
+        let mut passes = PassManager::new();
+        assert!(passes.is_enabled("gvn"));
+        assert!(passes.is_enabled("peephole"));
+        assert!(passes.disable("no-such-pass").is_err());
+        assert!(passes.print_after("peephole").is_err());
+        passes.disable("gvn").unwrap();
+        assert!(!passes.is_enabled("gvn"));
+
+        // 指定流水线后,没有列出的后端遍不再运行
+        passes
+            .set_pipeline(vec!["licm".to_string(), "clear".to_string()])
+            .unwrap();
+        assert!(passes.is_enabled("licm"));
+        assert!(passes.is_enabled("clear"));
+        assert!(!passes.is_enabled("peephole"));
+    }
+
+    #[test]
+    fn test_check_passes() {
+        let text = "
+define dso_local signext i32 @main() #0 {
+bb_entry:
+  call void @putint(i32 noundef 7)
+  ret i32 0
+}
+
+declare void @putint(i32)
+";
+        let mut module = Module::new();
+        let mut pool_inst: ObjPool<Inst> = ObjPool::new();
+        let mut pool_bb: ObjPool<BasicBlock> = ObjPool::new();
+        let mut pool_func: ObjPool<Function> = ObjPool::new();
+        parse_ir(
+            text,
+            &mut module,
+            &mut pool_inst,
+            &mut pool_bb,
+            &mut pool_func,
+        )
+        .unwrap();
+
+        let mut passes = PassManager::new();
+        passes.check_passes(String::new());
+        let mut pools = (&mut pool_bb, &mut pool_inst);
+        // 不改变行为的遍
+        passes.run("gvn", &mut module, &mut pools, |_, _| {});
+        assert!(passes.get_broken_passes().is_empty());
+        // 删除输出的遍
+        passes.run("dce", &mut module, &mut pools, |module, _| {
+            let mut inst = module.get_function("main").get_head().get_head_inst();
+            while !matches!(inst.get_kind(), InstKind::Call(_)) {
+                inst = inst.get_next();
+            }
+            inst.as_mut().remove_self();
+        });
+        assert_eq!(passes.get_broken_passes(), vec!["dce".to_string()]);
+    }
+}